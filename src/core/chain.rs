@@ -1,9 +1,10 @@
 use std::collections::HashMap;
 use std::path::Path;
+use std::sync::Arc;
 use serde::{Serialize, Deserialize};
 use crate::core::types::*;
 use crate::core::params::*;
-use crate::storage::Storage;
+use crate::storage::{Storage, StoredAddressEntry, WriteBatch};
 use crate::core::script;
 
 /// Represents an unspent transaction output in the UTXO set
@@ -18,12 +19,45 @@ pub struct UtxoEntry {
 #[derive(Debug, Clone)]
 pub struct UtxoSet {
     utxos: HashMap<OutPoint, UtxoEntry>,
+    /// XOR of every entry's hash — order-independent and updated incrementally
+    digest: Hash256,
+}
+
+/// Hash of one UTXO entry, folded into `UtxoSet::digest`
+fn utxo_entry_hash(outpoint: &OutPoint, entry: &UtxoEntry) -> Hash256 {
+    use sha2::{Digest, Sha256};
+    let mut hasher = Sha256::new();
+    hasher.update(outpoint.txid);
+    hasher.update(outpoint.vout.to_le_bytes());
+    hasher.update(entry.output.amount.to_le_bytes());
+    hasher.update(entry.output.pubkey_hash);
+    hasher.update((entry.output.script_pubkey.len() as u32).to_le_bytes());
+    hasher.update(&entry.output.script_pubkey);
+    hasher.update(entry.height.to_le_bytes());
+    hasher.update([entry.is_coinbase as u8]);
+    hasher.finalize().into()
+}
+
+impl Default for UtxoSet {
+    fn default() -> Self { Self::new() }
 }
 
 impl UtxoSet {
-    pub fn new() -> Self { Self { utxos: HashMap::new() } }
-    pub fn add(&mut self, outpoint: OutPoint, entry: UtxoEntry) { self.utxos.insert(outpoint, entry); }
-    pub fn spend(&mut self, outpoint: &OutPoint) -> Option<UtxoEntry> { self.utxos.remove(outpoint) }
+    pub fn new() -> Self { Self { utxos: HashMap::new(), digest: NULL_HASH } }
+    pub fn add(&mut self, outpoint: OutPoint, entry: UtxoEntry) {
+        self.toggle_digest(&outpoint, &entry);
+        if let Some(old) = self.utxos.insert(outpoint.clone(), entry) { self.toggle_digest(&outpoint, &old); }
+    }
+    pub fn spend(&mut self, outpoint: &OutPoint) -> Option<UtxoEntry> {
+        let removed = self.utxos.remove(outpoint);
+        if let Some(ref entry) = removed { self.toggle_digest(outpoint, entry); }
+        removed
+    }
+    fn toggle_digest(&mut self, outpoint: &OutPoint, entry: &UtxoEntry) {
+        for (d, h) in self.digest.iter_mut().zip(utxo_entry_hash(outpoint, entry)) { *d ^= h; }
+    }
+    /// Order-independent digest of the whole set (used to cross-check persisted state)
+    pub fn digest(&self) -> Hash256 { self.digest }
     pub fn contains(&self, outpoint: &OutPoint) -> bool { self.utxos.contains_key(outpoint) }
     pub fn get(&self, outpoint: &OutPoint) -> Option<&UtxoEntry> { self.utxos.get(outpoint) }
     pub fn balance_of(&self, pubkey_hash: &Hash256) -> u64 {
@@ -84,6 +118,49 @@ pub fn calculate_next_difficulty(current: u32, timestamps: &[u64]) -> u32 {
     fractional_to_integer_difficulty(calculate_next_difficulty_fractional(current as f64, timestamps))
 }
 
+/// Default averaging window for `Chain::network_hashps`: the LWMA window
+pub const NETWORK_HASHPS_WINDOW: u64 = DIFFICULTY_WINDOW as u64;
+
+/// Hashrate behind a run of consecutive headers (oldest first): the expected
+/// hashes to solve every header after the first, over the time between the
+/// first and the last. Zero when no time elapsed.
+pub fn network_hashps(headers: &[BlockHeader]) -> f64 {
+    let (Some(first), Some(last)) = (headers.first(), headers.last()) else { return 0.0 };
+    let elapsed = last.timestamp.saturating_sub(first.timestamp);
+    if elapsed == 0 { return 0.0; }
+    let work: f64 = headers[1..].iter().map(|h| estimated_hashes_for_difficulty(h.difficulty_target)).sum();
+    work / elapsed as f64
+}
+
+/// Recent blocks examined by `Chain::anomaly_warning`
+pub const ANOMALY_WINDOW: u64 = 10;
+/// Average solve time this many times off `TARGET_BLOCK_TIME` counts as an anomaly
+const ANOMALY_SOLVE_TIME_FACTOR: u64 = 6;
+
+/// Advisory check of a run of consecutive headers (oldest first) for signs of
+/// timestamp manipulation: an average solve time far from the target, or a
+/// difficulty fall steeper than the LWMA allows
+pub fn detect_anomaly(headers: &[BlockHeader]) -> Option<String> {
+    let (Some(first), Some(last)) = (headers.first(), headers.last()) else { return None };
+    let blocks = (headers.len() - 1) as u64;
+    if blocks == 0 { return None; }
+
+    let avg = (last.timestamp as i64 - first.timestamp as i64) / blocks as i64;
+    if avg < (TARGET_BLOCK_TIME / ANOMALY_SOLVE_TIME_FACTOR) as i64 || avg > (TARGET_BLOCK_TIME * ANOMALY_SOLVE_TIME_FACTOR) as i64 {
+        return Some(format!("blocks {}-{} averaged {}s apart (target {}s): possible timestamp manipulation",
+            first.height, last.height, avg, TARGET_BLOCK_TIME));
+    }
+
+    // Fractional steps are capped per block; one extra for integer rounding
+    let max_drop = (MAX_ADJUSTMENT_PER_BLOCK * blocks as f64).ceil() as u32 + 1;
+    let peak = headers.iter().map(|h| h.difficulty_target).max().unwrap_or(0);
+    if peak.saturating_sub(last.difficulty_target) > max_drop {
+        return Some(format!("difficulty fell from {} to {} within blocks {}-{}: possible time-warp attack",
+            peak, last.difficulty_target, first.height, last.height));
+    }
+    None
+}
+
 // ─── Cumulative Work (Integer — Consensus Safe) ─────────────────────
 //
 // CRITICAL: Fork choice must NEVER use floating point.
@@ -96,6 +173,65 @@ fn block_work(difficulty: u32) -> u128 {
 }
 
 
+// ─── Version Bits ───────────────────────────────────────────────────
+
+/// Tally of version-bit signaling over a window of active-chain blocks
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VersionBitsStats {
+    /// Number of blocks actually examined (may be less than requested near genesis)
+    pub blocks: u64,
+    pub start_height: u64,
+    pub end_height: u64,
+    /// Blocks using the signaling version format at all
+    pub signaling_blocks: u64,
+    /// Per-bit count of blocks setting that bit
+    pub bit_counts: [u64; VERSIONBITS_NUM_BITS as usize],
+}
+
+/// Where a version-bits deployment stands
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DeploymentState {
+    /// Counting signals; no period has reached the threshold yet
+    Started,
+    /// A period reached the threshold; the rule applies from the next period
+    LockedIn,
+    /// The rule is enforced
+    Active,
+}
+
+impl DeploymentState {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DeploymentState::Started => "started",
+            DeploymentState::LockedIn => "locked_in",
+            DeploymentState::Active => "active",
+        }
+    }
+}
+
+/// Activation progress of one version bit, as of the next block (see `Chain::deployment_status`)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeploymentStatus {
+    pub bit: u32,
+    pub state: DeploymentState,
+    /// Height the current state took effect
+    pub since: u64,
+    /// First height of the period the next block belongs to
+    pub period_start: u64,
+    /// Blocks of that period already on the chain, and how many set the bit
+    pub period_blocks: u64,
+    pub period_signaled: u64,
+}
+
+/// Return the signaled bits of a header version, or 0 if it isn't a signaling version
+pub fn version_signal_bits(version: u32) -> u32 {
+    if version & VERSIONBITS_TOP_MASK == VERSIONBITS_TOP_BITS {
+        version & !VERSIONBITS_TOP_MASK
+    } else {
+        0
+    }
+}
+
 // ─── Undo (Monero-like fast reorg) ───────────────────────────────────
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -120,8 +256,291 @@ impl BlockUndo {
     }
 }
 
+/// Spend a block's inputs and add its outputs to `utxo_set`, returning the undo record
+fn apply_block_utxos(utxo_set: &mut UtxoSet, block: &Block) -> BlockUndo {
+    let mut undo = BlockUndo { spent: Vec::new(), created: Vec::new() };
+
+    for tx in &block.transactions {
+        if !tx.is_coinbase() {
+            for input in &tx.inputs {
+                if let Some(spent) = utxo_set.spend(&input.previous_output) {
+                    undo.spent.push((input.previous_output.clone(), spent));
+                }
+            }
+        }
+
+        let txid = crate::crypto::txid::txid_v1(tx);
+        for (vout, output) in tx.outputs.iter().enumerate() {
+            let op = OutPoint { txid, vout: vout as u32 };
+            utxo_set.add(op.clone(), UtxoEntry {
+                output: output.clone(),
+                height: block.header.height,
+                is_coinbase: tx.is_coinbase(),
+            });
+            undo.created.push(op);
+        }
+    }
+
+    undo
+}
+
+/// Tx index entries for a block: (txid, location) per transaction
+fn block_tx_locations(block_hash: &Hash256, block: &Block) -> Vec<(Hash256, crate::storage::StoredTxLocation)> {
+    block.transactions.iter().enumerate().map(|(i, tx)| {
+        let loc = crate::storage::StoredTxLocation {
+            block_hash: *block_hash,
+            index: i as u32,
+            vout_count: tx.outputs.len() as u32,
+        };
+        (crate::crypto::txid::txid_v1(tx), loc)
+    }).collect()
+}
+
+/// Address index entries for a connected block, keyed by pubkey hash: one per
+/// output received and one per input spent (amounts and owners from `undo`)
+fn block_address_entries(block: &Block, undo: &BlockUndo) -> Vec<(Hash256, StoredAddressEntry)> {
+    let spent: HashMap<&OutPoint, &UtxoEntry> = undo.spent.iter().map(|(op, e)| (op, e)).collect();
+    let height = block.header.height;
+    let mut entries = Vec::new();
+    for tx in &block.transactions {
+        let txid = crate::crypto::txid::txid_v1(tx);
+        if !tx.is_coinbase() {
+            for (i, input) in tx.inputs.iter().enumerate() {
+                if let Some(prev) = spent.get(&input.previous_output) {
+                    entries.push((prev.output.pubkey_hash, StoredAddressEntry {
+                        txid, index: i as u32, height, amount: prev.output.amount, sent: true,
+                    }));
+                }
+            }
+        }
+        for (vout, output) in tx.outputs.iter().enumerate() {
+            entries.push((output.pubkey_hash, StoredAddressEntry {
+                txid, index: vout as u32, height, amount: output.amount, sent: false,
+            }));
+        }
+    }
+    entries
+}
+
+// ─── Rejected Blocks ────────────────────────────────────────────────
+
+/// How many recently rejected blocks to remember
+const MAX_REJECTED_BLOCKS: usize = 1000;
+
+/// Bounded LRU of blocks that failed a check that can never start passing,
+/// keyed by the header's `id_hash` (cheap, unlike the PoW hash). A merkle
+/// failure also records the root the transactions actually hashed to: the
+/// header may be fine and only this copy's body corrupted, so the real block
+/// must still get through.
+#[derive(Debug, Default)]
+struct RejectedBlocks {
+    entries: HashMap<Hash256, RejectedEntry>,
+    order: std::collections::VecDeque<Hash256>,
+}
+
+#[derive(Debug)]
+struct RejectedEntry {
+    err: BlockError,
+    bad_root: Option<Hash256>,
+    height: u64,
+    rejected_at: u64,
+}
+
+/// A recently rejected block (see `Chain::recent_rejections`)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RejectedBlockInfo {
+    /// The header's `id_hash`
+    pub id: Hash256,
+    pub height: u64,
+    pub reason: String,
+    pub rejected_at: u64,
+}
+
+impl RejectedBlocks {
+    /// The cached error if this block was already rejected
+    fn lookup(&mut self, id: &Hash256, block: &Block) -> Option<BlockError> {
+        let entry = self.entries.get(id)?;
+        if entry.bad_root.is_some_and(|root| root != block.compute_merkle_root()) {
+            return None;
+        }
+        let err = entry.err.clone();
+        // Move to the back so persistent resenders stay cached
+        self.order.retain(|h| h != id);
+        self.order.push_back(*id);
+        Some(err)
+    }
+
+    fn insert(&mut self, id: Hash256, height: u64, err: BlockError, bad_root: Option<Hash256>) {
+        let rejected_at = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+        if self.entries.insert(id, RejectedEntry { err, bad_root, height, rejected_at }).is_some() {
+            self.order.retain(|h| *h != id);
+        }
+        self.order.push_back(id);
+        if self.order.len() > MAX_REJECTED_BLOCKS {
+            if let Some(old) = self.order.pop_front() { self.entries.remove(&old); }
+        }
+    }
+
+    /// Most recently rejected (or re-sent) first
+    fn list(&self) -> Vec<RejectedBlockInfo> {
+        self.order.iter().rev().filter_map(|id| {
+            let e = self.entries.get(id)?;
+            Some(RejectedBlockInfo { id: *id, height: e.height, reason: e.err.to_string(), rejected_at: e.rejected_at })
+        }).collect()
+    }
+}
+
+// ─── Metrics ────────────────────────────────────────────────────────
+
+/// Block-acceptance and reorg counters since startup (served on `/metrics`)
+#[derive(Debug, Clone, Default)]
+pub struct BlockMetrics {
+    /// Blocks stored, whether they extended the tip or a side chain
+    pub accepted: u64,
+    /// Failed `add_block` calls by `BlockError::reason`
+    pub rejected: std::collections::BTreeMap<&'static str, u64>,
+    pub reorgs: u64,
+    /// Blocks disconnected, summed over all completed reorgs
+    pub reorg_depth_sum: u64,
+    pub reorg_depth_max: u64,
+}
+
 // ─── Chain ──────────────────────────────────────────────────────────
 
+/// Where a confirmed transaction lives on the active chain
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TxLocation {
+    pub block_hash: Hash256,
+    /// Position of the transaction within the block
+    pub index: u32,
+}
+
+/// Successful `Chain::verify_chain` run
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChainVerification {
+    /// First height whose PoW and merkle root were re-checked
+    pub start_height: u64,
+    pub end_height: u64,
+    pub blocks_checked: u64,
+    pub utxos: usize,
+    /// False on pruned chains, where the UTXO set can't be replayed
+    pub utxo_set_checked: bool,
+}
+
+/// First problem `Chain::verify_chain` found
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChainInconsistency {
+    pub height: u64,
+    pub reason: String,
+}
+
+impl std::fmt::Display for ChainInconsistency {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "block #{}: {}", self.height, self.reason)
+    }
+}
+
+/// Aggregate view of the active UTXO set (see `Chain::utxo_set_info`)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UtxoSetInfo {
+    pub height: u64,
+    pub tip: Hash256,
+    pub utxos: usize,
+    /// Sum of every unspent output (base units)
+    pub total_amount: u64,
+    pub digest: Hash256,
+}
+
+/// One address on the rich list
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RichListEntry {
+    pub pubkey_hash: Hash256,
+    pub balance: u64,
+    pub utxos: usize,
+}
+
+/// Every address holding unspent outputs, richest first (see `Chain::rich_list`)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RichList {
+    pub tip: Hash256,
+    pub digest: Hash256,
+    pub entries: Vec<RichListEntry>,
+}
+
+/// Per-block aggregates (see `Chain::block_stats`)
+#[derive(Debug, Clone, PartialEq)]
+pub struct BlockStats {
+    pub height: u64,
+    pub hash: Hash256,
+    pub size: usize,
+    /// Transactions including the coinbase
+    pub tx_count: usize,
+    /// Sum of non-coinbase outputs (base units)
+    pub total_out: u64,
+    pub total_fees: u64,
+    /// Fees per byte of non-coinbase transactions; None for coinbase-only blocks
+    pub avg_fee_rate: Option<f64>,
+    /// Block subsidy at this height
+    pub subsidy: u64,
+    /// What the coinbase actually paid out
+    pub coinbase_total: u64,
+    /// True if fees were summed per transaction from undo data. Otherwise
+    /// they're the coinbase's claim above the subsidy, which is a lower bound:
+    /// a miner may leave fees unclaimed.
+    pub fees_from_undo: bool,
+}
+
+/// Startup options for a persistent chain
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChainOptions {
+    /// Maintain the on-disk txid index (light nodes can turn this off)
+    pub tx_index: bool,
+    /// Maintain the per-address history index (roughly doubles write volume)
+    pub address_index: bool,
+    /// Refuse reorgs that would disconnect more than this many blocks
+    pub max_reorg_depth: u64,
+    /// Keep full blocks only for the newest this-many heights (None = keep all)
+    pub prune_depth: Option<u64>,
+}
+
+impl Default for ChainOptions {
+    fn default() -> Self { ChainOptions { tx_index: true, address_index: false, max_reorg_depth: MAX_REORG_DEPTH, prune_depth: None } }
+}
+
+/// How far a known tip got through validation
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TipStatus {
+    /// The current best chain
+    Active,
+    /// Every block on the branch was connected (fully validated) at some point
+    ValidFork,
+    /// All blocks on the branch are stored but some were never connected
+    ValidHeaders,
+    /// At least one block on the branch is missing
+    HeadersOnly,
+}
+
+impl TipStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TipStatus::Active => "active",
+            TipStatus::ValidFork => "valid-fork",
+            TipStatus::ValidHeaders => "valid-headers",
+            TipStatus::HeadersOnly => "headers-only",
+        }
+    }
+}
+
+/// A leaf of the block tree (a header nothing builds on yet)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChainTip {
+    pub hash: Hash256,
+    pub height: u64,
+    /// Blocks between the tip and where it forks off the active chain
+    pub branch_len: u64,
+    pub status: TipStatus,
+}
+
 pub struct Chain {
     /// All known block headers, indexed by hash
     headers: HashMap<Hash256, BlockHeader>,
@@ -147,6 +566,22 @@ pub struct Chain {
     storage: Option<Storage>,
     /// When true, skip per-block disk writes (flush at end of batch)
     batch_mode: bool,
+    /// True if startup state matched the chain-state snapshot (consistency walk skipped)
+    fast_start: bool,
+    /// Sum of block rewards over the active chain (maintained on connect/disconnect)
+    money_supply: u64,
+    /// txid (v1) -> location, for transactions on the active chain
+    tx_index: HashMap<Hash256, TxLocation>,
+    /// Blocks that failed a permanent check, so resends short-circuit
+    rejected_blocks: RejectedBlocks,
+    metrics: BlockMetrics,
+    /// Block bodies below this height (genesis excepted) were pruned; 0 if none
+    prune_height: u64,
+    options: ChainOptions,
+    /// `utxo_set_info` result, cleared whenever a block is connected or disconnected
+    utxo_info_cache: std::sync::Mutex<Option<UtxoSetInfo>>,
+    /// `rich_list` result, cleared alongside `utxo_info_cache`
+    rich_list_cache: std::sync::Mutex<Option<Arc<RichList>>>,
 }
 
 impl std::fmt::Debug for Chain {
@@ -181,9 +616,18 @@ impl Chain {
             fractional_difficulty: INITIAL_DIFFICULTY as f64,
             storage: None,
             batch_mode: false,
+            fast_start: false,
+            money_supply: 0,
+            tx_index: HashMap::new(),
+            rejected_blocks: RejectedBlocks::default(),
+            metrics: BlockMetrics::default(),
+            prune_height: 0,
+            options: ChainOptions::default(),
+            utxo_info_cache: std::sync::Mutex::new(None),
+            rich_list_cache: std::sync::Mutex::new(None),
         };
 
-        let undo = chain.connect_block_utxos(&genesis);
+        let undo = chain.connect_block_utxos(&genesis_hash, &genesis);
         chain.undo_cache.insert(genesis_hash, undo.clone());
         chain.headers.insert(genesis_hash, genesis.header.clone());
         chain.height_index.insert(0, genesis_hash);
@@ -194,11 +638,17 @@ impl Chain {
 
     /// Open with persistent storage
     pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, String> {
+        Self::open_with_options(path, ChainOptions::default())
+    }
+
+    /// Open with persistent storage and explicit startup options
+    pub fn open_with_options<P: AsRef<Path>>(path: P, options: ChainOptions) -> Result<Self, String> {
         let storage = Storage::open(path).map_err(|e| e.to_string())?;
         if storage.has_chain_data() {
-            Self::load_from_storage(storage)
+            Self::load_from_storage(storage, options)
         } else {
             let mut chain = Self::new();
+            chain.options = options;
             chain.persist_genesis(&storage)?;
             chain.storage = Some(storage);
             Ok(chain)
@@ -223,8 +673,11 @@ impl Chain {
         self.recent_timestamps = vec![genesis.header.timestamp];
         self.fractional_difficulty = INITIAL_DIFFICULTY as f64;
         self.batch_mode = false;
+        self.money_supply = 0;
+        self.tx_index.clear();
+        self.prune_height = 0;
 
-        let undo = self.connect_block_utxos(&genesis);
+        let undo = self.connect_block_utxos(&genesis_hash, &genesis);
         self.undo_cache.insert(genesis_hash, undo.clone());
         self.headers.insert(genesis_hash, genesis.header.clone());
         self.height_index.insert(0, genesis_hash);
@@ -239,7 +692,7 @@ impl Chain {
         }
     }
 
-    fn load_from_storage(storage: Storage) -> Result<Self, String> {
+    fn load_from_storage(storage: Storage, options: ChainOptions) -> Result<Self, String> {
         let tip = storage.get_tip().map_err(|e| e.to_string())?.ok_or("no tip")?;
         let height = storage.get_height().map_err(|e| e.to_string())?.ok_or("no height")?;
         let timestamps = storage.get_timestamps().map_err(|e| e.to_string())?
@@ -274,13 +727,281 @@ impl Chain {
             utxo_set.add(outpoint, entry);
         }
 
+        let prune_height = storage.get_prune_height().map_err(|e| e.to_string())?;
+
         tracing::info!("💾 Loaded chain: height={} tip={} utxos={} blocks={}",
             height, &hex::encode(tip)[..16], utxo_set.len(), blocks.len());
 
-        Ok(Chain { headers, blocks, height_index, cumulative_work, children,
+        let mut chain = Chain { headers, blocks, height_index, cumulative_work, children,
             utxo_set, tip, height, recent_timestamps: timestamps,
             fractional_difficulty, storage: Some(storage), batch_mode: false,
-            undo_cache: HashMap::new() })
+            undo_cache: HashMap::new(), fast_start: false,
+            money_supply: total_emission(height), tx_index: HashMap::new(),
+            rejected_blocks: RejectedBlocks::default(), metrics: BlockMetrics::default(), prune_height, options,
+            utxo_info_cache: std::sync::Mutex::new(None), rich_list_cache: std::sync::Mutex::new(None) };
+        chain.load_tx_index()?;
+        chain.load_address_index()?;
+
+        // Fast path: sled state agrees with the last chain-state snapshot
+        let snapshot = chain.storage.as_ref().and_then(|s| s.get_state_snapshot().ok().flatten());
+        if snapshot.as_ref() == Some(&chain.state_snapshot()) {
+            tracing::info!("⚡ Chain state matches snapshot — skipping UTXO consistency walk");
+            chain.fast_start = true;
+        } else if chain.prune_height > 0 {
+            tracing::info!("✂️  Chain is pruned below #{} — trusting stored UTXO set", chain.prune_height);
+            chain.persist_state_snapshot();
+        } else {
+            tracing::info!("🔍 No matching chain-state snapshot — verifying UTXO set against blocks...");
+            chain.verify_utxos_against_blocks();
+            chain.persist_state_snapshot();
+        }
+        // Catch up if pruning was just enabled or the depth lowered
+        chain.prune_blocks();
+
+        Ok(chain)
+    }
+
+    fn index_block_txs(&mut self, block_hash: &Hash256, block: &Block) {
+        for (i, tx) in block.transactions.iter().enumerate() {
+            let txid = crate::crypto::txid::txid_v1(tx);
+            self.tx_index.insert(txid, TxLocation { block_hash: *block_hash, index: i as u32 });
+        }
+    }
+
+    /// Populate the in-memory txid index at startup. With the persistent index
+    /// enabled it's read from disk, or rebuilt once from the active chain and
+    /// written back if the on-disk index is missing (first run, or re-enabled).
+    fn load_tx_index(&mut self) -> Result<(), String> {
+        let Some(storage) = self.storage.as_ref() else {
+            self.rebuild_tx_index();
+            return Ok(());
+        };
+        if !self.options.tx_index {
+            // A disabled index goes stale immediately — drop it so re-enabling reindexes
+            if storage.has_tx_index() {
+                storage.clear_tx_index().map_err(|e| e.to_string())?;
+            }
+            self.rebuild_tx_index();
+            return Ok(());
+        }
+        if storage.has_tx_index() {
+            for (txid, loc) in storage.load_tx_index().map_err(|e| e.to_string())? {
+                self.tx_index.insert(txid, TxLocation { block_hash: loc.block_hash, index: loc.index });
+            }
+            return Ok(());
+        }
+
+        tracing::info!("🗂️  Transaction index missing — reindexing {} blocks...", self.height + 1);
+        storage.clear_tx_index().map_err(|e| e.to_string())?;
+        for h in 0..=self.height {
+            let Some(hash) = self.height_index.get(&h) else { continue };
+            if let Some(block) = self.blocks.get(hash) {
+                Self::persist_block_txs(storage, hash, block)?;
+            }
+        }
+        storage.mark_tx_index_built().map_err(|e| e.to_string())?;
+        storage.flush().map_err(|e| e.to_string())?;
+        self.rebuild_tx_index();
+        tracing::info!("🗂️  Transaction index built ({} transactions)", self.tx_index.len());
+        Ok(())
+    }
+
+    /// Write a block's transactions to the persistent tx index
+    fn persist_block_txs(storage: &Storage, block_hash: &Hash256, block: &Block) -> Result<(), String> {
+        for (txid, loc) in block_tx_locations(block_hash, block) {
+            storage.put_tx_location(&txid, &loc).map_err(|e| e.to_string())?;
+        }
+        Ok(())
+    }
+
+    /// Bring the on-disk address index in line with the options: drop it when
+    /// disabled, rebuild it from block data when enabled but missing.
+    fn load_address_index(&self) -> Result<(), String> {
+        let Some(storage) = self.storage.as_ref() else { return Ok(()) };
+        if !self.options.address_index {
+            if storage.has_address_index() {
+                storage.clear_address_index().map_err(|e| e.to_string())?;
+            }
+            return Ok(());
+        }
+        if !storage.has_address_index() {
+            tracing::info!("🗂️  Address index missing — reindexing {} blocks...", self.height + 1);
+            self.reindex_addresses()?;
+            tracing::info!("🗂️  Address index built");
+        }
+        Ok(())
+    }
+
+    /// Rebuild the address index by replaying the active chain into a scratch UTXO
+    /// set (the replay supplies the spent outputs each input's entry needs)
+    fn reindex_addresses(&self) -> Result<(), String> {
+        let Some(storage) = self.storage.as_ref() else { return Ok(()) };
+        storage.clear_address_index().map_err(|e| e.to_string())?;
+        let mut scratch = UtxoSet::new();
+        for h in 0..=self.height {
+            let block = self.height_index.get(&h).and_then(|hash| self.blocks.get(hash))
+                .ok_or_else(|| format!("block #{} missing — cannot build address index", h))?;
+            let undo = apply_block_utxos(&mut scratch, block);
+            Self::persist_block_addresses(storage, block, &undo)?;
+        }
+        storage.mark_address_index_built().map_err(|e| e.to_string())?;
+        storage.flush().map_err(|e| e.to_string())?;
+        Ok(())
+    }
+
+    fn persist_block_addresses(storage: &Storage, block: &Block, undo: &BlockUndo) -> Result<(), String> {
+        for (pubkey_hash, entry) in block_address_entries(block, undo) {
+            storage.put_address_entry(&pubkey_hash, &entry).map_err(|e| e.to_string())?;
+        }
+        Ok(())
+    }
+
+    fn remove_block_addresses(storage: &Storage, block: &Block, undo: &BlockUndo) {
+        for (pubkey_hash, entry) in block_address_entries(block, undo) {
+            let _ = storage.remove_address_entry(&pubkey_hash, &entry);
+        }
+    }
+
+    /// Rebuild the txid index from the active chain's blocks
+    fn rebuild_tx_index(&mut self) {
+        self.tx_index.clear();
+        for h in 0..=self.height {
+            let Some(hash) = self.height_index.get(&h).copied() else { continue };
+            if let Some(block) = self.blocks.get(&hash).cloned() {
+                self.index_block_txs(&hash, &block);
+            }
+        }
+    }
+
+    /// Full consistency walk: replay the active chain into a scratch UTXO set and
+    /// replace the loaded set (in memory and on disk) if the two disagree.
+    fn verify_utxos_against_blocks(&mut self) {
+        let mut rebuilt = UtxoSet::new();
+        for h in 0..=self.height {
+            let block = match self.height_index.get(&h).and_then(|hash| self.blocks.get(hash)) {
+                Some(b) => b,
+                None => {
+                    tracing::warn!("⚠️  Block #{} missing — cannot verify UTXO set, trusting database", h);
+                    return;
+                }
+            };
+            apply_block_utxos(&mut rebuilt, block);
+        }
+
+        if rebuilt.digest() == self.utxo_set.digest() && rebuilt.len() == self.utxo_set.len() {
+            tracing::info!("✅ UTXO set verified ({} entries)", rebuilt.len());
+            return;
+        }
+
+        tracing::warn!("⚠️  Stored UTXO set diverges from blocks ({} stored vs {} rebuilt) — repairing",
+            self.utxo_set.len(), rebuilt.len());
+        self.utxo_set = rebuilt;
+        if let Some(ref storage) = self.storage {
+            let _ = storage.clear_utxos();
+            for (op, entry) in self.utxo_set.iter() {
+                let _ = storage.put_utxo(op, entry);
+            }
+            let _ = storage.flush();
+        }
+    }
+
+    /// Re-check the active chain end to end. Linkage, difficulty targets and a
+    /// scratch UTXO replay always cover the whole chain (the replay needs every
+    /// block); PoW and merkle roots, the expensive part, are re-checked for the
+    /// newest `depth` blocks, or all of them when `depth` is None. On a pruned
+    /// chain the UTXO comparison is skipped and merkle roots are only checked
+    /// where the body is still stored.
+    pub fn verify_chain(&self, depth: Option<u64>) -> Result<ChainVerification, ChainInconsistency> {
+        let start_height = depth.map_or(0, |d| self.height.saturating_sub(d));
+        let fail = |height: u64, reason: String| ChainInconsistency { height, reason };
+        let replay_utxos = self.prune_height == 0;
+
+        let mut scratch = UtxoSet::new();
+        let mut timestamps: Vec<u64> = Vec::new();
+        let mut frac_diff = INITIAL_DIFFICULTY as f64;
+        let mut prev_hash = NULL_HASH;
+        let mut blocks_checked = 0;
+
+        for h in 0..=self.height {
+            let hash = *self.height_index.get(&h).ok_or_else(|| fail(h, "missing from height index".into()))?;
+            let header = self.headers.get(&hash).ok_or_else(|| fail(h, "header missing".into()))?;
+            if header.height != h {
+                return Err(fail(h, format!("header claims height {}", header.height)));
+            }
+            if header.prev_hash != prev_hash {
+                return Err(fail(h, "prev_hash doesn't link to the block below".into()));
+            }
+            if h > 0 {
+                let expected = fractional_to_integer_difficulty(frac_diff);
+                if header.difficulty_target != expected {
+                    return Err(fail(h, format!("difficulty {} but LWMA expects {}", header.difficulty_target, expected)));
+                }
+            }
+            timestamps.push(header.timestamp);
+            if timestamps.len() > DIFFICULTY_WINDOW + 10 { timestamps.remove(0); }
+            if h > 0 {
+                frac_diff = calculate_next_difficulty_fractional(frac_diff, &timestamps);
+            }
+
+            let block = self.blocks.get(&hash);
+            if h >= start_height {
+                if h > 0 {
+                    let pow_hash = header.hash();
+                    if pow_hash != hash {
+                        return Err(fail(h, "header hash doesn't match its index key".into()));
+                    }
+                    if leading_zero_bits(&pow_hash) < header.difficulty_target {
+                        return Err(fail(h, "insufficient PoW".into()));
+                    }
+                }
+                if let Some(block) = block {
+                    if !block.validate_merkle_root() {
+                        return Err(fail(h, "invalid merkle root".into()));
+                    }
+                }
+                blocks_checked += 1;
+            }
+            if replay_utxos {
+                let block = block.ok_or_else(|| fail(h, "block body missing".into()))?;
+                apply_block_utxos(&mut scratch, block);
+            }
+            prev_hash = hash;
+        }
+
+        if replay_utxos && (scratch.digest() != self.utxo_set.digest() || scratch.len() != self.utxo_set.len()) {
+            return Err(fail(self.height, format!(
+                "UTXO set differs from replay ({} stored vs {} rebuilt)", self.utxo_set.len(), scratch.len())));
+        }
+
+        Ok(ChainVerification {
+            start_height,
+            end_height: self.height,
+            blocks_checked,
+            utxos: self.utxo_set.len(),
+            utxo_set_checked: replay_utxos,
+        })
+    }
+
+    /// Summary of the current tip state, as written to the chain-state snapshot
+    fn state_snapshot(&self) -> crate::storage::ChainStateSnapshot {
+        crate::storage::ChainStateSnapshot {
+            tip: self.tip,
+            height: self.height,
+            fractional_difficulty: self.fractional_difficulty,
+            recent_timestamps: self.recent_timestamps.clone(),
+            utxo_count: self.utxo_set.len() as u64,
+            utxo_digest: self.utxo_set.digest(),
+        }
+    }
+
+    /// Write the chain-state snapshot. Call only after sled has been flushed so the
+    /// snapshot never describes state the database doesn't have yet.
+    fn persist_state_snapshot(&self) {
+        if let Some(ref storage) = self.storage {
+            if let Err(e) = storage.put_state_snapshot(&self.state_snapshot()) {
+                tracing::warn!("⚠️  Failed to write chain-state snapshot: {}", e);
+            }
+        }
     }
 
     fn persist_genesis(&self, storage: &Storage) -> Result<(), String> {
@@ -296,13 +1017,24 @@ impl Chain {
         for (op, entry) in self.utxo_set.iter() {
             storage.put_utxo(op, entry).map_err(|e| e.to_string())?;
         }
+        if self.options.tx_index {
+            Self::persist_block_txs(storage, &hash, genesis)?;
+            storage.mark_tx_index_built().map_err(|e| e.to_string())?;
+        }
+        if self.options.address_index {
+            if let Some(undo) = self.undo_cache.get(&hash) {
+                Self::persist_block_addresses(storage, genesis, undo)?;
+            }
+            storage.mark_address_index_built().map_err(|e| e.to_string())?;
+        }
         storage.flush().map_err(|e| e.to_string())?;
+        storage.put_state_snapshot(&self.state_snapshot()).map_err(|e| e.to_string())?;
         Ok(())
     }
 
     fn create_genesis_block() -> Block {
         let genesis_miner = [0u8; 32];
-        let community_fund = [0xCF; 32];
+        let community_fund = community_fund_hash();
         let reward = block_reward(0);
         let coinbase = Transaction::new_coinbase(0, reward, genesis_miner, community_fund, "");
         let ts = genesis_timestamp();
@@ -332,7 +1064,44 @@ impl Chain {
 
     // ─── Block Acceptance ───────────────────────────────────────────
 
+    /// Remember a permanent rejection and hand the error back
+    fn reject_block(&mut self, id: Hash256, height: u64, err: BlockError, bad_root: Option<Hash256>) -> BlockError {
+        self.rejected_blocks.insert(id, height, err.clone(), bad_root);
+        err
+    }
+
+    /// Blocks rejected for good, newest first (bounded by `MAX_REJECTED_BLOCKS`)
+    pub fn recent_rejections(&self) -> Vec<RejectedBlockInfo> {
+        self.rejected_blocks.list()
+    }
+
+    /// Acceptance and reorg counters since startup
+    pub fn metrics(&self) -> &BlockMetrics {
+        &self.metrics
+    }
+
     pub fn add_block(&mut self, block: Block) -> Result<Hash256, BlockError> {
+        let result = self.try_add_block(block);
+        match &result {
+            Ok(_) => self.metrics.accepted += 1,
+            Err(e) => *self.metrics.rejected.entry(e.reason()).or_insert(0) += 1,
+        }
+        result
+    }
+
+    fn try_add_block(&mut self, block: Block) -> Result<Hash256, BlockError> {
+        let now = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap().as_secs();
+        self.try_add_block_at(block, now)
+    }
+
+    /// `try_add_block` with the wall clock read as `now`
+    fn try_add_block_at(&mut self, block: Block, now: u64) -> Result<Hash256, BlockError> {
+        // 0. Already rejected for a reason that can't change
+        let id = block.header.id_hash();
+        if let Some(err) = self.rejected_blocks.lookup(&id, &block) {
+            return Err(err);
+        }
+
         let block_hash = block.header.hash();
 
         // 1. Duplicate
@@ -353,13 +1122,15 @@ impl Chain {
             return Err(BlockError::InvalidHeight);
         }
 
-        // 4. Timestamp > parent
+        // 4. Timestamp > parent and > median-time-past of the parent's chain,
+        // and at most two hours ahead of our clock
         if block.header.timestamp <= parent.timestamp {
-            return Err(BlockError::InvalidTimestamp);
+            return Err(self.reject_block(id, block.header.height, BlockError::InvalidTimestamp, None));
         }
-        let now = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap().as_secs();
-        let is_minimal = block.header.timestamp == parent.timestamp + 1;
-        if !is_minimal && block.header.timestamp > now + 7200 {
+        if block.header.timestamp <= self.median_time_past_at(&parent_hash) {
+            return Err(self.reject_block(id, block.header.height, BlockError::TimestampTooOld, None));
+        }
+        if block.header.timestamp > now + MAX_FUTURE_BLOCK_TIME {
             return Err(BlockError::TimestampTooFarInFuture);
         }
 
@@ -372,23 +1143,26 @@ impl Chain {
             self.difficulty_for_block_on_parent(&parent_hash)
         };
         if block.header.difficulty_target != expected_diff {
-            return Err(BlockError::InvalidDifficulty { expected: expected_diff, got: block.header.difficulty_target });
+            let err = BlockError::InvalidDifficulty { expected: expected_diff, got: block.header.difficulty_target };
+            return Err(self.reject_block(id, block.header.height, err, None));
         }
 
         // 6. PoW
-        if !block.header.meets_difficulty() {
-            return Err(BlockError::InsufficientPoW);
+        if leading_zero_bits(&block_hash) < block.header.difficulty_target {
+            return Err(self.reject_block(id, block.header.height, BlockError::InsufficientPoW, None));
         }
 
         // 7. Merkle root
-        if !block.validate_merkle_root() {
-            return Err(BlockError::InvalidMerkleRoot);
+        let merkle_root = block.compute_merkle_root();
+        if merkle_root != block.header.merkle_root {
+            return Err(self.reject_block(id, block.header.height, BlockError::InvalidMerkleRoot, Some(merkle_root)));
         }
 
         // 8. Block size
         if block.size() > MAX_BLOCK_SIZE {
             return Err(BlockError::BlockTooLarge);
         }
+        Self::check_block_sigops(&block)?;
 
         // 9. Basic tx structure
         if block.transactions.is_empty() { return Err(BlockError::NoTransactions); }
@@ -405,12 +1179,15 @@ impl Chain {
             if block.transactions[0].total_output() > expected_reward + total_fees {
                 return Err(BlockError::InvalidCoinbaseAmount);
             }
+            Self::check_community_fund(&block.transactions[0], block.header.height, total_fees)?;
+            self.check_coinbase_unique(&block.transactions[0], block.header.height)?;
+            let median_time = self.median_time_past_at(&parent_hash);
             for tx in &block.transactions[1..] {
-                self.validate_transaction(tx, block.header.height)?;
+                self.validate_transaction(tx, block.header.height, median_time)?;
             }
 
             // Commit directly
-            let undo = self.connect_block_utxos(&block);
+            let undo = self.connect_block_utxos(&block_hash, &block);
             self.undo_cache.insert(block_hash, undo.clone());
             undo_opt = Some(undo.clone());
             self.recent_timestamps.push(block.header.timestamp);
@@ -451,10 +1228,47 @@ impl Chain {
         if let Some(ref undo) = undo_opt {
             self.persist_tip_extension(&block_hash, &block, undo);
         }
+        self.prune_blocks();
 
         Ok(block_hash)
     }
 
+    // ─── Pruning ────────────────────────────────────────────────────
+
+    /// Drop full blocks (and their undo data) more than `prune_depth` below the
+    /// tip. Headers, the height index and the UTXO set stay, so validation and
+    /// mining are unaffected; only serving deep history is. Genesis is kept for
+    /// the handshake.
+    fn prune_blocks(&mut self) {
+        let Some(keep) = self.options.prune_depth else { return };
+        let cutoff = self.height.saturating_sub(keep);
+        if cutoff <= self.prune_height.max(1) { return; }
+
+        let mut pruned = 0;
+        for h in self.prune_height.max(1)..cutoff {
+            let Some(hash) = self.height_index.get(&h).copied() else { continue };
+            if self.blocks.remove(&hash).is_some() { pruned += 1; }
+            self.undo_cache.remove(&hash);
+            if let Some(ref storage) = self.storage {
+                let _ = storage.remove_block(&hash);
+                let _ = storage.remove_undo(&hash);
+            }
+        }
+        self.prune_height = cutoff;
+        if let Some(ref storage) = self.storage {
+            let _ = storage.put_prune_height(cutoff);
+        }
+        if pruned > 0 {
+            tracing::info!("✂️  Pruned {} blocks (now keeping #{}+)", pruned, cutoff);
+        }
+    }
+
+    /// True if some block bodies have been deleted
+    pub fn is_pruned(&self) -> bool { self.prune_height > 0 }
+
+    /// Lowest height above genesis whose full block is still available (0 if unpruned)
+    pub fn prune_height(&self) -> u64 { self.prune_height }
+
     // ─── Reorg ──────────────────────────────────────────────────────
 
     fn reorg_to(&mut self, new_tip: Hash256) -> Result<(), BlockError> {
@@ -485,6 +1299,20 @@ impl Chain {
         let old_height = self.height;
         let new_height = self.headers.get(&new_tip).ok_or(BlockError::OrphanBlock)?.height;
 
+        let depth = disconnect.len() as u64;
+        if depth > self.options.max_reorg_depth {
+            tracing::error!(
+                "🚨 REFUSING REORG of {} blocks (max {}): fork {} at height {}, side chain tip {} at height {} — possible attack",
+                depth,
+                self.options.max_reorg_depth,
+                &hex::encode(fork_point)[..16],
+                old_height - depth,
+                &hex::encode(new_tip)[..16],
+                new_height
+            );
+            return Err(BlockError::ReorgTooDeep { depth, max: self.options.max_reorg_depth });
+        }
+
         tracing::info!(
             "🔄 Reorg: height {} -> {} (disconnect {}, connect {}, fork {})",
             old_height,
@@ -505,13 +1333,20 @@ impl Chain {
                 return Err(BlockError::OrphanBlock);
             };
 
-            self.disconnect_block_utxos(&undo);
+            let height = self.headers.get(bh).ok_or(BlockError::OrphanBlock)?.height;
+            self.disconnect_block_utxos(height, &undo);
 
             // Persist UTXO diffs if we have storage
             if !self.batch_mode {
                 if let Some(ref storage) = self.storage {
                     for op in &undo.created { let _ = storage.remove_utxo(op); }
                     for (op, entry) in &undo.spent { let _ = storage.put_utxo(op, entry); }
+                    if self.options.tx_index {
+                        for op in &undo.created { let _ = storage.remove_tx_location(&op.txid); }
+                    }
+                    if self.options.address_index {
+                        if let Some(block) = self.blocks.get(bh) { Self::remove_block_addresses(storage, block, &undo); }
+                    }
                     let _ = storage.flush();
                 }
             }
@@ -531,8 +1366,11 @@ impl Chain {
                 if block.transactions[0].total_output() > expected_reward + total_fees {
                     return Err(BlockError::InvalidCoinbaseAmount);
                 }
+                Self::check_community_fund(&block.transactions[0], block.header.height, total_fees)?;
+                self.check_coinbase_unique(&block.transactions[0], block.header.height)?;
+                let median_time = self.median_time_past_at(&block.header.prev_hash);
                 for tx in &block.transactions[1..] {
-                    self.validate_transaction(tx, block.header.height)?;
+                    self.validate_transaction(tx, block.header.height, median_time)?;
                 }
                 Ok(())
             })();
@@ -545,15 +1383,37 @@ impl Chain {
 
                 // Roll back any blocks we already connected in this reorg
                 for (rollback_hash, rollback_undo) in connected_undos.iter().rev() {
-                    self.disconnect_block_utxos(rollback_undo);
+                    let rollback_height = self.headers.get(rollback_hash).map(|h| h.height).unwrap_or(0);
+                    self.disconnect_block_utxos(rollback_height, rollback_undo);
                     self.undo_cache.remove(rollback_hash);
                 }
 
                 // Reconnect the old chain blocks we disconnected
+                let mut reconnected: Vec<(Hash256, BlockUndo)> = Vec::new();
                 for old_bh in disconnect.iter().rev() {
                     let old_block = self.blocks.get(old_bh).cloned();
                     if let Some(ob) = old_block {
-                        let _ = self.connect_block_utxos(&ob);
+                        reconnected.push((*old_bh, self.connect_block_utxos(old_bh, &ob)));
+                    }
+                }
+
+                // Put the on-disk indexes back to the old chain
+                if !self.batch_mode {
+                    if let Some(ref storage) = self.storage {
+                        for (rollback_hash, rollback_undo) in &connected_undos {
+                            if self.options.tx_index {
+                                for op in &rollback_undo.created { let _ = storage.remove_tx_location(&op.txid); }
+                            }
+                            if let (true, Some(rb)) = (self.options.address_index, self.blocks.get(rollback_hash)) {
+                                Self::remove_block_addresses(storage, rb, rollback_undo);
+                            }
+                        }
+                        for (old_bh, old_undo) in &reconnected {
+                            let Some(ob) = self.blocks.get(old_bh) else { continue };
+                            if self.options.tx_index { let _ = Self::persist_block_txs(storage, old_bh, ob); }
+                            if self.options.address_index { let _ = Self::persist_block_addresses(storage, ob, old_undo); }
+                        }
+                        let _ = storage.flush();
                     }
                 }
 
@@ -568,7 +1428,7 @@ impl Chain {
             }
 
             // Validation passed — connect the block
-            let undo = self.connect_block_utxos(&block);
+            let undo = self.connect_block_utxos(bh, &block);
             self.undo_cache.insert(*bh, undo.clone());
             connected_undos.push((*bh, undo.clone()));
 
@@ -579,6 +1439,12 @@ impl Chain {
                     for op in &undo.created {
                         if let Some(entry) = self.utxo_set.get(op) { let _ = storage.put_utxo(op, entry); }
                     }
+                    if self.options.tx_index {
+                        let _ = Self::persist_block_txs(storage, bh, &block);
+                    }
+                    if self.options.address_index {
+                        let _ = Self::persist_block_addresses(storage, &block, &undo);
+                    }
                     let _ = storage.flush();
                 }
             }
@@ -618,6 +1484,10 @@ impl Chain {
         // Persist height-index + chain metadata
         self.persist_reorg_height_index();
 
+        self.metrics.reorgs += 1;
+        self.metrics.reorg_depth_sum += depth;
+        self.metrics.reorg_depth_max = self.metrics.reorg_depth_max.max(depth);
+
         tracing::info!("🔄 Reorg complete. New tip: {} height: {}", &hex::encode(new_tip)[..16], new_height);
         Ok(())
     }
@@ -637,6 +1507,53 @@ impl Chain {
         chain
     }
 
+    /// Find the most recent block shared by the ancestries of `a` and `b`.
+    /// Returns None if either hash is unknown or the two don't share a root.
+    pub fn common_ancestor(&self, a: &Hash256, b: &Hash256) -> Option<Hash256> {
+        if !self.headers.contains_key(a) || !self.headers.contains_key(b) {
+            return None;
+        }
+        let a_set: std::collections::HashSet<Hash256> = self.chain_from_tip(*a).into_iter().collect();
+        self.chain_from_tip(*b).into_iter().find(|h| a_set.contains(h))
+    }
+
+    /// Every leaf of the block tree, active tip first, then by height (highest first)
+    pub fn chain_tips(&self) -> Vec<ChainTip> {
+        let mut tips: Vec<ChainTip> = self.headers.iter()
+            .filter(|(hash, _)| self.children.get(*hash).is_none_or(|c| c.is_empty()))
+            .map(|(&hash, header)| {
+                if hash == self.tip {
+                    return ChainTip { hash, height: header.height, branch_len: 0, status: TipStatus::Active };
+                }
+                // Walk down to the active chain, noting what the branch is missing
+                let (mut branch_len, mut all_blocks, mut all_connected) = (0u64, true, true);
+                let mut cursor = hash;
+                while let Some(h) = self.headers.get(&cursor) {
+                    if self.height_index.get(&h.height) == Some(&cursor) { break; }
+                    branch_len += 1;
+                    all_blocks &= self.blocks.contains_key(&cursor);
+                    all_connected &= self.has_undo(&cursor);
+                    if h.height == 0 { break; }
+                    cursor = h.prev_hash;
+                }
+                let status = match (all_blocks, all_connected) {
+                    (false, _) => TipStatus::HeadersOnly,
+                    (true, true) => TipStatus::ValidFork,
+                    (true, false) => TipStatus::ValidHeaders,
+                };
+                ChainTip { hash, height: header.height, branch_len, status }
+            })
+            .collect();
+        tips.sort_by_key(|t| (t.status != TipStatus::Active, std::cmp::Reverse(t.height), t.hash));
+        tips
+    }
+
+    /// Whether undo data exists, i.e. the block was connected at some point
+    fn has_undo(&self, hash: &Hash256) -> bool {
+        self.undo_cache.contains_key(hash)
+            || self.storage.as_ref().is_some_and(|s| matches!(s.get_undo(hash), Ok(Some(_))))
+    }
+
     // rebuild_utxo_to removed: reorg uses per-block undo (fast reorg)
 
     // ─── Difficulty ─────────────────────────────────────────────────
@@ -647,6 +1564,14 @@ impl Chain {
         fractional_to_integer_difficulty(self.fractional_difficulty)
     }
 
+    /// Lowest difficulty a block at `height` could plausibly claim, as each
+    /// block moves it at most `MAX_ADJUSTMENT_PER_BLOCK` from our tip's. Not a
+    /// consensus rule; it decides which orphans are worth buffering.
+    pub fn difficulty_floor_at(&self, height: u64) -> u32 {
+        let steps = height.abs_diff(self.height) as f64;
+        (self.fractional_difficulty - MAX_ADJUSTMENT_PER_BLOCK * steps).floor().max(MIN_DIFFICULTY as f64) as u32
+    }
+
     /// Calculate the expected difficulty for a block whose parent is `parent_hash`.
     /// Walks back along that block's ancestry to gather timestamps.
     /// Used for side-chain validation. O(N) walk.
@@ -679,34 +1604,20 @@ impl Chain {
     // ─── Block/TX Operations ────────────────────────────────────────
 
     
-    fn connect_block_utxos(&mut self, block: &Block) -> BlockUndo {
-        let mut undo = BlockUndo { spent: Vec::new(), created: Vec::new() };
-
-        for tx in &block.transactions {
-            if !tx.is_coinbase() {
-                for input in &tx.inputs {
-                    if let Some(spent) = self.utxo_set.spend(&input.previous_output) {
-                        undo.spent.push((input.previous_output.clone(), spent));
-                    }
-                }
-            }
+    fn connect_block_utxos(&mut self, block_hash: &Hash256, block: &Block) -> BlockUndo {
+        self.invalidate_utxo_info();
+        self.money_supply += block_reward(block.header.height);
+        self.index_block_txs(block_hash, block);
+        apply_block_utxos(&mut self.utxo_set, block)
+    }
 
-            let txid = crate::crypto::txid::txid_v1(tx);
-            for (vout, output) in tx.outputs.iter().enumerate() {
-                let op = OutPoint { txid, vout: vout as u32 };
-                self.utxo_set.add(op.clone(), UtxoEntry {
-                    output: output.clone(),
-                    height: block.header.height,
-                    is_coinbase: tx.is_coinbase(),
-                });
-                undo.created.push(op);
-            }
+    fn disconnect_block_utxos(&mut self, block_height: u64, undo: &BlockUndo) {
+        self.invalidate_utxo_info();
+        self.money_supply -= block_reward(block_height);
+        // Every transaction has at least one output, so `created` covers all txids
+        for op in &undo.created {
+            self.tx_index.remove(&op.txid);
         }
-
-        undo
-    }
-
-    fn disconnect_block_utxos(&mut self, undo: &BlockUndo) {
         // Remove outputs created by this block
         for op in &undo.created {
             self.utxo_set.spend(op);
@@ -717,10 +1628,15 @@ impl Chain {
         }
     }
 
-    fn validate_transaction(&self, tx: &Transaction, block_height: u64) -> Result<(), BlockError> {
+    /// `median_time` is the median-time-past the including block is judged
+    /// against (time-based lock_times compare to it, not the block timestamp)
+    fn validate_transaction(&self, tx: &Transaction, block_height: u64, median_time: u64) -> Result<(), BlockError> {
         if tx.inputs.is_empty() || tx.outputs.is_empty() {
             return Err(BlockError::InvalidTransaction("empty inputs or outputs".into()));
         }
+        if block_height >= LOCKTIME_HEIGHT && !tx.is_final(block_height, median_time) {
+            return Err(BlockError::NonFinalTransaction);
+        }
 
         let mut input_sum: u64 = 0;
 
@@ -733,26 +1649,17 @@ impl Chain {
                 return Err(BlockError::InvalidTransaction("coinbase not mature".into()));
             }
 
-            // Authorization: try new script_sig first, fall back to legacy sig+pubkey
-            if !input.script_sig.is_empty() {
-                // v1 standard: P2PKH script validation
-                script::validate_p2pkh_spend(tx, idx, input, &utxo.output)
-                    .map_err(|e| BlockError::InvalidTransaction(format!("script fail input {}: {:?}", idx, e)))?;
-            } else if !input.pubkey.is_empty() {
-                // Legacy validation (pre-script era)
-                if input.pubkey.len() != 32 {
-                    return Err(BlockError::InvalidTransaction(format!("input {} bad pubkey len", idx)));
+            // Authorization depends on the type of the output being spent
+            match script::classify_output(&utxo.output) {
+                Ok(script::OutputType::PubkeyHash(lock_hash)) => {
+                    Self::authorize_pubkey_hash_input(tx, idx, input, &utxo.output, &lock_hash)?;
                 }
-                let claimed_hash = crate::wallet::pubkey_bytes_to_hash(&input.pubkey);
-                if claimed_hash != utxo.output.pubkey_hash {
-                    return Err(BlockError::InvalidTransaction(format!("input {} pubkey mismatch", idx)));
+                Ok(script::OutputType::Multisig { .. }) => {
+                    script::validate_multisig_spend(tx, idx, input, &utxo.output)
+                        .map_err(|e| BlockError::InvalidTransaction(format!("multisig fail input {}: {:?}", idx, e)))?;
                 }
-                let signing_hash = crate::wallet::tx_signing_hash(tx, idx);
-                if !crate::wallet::verify_signature(&input.pubkey, &signing_hash, &input.signature) {
-                    return Err(BlockError::InvalidTransaction(format!("input {} bad signature", idx)));
-                }
-            } else {
-                return Err(BlockError::InvalidTransaction(format!("input {} has no authorization", idx)));
+                Err(e) => return Err(BlockError::InvalidTransaction(
+                    format!("input {} spends unsupported output: {:?}", idx, e))),
             }
 
             input_sum = input_sum.saturating_add(utxo.output.amount);
@@ -775,6 +1682,76 @@ impl Chain {
     }
 
 
+    /// Type-0 authorization: script_sig if present, else the legacy sig+pubkey fields
+    fn authorize_pubkey_hash_input(
+        tx: &Transaction,
+        idx: usize,
+        input: &TxInput,
+        prev_output: &TxOutput,
+        lock_hash: &Hash256,
+    ) -> Result<(), BlockError> {
+        if !input.script_sig.is_empty() {
+            // v1 standard: P2PKH script validation
+            script::validate_p2pkh_spend(tx, idx, input, prev_output)
+                .map_err(|e| BlockError::InvalidTransaction(format!("script fail input {}: {:?}", idx, e)))
+        } else if !input.pubkey.is_empty() {
+            // Legacy validation (pre-script era)
+            if input.pubkey.len() != 32 {
+                return Err(BlockError::InvalidTransaction(format!("input {} bad pubkey len", idx)));
+            }
+            let claimed_hash = crate::wallet::pubkey_bytes_to_hash(&input.pubkey);
+            if claimed_hash != *lock_hash {
+                return Err(BlockError::InvalidTransaction(format!("input {} pubkey mismatch", idx)));
+            }
+            let signing_hash = if tx.version >= TX_VERSION_SIGHASH_V1 {
+                crate::crypto::tx_signing_hash_v1(tx, idx, prev_output)
+            } else {
+                crate::wallet::tx_signing_hash(tx, idx)
+            };
+            if !crate::wallet::verify_signature(&input.pubkey, &signing_hash, &input.signature) {
+                return Err(BlockError::InvalidTransaction(format!("input {} bad signature", idx)));
+            }
+            Ok(())
+        } else {
+            Err(BlockError::InvalidTransaction(format!("input {} has no authorization", idx)))
+        }
+    }
+
+    /// At most `MAX_BLOCK_SIGOPS` signature checks, from `MAX_BLOCK_SIGOPS_HEIGHT`
+    fn check_block_sigops(block: &Block) -> Result<(), BlockError> {
+        if block.header.height >= MAX_BLOCK_SIGOPS_HEIGHT && block.sigops() > MAX_BLOCK_SIGOPS {
+            return Err(BlockError::TooManySigops);
+        }
+        Ok(())
+    }
+
+    /// One coinbase output must pay exactly `required_community_fund(height)`
+    /// to the fund hash. Nothing is due once the subsidy rounds to zero.
+    fn check_community_fund(coinbase: &Transaction, height: u64, fees: u64) -> Result<(), BlockError> {
+        let fund_hash = community_fund_hash();
+        let required = coinbase_community_fund(height, fees);
+        let paid: u64 = coinbase.outputs.iter().filter(|o| o.pubkey_hash == fund_hash).map(|o| o.amount).sum();
+        // Before the exact-amount rule, paying at least the share in any outputs is enough
+        if height < COMMUNITY_FUND_EXACT_HEIGHT {
+            return if paid >= required { Ok(()) } else { Err(BlockError::InvalidCommunityFund { required, paid }) };
+        }
+        if required == 0 || coinbase.outputs.iter().any(|o| o.pubkey_hash == fund_hash && o.amount == required) {
+            return Ok(());
+        }
+        Err(BlockError::InvalidCommunityFund { required, paid })
+    }
+
+    /// A coinbase whose txid still has unspent outputs would overwrite them
+    /// (BIP30): the earlier coins would vanish, and disconnecting this block
+    /// would delete them for good. Enforced from `UNIQUE_COINBASE_HEIGHT`, as
+    /// earlier chains contain such duplicates.
+    fn check_coinbase_unique(&self, coinbase: &Transaction, height: u64) -> Result<(), BlockError> {
+        if height < UNIQUE_COINBASE_HEIGHT { return Ok(()); }
+        let txid = crate::crypto::txid::txid_v1(coinbase);
+        let collides = (0..coinbase.outputs.len() as u32).any(|vout| self.utxo_set.get(&OutPoint { txid, vout }).is_some());
+        if collides { Err(BlockError::DuplicateCoinbase) } else { Ok(()) }
+    }
+
     fn calculate_block_fees(&self, block: &Block) -> Result<u64, BlockError> {
         let mut total_fees: u64 = 0;
         for tx in &block.transactions[1..] {
@@ -805,46 +1782,73 @@ impl Chain {
     fn persist_tip_extension(&self, block_hash: &Hash256, block: &Block, undo: &BlockUndo) {
         if self.batch_mode { return; }
         if let Some(ref storage) = self.storage {
-            // Always persist the block/header
+            // Always persist the block/header (harmless on its own if we crash
+            // before the tip moves)
             let _ = storage.put_block(block_hash, block);
             let _ = storage.put_header(block_hash, &block.header);
 
-            // Active-chain metadata
-            let _ = storage.put_height_index(block.header.height, block_hash);
-            let _ = storage.put_tip(&self.tip);
-            let _ = storage.put_height(self.height);
-            let _ = storage.put_timestamps(&self.recent_timestamps);
-            let _ = storage.put_fractional_difficulty(self.fractional_difficulty);
-
-            // Persist undo (for fast reorg)
-            let _ = storage.put_undo(block_hash, &undo.to_stored());
-
-            // Persist UTXO diffs
-            for (op, _) in &undo.spent { let _ = storage.remove_utxo(op); }
-            for op in &undo.created {
-                if let Some(entry) = self.utxo_set.get(op) {
-                    let _ = storage.put_utxo(op, entry);
-                }
+            // Tip, metadata, undo and UTXO diffs commit together so a crash
+            // can't leave the tip pointing past the UTXO set
+            if let Err(e) = self.tip_extension_batch(block_hash, block, undo).and_then(|b| storage.apply_batch(b)) {
+                tracing::error!("💥 Failed to persist block #{}: {}", block.header.height, e);
+                return;
+            }
+            if self.options.address_index {
+                let _ = Self::persist_block_addresses(storage, block, undo);
             }
 
             let _ = storage.flush();
+            self.persist_state_snapshot();
         }
     }
 
+    /// Active-chain writes for connecting `block` on top of the old tip
+    fn tip_extension_batch(&self, block_hash: &Hash256, block: &Block, undo: &BlockUndo) -> Result<WriteBatch, crate::storage::StorageError> {
+        let mut batch = WriteBatch::default();
+        batch.put_height_index(block.header.height, block_hash);
+        batch.put_tip(&self.tip);
+        batch.put_height(self.height);
+        batch.put_timestamps(&self.recent_timestamps)?;
+        batch.put_fractional_difficulty(self.fractional_difficulty);
+
+        // Undo data (for fast reorg)
+        batch.put_undo(block_hash, &undo.to_stored())?;
+
+        // UTXO diffs
+        for (op, _) in &undo.spent { batch.remove_utxo(op); }
+        for op in &undo.created {
+            if let Some(entry) = self.utxo_set.get(op) {
+                batch.put_utxo(op, entry)?;
+            }
+        }
+        if self.options.tx_index {
+            for (txid, loc) in block_tx_locations(block_hash, block) {
+                batch.put_tx_location(&txid, &loc)?;
+            }
+        }
+        Ok(batch)
+    }
+
     fn persist_reorg_height_index(&self) {
         if self.batch_mode { return; }
         if let Some(ref storage) = self.storage {
-            let _ = storage.clear_height_index();
+            let mut batch = WriteBatch::default();
+            storage.clear_height_index_into(&mut batch);
             for h in 0..=self.height {
                 if let Some(hash) = self.height_index.get(&h) {
-                    let _ = storage.put_height_index(h, hash);
+                    batch.put_height_index(h, hash);
                 }
             }
-            let _ = storage.put_tip(&self.tip);
-            let _ = storage.put_height(self.height);
-            let _ = storage.put_timestamps(&self.recent_timestamps);
-            let _ = storage.put_fractional_difficulty(self.fractional_difficulty);
+            batch.put_tip(&self.tip);
+            batch.put_height(self.height);
+            if batch.put_timestamps(&self.recent_timestamps).is_err() { return; }
+            batch.put_fractional_difficulty(self.fractional_difficulty);
+            if let Err(e) = storage.apply_batch(batch) {
+                tracing::error!("💥 Failed to persist reorg to height {}: {}", self.height, e);
+                return;
+            }
             let _ = storage.flush();
+            self.persist_state_snapshot();
         }
     }
 
@@ -868,7 +1872,25 @@ impl Chain {
             for (op, entry) in self.utxo_set.iter() {
                 let _ = storage.put_utxo(op, entry);
             }
+            if self.options.tx_index {
+                // Batches can include reorgs, so rewrite the index from the active chain
+                let _ = storage.clear_tx_index();
+                for (h, hash) in &self.height_index {
+                    if let Some(block) = self.blocks.get(hash) {
+                        if Self::persist_block_txs(storage, hash, block).is_err() {
+                            tracing::warn!("⚠️  Failed to index transactions of block #{}", h);
+                        }
+                    }
+                }
+                let _ = storage.mark_tx_index_built();
+            }
             let _ = storage.flush();
+            if self.options.address_index {
+                if let Err(e) = self.reindex_addresses() {
+                    tracing::warn!("⚠️  Address index rebuild failed: {}", e);
+                }
+            }
+            self.persist_state_snapshot();
             tracing::info!("💾 Batch flush complete (height {})", self.height);
         }
     }
@@ -881,17 +1903,219 @@ impl Chain {
         self.height_index.get(&height).and_then(|h| self.blocks.get(h))
     }
 
+    /// Estimated hashes per second over the last `blocks` active-chain blocks
+    /// (0 = `NETWORK_HASHPS_WINDOW`). Uses headers only, so pruning doesn't matter.
+    /// The genesis timestamp predates mining, so the window starts at block 1 at the earliest.
+    pub fn network_hashps(&self, blocks: u64) -> f64 {
+        let blocks = if blocks == 0 { NETWORK_HASHPS_WINDOW } else { blocks }.min(self.height.saturating_sub(1));
+        network_hashps(&self.headers_in_range(self.height - blocks, blocks as u32 + 1))
+    }
+
+    /// Warning if the last `ANOMALY_WINDOW` blocks look attacked (see
+    /// `detect_anomaly`). The genesis timestamp predates mining, so block 1
+    /// is the earliest start.
+    pub fn anomaly_warning(&self) -> Option<String> {
+        if self.height <= ANOMALY_WINDOW { return None; }
+        detect_anomaly(&self.headers_in_range(self.height - ANOMALY_WINDOW, ANOMALY_WINDOW as u32 + 1))
+    }
+
+    /// Active-chain block hash at `height` (works for pruned blocks too)
+    pub fn hash_at_height(&self, height: u64) -> Option<Hash256> {
+        self.height_index.get(&height).copied()
+    }
+
+    /// Fee rates (base units per byte) of a block's non-coinbase transactions,
+    /// using the block's undo data for input amounts. None if undo data is gone.
+    pub fn block_fee_rates(&self, hash: &Hash256) -> Option<Vec<f64>> {
+        let fees = self.block_tx_fees(hash)?;
+        Some(fees.into_iter().map(|(fee, size)| fee as f64 / size.max(1) as f64).collect())
+    }
+
+    /// (fee, size) of each non-coinbase transaction in a stored block, from its undo data
+    fn block_tx_fees(&self, hash: &Hash256) -> Option<Vec<(u64, usize)>> {
+        let block = self.blocks.get(hash)?;
+        let undo = match self.undo_cache.get(hash) {
+            Some(u) => u.clone(),
+            None => BlockUndo::from_stored(self.storage.as_ref()?.get_undo(hash).ok()??),
+        };
+        let spent: HashMap<&OutPoint, u64> = undo.spent.iter().map(|(op, e)| (op, e.output.amount)).collect();
+
+        let mut fees = Vec::with_capacity(block.transactions.len().saturating_sub(1));
+        for tx in block.transactions.iter().skip(1) {
+            let input_sum: u64 = tx.inputs.iter().map(|i| spent.get(&i.previous_output).copied()).sum::<Option<u64>>()?;
+            fees.push((input_sum.saturating_sub(tx.total_output()), tx.size()));
+        }
+        Some(fees)
+    }
+
+    /// Aggregates for a stored block. Fees come from undo data when it's
+    /// still around, otherwise from the coinbase (see `BlockStats::fees_from_undo`).
+    pub fn block_stats(&self, hash: &Hash256) -> Option<BlockStats> {
+        let block = self.blocks.get(hash)?;
+        let height = block.header.height;
+        let subsidy = block_reward(height);
+        let coinbase_total = block.transactions.first().map(|tx| tx.total_output()).unwrap_or(0);
+        let non_coinbase = &block.transactions[1.min(block.transactions.len())..];
+        let tx_bytes: usize = non_coinbase.iter().map(|tx| tx.size()).sum();
+
+        let exact = self.block_tx_fees(hash);
+        let total_fees = match &exact {
+            Some(fees) => fees.iter().map(|(fee, _)| fee).sum(),
+            None => coinbase_total.saturating_sub(subsidy),
+        };
+        Some(BlockStats {
+            height,
+            hash: *hash,
+            size: block.size(),
+            tx_count: block.transactions.len(),
+            total_out: non_coinbase.iter().map(|tx| tx.total_output()).sum(),
+            total_fees,
+            avg_fee_rate: (tx_bytes > 0).then(|| total_fees as f64 / tx_bytes as f64),
+            subsidy,
+            coinbase_total,
+            fees_from_undo: exact.is_some(),
+        })
+    }
+
+    /// Per-block fee rates for the last `blocks` active-chain blocks, newest first
+    pub fn recent_fee_rates(&self, blocks: usize) -> Vec<Vec<f64>> {
+        (0..=self.height).rev().take(blocks)
+            .filter_map(|h| self.height_index.get(&h))
+            .filter_map(|hash| self.block_fee_rates(hash))
+            .collect()
+    }
+
     pub fn header(&self, hash: &Hash256) -> Option<&BlockHeader> { self.headers.get(hash) }
 
     pub fn tip_header(&self) -> &BlockHeader { self.headers.get(&self.tip).unwrap() }
 
     pub fn is_persistent(&self) -> bool { self.storage.is_some() }
 
+    /// The backing database, if the chain is persistent
+    pub fn storage(&self) -> Option<&Storage> { self.storage.as_ref() }
+
+    /// Median of the last `MEDIAN_TIME_SPAN` active-chain timestamps
+    pub fn median_time_past(&self) -> u64 {
+        let start = self.recent_timestamps.len().saturating_sub(MEDIAN_TIME_SPAN);
+        let mut window = self.recent_timestamps[start..].to_vec();
+        window.sort_unstable();
+        window.get(window.len() / 2).copied().unwrap_or(0)
+    }
+
+    /// Median-time-past as seen by a child of `hash`: the median of the last
+    /// `MEDIAN_TIME_SPAN` timestamps ending at `hash`, on whichever branch it is
+    pub fn median_time_past_at(&self, hash: &Hash256) -> u64 {
+        let mut window = Vec::with_capacity(MEDIAN_TIME_SPAN);
+        let mut cursor = *hash;
+        while window.len() < MEDIAN_TIME_SPAN {
+            let Some(header) = self.headers.get(&cursor) else { break };
+            window.push(header.timestamp);
+            if header.height == 0 { break; }
+            cursor = header.prev_hash;
+        }
+        window.sort_unstable();
+        window.get(window.len() / 2).copied().unwrap_or(0)
+    }
+
+    /// Whether the persistent txid index is maintained
+    pub fn tx_index_enabled(&self) -> bool { self.options.tx_index }
+
+    /// Every output received by and input spent from an address on the active
+    /// chain, ordered by height. Errors if the address index is disabled.
+    pub fn address_history(&self, pubkey_hash: &Hash256) -> Result<Vec<StoredAddressEntry>, String> {
+        if !self.options.address_index {
+            return Err("address index disabled (start the node with --addressindex)".into());
+        }
+        let storage = self.storage.as_ref().ok_or("address index requires persistent storage")?;
+        storage.get_address_history(pubkey_hash).map_err(|e| e.to_string())
+    }
+
+    /// Locate a confirmed transaction on the active chain by its (v1) txid
+    pub fn tx_location(&self, txid: &Hash256) -> Option<TxLocation> {
+        self.tx_index.get(txid).copied()
+    }
+
+    /// Confirmed transaction plus its containing block
+    pub fn get_transaction(&self, txid: &Hash256) -> Option<(&Block, &Transaction)> {
+        let loc = self.tx_index.get(txid)?;
+        let block = self.blocks.get(&loc.block_hash)?;
+        Some((block, block.transactions.get(loc.index as usize)?))
+    }
+
+    /// Total coins emitted by block rewards on the active chain (base units)
+    pub fn money_supply(&self) -> u64 { self.money_supply }
+
+    /// UTXO count and total amount at the current tip. Walking the set is
+    /// O(n), so the result is cached until the next block is (dis)connected.
+    pub fn utxo_set_info(&self) -> UtxoSetInfo {
+        let mut cache = self.utxo_info_cache.lock().unwrap_or_else(|e| e.into_inner());
+        // The digest check also catches direct edits to the public `utxo_set`
+        if let Some(info) = cache.as_ref().filter(|i| i.tip == self.tip && i.digest == self.utxo_set.digest()) {
+            return info.clone();
+        }
+        let info = UtxoSetInfo {
+            height: self.height,
+            tip: self.tip,
+            utxos: self.utxo_set.len(),
+            total_amount: self.utxo_set.iter().map(|(_, e)| e.output.amount).sum(),
+            digest: self.utxo_set.digest(),
+        };
+        *cache = Some(info.clone());
+        info
+    }
+
+    /// UTXO balances grouped by `pubkey_hash`, largest first (ties by hash).
+    /// Walking the whole set is O(n), so the result is cached until the next
+    /// block is connected or disconnected.
+    pub fn rich_list(&self) -> Arc<RichList> {
+        let mut cache = self.rich_list_cache.lock().unwrap_or_else(|e| e.into_inner());
+        let digest = self.utxo_set.digest();
+        if let Some(list) = cache.as_ref().filter(|l| l.tip == self.tip && l.digest == digest) {
+            return list.clone();
+        }
+        let mut by_hash: HashMap<Hash256, (u64, usize)> = HashMap::new();
+        for (_, entry) in self.utxo_set.iter() {
+            let slot = by_hash.entry(entry.output.pubkey_hash).or_default();
+            slot.0 += entry.output.amount;
+            slot.1 += 1;
+        }
+        let mut entries: Vec<RichListEntry> = by_hash.into_iter()
+            .map(|(pubkey_hash, (balance, utxos))| RichListEntry { pubkey_hash, balance, utxos })
+            .collect();
+        entries.sort_by(|a, b| b.balance.cmp(&a.balance).then(a.pubkey_hash.cmp(&b.pubkey_hash)));
+        let list = Arc::new(RichList { tip: self.tip, digest, entries });
+        *cache = Some(list.clone());
+        list
+    }
+
+    fn invalidate_utxo_info(&mut self) {
+        *self.utxo_info_cache.get_mut().unwrap_or_else(|e| e.into_inner()) = None;
+        *self.rich_list_cache.get_mut().unwrap_or_else(|e| e.into_inner()) = None;
+    }
+
+    /// Whether startup skipped the UTXO consistency walk thanks to a matching snapshot
+    pub fn started_from_snapshot(&self) -> bool { self.fast_start }
+
     pub fn validate_transaction_for_mempool(&self, tx: &Transaction) -> Result<(), BlockError> {
         if tx.is_coinbase() {
             return Err(BlockError::InvalidTransaction("coinbase not allowed in mempool".into()));
         }
-        self.validate_transaction(tx, self.height + 1)
+        let (height, median_time) = (self.height + 1, self.median_time_past_at(&self.tip));
+        if !tx.is_final(height, median_time) {
+            return Err(BlockError::NonFinalTransaction);
+        }
+        self.validate_transaction(tx, height, median_time)?;
+        // Relay policy only, as older blocks hold outputs that break it: refuse
+        // to create outputs nobody knows how to spend, or change of dust size
+        for (idx, output) in tx.outputs.iter().enumerate() {
+            if let Err(e) = script::classify_output(output) {
+                return Err(BlockError::InvalidTransaction(format!("output {} has unsupported type: {:?}", idx, e)));
+            }
+            if output.amount > 0 && output.amount < DUST_THRESHOLD {
+                return Err(BlockError::DustOutput { index: idx, amount: output.amount });
+            }
+        }
+        Ok(())
     }
 
     pub fn total_known_blocks(&self) -> usize { self.blocks.len() }
@@ -1000,6 +2224,64 @@ impl Chain {
             .collect()
     }
 
+    /// Tally version-bit signaling over the last `window` blocks ending at the tip
+    pub fn version_bit_stats(&self, window: u64) -> VersionBitsStats {
+        let mut stats = VersionBitsStats {
+            blocks: 0, start_height: self.height, end_height: self.height,
+            signaling_blocks: 0, bit_counts: [0; VERSIONBITS_NUM_BITS as usize],
+        };
+        let mut current = self.tip;
+        while stats.blocks < window {
+            let header = match self.headers.get(&current) { Some(h) => h, None => break };
+            stats.blocks += 1;
+            stats.start_height = header.height;
+            if header.version & VERSIONBITS_TOP_MASK == VERSIONBITS_TOP_BITS {
+                stats.signaling_blocks += 1;
+                let bits = version_signal_bits(header.version);
+                for (bit, count) in stats.bit_counts.iter_mut().enumerate() {
+                    if bits & (1 << bit) != 0 { *count += 1; }
+                }
+            }
+            if header.prev_hash == NULL_HASH { break; }
+            current = header.prev_hash;
+        }
+        stats
+    }
+
+    /// Activation state of `bit` for the block after the tip. Walks every
+    /// completed period, so it costs one header lookup per active-chain block.
+    pub fn deployment_status(&self, bit: u32) -> DeploymentStatus {
+        let next = self.height + 1;
+        let period_start = next - next % VERSIONBITS_PERIOD;
+
+        let locked_in = (0..period_start).step_by(VERSIONBITS_PERIOD as usize)
+            .find(|&start| self.count_signaled(bit, start, start + VERSIONBITS_PERIOD) >= VERSIONBITS_THRESHOLD)
+            .map(|start| start + VERSIONBITS_PERIOD);
+        let (state, since) = match locked_in {
+            Some(h) if h + VERSIONBITS_PERIOD <= next => (DeploymentState::Active, h + VERSIONBITS_PERIOD),
+            Some(h) => (DeploymentState::LockedIn, h),
+            None => (DeploymentState::Started, 0),
+        };
+        DeploymentStatus {
+            bit, state, since, period_start,
+            period_blocks: next - period_start,
+            period_signaled: self.count_signaled(bit, period_start, next),
+        }
+    }
+
+    /// Whether the deployment on `bit` is enforced for the next block
+    pub fn deployment_active(&self, bit: u32) -> bool {
+        self.deployment_status(bit).state == DeploymentState::Active
+    }
+
+    /// Active-chain blocks in heights `start..end` setting version bit `bit`
+    fn count_signaled(&self, bit: u32, start: u64, end: u64) -> u64 {
+        (start..end)
+            .filter_map(|h| self.height_index.get(&h).and_then(|hash| self.headers.get(hash)))
+            .filter(|header| version_signal_bits(header.version) & (1 << bit) != 0)
+            .count() as u64
+    }
+
     pub fn genesis_hash(&self) -> Hash256 {
         self.height_index.get(&0).copied().unwrap_or(NULL_HASH)
     }
@@ -1007,14 +2289,18 @@ impl Chain {
 
 // ─── Errors ─────────────────────────────────────────────────────────
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum BlockError {
     DuplicateBlock, OrphanBlock, InvalidHeight, InvalidPrevHash,
-    InvalidTimestamp, TimestampTooFarInFuture,
+    InvalidTimestamp, TimestampTooOld, TimestampTooFarInFuture,
     InvalidDifficulty { expected: u32, got: u32 },
-    InsufficientPoW, InvalidMerkleRoot, BlockTooLarge,
-    NoTransactions, NoCoinbase, InvalidCoinbaseAmount,
+    InsufficientPoW, InvalidMerkleRoot, BlockTooLarge, TooManySigops,
+    NoTransactions, NoCoinbase, InvalidCoinbaseAmount, DuplicateCoinbase,
+    InvalidCommunityFund { required: u64, paid: u64 },
     InvalidTransaction(String),
+    NonFinalTransaction,
+    DustOutput { index: usize, amount: u64 },
+    ReorgTooDeep { depth: u64, max: u64 },
 }
 
 impl std::fmt::Display for BlockError {
@@ -1025,40 +2311,1057 @@ impl std::fmt::Display for BlockError {
             BlockError::InvalidHeight => write!(f, "invalid height"),
             BlockError::InvalidPrevHash => write!(f, "prev_hash mismatch"),
             BlockError::InvalidTimestamp => write!(f, "invalid timestamp"),
+            BlockError::TimestampTooOld => write!(f, "timestamp not after median-time-past"),
             BlockError::TimestampTooFarInFuture => write!(f, "timestamp too far in future"),
             BlockError::InvalidDifficulty { expected, got } => write!(f, "difficulty mismatch ({} vs {})", expected, got),
             BlockError::InsufficientPoW => write!(f, "insufficient PoW"),
             BlockError::InvalidMerkleRoot => write!(f, "invalid merkle root"),
             BlockError::BlockTooLarge => write!(f, "block too large"),
+            BlockError::TooManySigops => write!(f, "too many signature checks"),
             BlockError::NoTransactions => write!(f, "no transactions"),
             BlockError::NoCoinbase => write!(f, "no coinbase"),
             BlockError::InvalidCoinbaseAmount => write!(f, "coinbase amount too large"),
+            BlockError::DuplicateCoinbase => write!(f, "coinbase txid matches an unspent earlier coinbase"),
+            BlockError::InvalidCommunityFund { required, paid } => write!(f, "coinbase pays {} to the community fund, {} required", paid, required),
             BlockError::InvalidTransaction(msg) => write!(f, "invalid tx: {}", msg),
+            BlockError::NonFinalTransaction => write!(f, "non-final transaction (lock_time not reached)"),
+            BlockError::DustOutput { index, amount } => write!(f, "output {} of {} is below the dust threshold {}", index, amount, DUST_THRESHOLD),
+            BlockError::ReorgTooDeep { depth, max } => write!(f, "reorg of {} blocks exceeds max depth {}", depth, max),
         }
     }
 }
 impl std::error::Error for BlockError {}
 
+impl BlockError {
+    /// Short stable label for metrics
+    pub fn reason(&self) -> &'static str {
+        match self {
+            BlockError::DuplicateBlock => "duplicate",
+            BlockError::OrphanBlock => "orphan",
+            BlockError::InvalidHeight => "bad_height",
+            BlockError::InvalidPrevHash => "bad_prev_hash",
+            BlockError::InvalidTimestamp => "bad_timestamp",
+            BlockError::TimestampTooOld => "timestamp_too_old",
+            BlockError::TimestampTooFarInFuture => "timestamp_in_future",
+            BlockError::InvalidDifficulty { .. } => "bad_difficulty",
+            BlockError::InsufficientPoW => "insufficient_pow",
+            BlockError::InvalidMerkleRoot => "bad_merkle_root",
+            BlockError::BlockTooLarge => "too_large",
+            BlockError::TooManySigops => "too_many_sigops",
+            BlockError::NoTransactions => "no_transactions",
+            BlockError::NoCoinbase => "no_coinbase",
+            BlockError::InvalidCoinbaseAmount => "bad_coinbase_amount",
+            BlockError::DuplicateCoinbase => "duplicate_coinbase",
+            BlockError::InvalidCommunityFund { .. } => "bad_community_fund",
+            BlockError::InvalidTransaction(_) => "invalid_transaction",
+            BlockError::NonFinalTransaction => "non_final_transaction",
+            BlockError::DustOutput { .. } => "dust_output",
+            BlockError::ReorgTooDeep { .. } => "reorg_too_deep",
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    fn test_chain() -> Chain {
+        let _ = std::panic::catch_unwind(|| init_network(false));
+        Chain::new()
+    }
+
+    /// Insert a bare header on top of `parent` (enough for ancestry walks)
+    fn insert_header(chain: &mut Chain, parent: Hash256, nonce: u64) -> Hash256 {
+        insert_header_with_version(chain, parent, nonce, PROTOCOL_VERSION)
+    }
+
+    fn insert_header_with_version(chain: &mut Chain, parent: Hash256, nonce: u64, version: u32) -> Hash256 {
+        let parent_header = chain.headers.get(&parent).unwrap().clone();
+        let header = BlockHeader {
+            version, prev_hash: parent, merkle_root: NULL_HASH,
+            timestamp: parent_header.timestamp + TARGET_BLOCK_TIME,
+            difficulty_target: INITIAL_DIFFICULTY, nonce, height: parent_header.height + 1,
+        };
+        let hash = header.hash();
+        chain.children.entry(parent).or_default().push(hash);
+        chain.headers.insert(hash, header);
+        hash
+    }
+
+    fn temp_data_dir(name: &str) -> std::path::PathBuf {
+        let dir = std::env::temp_dir().join(format!("equiforge-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        dir
+    }
+
+    #[test]
+    fn test_state_snapshot_fast_and_full_start() {
+        let _ = std::panic::catch_unwind(|| init_network(false));
+        let dir = temp_data_dir("state-snapshot");
+
+        let chain = Chain::open(&dir).unwrap();
+        let expected_digest = chain.utxo_set.digest();
+        drop(chain);
+
+        // Matching snapshot → consistency walk skipped
+        let chain = Chain::open(&dir).unwrap();
+        assert!(chain.started_from_snapshot());
+        assert_eq!(chain.utxo_set.digest(), expected_digest);
+        drop(chain);
+
+        // Diverge sled from the blocks: drop a UTXO behind the chain's back
+        {
+            let storage = Storage::open(&dir).unwrap();
+            let (op, _) = storage.load_all_utxos().unwrap().into_iter().next().unwrap();
+            storage.remove_utxo(&op).unwrap();
+            storage.flush().unwrap();
+        }
+
+        // Mismatched snapshot → full walk repairs the set and rewrites the snapshot
+        let chain = Chain::open(&dir).unwrap();
+        assert!(!chain.started_from_snapshot());
+        assert_eq!(chain.utxo_set.digest(), expected_digest);
+        drop(chain);
+
+        let chain = Chain::open(&dir).unwrap();
+        assert!(chain.started_from_snapshot());
+        drop(chain);
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_utxo_digest_is_order_independent() {
+        let entry = |amount| UtxoEntry {
+            output: TxOutput { amount, pubkey_hash: [7u8; 32], script_pubkey: vec![] }, height: 1, is_coinbase: false,
+        };
+        let a = OutPoint { txid: [1u8; 32], vout: 0 };
+        let b = OutPoint { txid: [2u8; 32], vout: 1 };
+
+        let mut x = UtxoSet::new();
+        x.add(a.clone(), entry(10));
+        x.add(b.clone(), entry(20));
+        let mut y = UtxoSet::new();
+        y.add(b.clone(), entry(20));
+        y.add(a.clone(), entry(10));
+        assert_eq!(x.digest(), y.digest());
+
+        x.spend(&b);
+        assert_ne!(x.digest(), y.digest());
+        y.spend(&b);
+        assert_eq!(x.digest(), y.digest());
+        y.spend(&a);
+        assert_eq!(y.digest(), NULL_HASH);
+    }
+
+    /// Give `kp` a spendable UTXO locked by `script_pubkey` and build a signed spend of it
+    fn signed_spend(chain: &mut Chain, kp: &crate::crypto::Keypair, script_pubkey: Vec<u8>, outputs: Vec<TxOutput>) -> Transaction {
+        let pubkey_hash = crate::crypto::pubkey_bytes_to_hash(&kp.public_key_bytes());
+        let prev_output = TxOutput { amount: 10 * MIN_TX_FEE, pubkey_hash, script_pubkey };
+        let outpoint = OutPoint { txid: [0x42; 32], vout: 0 };
+        chain.utxo_set.add(outpoint.clone(), UtxoEntry { output: prev_output.clone(), height: 0, is_coinbase: false });
+
+        let mut tx = Transaction {
+            version: 1,
+            inputs: vec![TxInput { previous_output: outpoint, signature: vec![], pubkey: vec![], script_sig: vec![], sequence: 0xFFFFFFFF }],
+            outputs,
+            lock_time: 0,
+        };
+        let sig = kp.sign_hash(&crate::crypto::tx_signing_hash_v1(&tx, 0, &prev_output));
+        tx.inputs[0].script_sig = script::script_sig_p2pkh(&sig, &kp.public_key_bytes());
+        tx
+    }
+
+    /// `mine_on_tip` with `tx` included and its fee claimed by the coinbase
+    fn block_with_tx(chain: &Chain, tx: Transaction) -> Block {
+        let mut block = mine_on_tip(chain, "tx");
+        let height = block.header.height;
+        let input: u64 = tx.inputs.iter().map(|i| chain.utxo_set.get(&i.previous_output).unwrap().output.amount).sum();
+        let reward = block_reward(height) + input - tx.total_output();
+        block.transactions[0] = Transaction::new_coinbase(height, reward, [1u8; 32], COMMUNITY_FUND_HASH, "tx");
+        block.transactions.push(tx);
+        block.header.merkle_root = block.compute_merkle_root();
+        while !block.header.meets_difficulty() { block.header.nonce += 1; }
+        block
+    }
+
+    #[test]
+    fn test_output_type_dispatch() {
+        let mut chain = test_chain();
+        let kp = crate::crypto::Keypair::generate();
+        let own_hash = crate::crypto::pubkey_bytes_to_hash(&kp.public_key_bytes());
+        let pay = |script_pubkey: Vec<u8>| vec![TxOutput { amount: MIN_TX_FEE, pubkey_hash: [9u8; 32], script_pubkey }];
+
+        // Type 0 (P2PKH template) validates as before
+        let tx = signed_spend(&mut chain, &kp, script::script_p2pkh(&own_hash), pay(script::script_p2pkh(&[9u8; 32])));
+        assert!(chain.validate_transaction_for_mempool(&tx).is_ok());
+
+        // Spending an output with a reserved type tag is rejected, not read as pubkey-hash
+        let tx = signed_spend(&mut chain, &kp, vec![0x05, 1, 2, 3], pay(vec![]));
+        let err = chain.validate_transaction_for_mempool(&tx).unwrap_err().to_string();
+        assert!(err.contains("UnknownOutputType(5)"), "{}", err);
+
+        // So is relaying a tx that creates one, though a block may hold it
+        let tx = signed_spend(&mut chain, &kp, vec![], pay(vec![0x07]));
+        let err = chain.validate_transaction_for_mempool(&tx).unwrap_err().to_string();
+        assert!(err.contains("output 0 has unsupported type"), "{}", err);
+        let block = block_with_tx(&chain, tx);
+        chain.add_block(block).unwrap();
+
+        // A P2PKH script must lock to the same hash as the pubkey_hash field
+        let tx = signed_spend(&mut chain, &kp, vec![], pay(script::script_p2pkh(&[8u8; 32])));
+        assert!(chain.validate_transaction_for_mempool(&tx).is_err());
+    }
+
+    #[test]
+    fn test_non_final_tx_rejected_from_mempool() {
+        let mut chain = test_chain();
+        let kp = crate::crypto::Keypair::generate();
+        let own_hash = crate::crypto::pubkey_bytes_to_hash(&kp.public_key_bytes());
+        let script_pubkey = script::script_p2pkh(&own_hash);
+        let pay = vec![TxOutput { amount: MIN_TX_FEE, pubkey_hash: [9u8; 32], script_pubkey: script::script_p2pkh(&[9u8; 32]) }];
+        let prev_output = TxOutput { amount: 10 * MIN_TX_FEE, pubkey_hash: own_hash, script_pubkey: script_pubkey.clone() };
+        let relock = |mut tx: Transaction, lock_time: u64, sequence: u32| {
+            tx.lock_time = lock_time;
+            tx.inputs[0].sequence = sequence;
+            let sig = kp.sign_hash(&crate::crypto::tx_signing_hash_v1(&tx, 0, &prev_output));
+            tx.inputs[0].script_sig = script::script_sig_p2pkh(&sig, &kp.public_key_bytes());
+            tx
+        };
+        let tx = signed_spend(&mut chain, &kp, script_pubkey, pay);
+
+        // Next block is height 1: a lock at height 5 isn't reached yet
+        let locked = relock(tx.clone(), 5, 0);
+        assert!(matches!(chain.validate_transaction_for_mempool(&locked), Err(BlockError::NonFinalTransaction)));
+        // ...unless the input opts out
+        assert!(chain.validate_transaction_for_mempool(&relock(tx.clone(), 5, SEQUENCE_FINAL)).is_ok());
+        assert!(chain.validate_transaction_for_mempool(&relock(tx.clone(), 0, 0)).is_ok());
+
+        // Time locks compare with median-time-past
+        let mtp = chain.median_time_past_at(&chain.tip);
+        assert!(chain.validate_transaction_for_mempool(&relock(tx.clone(), mtp - 1, 0)).is_ok());
+        assert!(matches!(chain.validate_transaction_for_mempool(&relock(tx.clone(), mtp + 3600, 0)), Err(BlockError::NonFinalTransaction)));
+
+        // Blocks only honor locks from the activation height
+        let late = relock(tx.clone(), LOCKTIME_HEIGHT + 5, 0);
+        assert!(matches!(chain.validate_transaction(&late, LOCKTIME_HEIGHT, mtp), Err(BlockError::NonFinalTransaction)));
+        assert!(chain.validate_transaction(&late, LOCKTIME_HEIGHT + 6, mtp).is_ok());
+        let block = block_with_tx(&chain, locked);
+        chain.add_block(block).unwrap();
+        assert_eq!(chain.height, 1);
+    }
+
+    #[test]
+    fn test_dust_output_rejected() {
+        let mut chain = test_chain();
+        let kp = crate::crypto::Keypair::generate();
+        let own_hash = crate::crypto::pubkey_bytes_to_hash(&kp.public_key_bytes());
+        let out = |amount: u64| TxOutput { amount, pubkey_hash: [9u8; 32], script_pubkey: script::script_p2pkh(&[9u8; 32]) };
+
+        let tx = signed_spend(&mut chain, &kp, script::script_p2pkh(&own_hash), vec![out(MIN_TX_FEE), out(DUST_THRESHOLD - 1)]);
+        assert!(matches!(chain.validate_transaction_for_mempool(&tx),
+            Err(BlockError::DustOutput { index: 1, amount }) if amount == DUST_THRESHOLD - 1));
+
+        // Blocks may still carry it
+        let block = block_with_tx(&chain, tx);
+        chain.add_block(block).unwrap();
+
+        // Exactly at the threshold is fine, and so is a zero-value output
+        let tx = signed_spend(&mut chain, &kp, script::script_p2pkh(&own_hash), vec![out(DUST_THRESHOLD), out(0)]);
+        assert!(chain.validate_transaction_for_mempool(&tx).is_ok());
+    }
+
+    #[test]
+    fn test_legacy_input_sighash_follows_tx_version() {
+        let mut chain = test_chain();
+        let kp = crate::crypto::Keypair::generate();
+        let pubkey_hash = crate::crypto::pubkey_bytes_to_hash(&kp.public_key_bytes());
+        let prev_output = TxOutput { amount: 10 * MIN_TX_FEE, pubkey_hash, script_pubkey: vec![] };
+        let outpoint = OutPoint { txid: [0x43; 32], vout: 0 };
+        chain.utxo_set.add(outpoint.clone(), UtxoEntry { output: prev_output.clone(), height: 0, is_coinbase: false });
+
+        // Legacy signature+pubkey fields only, no script_sig
+        let unsigned = |version: u32| Transaction {
+            version,
+            inputs: vec![TxInput { previous_output: outpoint.clone(), signature: vec![], pubkey: kp.public_key_bytes().to_vec(), script_sig: vec![], sequence: 0xFFFFFFFF }],
+            outputs: vec![TxOutput { amount: MIN_TX_FEE, pubkey_hash: [9u8; 32], script_pubkey: vec![] }],
+            lock_time: 0,
+        };
+        let signed = |mut tx: Transaction, hash: Hash256| { tx.inputs[0].signature = kp.sign_hash(&hash).to_vec(); tx };
+
+        let v1 = unsigned(1);
+        let old = crate::wallet::tx_signing_hash(&v1, 0);
+        assert!(chain.validate_transaction_for_mempool(&signed(v1.clone(), old)).is_ok());
+
+        let v2 = unsigned(TX_VERSION_SIGHASH_V1);
+        let new = crate::crypto::tx_signing_hash_v1(&v2, 0, &prev_output);
+        assert!(chain.validate_transaction_for_mempool(&signed(v2.clone(), new)).is_ok());
+        // The old digest no longer authorizes a v2 spend
+        let old = crate::wallet::tx_signing_hash(&v2, 0);
+        assert!(chain.validate_transaction_for_mempool(&signed(v2, old)).is_err());
+    }
+
+    #[test]
+    fn test_multisig_2_of_3_spend() {
+        let mut chain = test_chain();
+        let signers: Vec<crate::wallet::Wallet> = (0..3).map(|i| crate::wallet::Wallet::new(&format!("s{}", i))).collect();
+        let key_hashes: Vec<Hash256> = signers.iter().map(|w| w.primary_pubkey_hash()).collect();
+        let (_, lock) = crate::wallet::create_multisig(2, &key_hashes).unwrap();
+
+        let prev_output = script::output_multisig(10 * MIN_TX_FEE, lock);
+        let outpoint = OutPoint { txid: [0x43; 32], vout: 0 };
+        chain.utxo_set.add(outpoint.clone(), UtxoEntry { output: prev_output.clone(), height: 0, is_coinbase: false });
+
+        let mut tx = Transaction {
+            version: 1,
+            inputs: vec![TxInput { previous_output: outpoint, signature: vec![], pubkey: vec![], script_sig: vec![], sequence: 0xFFFFFFFF }],
+            outputs: vec![TxOutput { amount: MIN_TX_FEE, pubkey_hash: [9u8; 32], script_pubkey: script::script_p2pkh(&[9u8; 32]) }],
+            lock_time: 0,
+        };
+
+        // One of three signatures is not enough
+        assert_eq!(signers[2].sign_multisig_input(&mut tx, 0, &prev_output).unwrap(), 1);
+        let err = chain.validate_transaction_for_mempool(&tx).unwrap_err().to_string();
+        assert!(err.contains("NotEnoughSignatures"), "{}", err);
+
+        // A second co-signer completes it; re-signing adds nothing
+        assert_eq!(signers[0].sign_multisig_input(&mut tx, 0, &prev_output).unwrap(), 1);
+        assert_eq!(signers[0].sign_multisig_input(&mut tx, 0, &prev_output).unwrap(), 0);
+        assert!(chain.validate_transaction_for_mempool(&tx).is_ok());
+
+        // A signature from a key outside the set is rejected
+        let outsider = crate::wallet::Wallet::new("x");
+        let mut sigs = script::parse_script_sig_multisig(&tx.inputs[0].script_sig).unwrap();
+        let other_pk: [u8; 32] = outsider.keypairs[0].public_key_bytes().try_into().unwrap();
+        sigs[0].1 = other_pk;
+        tx.inputs[0].script_sig = script::script_sig_multisig(&sigs);
+        assert!(chain.validate_transaction_for_mempool(&tx).is_err());
+
+        // Listing one key twice doesn't let it sign for two of the slots
+        let dup = [key_hashes[0], key_hashes[0], key_hashes[1]];
+        assert!(crate::wallet::create_multisig(2, &dup).is_err());
+        let mut lock = vec![script::OUTPUT_TYPE_MULTISIG, 2, 3];
+        dup.iter().for_each(|h| lock.extend_from_slice(h));
+        let prev_output = script::output_multisig(10 * MIN_TX_FEE, lock);
+        let outpoint = OutPoint { txid: [0x44; 32], vout: 0 };
+        chain.utxo_set.add(outpoint.clone(), UtxoEntry { output: prev_output.clone(), height: 0, is_coinbase: false });
+        tx.inputs[0].previous_output = outpoint;
+        tx.inputs[0].script_sig.clear();
+        signers[0].sign_multisig_input(&mut tx, 0, &prev_output).unwrap();
+        let mut sigs = script::parse_script_sig_multisig(&tx.inputs[0].script_sig).unwrap();
+        sigs.push((1, sigs[0].1, sigs[0].2));
+        tx.inputs[0].script_sig = script::script_sig_multisig(&sigs);
+        let err = chain.validate_transaction_for_mempool(&tx).unwrap_err().to_string();
+        assert!(err.contains("NotEnoughSignatures"), "{}", err);
+    }
+
+    fn coinbase_block(prev_hash: Hash256, height: u64, tag: &str) -> Block {
+        Block {
+            header: BlockHeader {
+                version: PROTOCOL_VERSION, prev_hash, merkle_root: NULL_HASH,
+                timestamp: genesis_timestamp() + height * TARGET_BLOCK_TIME, difficulty_target: INITIAL_DIFFICULTY,
+                nonce: 0, height,
+            },
+            transactions: vec![Transaction::new_coinbase(height, block_reward(height), [1u8; 32], COMMUNITY_FUND_HASH, tag)],
+        }
+    }
+
+    #[test]
+    fn test_money_supply_tracks_active_chain() {
+        let mut chain = test_chain();
+        let schedule = |h: u64| (0..=h).map(block_reward).sum::<u64>();
+        assert_eq!(chain.money_supply(), schedule(0));
+
+        // Extend by three blocks
+        let mut undos = Vec::new();
+        let mut prev = chain.tip;
+        let mut fork_point = prev;
+        for h in 1..=3 {
+            let block = coinbase_block(prev, h, "a");
+            prev = block.header.id_hash();
+            if h == 1 { fork_point = prev; }
+            undos.push((h, chain.connect_block_utxos(&prev, &block)));
+        }
+        assert_eq!(chain.money_supply(), schedule(3));
+
+        // Reorg: drop back to height 1, then connect a longer fork
+        for (h, undo) in undos.iter().rev().take(2) {
+            chain.disconnect_block_utxos(*h, undo);
+        }
+        assert_eq!(chain.money_supply(), schedule(1));
+        prev = fork_point;
+        for h in 2..=5 {
+            let block = coinbase_block(prev, h, "b");
+            prev = block.header.id_hash();
+            chain.connect_block_utxos(&prev, &block);
+        }
+        assert_eq!(chain.money_supply(), schedule(5));
+    }
+
+    #[test]
+    fn test_utxo_set_info_cached_until_next_block() {
+        let mut chain = test_chain();
+        let info = chain.utxo_set_info();
+        assert_eq!(info.utxos, chain.utxo_set.len());
+        assert_eq!(info.total_amount, chain.money_supply());
+        assert!(chain.utxo_info_cache.lock().unwrap().is_some());
+
+        let block = coinbase_block(chain.tip, 1, "info");
+        extend_tip(&mut chain, block);
+        assert!(chain.utxo_info_cache.lock().unwrap().is_none());
+        let info = chain.utxo_set_info();
+        assert_eq!((info.height, info.tip), (1, chain.tip));
+        assert_eq!(info.total_amount, block_reward(0) + block_reward(1));
+        assert_eq!(info.total_amount, chain.money_supply());
+    }
+
+    #[test]
+    fn test_rich_list() {
+        let mut chain = test_chain();
+        let first = chain.rich_list();
+        assert_eq!(first.entries.iter().map(|e| e.utxos).sum::<usize>(), chain.utxo_set.len());
+        assert!(Arc::ptr_eq(&first, &chain.rich_list()));
+
+        // Two more blocks to one address, one to another
+        let mut rich_balance = 0;
+        for (height, owner) in [(1, 0xAA), (2, 0xAA), (3, 0xBB)] {
+            let mut block = coinbase_block(chain.tip, height, "rich");
+            block.transactions[0].outputs[0].pubkey_hash = [owner; 32];
+            // Underclaim by the height so the two 0xAA coinbases get distinct txids
+            block.transactions[0].outputs[0].amount -= height;
+            if owner == 0xAA { rich_balance += block.transactions[0].outputs[0].amount; }
+            extend_tip(&mut chain, block);
+        }
+        assert!(chain.rich_list_cache.lock().unwrap().is_none());
+        let list = chain.rich_list();
+        assert_eq!(list.tip, chain.tip);
+        assert_eq!(list.entries[0], RichListEntry { pubkey_hash: [0xAA; 32], balance: rich_balance, utxos: 2 });
+        assert!(list.entries.windows(2).all(|w| w[0].balance >= w[1].balance));
+        assert_eq!(list.entries.iter().map(|e| e.balance).sum::<u64>(), chain.utxo_set_info().total_amount);
+    }
+
+    #[test]
+    fn test_tx_index_follows_active_chain() {
+        let mut chain = test_chain();
+        let genesis_cb = crate::crypto::txid::txid_v1(&chain.block_at_height(0).unwrap().transactions[0]);
+        assert_eq!(chain.tx_location(&genesis_cb), Some(TxLocation { block_hash: chain.tip, index: 0 }));
+
+        let block = coinbase_block(chain.tip, 1, "idx");
+        let hash = block.header.id_hash();
+        let txid = crate::crypto::txid::txid_v1(&block.transactions[0]);
+        let undo = chain.connect_block_utxos(&hash, &block);
+        chain.blocks.insert(hash, block);
+        let (found_block, found_tx) = chain.get_transaction(&txid).unwrap();
+        assert_eq!(found_block.header.height, 1);
+        assert!(found_tx.is_coinbase());
+
+        chain.disconnect_block_utxos(1, &undo);
+        assert!(chain.tx_location(&txid).is_none());
+        assert!(chain.tx_location(&genesis_cb).is_some());
+    }
+
+    /// Connect `block` on top of the tip and persist it like a normal tip extension
+    fn extend_tip(chain: &mut Chain, block: Block) -> Hash256 {
+        let hash = block.header.id_hash();
+        let undo = chain.connect_block_utxos(&hash, &block);
+        chain.headers.insert(hash, block.header.clone());
+        chain.height_index.insert(block.header.height, hash);
+        chain.blocks.insert(hash, block.clone());
+        chain.tip = hash;
+        chain.height = block.header.height;
+        chain.persist_tip_extension(&hash, &block, &undo);
+        chain.undo_cache.insert(hash, undo);
+        hash
+    }
+
+    #[test]
+    fn test_reorg_deeper_than_limit_refused() {
+        let mut chain = test_chain();
+        chain.options.max_reorg_depth = 2;
+        let genesis = chain.tip;
+
+        let mut prev = genesis;
+        for h in 1..=3 {
+            prev = extend_tip(&mut chain, coinbase_block(prev, h, "main"));
+        }
+        let main_tip = chain.tip;
+
+        // Heavier side chain forking at genesis: switching would disconnect 3
+        let mut side = genesis;
+        for h in 1..=4 {
+            let mut block = coinbase_block(side, h, "side");
+            block.header.nonce = 1;
+            let hash = block.header.id_hash();
+            chain.headers.insert(hash, block.header.clone());
+            chain.blocks.insert(hash, block);
+            side = hash;
+        }
+
+        match chain.reorg_to(side) {
+            Err(BlockError::ReorgTooDeep { depth, max }) => assert_eq!((depth, max), (3, 2)),
+            other => panic!("expected ReorgTooDeep, got {:?}", other),
+        }
+        assert_eq!(chain.tip, main_tip);
+        assert_eq!(chain.height, 3);
+        // The side chain is kept, just not adopted
+        assert!(chain.block_by_hash(&side).is_some());
+    }
+
+    #[test]
+    fn test_reorg_metrics() {
+        let mut chain = test_chain();
+        let genesis = chain.tip;
+        extend_tip(&mut chain, coinbase_block(genesis, 1, "main"));
+
+        let mut side = genesis;
+        for h in 1..=2 {
+            let mut block = coinbase_block(side, h, "side");
+            block.header.nonce = 1;
+            let hash = block.header.id_hash();
+            chain.headers.insert(hash, block.header.clone());
+            chain.blocks.insert(hash, block);
+            side = hash;
+        }
+        chain.reorg_to(side).unwrap();
+        assert_eq!(chain.tip, side);
+
+        let m = chain.metrics();
+        assert_eq!((m.reorgs, m.reorg_depth_sum, m.reorg_depth_max), (1, 1, 1));
+    }
+
+    #[test]
+    fn test_chain_tips() {
+        let mut chain = test_chain();
+        let genesis = chain.tip;
+        assert_eq!(chain.chain_tips().len(), 1);
+        assert_eq!(chain.chain_tips()[0].status, TipStatus::Active);
+
+        let mut prev = genesis;
+        for h in 1..=3 {
+            prev = extend_tip(&mut chain, coinbase_block(prev, h, "main"));
+            chain.children.entry(chain.headers[&prev].prev_hash).or_default().push(prev);
+        }
+        let b1 = chain.height_index[&1];
+
+        // Two-block fork off b1 that was never connected
+        let mut side = b1;
+        for h in 2..=3 {
+            let mut block = coinbase_block(side, h, "side");
+            block.header.nonce = 1;
+            let hash = block.header.id_hash();
+            chain.headers.insert(hash, block.header.clone());
+            chain.blocks.insert(hash, block);
+            chain.children.entry(side).or_default().push(hash);
+            side = hash;
+        }
+        // One-block fork off genesis: connected once (has undo), then left behind
+        let mut once = coinbase_block(genesis, 1, "once");
+        once.header.nonce = 2;
+        let once_hash = once.header.id_hash();
+        chain.headers.insert(once_hash, once.header.clone());
+        chain.undo_cache.insert(once_hash, BlockUndo { spent: vec![], created: vec![] });
+        chain.blocks.insert(once_hash, once);
+        chain.children.entry(genesis).or_default().push(once_hash);
+        // Header with no block body
+        let bare = insert_header(&mut chain, side, 7);
+        // Stored but never connected fork off b2
+        let mut stored = coinbase_block(chain.height_index[&2], 3, "stored");
+        stored.header.nonce = 3;
+        let stored_hash = stored.header.id_hash();
+        chain.headers.insert(stored_hash, stored.header.clone());
+        chain.blocks.insert(stored_hash, stored);
+        chain.children.entry(chain.height_index[&2]).or_default().push(stored_hash);
+
+        let tips = chain.chain_tips();
+        let summary: Vec<(Hash256, u64, u64, TipStatus)> = tips.iter().map(|t| (t.hash, t.height, t.branch_len, t.status)).collect();
+        assert_eq!(summary, vec![
+            (chain.tip, 3, 0, TipStatus::Active),
+            (bare, 4, 3, TipStatus::HeadersOnly),
+            (stored_hash, 3, 1, TipStatus::ValidHeaders),
+            (once_hash, 1, 1, TipStatus::ValidFork),
+        ]);
+        // `side` is no longer a leaf once `bare` builds on it
+        assert!(tips.iter().all(|t| t.hash != side));
+    }
+
+    #[test]
+    fn test_persistent_tx_index_survives_restart() {
+        let _ = std::panic::catch_unwind(|| init_network(false));
+        let dir = temp_data_dir("txindex");
+
+        let mut chain = Chain::open(&dir).unwrap();
+        let block = coinbase_block(chain.tip, 1, "persisted");
+        let txid = crate::crypto::txid::txid_v1(&block.transactions[0]);
+        let hash = extend_tip(&mut chain, block.clone());
+        drop(chain);
+
+        let expected = TxLocation { block_hash: hash, index: 0 };
+        let chain = Chain::open(&dir).unwrap();
+        assert_eq!(chain.tx_location(&txid), Some(expected));
+        let stored = chain.storage.as_ref().unwrap().get_tx_location(&txid).unwrap().unwrap();
+        assert_eq!(stored.vout_count, block.transactions[0].outputs.len() as u32);
+        drop(chain);
+
+        // Disabling drops the on-disk index; lookups still work from memory
+        let chain = Chain::open_with_options(&dir, ChainOptions { tx_index: false, ..Default::default() }).unwrap();
+        assert_eq!(chain.tx_location(&txid), Some(expected));
+        assert!(chain.storage.as_ref().unwrap().get_tx_location(&txid).unwrap().is_none());
+        drop(chain);
+
+        // Re-enabling reindexes from the stored blocks
+        let chain = Chain::open(&dir).unwrap();
+        assert!(chain.storage.as_ref().unwrap().has_tx_index());
+        assert_eq!(chain.storage.as_ref().unwrap().get_tx_location(&txid).unwrap(), Some(stored));
+        drop(chain);
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_prune_keeps_headers_and_recent_blocks() {
+        let _ = std::panic::catch_unwind(|| init_network(false));
+        let dir = temp_data_dir("prune");
+        let mut chain = Chain::open_with_options(&dir, ChainOptions { prune_depth: Some(3), ..Default::default() }).unwrap();
+        let mut hashes = vec![chain.tip];
+        for h in 1..=8 {
+            let block = coinbase_block(*hashes.last().unwrap(), h, "p");
+            hashes.push(extend_tip(&mut chain, block));
+        }
+        let utxos = chain.utxo_set.len();
+        chain.prune_blocks();
+
+        assert!(chain.is_pruned());
+        assert_eq!(chain.prune_height(), 5);
+        assert!(chain.block_at_height(0).is_some(), "genesis is never pruned");
+        for (h, hash) in hashes.iter().enumerate().skip(1) {
+            let kept = h >= 5;
+            assert_eq!(chain.block_by_hash(hash).is_some(), kept, "height {}", h);
+            assert_eq!(chain.storage.as_ref().unwrap().get_block(hash).unwrap().is_some(), kept, "height {}", h);
+            assert!(chain.headers.contains_key(hash));
+        }
+        assert_eq!(chain.utxo_set.len(), utxos);
+        drop(chain);
+
+        // The prune height survives a restart, even with pruning off
+        let chain = Chain::open(&dir).unwrap();
+        assert_eq!((chain.height, chain.prune_height()), (8, 5));
+        assert_eq!(chain.utxo_set.len(), utxos);
+        assert!(chain.block_at_height(3).is_none());
+        drop(chain);
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_recovery_from_partial_tip_write() {
+        let _ = std::panic::catch_unwind(|| init_network(false));
+        let dir = temp_data_dir("partial-write");
+        let mut chain = Chain::open(&dir).unwrap();
+        let mut prev = chain.tip;
+        for h in 1..=2 {
+            prev = extend_tip(&mut chain, coinbase_block(prev, h, "ok"));
+        }
+        let block = coinbase_block(prev, 3, "crash");
+        let hash = block.header.id_hash();
+        let mut expected = chain.utxo_set.clone();
+        apply_block_utxos(&mut expected, &block);
+        drop(chain);
+
+        // Crash after the tip moved but before any UTXO edit landed
+        {
+            let storage = Storage::open(&dir).unwrap();
+            storage.put_block(&hash, &block).unwrap();
+            storage.put_header(&hash, &block.header).unwrap();
+            storage.put_height_index(3, &hash).unwrap();
+            storage.put_tip(&hash).unwrap();
+            storage.put_height(3).unwrap();
+            storage.flush().unwrap();
+        }
+        let chain = Chain::open(&dir).unwrap();
+        assert!(!chain.started_from_snapshot());
+        assert_eq!((chain.height, chain.tip), (3, hash));
+        assert_eq!(chain.utxo_set.digest(), expected.digest());
+        drop(chain);
+
+        // Written atomically, the same extension needs no repair
+        let mut chain = Chain::open(&dir).unwrap();
+        let next = coinbase_block(hash, 4, "atomic");
+        extend_tip(&mut chain, next);
+        let digest = chain.utxo_set.digest();
+        drop(chain);
+        let chain = Chain::open(&dir).unwrap();
+        assert!(chain.started_from_snapshot());
+        assert_eq!((chain.height, chain.utxo_set.digest()), (4, digest));
+        drop(chain);
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_storage_key_counts() {
+        let _ = std::panic::catch_unwind(|| init_network(false));
+        let dir = temp_data_dir("dbinfo");
+        let mut chain = Chain::open(&dir).unwrap();
+        let mut prev = chain.tip;
+        for h in 1..=2 {
+            prev = extend_tip(&mut chain, coinbase_block(prev, h, "db"));
+        }
+        let storage = chain.storage().unwrap();
+        let counts = storage.key_counts();
+        assert_eq!((counts.blocks, counts.headers), (3, 3));
+        assert_eq!(counts.utxos, chain.utxo_set.len() as u64);
+        assert_eq!(counts.tx_index, chain.tx_index.len() as u64);
+        assert_eq!(counts.address_index, 0);
+        storage.flush().unwrap();
+        assert!(storage.size_on_disk().unwrap() > 0);
+        assert!(Chain::new().storage().is_none());
+        drop(chain);
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_address_history_and_reindex() {
+        let _ = std::panic::catch_unwind(|| init_network(false));
+        let dir = temp_data_dir("addrindex");
+        let with_index = ChainOptions { address_index: true, ..Default::default() };
+        let (miner, payee) = ([1u8; 32], [2u8; 32]);
+
+        // Block 1 pays the miner; block 2 spends that coinbase to the payee
+        let mut chain = Chain::open_with_options(&dir, with_index).unwrap();
+        let b1 = coinbase_block(chain.tip, 1, "a");
+        let cb1 = crate::crypto::txid::txid_v1(&b1.transactions[0]);
+        let reward = b1.transactions[0].outputs[0].amount;
+        let prev = extend_tip(&mut chain, b1);
+        let mut b2 = coinbase_block(prev, 2, "b");
+        b2.transactions[0].outputs[0].pubkey_hash = [3u8; 32];
+        let spend = Transaction {
+            version: 1,
+            inputs: vec![TxInput { previous_output: OutPoint { txid: cb1, vout: 0 }, signature: vec![], pubkey: vec![], script_sig: vec![], sequence: 0 }],
+            outputs: vec![TxOutput { amount: reward, pubkey_hash: payee, script_pubkey: vec![] }],
+            lock_time: 0,
+        };
+        let spend_txid = crate::crypto::txid::txid_v1(&spend);
+        b2.transactions.push(spend);
+        extend_tip(&mut chain, b2);
+
+        let history = chain.address_history(&miner).unwrap();
+        let mine: Vec<_> = history.iter().filter(|e| e.txid == cb1 || e.txid == spend_txid).collect();
+        assert_eq!(mine.len(), 2);
+        assert_eq!((mine[0].height, mine[0].sent, mine[0].amount), (1, false, reward));
+        assert_eq!((mine[1].height, mine[1].sent, mine[1].txid), (2, true, spend_txid));
+        assert!(history.windows(2).all(|w| w[0].height <= w[1].height));
+        let payee_history = chain.address_history(&payee).unwrap();
+        assert_eq!(payee_history.len(), 1);
+        drop(chain);
+
+        // Turning the index off drops it; turning it back on rebuilds the same history
+        let chain = Chain::open(&dir).unwrap();
+        assert!(chain.address_history(&miner).is_err());
+        assert!(!chain.storage.as_ref().unwrap().has_address_index());
+        drop(chain);
+        let chain = Chain::open_with_options(&dir, with_index).unwrap();
+        assert_eq!(chain.address_history(&miner).unwrap(), history);
+        drop(chain);
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_block_stats() {
+        let mut chain = test_chain();
+        let b1 = coinbase_block(chain.tip, 1, "a");
+        let cb1 = crate::crypto::txid::txid_v1(&b1.transactions[0]);
+        let reward = b1.transactions[0].outputs[0].amount;
+        let h1 = extend_tip(&mut chain, b1);
+
+        // Block 2 spends the first coinbase output, paying a 5_000 fee the coinbase claims
+        let mut b2 = coinbase_block(h1, 2, "b");
+        b2.transactions[0].outputs[0].amount += 5_000;
+        b2.transactions.push(Transaction {
+            version: 1,
+            inputs: vec![TxInput { previous_output: OutPoint { txid: cb1, vout: 0 }, signature: vec![], pubkey: vec![], script_sig: vec![], sequence: 0 }],
+            outputs: vec![TxOutput { amount: reward - 5_000, pubkey_hash: [2u8; 32], script_pubkey: vec![] }],
+            lock_time: 0,
+        });
+        let spend_size = b2.transactions[1].size();
+        let h2 = extend_tip(&mut chain, b2.clone());
+
+        let stats = chain.block_stats(&h2).unwrap();
+        assert_eq!((stats.height, stats.tx_count, stats.size), (2, 2, b2.size()));
+        assert_eq!(stats.total_out, reward - 5_000);
+        assert_eq!(stats.total_fees, 5_000);
+        assert_eq!(stats.avg_fee_rate, Some(5_000.0 / spend_size as f64));
+        assert_eq!((stats.subsidy, stats.coinbase_total), (block_reward(2), block_reward(2) + 5_000));
+        assert!(stats.fees_from_undo);
+
+        // Without undo data the coinbase's claim stands in for the fees
+        chain.undo_cache.remove(&h2);
+        let fallback = chain.block_stats(&h2).unwrap();
+        assert!(!fallback.fees_from_undo);
+        assert_eq!(fallback.total_fees, 5_000);
+
+        let empty = chain.block_stats(&h1).unwrap();
+        assert_eq!((empty.tx_count, empty.total_fees, empty.avg_fee_rate), (1, 0, None));
+        assert!(chain.block_stats(&[0xEE; 32]).is_none());
+    }
+
+    #[test]
+    fn test_median_time_past() {
+        let mut chain = test_chain();
+        chain.recent_timestamps = vec![100, 5, 300, 200, 400];
+        assert_eq!(chain.median_time_past(), 200);
+        chain.recent_timestamps = (1..=20).collect();
+        // Only the last 11 (10..=20) count
+        assert_eq!(chain.median_time_past(), 15);
+    }
+
+    #[test]
+    fn test_block_timestamp_rules() {
+        let mut chain = test_chain();
+        for tag in ["a", "b", "c"] {
+            let block = mine_on_tip(&chain, tag);
+            chain.add_block(block).unwrap();
+        }
+        let parent = chain.tip_header().clone();
+        let mtp = chain.median_time_past_at(&chain.tip);
+        assert!(mtp < parent.timestamp);
+        let retimed = |chain: &Chain, timestamp: u64| {
+            let mut block = mine_on_tip(chain, "t");
+            block.header.timestamp = timestamp;
+            while !block.header.meets_difficulty() { block.header.nonce += 1; }
+            block
+        };
+
+        // Past MTP but not past the parent: still invalid, so timestamps
+        // can't step back to drag difficulty down
+        for timestamp in [mtp, mtp + 1, parent.timestamp] {
+            assert!(matches!(chain.add_block(retimed(&chain, timestamp)), Err(BlockError::InvalidTimestamp)));
+        }
+        assert_eq!(chain.height, 3);
+
+        // One second after the parent gets no pass on the future limit
+        let next = retimed(&chain, parent.timestamp + 1);
+        let clock = next.header.timestamp - MAX_FUTURE_BLOCK_TIME - 1;
+        assert!(matches!(chain.try_add_block_at(next.clone(), clock), Err(BlockError::TimestampTooFarInFuture)));
+        chain.try_add_block_at(next, clock + 1).unwrap();
+        assert_eq!(chain.height, 4);
+
+        // A parent that jumped back most of the way (only possible through
+        // injected headers) leaves MTP above parent + 1
+        let mut prev = chain.tip;
+        for nonce in 0..10 {
+            prev = insert_header(&mut chain, prev, nonce);
+        }
+        let last = chain.headers[&prev].clone();
+        let mut low = last.clone();
+        low.prev_hash = prev;
+        low.height += 1;
+        low.timestamp = last.timestamp - 8 * TARGET_BLOCK_TIME;
+        let low_hash = low.hash();
+        chain.headers.insert(low_hash, low.clone());
+        assert!(chain.median_time_past_at(&low_hash) > low.timestamp + 1);
+
+        let mut block = coinbase_block(low_hash, low.height + 1, "old");
+        block.header.timestamp = low.timestamp + 1;
+        assert!(matches!(chain.add_block(block), Err(BlockError::TimestampTooOld)));
+    }
+
+    #[test]
+    fn test_rejected_block_cache() {
+        let mut chain = test_chain();
+        let tip = chain.headers[&chain.tip].clone();
+        let block = mine_on_tip(&chain, "good");
+
+        // Same header, tampered body: rejected and cached with the bad root
+        let mut poisoned = block.clone();
+        poisoned.transactions[0].outputs[0].amount -= 1;
+        assert!(matches!(chain.add_block(poisoned.clone()), Err(BlockError::InvalidMerkleRoot)));
+        assert_eq!(chain.rejected_blocks.entries.len(), 1);
+        assert!(matches!(chain.add_block(poisoned), Err(BlockError::InvalidMerkleRoot)));
+
+        // Header-level failures are cached too
+        let mut stale = block.clone();
+        stale.header.timestamp = tip.timestamp;
+        assert!(matches!(chain.add_block(stale.clone()), Err(BlockError::InvalidTimestamp)));
+        assert_eq!(chain.rejected_blocks.entries.len(), 2);
+        let recent = chain.recent_rejections();
+        assert_eq!((recent[0].id, recent[0].height), (stale.header.id_hash(), stale.header.height));
+        assert_eq!(recent[0].reason, BlockError::InvalidTimestamp.to_string());
+        assert!(chain.rejected_blocks.lookup(&stale.header.id_hash(), &stale).is_some());
+
+        // Orphans aren't: the parent may still arrive
+        let orphan = coinbase_block([0xEE; 32], 5, "orphan");
+        assert!(matches!(chain.add_block(orphan), Err(BlockError::OrphanBlock)));
+        assert_eq!(chain.rejected_blocks.entries.len(), 2);
+
+        // The genuine block behind the poisoned copy is still accepted
+        chain.add_block(block).unwrap();
+        assert_eq!(chain.height, 1);
+
+        let m = chain.metrics();
+        assert_eq!(m.accepted, 1);
+        assert_eq!(m.rejected.get("bad_merkle_root"), Some(&2));
+        assert_eq!(m.rejected.get("bad_timestamp"), Some(&1));
+        assert_eq!(m.rejected.get("orphan"), Some(&1));
+    }
+
+    /// Mine a coinbase-only block on the tip that passes `add_block`
+    fn mine_on_tip(chain: &Chain, tag: &str) -> Block {
+        let tip = chain.headers[&chain.tip].clone();
+        let mut block = coinbase_block(chain.tip, tip.height + 1, tag);
+        block.header.timestamp = tip.timestamp + TARGET_BLOCK_TIME;
+        block.header.difficulty_target = chain.next_difficulty();
+        block.header.merkle_root = block.compute_merkle_root();
+        while !block.header.meets_difficulty() { block.header.nonce += 1; }
+        block
+    }
+
+    #[test]
+    fn test_too_many_sigops_rejected() {
+        let mut chain = test_chain();
+        let input = |i: usize| TxInput {
+            previous_output: OutPoint { txid: [0xEE; 32], vout: i as u32 }, signature: vec![], pubkey: vec![], script_sig: vec![], sequence: 0,
+        };
+        let heavy = Transaction {
+            version: 1,
+            inputs: (0..=MAX_BLOCK_SIGOPS).map(input).collect(),
+            outputs: vec![TxOutput { amount: 1, pubkey_hash: [2; 32], script_pubkey: vec![] }],
+            lock_time: 0,
+        };
+        let mut block = mine_on_tip(&chain, "heavy");
+        block.transactions.push(heavy);
+        block.header.merkle_root = block.compute_merkle_root();
+        while !block.header.meets_difficulty() { block.header.nonce += 1; }
+
+        // Small enough by bytes, but over the sigops budget. Before the
+        // activation height only later checks (here its missing inputs) fail it
+        assert!(block.size() <= MAX_BLOCK_SIZE);
+        assert!(Chain::check_block_sigops(&block).is_ok());
+        assert!(matches!(chain.add_block(block.clone()), Err(BlockError::InvalidTransaction(_))));
+
+        block.header.height = MAX_BLOCK_SIGOPS_HEIGHT;
+        assert!(matches!(Chain::check_block_sigops(&block), Err(BlockError::TooManySigops)));
+        block.transactions[1].inputs.pop();
+        assert!(Chain::check_block_sigops(&block).is_ok());
+    }
+
+    #[test]
+    fn test_same_miner_coinbases_dont_collide() {
+        let mut chain = test_chain();
+        let txid = |block: &Block| crate::crypto::txid::txid_v1(&block.transactions[0]);
+
+        // Before activation the same payee and amounts repeat a txid, and the
+        // later coinbase replaces the earlier one's coins as it always did
+        let first = mine_on_tip(&chain, "rig");
+        chain.add_block(first.clone()).unwrap();
+        let second = mine_on_tip(&chain, "rig");
+        assert_eq!(txid(&first), txid(&second));
+        chain.add_block(second.clone()).unwrap();
+        assert_eq!(chain.height, 2);
+        assert_eq!(chain.utxo_set.get(&OutPoint { txid: txid(&second), vout: 0 }).unwrap().height, 2);
+
+        // From activation, heights keep coinbases apart
+        let height = UNIQUE_COINBASE_HEIGHT;
+        let coinbase = |height: u64| Transaction::new_coinbase(height, block_reward(height), [1u8; 32], COMMUNITY_FUND_HASH, "rig");
+        let (a, b) = (coinbase(height), coinbase(height + 1));
+        assert_eq!(a.outputs, b.outputs);
+        assert_ne!(crate::crypto::txid::txid_v1(&a), crate::crypto::txid::txid_v1(&b));
+
+        // ...and one repeating an unspent coinbase is refused
+        let entry = UtxoEntry { output: a.outputs[0].clone(), height, is_coinbase: true };
+        chain.utxo_set.add(OutPoint { txid: crate::crypto::txid::txid_v1(&a), vout: 0 }, entry);
+        assert!(chain.check_coinbase_unique(&b, height + 1).is_ok());
+        assert!(matches!(chain.check_coinbase_unique(&a, height + 1), Err(BlockError::DuplicateCoinbase)));
+        assert!(chain.check_coinbase_unique(&a, height - 1).is_ok());
+    }
+
+    #[test]
+    fn test_verify_chain() {
+        let mut chain = test_chain();
+        for tag in ["a", "b", "c"] {
+            chain.add_block(mine_on_tip(&chain, tag)).unwrap();
+        }
+        let full = chain.verify_chain(None).unwrap();
+        assert_eq!((full.start_height, full.end_height, full.blocks_checked), (0, 3, 4));
+        assert!(full.utxo_set_checked);
+        let recent = chain.verify_chain(Some(1)).unwrap();
+        assert_eq!((recent.start_height, recent.blocks_checked), (2, 2));
+
+        // A body that no longer matches its header
+        let hash2 = chain.height_index[&2];
+        let original = chain.blocks[&hash2].clone();
+        chain.blocks.get_mut(&hash2).unwrap().transactions[0].outputs[0].amount -= 1;
+        let err = chain.verify_chain(None).unwrap_err();
+        assert_eq!(err.height, 2);
+        assert!(err.reason.contains("merkle"), "{}", err);
+        // ...outside the depth window only the UTXO replay notices
+        let err = chain.verify_chain(Some(0)).unwrap_err();
+        assert!(err.reason.contains("UTXO"), "{}", err);
+        chain.blocks.insert(hash2, original);
+
+        // A UTXO set that drifted from the blocks
+        let op = chain.utxo_set.iter().next().map(|(op, _)| op.clone()).unwrap();
+        let entry = chain.utxo_set.spend(&op).unwrap();
+        let err = chain.verify_chain(Some(0)).unwrap_err();
+        assert_eq!(err.height, 3);
+        chain.utxo_set.add(op, entry);
+
+        // A header whose difficulty doesn't follow LWMA
+        chain.headers.get_mut(&chain.height_index[&1]).unwrap().difficulty_target += 1;
+        let err = chain.verify_chain(Some(0)).unwrap_err();
+        assert_eq!(err.height, 1);
+        assert!(err.reason.contains("difficulty"), "{}", err);
+    }
+
+    #[test]
+    fn test_rejected_blocks_bounded() {
+        let mut cache = RejectedBlocks::default();
+        for i in 0..MAX_REJECTED_BLOCKS as u64 + 10 {
+            let mut id = [0u8; 32];
+            id[..8].copy_from_slice(&i.to_le_bytes());
+            cache.insert(id, i, BlockError::InsufficientPoW, None);
+        }
+        assert_eq!(cache.entries.len(), MAX_REJECTED_BLOCKS);
+        assert_eq!(cache.list().len(), MAX_REJECTED_BLOCKS);
+        assert_eq!(cache.list()[0].height, MAX_REJECTED_BLOCKS as u64 + 9);
+        assert!(!cache.entries.contains_key(&[0u8; 32]));
+    }
+
     #[test]
     fn test_chain_genesis() {
-        let chain = Chain::new();
+        let chain = test_chain();
         assert_eq!(chain.height, 0);
         assert!(!chain.utxo_set.is_empty());
     }
 
     #[test]
     fn test_initial_difficulty() {
-        let chain = Chain::new();
+        let chain = test_chain();
         assert_eq!(chain.next_difficulty(), INITIAL_DIFFICULTY);
     }
 
+    #[test]
+    fn test_network_hashps() {
+        let header = |timestamp: u64, bits: u32| BlockHeader {
+            version: PROTOCOL_VERSION, prev_hash: NULL_HASH, merkle_root: NULL_HASH,
+            timestamp, difficulty_target: bits, nonce: 0, height: 0,
+        };
+        // 2^10 + 2^10 + 2^11 hashes over 100 + 50 + 50 seconds
+        let headers = [header(1_000, 9), header(1_100, 10), header(1_150, 10), header(1_200, 11)];
+        assert_eq!(network_hashps(&headers), 4096.0 / 200.0);
+        // The first header only marks the start of the window
+        assert_eq!(network_hashps(&headers[2..]), 2048.0 / 50.0);
+        assert_eq!(network_hashps(&headers[..1]), 0.0);
+        assert_eq!(network_hashps(&[]), 0.0);
+
+        // Nothing mined past genesis yet
+        assert_eq!(test_chain().network_hashps(0), 0.0);
+    }
+
     #[test]
     fn test_cumulative_work() {
-        let chain = Chain::new();
+        let chain = test_chain();
         let genesis_hash = chain.tip;
         let work = chain.cumulative_work.get(&genesis_hash).unwrap();
         assert!(*work > 0);
@@ -1075,4 +3378,199 @@ mod tests {
         // Clamped at 127 for safety
         assert_eq!(block_work(200), 1u128 << 127);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_common_ancestor() {
+        let mut chain = test_chain();
+        let genesis = chain.tip;
+        let fork = insert_header(&mut chain, genesis, 1);
+        let fork_child = insert_header(&mut chain, fork, 2);
+
+        // Branch A: fork -> a1 -> a2 ; Branch B: fork -> b1
+        let a1 = insert_header(&mut chain, fork_child, 10);
+        let a2 = insert_header(&mut chain, a1, 11);
+        let b1 = insert_header(&mut chain, fork_child, 20);
+
+        assert_eq!(chain.common_ancestor(&a2, &b1), Some(fork_child));
+        assert_eq!(chain.common_ancestor(&b1, &a2), Some(fork_child));
+        // An ancestor of the other hash is itself the common ancestor
+        assert_eq!(chain.common_ancestor(&a2, &fork), Some(fork));
+        assert_eq!(chain.common_ancestor(&a1, &a1), Some(a1));
+        assert_eq!(chain.common_ancestor(&a2, &genesis), Some(genesis));
+        // Unknown hashes have no common ancestor
+        assert_eq!(chain.common_ancestor(&a2, &[0x42; 32]), None);
+    }
+
+    #[test]
+    fn test_version_bit_stats() {
+        let mut chain = test_chain();
+        let mut tip = chain.tip;
+        // 10 blocks: bit 1 on 6 of them, bit 5 on 3 of them, 1 legacy-version block
+        for i in 0..10u64 {
+            let version = match i {
+                0..=2 => VERSIONBITS_TOP_BITS | (1 << 1) | (1 << 5),
+                3..=5 => VERSIONBITS_TOP_BITS | (1 << 1),
+                6 => PROTOCOL_VERSION, // not a signaling version despite bit 2 being set
+                _ => VERSIONBITS_TOP_BITS,
+            };
+            tip = insert_header_with_version(&mut chain, tip, i, version);
+        }
+        chain.tip = tip;
+        chain.height = 10;
+
+        let stats = chain.version_bit_stats(10);
+        assert_eq!(stats.blocks, 10);
+        assert_eq!((stats.start_height, stats.end_height), (1, 10));
+        assert_eq!(stats.signaling_blocks, 9);
+        assert_eq!(stats.bit_counts[1], 6);
+        assert_eq!(stats.bit_counts[5], 3);
+        assert_eq!(stats.bit_counts[2], 0);
+
+        // Window only covers the most recent blocks
+        let recent = chain.version_bit_stats(4);
+        assert_eq!(recent.blocks, 4);
+        assert_eq!(recent.bit_counts[1], 0);
+
+        // Window larger than the chain stops at genesis
+        assert_eq!(chain.version_bit_stats(1000).blocks, 11);
+    }
+
+    #[test]
+    fn test_detect_anomaly() {
+        let run = |solve_time: u64, difficulties: &[u32]| -> Vec<BlockHeader> {
+            difficulties.iter().enumerate().map(|(i, &difficulty_target)| BlockHeader {
+                version: PROTOCOL_VERSION, prev_hash: NULL_HASH, merkle_root: NULL_HASH,
+                timestamp: 1_000_000 + i as u64 * solve_time, difficulty_target, nonce: 0, height: 100 + i as u64,
+            }).collect()
+        };
+        assert_eq!(detect_anomaly(&run(TARGET_BLOCK_TIME, &[20; 11])), None);
+        assert_eq!(detect_anomaly(&run(TARGET_BLOCK_TIME, &[20])), None);
+
+        // Far too fast or far too slow
+        assert!(detect_anomaly(&run(2, &[20; 11])).unwrap().contains("timestamp manipulation"));
+        assert!(detect_anomaly(&run(TARGET_BLOCK_TIME * 10, &[20; 11])).unwrap().contains("timestamp manipulation"));
+
+        // Falling by the most the LWMA allows is fine; faster is flagged
+        assert_eq!(detect_anomaly(&run(TARGET_BLOCK_TIME, &[20, 20, 19, 19, 18])), None);
+        let steep = detect_anomaly(&run(TARGET_BLOCK_TIME, &[20, 20, 20, 16])).unwrap();
+        assert!(steep.contains("difficulty fell from 20 to 16"), "{}", steep);
+    }
+
+    #[test]
+    fn test_deployment_activation() {
+        let mut chain = test_chain();
+        let mut tip = chain.tip;
+        let mut extend = |chain: &mut Chain, n: u64, version: u32| {
+            for _ in 0..n {
+                tip = insert_header_with_version(chain, tip, chain.height, version);
+                chain.height += 1;
+                chain.height_index.insert(chain.height, tip);
+                chain.tip = tip;
+            }
+        };
+        let signaling = VERSIONBITS_TOP_BITS | (1 << 4);
+
+        // One short of the threshold in the first period: still counting
+        extend(&mut chain, VERSIONBITS_THRESHOLD - 1, signaling);
+        extend(&mut chain, VERSIONBITS_PERIOD - VERSIONBITS_THRESHOLD, PROTOCOL_VERSION);
+        let status = chain.deployment_status(4);
+        assert_eq!(status.state, DeploymentState::Started);
+        assert_eq!((status.period_start, status.period_blocks, status.period_signaled), (VERSIONBITS_PERIOD, 0, 0));
+
+        // The second period reaches it and locks in
+        extend(&mut chain, VERSIONBITS_THRESHOLD, signaling);
+        assert_eq!(chain.deployment_status(4).period_signaled, VERSIONBITS_THRESHOLD);
+        extend(&mut chain, VERSIONBITS_PERIOD - VERSIONBITS_THRESHOLD, PROTOCOL_VERSION);
+        let status = chain.deployment_status(4);
+        assert_eq!((status.state, status.since), (DeploymentState::LockedIn, 2 * VERSIONBITS_PERIOD));
+        assert!(!chain.deployment_active(4));
+
+        // Active one period later, even once miners stop signaling
+        extend(&mut chain, VERSIONBITS_PERIOD, PROTOCOL_VERSION);
+        let status = chain.deployment_status(4);
+        assert_eq!((status.state, status.since), (DeploymentState::Active, 3 * VERSIONBITS_PERIOD));
+        assert!(chain.deployment_active(4));
+        assert_eq!(chain.deployment_status(5).state, DeploymentState::Started);
+    }
+
+    #[test]
+    fn test_coinbase_must_pay_community_fund() {
+        let mut chain = test_chain();
+        let required = required_community_fund(1);
+        let remine = |chain: &Chain, edit: &dyn Fn(&mut Vec<TxOutput>)| {
+            let mut block = mine_on_tip(chain, "split");
+            edit(&mut block.transactions[0].outputs);
+            block.header.merkle_root = block.compute_merkle_root();
+            while !block.header.meets_difficulty() { block.header.nonce += 1; }
+            block
+        };
+
+        // Miner keeps everything
+        let greedy = remine(&chain, &|outputs| {
+            let fund = outputs.pop().unwrap();
+            outputs[0].amount += fund.amount;
+        });
+        let err = chain.add_block(greedy).unwrap_err();
+        assert!(matches!(err, BlockError::InvalidCommunityFund { paid: 0, .. }), "{}", err);
+
+        // Right amount, wrong hash
+        let redirected = remine(&chain, &|outputs| outputs[1].pubkey_hash = [0xAB; 32]);
+        assert!(matches!(chain.add_block(redirected), Err(BlockError::InvalidCommunityFund { paid: 0, .. })));
+
+        // Short by one unit
+        let short = remine(&chain, &|outputs| {
+            outputs[1].amount -= 1;
+            outputs[0].amount += 1;
+        });
+        assert!(matches!(chain.add_block(short), Err(BlockError::InvalidCommunityFund { .. })));
+
+        assert_eq!(chain.height, 0);
+
+        let good = mine_on_tip(&chain, "split");
+        assert_eq!(good.transactions[0].outputs[1].amount, required);
+        chain.add_block(good).unwrap();
+        assert_eq!(chain.height, 1);
+    }
+
+    #[test]
+    fn test_community_fund_before_exact_rule() {
+        let mut chain = test_chain();
+        let kp = crate::crypto::Keypair::generate();
+        let own_hash = crate::crypto::pubkey_bytes_to_hash(&kp.public_key_bytes());
+        let pay = vec![TxOutput { amount: 5 * MIN_TX_FEE, pubkey_hash: [9u8; 32], script_pubkey: script::script_p2pkh(&[9u8; 32]) }];
+        let spend = signed_spend(&mut chain, &kp, script::script_p2pkh(&own_hash), pay);
+        let fees = 5 * MIN_TX_FEE;
+
+        // A baseline coinbase shares subsidy and fees alike, over split outputs too
+        let block_with = |chain: &Chain, outputs: Vec<TxOutput>| {
+            let mut block = mine_on_tip(chain, "old");
+            block.transactions[0].outputs = outputs;
+            block.transactions.push(spend.clone());
+            block.header.merkle_root = block.compute_merkle_root();
+            while !block.header.meets_difficulty() { block.header.nonce += 1; }
+            block
+        };
+        let reward = block_reward(1) + fees;
+        let share = community_fund_amount(reward);
+        assert!(share > required_community_fund(1));
+        let out = |amount: u64, pubkey_hash: Hash256| TxOutput { amount, pubkey_hash, script_pubkey: vec![] };
+
+        let short = block_with(&chain, vec![out(reward - required_community_fund(1), [1; 32]), out(required_community_fund(1), COMMUNITY_FUND_HASH)]);
+        assert!(matches!(chain.add_block(short), Err(BlockError::InvalidCommunityFund { required, .. }) if required == share));
+        let baseline = block_with(&chain, vec![out(reward - share, [1; 32]), out(share - 1, COMMUNITY_FUND_HASH), out(1, COMMUNITY_FUND_HASH)]);
+        chain.add_block(baseline).unwrap();
+        assert_eq!(chain.height, 1);
+
+        // From the activation height only one exact subsidy-share output passes
+        let height = COMMUNITY_FUND_EXACT_HEIGHT;
+        let required = required_community_fund(height);
+        let coinbase = |fund: Vec<u64>| Transaction {
+            outputs: fund.into_iter().map(|a| out(a, COMMUNITY_FUND_HASH)).collect(),
+            ..Transaction::new_coinbase(height, block_reward(height), [1; 32], COMMUNITY_FUND_HASH, "")
+        };
+        assert!(Chain::check_community_fund(&coinbase(vec![required]), height, fees).is_ok());
+        let split = Chain::check_community_fund(&coinbase(vec![required / 2, required - required / 2]), height, fees).unwrap_err();
+        assert!(matches!(split, BlockError::InvalidCommunityFund { required: r, paid } if r == required && paid == required), "{}", split);
+        assert!(Chain::check_community_fund(&coinbase(vec![community_fund_amount(block_reward(height) + fees)]), height, fees).is_err());
+    }
+}
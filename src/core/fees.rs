@@ -0,0 +1,216 @@
+//! Fee-rate estimation from recent block history.
+//!
+//! Samples are the fee rates (base units per byte) of every non-coinbase
+//! transaction in the last few blocks. The mode decides how they're aggregated:
+//!
+//! | mode         | percentile | lookback                       |
+//! |--------------|------------|--------------------------------|
+//! | economical   | 50th       | max(6, target) blocks          |
+//! | conservative | 90th       | max(24, target) blocks, and never below the economical estimate |
+//!
+//! The mempool gives a second, forward-looking signal: if more than `target`
+//! blocks' worth of transactions are already waiting, a new one has to outbid
+//! the backlog (`mempool_fee_rate`).
+
+use crate::core::params::{MAX_BLOCK_SIZE, MIN_TX_FEE};
+
+/// Economical mode: median of the most recent blocks
+pub const ECONOMICAL_PERCENTILE: f64 = 50.0;
+pub const ECONOMICAL_WINDOW: usize = 6;
+/// Conservative mode: high percentile over a longer history
+pub const CONSERVATIVE_PERCENTILE: f64 = 90.0;
+pub const CONSERVATIVE_WINDOW: usize = 24;
+/// Largest confirmation target accepted
+pub const MAX_CONF_TARGET: usize = 1008;
+
+/// Bincode size of a signed 1-input, 2-output P2PKH transaction
+pub const TYPICAL_TX_SIZE: usize = 460;
+
+/// Fee rate used when there's no history to estimate from
+pub const FALLBACK_FEE_RATE: f64 = MIN_TX_FEE as f64 / TYPICAL_TX_SIZE as f64;
+
+/// Each mempool fee-rate bucket starts this factor above the previous one
+pub const FEE_BUCKET_SPACING: f64 = 1.25;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EstimateMode {
+    Economical,
+    Conservative,
+}
+
+impl EstimateMode {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "economical" => Some(EstimateMode::Economical),
+            "conservative" => Some(EstimateMode::Conservative),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            EstimateMode::Economical => "economical",
+            EstimateMode::Conservative => "conservative",
+        }
+    }
+
+    /// Number of recent blocks sampled for a confirmation target
+    pub fn window(&self, target: usize) -> usize {
+        match self {
+            EstimateMode::Economical => ECONOMICAL_WINDOW.max(target),
+            EstimateMode::Conservative => CONSERVATIVE_WINDOW.max(target),
+        }
+    }
+
+    fn percentile(&self) -> f64 {
+        match self {
+            EstimateMode::Economical => ECONOMICAL_PERCENTILE,
+            EstimateMode::Conservative => CONSERVATIVE_PERCENTILE,
+        }
+    }
+}
+
+/// Estimate a fee rate for confirmation within `target` blocks.
+///
+/// `history` holds each block's transaction fee rates, newest block first.
+/// Returns None when the sampled blocks contain no transactions.
+pub fn estimate_fee_rate(history: &[Vec<f64>], target: usize, mode: EstimateMode) -> Option<f64> {
+    let target = target.clamp(1, MAX_CONF_TARGET);
+    let sampled = percentile_of_window(history, mode.window(target), mode.percentile());
+    match mode {
+        EstimateMode::Economical => sampled,
+        EstimateMode::Conservative => {
+            let economical = estimate_fee_rate(history, target, EstimateMode::Economical);
+            match (sampled, economical) {
+                (Some(c), Some(e)) => Some(c.max(e)),
+                (c, e) => c.or(e),
+            }
+        }
+    }
+}
+
+/// Fee rate needed to be mined within `target` blocks given the mempool backlog.
+///
+/// `pending` holds (size in bytes, fee rate) for every mempool transaction.
+/// Rates are grouped into geometric buckets starting at `FALLBACK_FEE_RATE`;
+/// walking from the richest bucket down, the first bucket that no longer fits
+/// in `target` blocks sets the price: a new transaction must beat that bucket.
+/// None when the whole mempool fits, i.e. the backlog isn't the constraint.
+pub fn mempool_fee_rate(pending: &[(usize, f64)], target: usize) -> Option<f64> {
+    let capacity = target.clamp(1, MAX_CONF_TARGET) * MAX_BLOCK_SIZE;
+    let bucket_of = |rate: f64| -> i32 {
+        if rate <= FALLBACK_FEE_RATE { 0 } else { (rate / FALLBACK_FEE_RATE).log(FEE_BUCKET_SPACING).floor() as i32 }
+    };
+    let mut buckets: std::collections::BTreeMap<i32, usize> = std::collections::BTreeMap::new();
+    for &(size, rate) in pending {
+        *buckets.entry(bucket_of(rate)).or_default() += size;
+    }
+
+    let mut queued = 0usize;
+    for (&bucket, &bytes) in buckets.iter().rev() {
+        queued += bytes;
+        if queued > capacity {
+            // Upper edge of the bucket that overflows the target
+            return Some(FALLBACK_FEE_RATE * FEE_BUCKET_SPACING.powi(bucket + 1));
+        }
+    }
+    None
+}
+
+fn percentile_of_window(history: &[Vec<f64>], window: usize, pct: f64) -> Option<f64> {
+    let mut samples: Vec<f64> = history.iter().take(window).flatten().copied().collect();
+    if samples.is_empty() {
+        return None;
+    }
+    samples.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+    // Nearest-rank percentile
+    let rank = ((pct / 100.0) * samples.len() as f64).ceil() as usize;
+    Some(samples[rank.clamp(1, samples.len()) - 1])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Deterministic pseudo-random fee histories
+    fn synthetic_history(seed: u64, blocks: usize) -> Vec<Vec<f64>> {
+        let mut state = seed;
+        let mut next = move || {
+            state = state.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+            state >> 33
+        };
+        (0..blocks).map(|_| {
+            let n = (next() % 20) as usize;
+            (0..n).map(|_| (next() % 10_000) as f64 / 100.0).collect()
+        }).collect()
+    }
+
+    #[test]
+    fn test_conservative_never_below_economical() {
+        for seed in 0..50 {
+            let history = synthetic_history(seed, 40);
+            for target in [1, 2, 6, 12, 25, 100] {
+                let e = estimate_fee_rate(&history, target, EstimateMode::Economical);
+                let c = estimate_fee_rate(&history, target, EstimateMode::Conservative);
+                if let (Some(e), Some(c)) = (e, c) {
+                    assert!(c >= e, "seed {} target {}: {} < {}", seed, target, c, e);
+                }
+            }
+        }
+
+        // Fee spike in recent blocks, cheap history behind it
+        let mut history = vec![vec![50.0, 60.0, 70.0]; 6];
+        history.extend(vec![vec![1.0, 2.0]; 30]);
+        let e = estimate_fee_rate(&history, 1, EstimateMode::Economical).unwrap();
+        let c = estimate_fee_rate(&history, 1, EstimateMode::Conservative).unwrap();
+        assert_eq!(e, 60.0);
+        assert!(c >= e);
+    }
+
+    #[test]
+    fn test_typical_tx_size() {
+        use crate::core::types::*;
+        let output = TxOutput { amount: 1, pubkey_hash: [0; 32], script_pubkey: crate::core::script::script_p2pkh(&[0; 32]) };
+        let tx = Transaction {
+            version: 1,
+            inputs: vec![TxInput {
+                previous_output: OutPoint { txid: [0; 32], vout: 0 },
+                signature: vec![0; 64], pubkey: vec![0; 32],
+                script_sig: crate::core::script::script_sig_p2pkh(&[0; 64], &[0; 32]),
+                sequence: 0,
+            }],
+            outputs: vec![output.clone(), output],
+            lock_time: 0,
+        };
+        assert_eq!(tx.size(), TYPICAL_TX_SIZE);
+    }
+
+    #[test]
+    fn test_mempool_fee_rate() {
+        assert_eq!(mempool_fee_rate(&[], 1), None);
+
+        // Half a block of waiting transactions: no backlog pressure
+        assert_eq!(mempool_fee_rate(&[(MAX_BLOCK_SIZE / 2, 50.0)], 1), None);
+
+        // 1.5 blocks at 50/byte ahead of 1 block at 10/byte
+        let pending = [(MAX_BLOCK_SIZE + MAX_BLOCK_SIZE / 2, 50.0), (MAX_BLOCK_SIZE, 10.0)];
+        let next_block = mempool_fee_rate(&pending, 1).unwrap();
+        assert!(next_block > 50.0 && next_block <= 50.0 * FEE_BUCKET_SPACING, "{}", next_block);
+        let two_blocks = mempool_fee_rate(&pending, 2).unwrap();
+        assert!(two_blocks > 10.0 && two_blocks <= 10.0 * FEE_BUCKET_SPACING, "{}", two_blocks);
+        assert_eq!(mempool_fee_rate(&pending, 3), None);
+    }
+
+    #[test]
+    fn test_estimate_windows_and_empty_history() {
+        assert_eq!(estimate_fee_rate(&[], 6, EstimateMode::Conservative), None);
+        assert_eq!(estimate_fee_rate(&[vec![], vec![]], 6, EstimateMode::Economical), None);
+
+        // Economical only looks at the newest 6 blocks
+        let mut history = vec![vec![10.0]; 6];
+        history.push(vec![1000.0]);
+        assert_eq!(estimate_fee_rate(&history, 1, EstimateMode::Economical), Some(10.0));
+        assert_eq!(estimate_fee_rate(&history, 7, EstimateMode::Economical), Some(10.0));
+        assert_eq!(estimate_fee_rate(&history, 1, EstimateMode::Conservative), Some(1000.0));
+    }
+}
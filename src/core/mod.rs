@@ -2,3 +2,4 @@ pub mod params;
 pub mod types;
 pub mod chain;
 pub mod script;
+pub mod fees;
@@ -21,6 +21,14 @@ pub const TARGET_BLOCK_TIME: u64 = 90;
 /// Maximum block size in bytes (4 MB)
 pub const MAX_BLOCK_SIZE: usize = 4 * 1024 * 1024;
 
+/// Maximum signature checks per block. Each non-coinbase input costs one,
+/// so a block can't pack more verification work than this into its size budget
+pub const MAX_BLOCK_SIGOPS: usize = 20_000;
+
+/// `MAX_BLOCK_SIGOPS` is enforced from this height; earlier blocks were only
+/// bounded by size
+pub const MAX_BLOCK_SIGOPS_HEIGHT: u64 = 400_000;
+
 /// Maximum transactions per block
 pub const MAX_TXS_PER_BLOCK: usize = 10_000;
 
@@ -34,15 +42,52 @@ pub const PROTOCOL_VERSION: u32 = 4;
 /// v4 required: fixed difficulty, fixed compact blocks, fixed sync
 pub const MIN_PROTOCOL_VERSION: u32 = 4;
 
+/// Version-bits signaling (BIP9-style). A block whose header version has the
+/// top three bits set to `001` is signaling, and each of bits 0..29 flags
+/// readiness for one deployment. Plain `PROTOCOL_VERSION` headers don't signal.
+pub const VERSIONBITS_TOP_MASK: u32 = 0xE000_0000;
+pub const VERSIONBITS_TOP_BITS: u32 = 0x2000_0000;
+pub const VERSIONBITS_NUM_BITS: u32 = 29;
+
+/// Default number of recent blocks tallied for version-bit statistics
+pub const VERSIONBITS_WINDOW: u64 = 1000;
+
+/// Deployments are tallied over fixed periods of this many blocks (aligned to
+/// height 0). A bit set by at least `VERSIONBITS_THRESHOLD` blocks of one
+/// period locks in, and its rule is enforced from the start of the period after.
+pub const VERSIONBITS_PERIOD: u64 = 1000;
+pub const VERSIONBITS_THRESHOLD: u64 = 950;
+
+/// Named deployments miners can signal for: (name, bit). None defined yet.
+pub const VERSIONBITS_DEPLOYMENTS: &[(&str, u32)] = &[];
+
 /// PoW algorithm identifier (stored in chain metadata for compatibility checks)
 pub const POW_ALGORITHM: &str = "equihash-x-v1";
 
 /// Community fund percentage of block reward (5%)
 pub const COMMUNITY_FUND_PERCENT: u64 = 5;
 
+/// Pubkey hash the community fund output pays
+pub const COMMUNITY_FUND_HASH: [u8; 32] = [0xCF; 32];
+
+/// From this height one coinbase output must pay the community fund exactly
+/// its share of the subsidy. Earlier coinbases split the whole reward, fees
+/// included, and only had to pay at least that share.
+pub const COMMUNITY_FUND_EXACT_HEIGHT: u64 = 400_000;
+
+/// From this height coinbases carry their height in `lock_time`, which txids
+/// cover, and a coinbase whose txid still has unspent outputs is rejected
+/// (BIP30/34-style). Older coinbases from one payout address could share a txid.
+pub const UNIQUE_COINBASE_HEIGHT: u64 = 400_000;
+
 /// Minimum transaction fee in base units
 pub const MIN_TX_FEE: u64 = 1000; // 0.00001 EQF
 
+/// Smallest non-zero output amount the mempool accepts. Anything below costs
+/// more to spend than it is worth and would sit in the UTXO set forever.
+/// Relay policy only; blocks may carry smaller outputs.
+pub const DUST_THRESHOLD: u64 = 546;
+
 /// Coinbase maturity (blocks before mined coins can be spent)
 pub const COINBASE_MATURITY: u64 = 100;
 
@@ -55,10 +100,71 @@ pub const MAX_OUTBOUND_PEERS: usize = 12;
 /// Maximum number of total peer connections (inbound + outbound)
 pub const MAX_PEERS: usize = 256;
 
+/// Version given to newly built transactions
+pub const CURRENT_TX_VERSION: u32 = 2;
+
+/// From this transaction version on, legacy signature+pubkey inputs must sign
+/// `tx_signing_hash_v1` (commits to the spent amount) instead of the old
+/// bincode digest. Version-1 transactions keep the old rule for existing data.
+pub const TX_VERSION_SIGHASH_V1: u32 = 2;
+
+/// `lock_time` values below this are block heights, at or above it unix times
+pub const LOCKTIME_THRESHOLD: u64 = 500_000_000;
+
+/// Input sequence that opts out of `lock_time`; a tx whose inputs all use it is final
+pub const SEQUENCE_FINAL: u32 = 0xFFFF_FFFF;
+
+/// From this height blocks may only hold final transactions (see
+/// `Transaction::is_final`); earlier blocks ignored `lock_time`. The mempool
+/// refuses non-final transactions at any height.
+pub const LOCKTIME_HEIGHT: u64 = 400_000;
+
+/// Deepest reorg (blocks disconnected below the tip) a node will perform by
+/// default. Heavier side chains forking further back are stored, never adopted.
+pub const MAX_REORG_DEPTH: u64 = 100;
+
+/// Number of recent blocks whose median timestamp forms median-time-past
+pub const MEDIAN_TIME_SPAN: usize = 11;
+
+/// How far past our clock (seconds) a block's timestamp may be
+pub const MAX_FUTURE_BLOCK_TIME: u64 = 7200;
+
+/// Memory cap for buffered orphan blocks (oldest evicted first)
+pub const MAX_ORPHAN_POOL_BYTES: usize = 32 * 1024 * 1024;
+
+/// Most orphan blocks held at once, however small
+pub const MAX_ORPHAN_BLOCKS: usize = 1000;
+
+/// Orphans connected per chain-lock hold; deeper chains resolve in later passes
+pub const MAX_ORPHANS_RESOLVED_PER_BLOCK: usize = 16;
+
 // ─── Network Configuration (Mainnet vs Testnet) ─────────────────────
 
 use std::sync::OnceLock;
 
+/// EquiHash-X cost parameters. The 64-byte chunk size isn't one of them:
+/// a chunk is exactly the algorithm's 8 x u64 mixing state.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PowParams {
+    /// Scratchpad size in bytes (a multiple of 64)
+    pub scratchpad_size: usize,
+    /// Number of mixing rounds
+    pub iterations: usize,
+}
+
+impl PowParams {
+    /// Number of 64-byte scratchpad chunks
+    pub fn chunks(&self) -> usize {
+        (self.scratchpad_size / 64).max(1)
+    }
+}
+
+/// Mainnet: 4 MB scratchpad, 64 rounds. Changing these changes every block hash.
+pub const MAINNET_POW: PowParams = PowParams { scratchpad_size: 4 * 1024 * 1024, iterations: 64 };
+
+/// Testnet: 1 MB scratchpad, cheap enough for test machines
+pub const TESTNET_POW: PowParams = PowParams { scratchpad_size: 1024 * 1024, iterations: 64 };
+
 /// Runtime network configuration — set once at startup based on --testnet flag
 #[derive(Debug, Clone)]
 pub struct NetworkConfig {
@@ -69,6 +175,10 @@ pub struct NetworkConfig {
     pub genesis_timestamp: u64,
     pub data_dir: &'static str,
     pub seed_nodes: Vec<String>,
+    pub pow: PowParams,
+    /// Share of the block subsidy every coinbase must pay the community fund
+    pub community_fund_percent: u64,
+    pub community_fund_hash: [u8; 32],
 }
 
 static NETWORK: OnceLock<NetworkConfig> = OnceLock::new();
@@ -83,6 +193,9 @@ pub fn init_network(testnet: bool) {
             genesis_timestamp: 1735689600 + 1, // Different genesis than mainnet
             data_dir: "equiforge_testnet",
             seed_nodes: vec!["129.80.239.237:19333".to_string()],
+            pow: TESTNET_POW,
+            community_fund_percent: COMMUNITY_FUND_PERCENT,
+            community_fund_hash: COMMUNITY_FUND_HASH,
         }
     } else {
         NetworkConfig {
@@ -93,6 +206,9 @@ pub fn init_network(testnet: bool) {
             genesis_timestamp: 1735689600,
             data_dir: "equiforge_data",
             seed_nodes: vec!["129.80.239.237:9333".to_string()],
+            pow: MAINNET_POW,
+            community_fund_percent: COMMUNITY_FUND_PERCENT,
+            community_fund_hash: COMMUNITY_FUND_HASH,
         }
     };
     NETWORK.set(config).expect("Network already initialized");
@@ -109,6 +225,10 @@ pub fn default_port() -> u16 { network().default_port }
 pub fn seed_nodes() -> &'static [String] { &network().seed_nodes }
 pub fn data_dir() -> &'static str { network().data_dir }
 pub fn is_testnet() -> bool { network().name == "testnet" }
+/// Mainnet's until `init_network` runs, so standalone hashing needs no setup
+pub fn pow_params() -> PowParams { NETWORK.get().map_or(MAINNET_POW, |n| n.pow) }
+pub fn community_fund_percent() -> u64 { NETWORK.get().map_or(COMMUNITY_FUND_PERCENT, |n| n.community_fund_percent) }
+pub fn community_fund_hash() -> [u8; 32] { NETWORK.get().map_or(COMMUNITY_FUND_HASH, |n| n.community_fund_hash) }
 
 /// Calculate block reward at a given height
 pub fn block_reward(height: u64) -> u64 {
@@ -119,9 +239,53 @@ pub fn block_reward(height: u64) -> u64 {
     INITIAL_BLOCK_REWARD >> halvings
 }
 
+/// Total subsidy of blocks 0..=height, summed per halving epoch
+pub fn total_emission(height: u64) -> u64 {
+    let mut total = 0u64;
+    for halvings in 0..64 {
+        let start = halvings * HALVING_INTERVAL;
+        if start > height {
+            break;
+        }
+        let last = height.min(start + HALVING_INTERVAL - 1);
+        total += (INITIAL_BLOCK_REWARD >> halvings) * (last - start + 1);
+    }
+    total
+}
+
+/// Everything the reward schedule will ever emit
+pub fn emission_cap() -> u64 {
+    total_emission(64 * HALVING_INTERVAL)
+}
+
+/// First height after `height` whose reward differs, None once rewards have run out
+pub fn next_reward_change(height: u64) -> Option<u64> {
+    if block_reward(height) == 0 {
+        return None;
+    }
+    Some((height / HALVING_INTERVAL + 1) * HALVING_INTERVAL)
+}
+
 /// Calculate the community fund amount for a given block reward
 pub fn community_fund_amount(reward: u64) -> u64 {
-    reward * COMMUNITY_FUND_PERCENT / 100
+    reward * community_fund_percent() / 100
+}
+
+/// What the coinbase at `height` must pay the community fund. Only the
+/// subsidy is split; transaction fees go to the miner in full.
+pub fn required_community_fund(height: u64) -> u64 {
+    community_fund_amount(block_reward(height))
+}
+
+/// What the coinbase at `height` must pay the community fund when its block
+/// collects `fees`: the subsidy share from `COMMUNITY_FUND_EXACT_HEIGHT`, the
+/// share of subsidy plus fees before it
+pub fn coinbase_community_fund(height: u64, fees: u64) -> u64 {
+    if height >= COMMUNITY_FUND_EXACT_HEIGHT {
+        required_community_fund(height)
+    } else {
+        community_fund_amount(block_reward(height) + fees)
+    }
 }
 
 /// Calculate miner reward (block reward minus community fund)
@@ -156,7 +320,7 @@ mod tests {
 
     #[test]
     fn test_total_supply_approximation() {
-        // Verify total mined supply approaches 42M
+        // Geometric emission: 2 * INITIAL_BLOCK_REWARD * HALVING_INTERVAL
         let mut total: u64 = 0;
         let mut height: u64 = 0;
         loop {
@@ -171,12 +335,30 @@ mod tests {
             height = epoch_end;
         }
         let total_coins = total / COIN;
-        // Should be close to 42M (within rounding)
-        assert!(total_coins <= 42_000_000);
-        assert!(total_coins > 41_900_000);
+        let cap = 2 * (INITIAL_BLOCK_REWARD / COIN) * HALVING_INTERVAL;
+        assert!(total_coins <= cap);
+        assert!(total_coins > cap - cap / 1000);
         println!("Total supply: {} EQF", total_coins);
     }
 
+    #[test]
+    fn test_total_emission() {
+        let naive = |h: u64| (0..=h).map(block_reward).sum::<u64>();
+        for h in [0, 1, 1000] {
+            assert_eq!(total_emission(h), naive(h));
+        }
+        let epoch = INITIAL_BLOCK_REWARD * HALVING_INTERVAL;
+        assert_eq!(total_emission(HALVING_INTERVAL - 1), epoch);
+        assert_eq!(total_emission(HALVING_INTERVAL), epoch + block_reward(HALVING_INTERVAL));
+        assert_eq!(total_emission(2 * HALVING_INTERVAL - 1), epoch + epoch / 2);
+        assert_eq!(total_emission(u64::MAX), emission_cap());
+        assert!(emission_cap() < 2 * epoch);
+
+        assert_eq!(next_reward_change(0), Some(HALVING_INTERVAL));
+        assert_eq!(next_reward_change(HALVING_INTERVAL), Some(2 * HALVING_INTERVAL));
+        assert_eq!(next_reward_change(64 * HALVING_INTERVAL), None);
+    }
+
     #[test]
     fn test_community_fund() {
         let reward = block_reward(0);
@@ -185,5 +367,11 @@ mod tests {
         assert_eq!(fund, 2 * COIN + COIN / 2); // 2.5 EQF
         assert_eq!(miner, 47 * COIN + COIN / 2); // 47.5 EQF
         assert_eq!(fund + miner, reward);
+        assert_eq!(required_community_fund(0), fund);
+        assert_eq!(required_community_fund(HALVING_INTERVAL), fund / 2);
+
+        // Fees are shared only before the exact-amount rule
+        assert_eq!(coinbase_community_fund(1, 100 * COIN), community_fund_amount(reward + 100 * COIN));
+        assert_eq!(coinbase_community_fund(COMMUNITY_FUND_EXACT_HEIGHT, 100 * COIN), required_community_fund(COMMUNITY_FUND_EXACT_HEIGHT));
     }
 }
@@ -4,6 +4,18 @@
 //! v1 standard script: P2PKH-like
 //! script_pubkey: OP_DUP OP_HASH256 OP_PUSH32 <pubkey_hash32> OP_EQUALVERIFY OP_CHECKSIG
 //! script_sig:    OP_PUSHDATA <sig64> OP_PUSHDATA <pubkey32>
+//!
+//! Output types: every output is classified before it is created or spent.
+//!   type 0 (pubkey-hash) — empty script_pubkey (legacy, uses `pubkey_hash`)
+//!                          or the P2PKH template above; one Ed25519 signature.
+//!   type 1 (multisig)    — `[0x01][m:u8][n:u8][key_hash32 × n]`, 1 ≤ m ≤ n ≤ 16.
+//!                          `pubkey_hash` must be `multisig_script_hash(script)`,
+//!                          which is also what the multisig address encodes.
+//!                          script_sig: `[count:u8]` then `count ×
+//!                          [key_index:u8][pubkey32][sig64]`, key indexes strictly
+//!                          ascending, each signature over `tx_signing_hash_v1`.
+//!   any other script     — `[type:u8][payload..]`; types 2..=255 are reserved
+//!                          and rejected until a rule for them is defined.
 use crate::core::types::{Hash256, Transaction, TxInput, TxOutput};
 use crate::crypto;
 
@@ -16,12 +28,194 @@ pub const OP_CHECKSIG: u8 = 0xAC;
 /// Push helpers
 pub const OP_PUSHDATA1: u8 = 0x4c;
 
+/// Output-type tag for pay-to-pubkey-hash outputs
+pub const OUTPUT_TYPE_PUBKEY_HASH: u8 = 0;
+/// Output-type tag for m-of-n multisig outputs
+pub const OUTPUT_TYPE_MULTISIG: u8 = 1;
+/// Maximum number of keys in a multisig output
+pub const MAX_MULTISIG_KEYS: usize = 16;
+/// Size of one `[key_index][pubkey32][sig64]` entry in a multisig script_sig
+const MULTISIG_SIG_ENTRY_LEN: usize = 1 + 32 + 64;
+
 #[derive(Debug)]
 pub enum ScriptError {
     NonStandard,
     BadEncoding,
     PubkeyHashMismatch,
     BadSignature,
+    UnknownOutputType(u8),
+    NotEnoughSignatures { required: u8, got: u8 },
+}
+
+/// What kind of lock an output carries
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum OutputType {
+    /// Type 0: spendable by the key whose hash is given
+    PubkeyHash(Hash256),
+    /// Type 1: spendable by `required` signatures from keys hashing to `key_hashes`
+    Multisig { required: u8, key_hashes: Vec<Hash256> },
+}
+
+impl OutputType {
+    pub fn tag(&self) -> u8 {
+        match self {
+            OutputType::PubkeyHash(_) => OUTPUT_TYPE_PUBKEY_HASH,
+            OutputType::Multisig { .. } => OUTPUT_TYPE_MULTISIG,
+        }
+    }
+}
+
+/// Classify an output's locking script.
+///
+/// The signing hash commits to `pubkey_hash` but not to `script_pubkey`, so a
+/// P2PKH script must lock to the same hash as the field — otherwise a relayer
+/// could swap the script without invalidating the signature.
+pub fn classify_output(output: &TxOutput) -> Result<OutputType, ScriptError> {
+    if output.script_pubkey.is_empty() {
+        return Ok(OutputType::PubkeyHash(output.pubkey_hash));
+    }
+    if let Ok(hash) = parse_script_pubkey_p2pkh(&output.script_pubkey) {
+        if hash != output.pubkey_hash {
+            return Err(ScriptError::PubkeyHashMismatch);
+        }
+        return Ok(OutputType::PubkeyHash(hash));
+    }
+    match output.script_pubkey[0] {
+        OUTPUT_TYPE_PUBKEY_HASH => Err(ScriptError::NonStandard),
+        OUTPUT_TYPE_MULTISIG => {
+            let (required, key_hashes) = parse_script_pubkey_multisig(&output.script_pubkey)?;
+            if multisig_script_hash(&output.script_pubkey) != output.pubkey_hash {
+                return Err(ScriptError::PubkeyHashMismatch);
+            }
+            Ok(OutputType::Multisig { required, key_hashes })
+        }
+        tag => Err(ScriptError::UnknownOutputType(tag)),
+    }
+}
+
+// ─── Multisig ────────────────────────────────────────────────────────
+
+/// Build an m-of-n multisig script_pubkey: `[0x01][m][n][key_hash32 × n]`.
+/// Every key hash must be distinct.
+pub fn script_multisig(required: u8, key_hashes: &[Hash256]) -> Result<Vec<u8>, ScriptError> {
+    let n = key_hashes.len();
+    if required == 0 || required as usize > n || n > MAX_MULTISIG_KEYS {
+        return Err(ScriptError::NonStandard);
+    }
+    if key_hashes.iter().enumerate().any(|(i, h)| key_hashes[..i].contains(h)) {
+        return Err(ScriptError::NonStandard);
+    }
+    let mut s = Vec::with_capacity(3 + 32 * n);
+    s.push(OUTPUT_TYPE_MULTISIG);
+    s.push(required);
+    s.push(n as u8);
+    for h in key_hashes {
+        s.extend_from_slice(h);
+    }
+    Ok(s)
+}
+
+/// Parse a multisig script_pubkey into (required, key_hashes)
+pub fn parse_script_pubkey_multisig(script_pubkey: &[u8]) -> Result<(u8, Vec<Hash256>), ScriptError> {
+    if script_pubkey.len() < 3 || script_pubkey[0] != OUTPUT_TYPE_MULTISIG {
+        return Err(ScriptError::NonStandard);
+    }
+    let (required, n) = (script_pubkey[1], script_pubkey[2] as usize);
+    if required == 0 || required as usize > n || n > MAX_MULTISIG_KEYS || script_pubkey.len() != 3 + 32 * n {
+        return Err(ScriptError::NonStandard);
+    }
+    let key_hashes = script_pubkey[3..].chunks_exact(32)
+        .map(|c| c.try_into().unwrap())
+        .collect();
+    Ok((required, key_hashes))
+}
+
+/// Hash committed to by a multisig output's `pubkey_hash` field (and its address)
+pub fn multisig_script_hash(script_pubkey: &[u8]) -> Hash256 {
+    crypto::pubkey_bytes_to_hash(script_pubkey)
+}
+
+/// A complete multisig output paying `amount` to `script_pubkey`
+pub fn output_multisig(amount: u64, script_pubkey: Vec<u8>) -> TxOutput {
+    TxOutput { amount, pubkey_hash: multisig_script_hash(&script_pubkey), script_pubkey }
+}
+
+/// One co-signer's contribution: (key_index, pubkey32, sig64)
+pub type MultisigSignature = (u8, [u8; 32], [u8; 64]);
+
+/// Encode a multisig script_sig. Entries are sorted by key index; a later
+/// entry for an index already present replaces it.
+pub fn script_sig_multisig(sigs: &[MultisigSignature]) -> Vec<u8> {
+    let mut sorted: Vec<MultisigSignature> = Vec::with_capacity(sigs.len());
+    for entry in sigs {
+        match sorted.binary_search_by_key(&entry.0, |e| e.0) {
+            Ok(pos) => sorted[pos] = *entry,
+            Err(pos) => sorted.insert(pos, *entry),
+        }
+    }
+    let mut s = Vec::with_capacity(1 + sorted.len() * MULTISIG_SIG_ENTRY_LEN);
+    s.push(sorted.len() as u8);
+    for (idx, pk, sig) in &sorted {
+        s.push(*idx);
+        s.extend_from_slice(pk);
+        s.extend_from_slice(sig);
+    }
+    s
+}
+
+/// Parse a multisig script_sig. An empty script_sig is an input nobody has signed yet.
+pub fn parse_script_sig_multisig(script_sig: &[u8]) -> Result<Vec<MultisigSignature>, ScriptError> {
+    if script_sig.is_empty() {
+        return Ok(Vec::new());
+    }
+    let count = script_sig[0] as usize;
+    if script_sig.len() != 1 + count * MULTISIG_SIG_ENTRY_LEN {
+        return Err(ScriptError::BadEncoding);
+    }
+    let mut out: Vec<MultisigSignature> = Vec::with_capacity(count);
+    for chunk in script_sig[1..].chunks_exact(MULTISIG_SIG_ENTRY_LEN) {
+        let idx = chunk[0];
+        if out.last().is_some_and(|prev| prev.0 >= idx) {
+            return Err(ScriptError::BadEncoding);
+        }
+        out.push((idx, chunk[1..33].try_into().unwrap(), chunk[33..97].try_into().unwrap()));
+    }
+    Ok(out)
+}
+
+/// Validate an m-of-n multisig spend: every provided signature must come from a
+/// listed key and verify, and at least `required` distinct keys must have signed
+/// (a key listed twice still only counts once).
+pub fn validate_multisig_spend(
+    tx: &Transaction,
+    input_index: usize,
+    input: &TxInput,
+    prev_output: &TxOutput,
+) -> Result<(), ScriptError> {
+    let (required, key_hashes) = match classify_output(prev_output)? {
+        OutputType::Multisig { required, key_hashes } => (required, key_hashes),
+        _ => return Err(ScriptError::NonStandard),
+    };
+
+    let sigs = parse_script_sig_multisig(&input.script_sig)?;
+    let sighash = crypto::tx_signing_hash_v1(tx, input_index, prev_output);
+    for (idx, pubkey32, sig64) in &sigs {
+        let expected = key_hashes.get(*idx as usize).ok_or(ScriptError::BadEncoding)?;
+        if crypto::pubkey_bytes_to_hash(pubkey32) != *expected {
+            return Err(ScriptError::PubkeyHashMismatch);
+        }
+        if !crypto::verify_signature(pubkey32, &sighash, sig64) {
+            return Err(ScriptError::BadSignature);
+        }
+    }
+
+    let mut signers: Vec<&[u8; 32]> = sigs.iter().map(|(_, pk, _)| pk).collect();
+    signers.sort();
+    signers.dedup();
+    if signers.len() < required as usize {
+        return Err(ScriptError::NotEnoughSignatures { required, got: signers.len() as u8 });
+    }
+    Ok(())
 }
 
 /// Build a standard P2PKH script_pubkey from a 32-byte pubkey hash.
@@ -99,7 +293,7 @@ pub fn parse_script_pubkey_p2pkh(script_pubkey: &[u8]) -> Result<Hash256, Script
 /// Validate a P2PKH spend.
 ///
 /// - Derive pubkey_hash from pubkey
-/// - Must match the pubkey_hash in script_pubkey (and/or output)
+/// - Must match the hash the output is locked to (see `classify_output`)
 /// - Verify Ed25519 signature over tx_signing_hash_v1(...)
 pub fn validate_p2pkh_spend(
     tx: &Transaction,
@@ -108,12 +302,10 @@ pub fn validate_p2pkh_spend(
     prev_output: &TxOutput,
 ) -> Result<(), ScriptError> {
     // Determine the expected pubkey hash from the locking script
-    let lock_hash = parse_script_pubkey_p2pkh(&prev_output.script_pubkey)
-        .or_else(|_| {
-            // If your TxOutput still stores pubkey_hash directly, you can fallback here.
-            // But recommended: use script_pubkey as the source of truth.
-            Ok(prev_output.pubkey_hash)
-        })?;
+    let lock_hash = match classify_output(prev_output)? {
+        OutputType::PubkeyHash(hash) => hash,
+        _ => return Err(ScriptError::NonStandard),
+    };
 
     // Unlocking script
     let (sig64, pubkey32) = parse_script_sig_p2pkh(&input.script_sig)?;
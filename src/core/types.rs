@@ -17,7 +17,7 @@ pub struct OutPoint {
 }
 
 /// Transaction input - spends a previous output
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct TxInput {
     pub previous_output: OutPoint,
     pub signature: Vec<u8>,
@@ -30,7 +30,7 @@ pub struct TxInput {
 }
 
 /// Transaction output - creates a new spendable output
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct TxOutput {
     pub amount: u64,
     pub pubkey_hash: Hash256,
@@ -40,7 +40,7 @@ pub struct TxOutput {
 }
 
 /// A complete transaction
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Transaction {
     pub version: u32,
     pub inputs: Vec<TxInput>,
@@ -58,18 +58,45 @@ impl Transaction {
         community_fund_hash: Hash256,
         miner_tag: &str,
     ) -> Self {
-        let community_amount = super::params::community_fund_amount(reward);
+        Self::new_coinbase_weighted(height, reward, &[(miner_pubkey_hash, 1)], community_fund_hash, miner_tag)
+    }
+
+    /// Create a coinbase that splits the miner's share across weighted payout targets.
+    ///
+    /// The community fund gets `coinbase_community_fund` (from
+    /// `COMMUNITY_FUND_EXACT_HEIGHT` its share of the subsidy alone, however
+    /// many fees `reward` includes); the rest is the miner share. Each target receives
+    /// `miner_share * weight / total_weight` (rounded down); the rounding
+    /// remainder goes to the first target so the outputs always sum to exactly
+    /// `reward`. Zero-weight targets are skipped.
+    ///
+    /// Panics if `payouts` has no target with a non-zero weight.
+    pub fn new_coinbase_weighted(
+        height: u64,
+        reward: u64,
+        payouts: &[(Hash256, u64)],
+        community_fund_hash: Hash256,
+        miner_tag: &str,
+    ) -> Self {
+        let fees = reward.saturating_sub(super::params::block_reward(height));
+        let community_amount = super::params::coinbase_community_fund(height, fees).min(reward);
         let miner_amount = reward - community_amount;
 
         let mut sig = height.to_le_bytes().to_vec();
         let tag_bytes = miner_tag.as_bytes();
         sig.extend_from_slice(&tag_bytes[..tag_bytes.len().min(32)]);
 
-        let mut outputs = vec![TxOutput {
-            amount: miner_amount,
-            pubkey_hash: miner_pubkey_hash,
+        let targets: Vec<&(Hash256, u64)> = payouts.iter().filter(|(_, w)| *w > 0).collect();
+        let total_weight: u128 = targets.iter().map(|(_, w)| *w as u128).sum();
+        assert!(total_weight > 0, "coinbase needs at least one payout target with non-zero weight");
+
+        let mut outputs: Vec<TxOutput> = targets.iter().map(|(pubkey_hash, weight)| TxOutput {
+            amount: (miner_amount as u128 * *weight as u128 / total_weight) as u64,
+            pubkey_hash: *pubkey_hash,
             script_pubkey: vec![],
-        }];
+        }).collect();
+        let distributed: u64 = outputs.iter().map(|o| o.amount).sum();
+        outputs[0].amount += miner_amount - distributed;
 
         if community_amount > 0 {
             outputs.push(TxOutput {
@@ -92,8 +119,22 @@ impl Transaction {
                 sequence: 0xFFFFFFFF,
             }],
             outputs,
-            lock_time: 0,
+            // txids skip the input's height bytes; this keeps coinbases at
+            // different heights distinct (the input is final, so no lock applies)
+            lock_time: if height >= super::params::UNIQUE_COINBASE_HEIGHT { height } else { 0 },
+        }
+    }
+
+    /// Whether the tx may be included in a block at `height` whose
+    /// median-time-past is `median_time`. A zero `lock_time`, or every input
+    /// at `SEQUENCE_FINAL`, disables the lock; otherwise it must have passed.
+    pub fn is_final(&self, height: u64, median_time: u64) -> bool {
+        use crate::core::params::{LOCKTIME_THRESHOLD, SEQUENCE_FINAL};
+        if self.lock_time == 0 {
+            return true;
         }
+        let cutoff = if self.lock_time < LOCKTIME_THRESHOLD { height } else { median_time };
+        self.lock_time < cutoff || self.inputs.iter().all(|i| i.sequence == SEQUENCE_FINAL)
     }
 
     pub fn is_coinbase(&self) -> bool {
@@ -119,7 +160,11 @@ impl Transaction {
         self.outputs.iter().map(|o| o.amount).sum()
     }
 
-    /// Compute the transaction hash (double SHA-256)
+    /// Double SHA-256 of the full serialized transaction, signatures included.
+    ///
+    /// This is NOT the txid. It only feeds the block merkle root; everything
+    /// that identifies a transaction (outpoints, mempool, indexes, relay, RPC
+    /// `txid` fields) uses `crypto::txid::txid_v1`.
     pub fn hash(&self) -> Hash256 {
         use sha2::{Digest, Sha256};
         let serialized = bincode::serialize(self).expect("tx serialization failed");
@@ -133,6 +178,11 @@ impl Transaction {
     pub fn size(&self) -> usize {
         bincode::serialized_size(self).unwrap_or(0) as usize
     }
+
+    /// Signature checks needed to validate this tx: one per spent input
+    pub fn sigops(&self) -> usize {
+        if self.is_coinbase() { 0 } else { self.inputs.len() }
+    }
 }
 
 // ─── Block Types ─────────────────────────────────────────────────────
@@ -167,7 +217,16 @@ impl BlockHeader {
     /// This replaces double-SHA256 with a custom algorithm that requires
     /// 4 MB of memory and 64 mixing iterations per hash, making dedicated
     /// hardware impractical while keeping CPUs and GPUs competitive.
+    ///
+    /// Goes through the verification cache (`pow::equihash_x_cached`), so
+    /// re-validating a header is cheap. Nonce grinding uses `compute_hash`.
     pub fn hash(&self) -> Hash256 {
+        let serialized = bincode::serialize(self).expect("header serialization failed");
+        crate::pow::equihash_x_cached(&serialized)
+    }
+
+    /// `hash` without the cache, for mining where every header is new
+    pub fn compute_hash(&self) -> Hash256 {
         let serialized = bincode::serialize(self).expect("header serialization failed");
         crate::pow::equihash_x(&serialized)
     }
@@ -227,34 +286,18 @@ pub struct Block {
 impl Block {
     /// Compute the merkle root from the block's transactions
     pub fn compute_merkle_root(&self) -> Hash256 {
-        if self.transactions.is_empty() {
-            return NULL_HASH;
-        }
-
-        let mut hashes: Vec<Hash256> = self.transactions.iter().map(|tx| tx.hash()).collect();
-
-        while hashes.len() > 1 {
-            if hashes.len() % 2 != 0 {
-                let last = *hashes.last().unwrap();
-                hashes.push(last);
-            }
-
-            let mut next_level = Vec::new();
-            for chunk in hashes.chunks(2) {
-                use sha2::{Digest, Sha256};
-                let mut combined = Vec::new();
-                combined.extend_from_slice(&chunk[0]);
-                combined.extend_from_slice(&chunk[1]);
-                let first = Sha256::digest(&combined);
-                let second = Sha256::digest(&first);
-                let mut hash = [0u8; 32];
-                hash.copy_from_slice(&second);
-                next_level.push(hash);
-            }
-            hashes = next_level;
-        }
+        let hashes: Vec<Hash256> = self.transactions.iter().map(|tx| tx.hash()).collect();
+        crate::verify::merkle_root(&hashes)
+    }
 
-        hashes[0]
+    /// Inclusion proof for the transaction with this txid (v1): sibling hashes
+    /// from the leaf up, each flagged true when it sits on the LEFT. The leaf
+    /// is the transaction's merkle hash (`Transaction::hash`), not its txid.
+    pub fn merkle_proof(&self, txid: &Hash256) -> Option<Vec<(Hash256, bool)>> {
+        let index = self.transactions.iter().position(|tx| crate::crypto::txid::txid_v1(tx) == *txid)?;
+        let hashes: Vec<Hash256> = self.transactions.iter().map(|tx| tx.hash()).collect();
+        let branch = crate::verify::merkle_branch(&hashes, index)?;
+        Some(branch.hashes.iter().enumerate().map(|(level, h)| (*h, (index >> level) & 1 == 1)).collect())
     }
 
     pub fn validate_merkle_root(&self) -> bool {
@@ -264,6 +307,175 @@ impl Block {
     pub fn size(&self) -> usize {
         bincode::serialized_size(self).unwrap_or(0) as usize
     }
+
+    pub fn sigops(&self) -> usize {
+        self.transactions.iter().map(Transaction::sigops).sum()
+    }
+}
+
+// ─── Stable Encoding (v1) ────────────────────────────────────────────
+//
+// Hand-rolled little-endian encoding that does not depend on serde/bincode
+// derive layout, so adding a field to a struct can't silently change how
+// previously written data is read back. Used by snapshots.
+//
+// v1 layout:
+//   format_version:u8
+//   header: version:u32 prev_hash:32 merkle_root:32 timestamp:u64
+//           difficulty_target:u32 nonce:u64 height:u64
+//   tx_count:u32, then per tx:
+//     version:u32
+//     input_count:u32, per input: txid:32 vout:u32 signature:bytes
+//                                 pubkey:bytes script_sig:bytes sequence:u32
+//     output_count:u32, per output: amount:u64 pubkey_hash:32 script_pubkey:bytes
+//     lock_time:u64
+// where `bytes` is a u32 length followed by that many bytes.
+
+/// Format version byte written by `Block::serialize_v1`
+pub const BLOCK_ENCODING_V1: u8 = 1;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DecodeError {
+    Truncated,
+    UnsupportedVersion { expected: u8, got: u8 },
+    TrailingBytes(usize),
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DecodeError::Truncated => write!(f, "unexpected end of data"),
+            DecodeError::UnsupportedVersion { expected, got } =>
+                write!(f, "unsupported block encoding version {} (expected {})", got, expected),
+            DecodeError::TrailingBytes(n) => write!(f, "{} trailing bytes after block", n),
+        }
+    }
+}
+impl std::error::Error for DecodeError {}
+
+struct StableReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> StableReader<'a> {
+    fn take(&mut self, n: usize) -> Result<&'a [u8], DecodeError> {
+        let end = self.pos.checked_add(n).ok_or(DecodeError::Truncated)?;
+        let slice = self.data.get(self.pos..end).ok_or(DecodeError::Truncated)?;
+        self.pos = end;
+        Ok(slice)
+    }
+    fn u8(&mut self) -> Result<u8, DecodeError> { Ok(self.take(1)?[0]) }
+    fn u32(&mut self) -> Result<u32, DecodeError> { Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap())) }
+    fn u64(&mut self) -> Result<u64, DecodeError> { Ok(u64::from_le_bytes(self.take(8)?.try_into().unwrap())) }
+    fn hash(&mut self) -> Result<Hash256, DecodeError> { Ok(self.take(32)?.try_into().unwrap()) }
+    fn bytes(&mut self) -> Result<Vec<u8>, DecodeError> {
+        let len = self.u32()? as usize;
+        Ok(self.take(len)?.to_vec())
+    }
+    /// Read a collection length, rejecting counts that can't fit in the remaining data
+    fn count(&mut self, min_item_size: usize) -> Result<usize, DecodeError> {
+        let n = self.u32()? as usize;
+        if n.saturating_mul(min_item_size) > self.data.len() - self.pos {
+            return Err(DecodeError::Truncated);
+        }
+        Ok(n)
+    }
+}
+
+fn put_bytes(buf: &mut Vec<u8>, bytes: &[u8]) {
+    buf.extend_from_slice(&(bytes.len() as u32).to_le_bytes());
+    buf.extend_from_slice(bytes);
+}
+
+impl Block {
+    /// Encode with the stable v1 format (see layout above)
+    pub fn serialize_v1(&self) -> Vec<u8> {
+        let mut buf = Vec::with_capacity(self.size() + 64);
+        buf.push(BLOCK_ENCODING_V1);
+
+        let h = &self.header;
+        buf.extend_from_slice(&h.version.to_le_bytes());
+        buf.extend_from_slice(&h.prev_hash);
+        buf.extend_from_slice(&h.merkle_root);
+        buf.extend_from_slice(&h.timestamp.to_le_bytes());
+        buf.extend_from_slice(&h.difficulty_target.to_le_bytes());
+        buf.extend_from_slice(&h.nonce.to_le_bytes());
+        buf.extend_from_slice(&h.height.to_le_bytes());
+
+        buf.extend_from_slice(&(self.transactions.len() as u32).to_le_bytes());
+        for tx in &self.transactions {
+            buf.extend_from_slice(&tx.version.to_le_bytes());
+            buf.extend_from_slice(&(tx.inputs.len() as u32).to_le_bytes());
+            for i in &tx.inputs {
+                buf.extend_from_slice(&i.previous_output.txid);
+                buf.extend_from_slice(&i.previous_output.vout.to_le_bytes());
+                put_bytes(&mut buf, &i.signature);
+                put_bytes(&mut buf, &i.pubkey);
+                put_bytes(&mut buf, &i.script_sig);
+                buf.extend_from_slice(&i.sequence.to_le_bytes());
+            }
+            buf.extend_from_slice(&(tx.outputs.len() as u32).to_le_bytes());
+            for o in &tx.outputs {
+                buf.extend_from_slice(&o.amount.to_le_bytes());
+                buf.extend_from_slice(&o.pubkey_hash);
+                put_bytes(&mut buf, &o.script_pubkey);
+            }
+            buf.extend_from_slice(&tx.lock_time.to_le_bytes());
+        }
+        buf
+    }
+
+    /// Decode a block written by `serialize_v1`. The whole slice must be consumed.
+    pub fn deserialize_v1(data: &[u8]) -> Result<Block, DecodeError> {
+        let mut r = StableReader { data, pos: 0 };
+        let version = r.u8()?;
+        if version != BLOCK_ENCODING_V1 {
+            return Err(DecodeError::UnsupportedVersion { expected: BLOCK_ENCODING_V1, got: version });
+        }
+
+        let header = BlockHeader {
+            version: r.u32()?,
+            prev_hash: r.hash()?,
+            merkle_root: r.hash()?,
+            timestamp: r.u64()?,
+            difficulty_target: r.u32()?,
+            nonce: r.u64()?,
+            height: r.u64()?,
+        };
+
+        let tx_count = r.count(20)?;
+        let mut transactions = Vec::with_capacity(tx_count);
+        for _ in 0..tx_count {
+            let version = r.u32()?;
+            let input_count = r.count(52)?;
+            let mut inputs = Vec::with_capacity(input_count);
+            for _ in 0..input_count {
+                inputs.push(TxInput {
+                    previous_output: OutPoint { txid: r.hash()?, vout: r.u32()? },
+                    signature: r.bytes()?,
+                    pubkey: r.bytes()?,
+                    script_sig: r.bytes()?,
+                    sequence: r.u32()?,
+                });
+            }
+            let output_count = r.count(44)?;
+            let mut outputs = Vec::with_capacity(output_count);
+            for _ in 0..output_count {
+                outputs.push(TxOutput {
+                    amount: r.u64()?,
+                    pubkey_hash: r.hash()?,
+                    script_pubkey: r.bytes()?,
+                });
+            }
+            transactions.push(Transaction { version, inputs, outputs, lock_time: r.u64()? });
+        }
+
+        if r.pos != data.len() {
+            return Err(DecodeError::TrailingBytes(data.len() - r.pos));
+        }
+        Ok(Block { header, transactions })
+    }
 }
 
 impl fmt::Display for BlockHeader {
@@ -304,9 +516,15 @@ mod tests {
 
     #[test]
     fn test_leading_zero_bits() {
-        assert_eq!(leading_zero_bits(&[0x00, 0x00, 0xFF, 0; 29]), 16);
-        assert_eq!(leading_zero_bits(&[0x00, 0x0F, 0; 30]), 12);
-        assert_eq!(leading_zero_bits(&[0x00, 0x00, 0x00, 0x01, 0; 28]), 31);
+        let mut h = [0u8; 32];
+        h[2] = 0xFF;
+        assert_eq!(leading_zero_bits(&h), 16);
+        let mut h = [0u8; 32];
+        h[1] = 0x0F;
+        assert_eq!(leading_zero_bits(&h), 12);
+        let mut h = [0u8; 32];
+        h[3] = 0x01;
+        assert_eq!(leading_zero_bits(&h), 31);
         assert_eq!(leading_zero_bits(&[0xFF; 32]), 0);
         assert_eq!(leading_zero_bits(&[0; 32]), 256);
     }
@@ -338,4 +556,144 @@ mod tests {
         };
         assert_eq!(block.compute_merkle_root(), tx.hash());
     }
+
+    #[test]
+    fn test_merkle_proof_reconstructs_root() {
+        let txs: Vec<Transaction> = (0..5u64)
+            .map(|i| Transaction::new_coinbase(i, 5_000_000_000 + i, [i as u8; 32], [2u8; 32], ""))
+            .collect();
+        let mut block = Block {
+            header: BlockHeader {
+                version: 1, prev_hash: NULL_HASH, merkle_root: NULL_HASH,
+                timestamp: 0, difficulty_target: 8, nonce: 0, height: 0,
+            },
+            transactions: txs.clone(),
+        };
+        block.header.merkle_root = block.compute_merkle_root();
+
+        for tx in &txs {
+            let proof = block.merkle_proof(&crate::crypto::txid::txid_v1(tx)).unwrap();
+            let root = proof.iter().fold(tx.hash(), |acc, (sibling, is_left)| {
+                if *is_left { crate::verify::merkle_parent(sibling, &acc) } else { crate::verify::merkle_parent(&acc, sibling) }
+            });
+            assert_eq!(root, block.header.merkle_root);
+        }
+        assert!(block.merkle_proof(&[0xEE; 32]).is_none());
+    }
+
+    #[test]
+    fn test_weighted_coinbase_split() {
+        let a = [0xA1u8; 32];
+        let b = [0xB2u8; 32];
+        let fund = super::super::params::COMMUNITY_FUND_HASH;
+        // 47.5 EQF miner share doesn't divide by 3: one unit of remainder
+        let reward = 50 * super::super::params::COIN;
+        let tx = Transaction::new_coinbase_weighted(1, reward, &[(a, 2), (b, 1), ([0u8; 32], 0)], fund, "");
+        assert_eq!(tx.outputs.len(), 3); // zero-weight target skipped
+        assert_eq!(tx.total_output(), reward);
+        let miner_share = reward - super::super::params::community_fund_amount(reward);
+        assert_eq!(tx.outputs[0].pubkey_hash, a);
+        assert_eq!(tx.outputs[0].amount, miner_share * 2 / 3 + 1);
+        assert_eq!(tx.outputs[1].pubkey_hash, b);
+        assert_eq!(tx.outputs[1].amount, miner_share / 3);
+        assert_eq!(tx.outputs[2].pubkey_hash, fund);
+
+        // The single-target form is unchanged
+        let single = Transaction::new_coinbase(1, reward, a, fund, "");
+        assert_eq!(single.outputs[0].amount, miner_share);
+        assert_eq!(single.total_output(), reward);
+
+        // Fees grow the fund's share only before the exact-amount rule
+        let with_fees = Transaction::new_coinbase(1, reward + 1000, a, fund, "");
+        assert_eq!(with_fees.outputs[1].amount, super::super::params::community_fund_amount(reward + 1000));
+        assert_eq!(with_fees.total_output(), reward + 1000);
+        let exact = super::super::params::COMMUNITY_FUND_EXACT_HEIGHT;
+        let subsidy = super::super::params::block_reward(exact);
+        let with_fees = Transaction::new_coinbase(exact, subsidy + 1000, a, fund, "");
+        assert_eq!(with_fees.outputs[0].amount, subsidy - super::super::params::required_community_fund(exact) + 1000);
+        assert_eq!(with_fees.outputs[1].amount, super::super::params::required_community_fund(exact));
+    }
+
+    #[test]
+    fn test_is_final() {
+        use crate::core::params::{LOCKTIME_THRESHOLD, SEQUENCE_FINAL};
+        let mut tx = Transaction {
+            version: 2,
+            inputs: vec![TxInput { previous_output: OutPoint { txid: [9u8; 32], vout: 0 }, signature: vec![], pubkey: vec![], script_sig: vec![], sequence: 0 }],
+            outputs: vec![],
+            lock_time: 0,
+        };
+        assert!(tx.is_final(0, 0));
+
+        // Height lock: final once the including block's height is past it
+        tx.lock_time = 100;
+        assert!(!tx.is_final(100, u64::MAX));
+        assert!(tx.is_final(101, 0));
+
+        // Time lock: compared with median-time-past, not height
+        tx.lock_time = LOCKTIME_THRESHOLD + 1000;
+        assert!(!tx.is_final(u64::MAX, LOCKTIME_THRESHOLD + 1000));
+        assert!(tx.is_final(0, LOCKTIME_THRESHOLD + 1001));
+
+        // Every input at SEQUENCE_FINAL disables the lock
+        tx.inputs[0].sequence = SEQUENCE_FINAL;
+        assert!(tx.is_final(0, 0));
+        tx.inputs.push(tx.inputs[0].clone());
+        tx.inputs[1].sequence = 0;
+        assert!(!tx.is_final(0, 0));
+    }
+
+    fn sample_block() -> Block {
+        let coinbase = Transaction::new_coinbase(7, 5_000_000_000, [1u8; 32], [2u8; 32], "tag");
+        let spend = Transaction {
+            version: 2,
+            inputs: vec![TxInput {
+                previous_output: OutPoint { txid: [9u8; 32], vout: 3 },
+                signature: vec![0xAB; 64],
+                pubkey: vec![0xCD; 32],
+                script_sig: vec![1, 2, 3],
+                sequence: 0xFFFFFFFE,
+            }],
+            outputs: vec![TxOutput { amount: 12345, pubkey_hash: [4u8; 32], script_pubkey: vec![0x76] }],
+            lock_time: 99,
+        };
+        let mut block = Block {
+            header: BlockHeader {
+                version: 4, prev_hash: [5u8; 32], merkle_root: NULL_HASH,
+                timestamp: 1_700_000_000, difficulty_target: 8, nonce: 42, height: 7,
+            },
+            transactions: vec![coinbase, spend],
+        };
+        block.header.merkle_root = block.compute_merkle_root();
+        block
+    }
+
+    #[test]
+    fn test_stable_encoding_roundtrip() {
+        let block = sample_block();
+        let encoded = block.serialize_v1();
+        assert_eq!(encoded[0], BLOCK_ENCODING_V1);
+        let decoded = Block::deserialize_v1(&encoded).unwrap();
+        assert_eq!(bincode::serialize(&decoded).unwrap(), bincode::serialize(&block).unwrap());
+        assert!(decoded.validate_merkle_root());
+    }
+
+    #[test]
+    fn test_stable_encoding_errors() {
+        let mut encoded = sample_block().serialize_v1();
+
+        // Truncation anywhere is an error, never a panic
+        for cut in [0, 1, 40, encoded.len() - 1] {
+            assert_eq!(Block::deserialize_v1(&encoded[..cut]).unwrap_err(), DecodeError::Truncated);
+        }
+
+        let mut trailing = encoded.clone();
+        trailing.push(0);
+        assert_eq!(Block::deserialize_v1(&trailing).unwrap_err(), DecodeError::TrailingBytes(1));
+
+        encoded[0] = 2;
+        let err = Block::deserialize_v1(&encoded).unwrap_err();
+        assert_eq!(err, DecodeError::UnsupportedVersion { expected: 1, got: 2 });
+        assert!(err.to_string().contains("unsupported block encoding version 2"));
+    }
 }
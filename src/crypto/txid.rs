@@ -1,3 +1,10 @@
+//! Transaction identifiers.
+//!
+//! `txid_v1` is THE canonical txid: outpoints reference it, and the mempool,
+//! tx/address indexes, inventory relay and RPC all key transactions by it.
+//! `Transaction::hash` (full bincode, signatures included) is only used for
+//! the merkle root and must not be used to look transactions up.
+
 use sha2::{Digest, Sha256};
 use crate::core::types::{Hash256, Transaction};
 
@@ -6,4 +6,5 @@ pub mod network;
 pub mod rpc;
 pub mod storage;
 pub mod pow;
-pub mod pool;
\ No newline at end of file
+pub mod pool;
+pub mod verify;
\ No newline at end of file
@@ -3,12 +3,13 @@ use std::path::PathBuf;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 
-use equiforge::core::chain::Chain;
+use equiforge::core::chain::{Chain, ChainOptions};
 use equiforge::core::params::*;
 use equiforge::miner::{self, MinerConfig};
 use equiforge::network::{self, NodeState};
 use equiforge::rpc;
-use equiforge::wallet::{self, Wallet};
+use equiforge::storage::snapshot;
+use equiforge::wallet::{self, CoinSelection, Wallet};
 
 const DEFAULT_DATA_DIR: &str = "equiforge_data";
 const DEFAULT_P2P_PORT: u16 = 9333;
@@ -27,6 +28,18 @@ struct Cli {
     /// Run on testnet (separate chain, port 19333, data in equiforge_testnet/)
     #[arg(long, global = true)]
     testnet: bool,
+    /// Stay on mainnet even if the --config file sets testnet = true
+    #[arg(long, global = true, conflicts_with = "testnet")]
+    no_testnet: bool,
+    /// Log output: human-readable text, or one JSON object per line
+    #[arg(long, global = true, default_value = "text", value_parser = ["text", "json"])]
+    log_format: String,
+    /// TOML file with node settings; flags given on the command line win
+    #[arg(long, global = true)]
+    config: Option<PathBuf>,
+    /// Bearer token for JSON-RPC: the node requires it, CLI commands send it
+    #[arg(long, global = true)]
+    rpc_token: Option<String>,
     #[command(subcommand)]
     command: Commands,
 }
@@ -37,12 +50,17 @@ enum Commands {
     Init,
     /// Run a full node
     Node {
+        /// Peer to connect to (host:port), or a DNS seed as dnsseed:host[:port]
         #[arg(short, long)]
         connect: Vec<String>,
         #[arg(short, long)]
         mine: bool,
-        #[arg(short, long, default_value_t = 0)]
-        threads: usize,
+        /// Don't mine, even if the --config file sets mine = true
+        #[arg(long, conflicts_with = "mine")]
+        no_mine: bool,
+        /// Mining threads (default: all cores)
+        #[arg(short, long)]
+        threads: Option<usize>,
         /// Run a mining pool server alongside the node
         #[arg(long)]
         pool: bool,
@@ -51,9 +69,78 @@ enum Commands {
         #[arg(long, default_value = "9334")]
         pool_port: u16,
 
+        /// Run the pool in solo mode: every share is a block paying the worker
+        /// that found it (implies --pool)
+        #[arg(long)]
+        pool_solo: bool,
+
          /// Miner identity tag embedded in blocks (max 32 chars)
         #[arg(long, default_value = "")]
         miner_tag: String,
+
+        /// Split the miner reward: ADDRESS:WEIGHT (repeatable, e.g. --payout A:90 --payout B:10)
+        #[arg(long)]
+        payout: Vec<String>,
+
+        /// Signal readiness for a version-bits deployment (0-28, repeatable)
+        #[arg(long, value_parser = clap::value_parser!(u32).range(0..29))]
+        signal_bit: Vec<u32>,
+
+        /// Only mine transactions paying at least this many base units per byte
+        #[arg(long, default_value_t = 0.0)]
+        min_fee_rate: f64,
+
+        /// Most transactions per mined block besides the coinbase (0 = empty blocks)
+        #[arg(long)]
+        max_block_txs: Option<usize>,
+
+        /// Route outbound P2P connections through a SOCKS5 proxy (host:port, e.g. Tor on 127.0.0.1:9050). DNS seeds are skipped
+        #[arg(long)]
+        proxy: Option<String>,
+
+        /// Onion service address (xyz.onion:port) to advertise to peers
+        #[arg(long)]
+        onion: Option<String>,
+
+        /// Don't maintain the on-disk transaction index (saves disk on light nodes)
+        #[arg(long)]
+        no_txindex: bool,
+
+        /// Maintain a per-address transaction history index (for getaddresshistory)
+        #[arg(long)]
+        addressindex: bool,
+
+        /// Refuse reorgs that would disconnect more than this many blocks
+        #[arg(long, default_value_t = MAX_REORG_DEPTH)]
+        max_reorg_depth: u64,
+
+        /// Delete full blocks older than this many blocks (keeps headers and UTXOs)
+        #[arg(long, value_name = "KEEP_DEPTH")]
+        prune: Option<u64>,
+
+        /// Address the RPC server and explorer listen on (default: 127.0.0.1)
+        #[arg(long)]
+        rpc_bind: Option<String>,
+
+        /// Require --rpc-token for GET requests too (explorer, snapshot, metrics, websocket)
+        #[arg(long)]
+        rpc_auth_get: bool,
+
+        /// Browser origin allowed to call JSON-RPC cross-origin (repeatable; "*" for any)
+        #[arg(long)]
+        rpc_cors: Vec<String>,
+
+        /// Testnet only: pay this many EQF from the node wallet per `GET /faucet?address=...`
+        #[arg(long, value_name = "EQF")]
+        faucet: Option<f64>,
+
+        /// Serve the explorer and snapshot on this port instead, leaving the RPC port JSON-RPC only
+        #[arg(long)]
+        explorer_port: Option<u16>,
+
+        /// Address the --explorer-port listener binds (default: 0.0.0.0)
+        #[arg(long)]
+        explorer_bind: Option<String>,
     },
     /// Send EQF to an address
     Send {
@@ -63,6 +150,43 @@ enum Commands {
         amount: f64,
         #[arg(short, long, default_value_t = 0.0001)]
         fee: f64,
+        /// Fee rate in base units per byte (overrides --fee; see `estimatefee`)
+        #[arg(long)]
+        fee_rate: Option<f64>,
+        /// Coin selection: largest-first, smallest-first, bnb (exact match, no change), oldest
+        #[arg(long, default_value = "largest-first", value_parser = parse_coin_selection)]
+        coin_selection: CoinSelection,
+    },
+    /// Prepare an unsigned send from the wallet's addresses (watch-only ones
+    /// included) for `signtx` on an offline machine
+    #[command(name = "createunsigned")]
+    CreateUnsigned {
+        #[arg(short, long)]
+        to: String,
+        #[arg(short, long)]
+        amount: f64,
+        #[arg(short, long, default_value_t = 0.0001)]
+        fee: f64,
+        /// Change address (default: the address of the first input spent)
+        #[arg(long)]
+        change: Option<String>,
+        #[arg(long, default_value = "largest-first", value_parser = parse_coin_selection)]
+        coin_selection: CoinSelection,
+        #[arg(short, long, default_value = "unsigned_tx.json")]
+        output: String,
+    },
+    /// Sign a `createunsigned` file with this wallet's keys; never touches the network
+    #[command(name = "signtx")]
+    SignTx {
+        #[arg(default_value = "unsigned_tx.json")]
+        input: String,
+        #[arg(short, long, default_value = "signed_tx.json")]
+        output: String,
+    },
+    /// Broadcast a signed transaction file through the node
+    Broadcast {
+        #[arg(default_value = "signed_tx.json")]
+        input: String,
     },
     /// Show balance
     Balance { address: Option<String> },
@@ -71,10 +195,31 @@ enum Commands {
         #[command(subcommand)]
         action: WalletAction,
     },
+    /// Sign a message with one of the wallet's addresses
+    SignMessage {
+        address: String,
+        message: String,
+    },
+    /// Verify a message signature against an address
+    VerifyMessage {
+        address: String,
+        message: String,
+        signature: String,
+    },
+    /// Decode a raw transaction without broadcasting it
+    /// (default: the pending_tx.json saved by an offline `send`)
+    DecodeTx { hex: Option<String> },
     /// Show blockchain info
     Info,
+    /// Re-check the stored chain: linkage, difficulty, PoW, merkle roots and the UTXO set
+    VerifyChain {
+        /// Only re-check PoW and merkle roots for the newest DEPTH blocks
+        depth: Option<u64>,
+    },
     /// Show connected peers
     Peers,
+    /// Shut down a running node (it must run with --rpc-token)
+    Stop,
     /// Export chain snapshot for fast bootstrap
     ExportSnapshot {
         /// Output file path (default: snapshot.bin)
@@ -92,6 +237,15 @@ enum Commands {
         #[arg(default_value_t = 5)]
         count: u64,
     },
+    /// Measure this machine's EquiHash-X hashrate
+    Benchmark {
+        /// How long to hash for
+        #[arg(long, default_value_t = 10)]
+        seconds: u64,
+        /// Hashing threads (default: all cores)
+        #[arg(long, short, default_value_t = 0)]
+        threads: usize,
+    },
     /// Connect to a mining pool (no full node needed)
     PoolMine {
         /// Pool server addresses — can specify multiple for failover
@@ -119,6 +273,19 @@ enum WalletAction {
     Show,
     /// Generate a new receiving address
     NewAddress,
+    /// Track an address's balance without its private key
+    Watch { address: String },
+    /// Move every mature output of a raw private key (hex) to an address.
+    /// The key is only used to sign; it isn't added to the wallet.
+    Sweep {
+        secret: String,
+        dest: String,
+        /// Fee rate in base units per byte (default: the node's estimate)
+        #[arg(long)]
+        fee_rate: Option<f64>,
+    },
+    /// Label one of the wallet's addresses (an empty name removes the label)
+    Label { address: String, name: String },
     /// Encrypt the wallet with a password
     Encrypt {
         #[arg(short, long)]
@@ -129,6 +296,18 @@ enum WalletAction {
         #[arg(short, long)]
         password: String,
     },
+    /// Print the 24-word recovery phrase for the wallet's HD seed
+    Mnemonic,
+    /// Recreate an HD wallet from its recovery phrase or seed (hex)
+    Restore {
+        #[arg(long, conflicts_with = "seed", required_unless_present = "seed")]
+        mnemonic: Option<String>,
+        #[arg(long)]
+        seed: Option<String>,
+        /// Number of addresses to derive (default: scan the chain for used addresses)
+        #[arg(long)]
+        count: Option<u32>,
+    },
 }
 
 fn wallet_path(data_dir: &str) -> PathBuf { PathBuf::from(data_dir).join("wallet.json") }
@@ -144,18 +323,95 @@ fn format_eqf(base_units: u64) -> String {
     else { format!("{}.{:08}", whole, frac).trim_end_matches('0').to_string() }
 }
 
+fn format_duration(secs: f64) -> String {
+    if secs < 120.0 { format!("{:.0}s", secs) }
+    else if secs < 7200.0 { format!("{:.1} min", secs / 60.0) }
+    else if secs < 172_800.0 { format!("{:.1} h", secs / 3600.0) }
+    else { format!("{:.1} days", secs / 86_400.0) }
+}
+
 fn parse_eqf(amount: f64) -> u64 { (amount * COIN as f64).round() as u64 }
 fn rpc_port(p2p: u16) -> u16 { p2p + rpc::RPC_PORT_OFFSET }
 
+/// Settings read from `--config`. Every key is optional; see `Config::load`.
+#[derive(Debug, Default, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Config {
+    data_dir: Option<String>,
+    port: Option<u16>,
+    testnet: Option<bool>,
+    /// Peers to connect to, as for `node --connect`
+    connect: Option<Vec<String>>,
+    mine: Option<bool>,
+    threads: Option<usize>,
+    rpc_bind: Option<String>,
+    rpc_token: Option<String>,
+    rpc_cors: Option<Vec<String>>,
+    explorer_port: Option<u16>,
+    explorer_bind: Option<String>,
+}
+
+impl Config {
+    fn load(path: &std::path::Path) -> Result<Self, String> {
+        let text = std::fs::read_to_string(path).map_err(|e| e.to_string())?;
+        toml::from_str(&text).map_err(|e| e.to_string())
+    }
+
+    /// Fill in whatever the command line left unset. A `true` switch in the
+    /// file is overridden with `--no-testnet` / `--no-mine`.
+    fn apply(self, cli: &mut Cli) {
+        if cli.data_dir.is_none() { cli.data_dir = self.data_dir; }
+        if cli.port.is_none() { cli.port = self.port; }
+        if cli.rpc_token.is_none() { cli.rpc_token = self.rpc_token; }
+        if !cli.no_testnet { cli.testnet |= self.testnet.unwrap_or(false); }
+        if let Commands::Node { connect, mine, no_mine, threads, rpc_bind, rpc_cors, explorer_port, explorer_bind, .. } = &mut cli.command {
+            if connect.is_empty() { *connect = self.connect.unwrap_or_default(); }
+            if !*no_mine { *mine |= self.mine.unwrap_or(false); }
+            if threads.is_none() { *threads = self.threads; }
+            if rpc_bind.is_none() { *rpc_bind = self.rpc_bind; }
+            if rpc_cors.is_empty() { *rpc_cors = self.rpc_cors.unwrap_or_default(); }
+            if explorer_port.is_none() { *explorer_port = self.explorer_port; }
+            if explorer_bind.is_none() { *explorer_bind = self.explorer_bind; }
+        }
+    }
+}
+
+fn parse_coin_selection(s: &str) -> Result<CoinSelection, String> {
+    CoinSelection::parse(s).ok_or_else(|| format!("unknown coin selection '{}'", s))
+}
+
+/// Parse a `--payout ADDRESS:WEIGHT` argument
+fn parse_payout(arg: &str) -> Result<(Hash256, u64), String> {
+    let (addr, weight) = arg.rsplit_once(':').ok_or("expected ADDRESS:WEIGHT")?;
+    let hash = wallet::address_to_pubkey_hash(addr).ok_or("invalid address")?;
+    let weight: u64 = weight.parse().map_err(|_| "weight must be a non-negative integer")?;
+    if weight == 0 { return Err("weight must be greater than zero".into()); }
+    Ok((hash, weight))
+}
+
 fn main() {
-    tracing_subscriber::fmt()
-        .with_env_filter(
-            tracing_subscriber::EnvFilter::from_default_env()
-                .add_directive("equiforge=info".parse().unwrap()),
-        )
-        .init();
+    let mut cli = Cli::parse();
+    if let Some(path) = cli.config.clone() {
+        match Config::load(&path) {
+            Ok(config) => config.apply(&mut cli),
+            Err(e) => {
+                eprintln!("❌ Can't read config {}: {}", path.display(), e);
+                std::process::exit(1);
+            }
+        }
+    }
+
+    let filter = tracing_subscriber::EnvFilter::from_default_env()
+        .add_directive("equiforge=info".parse().unwrap());
+    if cli.log_format == "json" {
+        tracing_subscriber::fmt().json().with_env_filter(filter).init();
+    } else {
+        tracing_subscriber::fmt().with_env_filter(filter).init();
+    }
 
-    let cli = Cli::parse();
+    if let Some(token) = &cli.rpc_token {
+        rpc::set_client_token(token.clone());
+    }
 
     // Initialize network config (must happen before anything touches params)
     init_network(cli.testnet);
@@ -183,18 +439,67 @@ fn main() {
             println!("\n  Run: equiforge node --mine");
         }
 
-        Commands::Node { connect, mine, threads, pool, pool_port, miner_tag } => {
+        Commands::Node { connect, mine, no_mine: _, threads, pool, pool_port, pool_solo, miner_tag, payout, signal_bit, min_fee_rate, max_block_txs, proxy, onion, no_txindex, addressindex, max_reorg_depth, prune, rpc_bind, rpc_auth_get, rpc_cors, faucet, explorer_port, explorer_bind } => {
+            if proxy.as_deref().is_some_and(|p| network::split_host_port(p).is_none()) {
+                eprintln!("❌ --proxy must be host:port");
+                std::process::exit(1);
+            }
+            if onion.as_deref().is_some_and(|o| !network::socks5::is_onion(o) || network::split_host_port(o).is_none()) {
+                eprintln!("❌ --onion must be an onion address with a port (xyz.onion:9333)");
+                std::process::exit(1);
+            }
+            if let Some(amount) = faucet {
+                if !is_testnet() {
+                    eprintln!("❌ --faucet is only available on --testnet");
+                    std::process::exit(1);
+                }
+                if !amount.is_finite() || amount <= 0.0 {
+                    eprintln!("❌ --faucet must be a positive amount");
+                    std::process::exit(1);
+                }
+            }
+            if !min_fee_rate.is_finite() || min_fee_rate < 0.0 {
+                eprintln!("❌ --min-fee-rate must be a non-negative number");
+                std::process::exit(1);
+            }
+            let payout_targets: Vec<(Hash256, u64)> = payout.iter()
+                .map(|p| parse_payout(p).unwrap_or_else(|e| { eprintln!("❌ Invalid --payout '{}': {}", p, e); std::process::exit(1); }))
+                .collect();
+            let version_bits = signal_bit.iter().fold(0u32, |acc, bit| acc | (1 << bit));
+            if let Some(keep) = prune {
+                // Reorgs need the disconnected blocks' undo data, which pruning deletes
+                if keep <= max_reorg_depth {
+                    eprintln!("❌ --prune must keep more than --max-reorg-depth ({}) blocks", max_reorg_depth);
+                    std::process::exit(1);
+                }
+                if addressindex {
+                    eprintln!("❌ --prune can't be combined with --addressindex (reindexing needs every block)");
+                    std::process::exit(1);
+                }
+            }
             let rt = tokio::runtime::Runtime::new().unwrap();
             rt.block_on(run_node(
         data_dir,
         port,
         connect,
         mine,
-        threads,
-        pool,
+        threads.unwrap_or(0),
+        pool || pool_solo,
         pool_port,
+        pool_solo,
         pw,
         miner_tag,
+        payout_targets,
+        version_bits,
+        min_fee_rate,
+        max_block_txs.map_or(MAX_TXS_PER_BLOCK - 1, |n| n.min(MAX_TXS_PER_BLOCK - 1)),
+        proxy,
+        onion,
+        ChainOptions { tx_index: !no_txindex, address_index: addressindex, max_reorg_depth, prune_depth: prune },
+        rpc_bind.unwrap_or_else(|| "127.0.0.1".to_string()),
+        rpc::RpcAuth { token: cli.rpc_token.clone(), protect_get: rpc_auth_get, cors_origins: rpc_cors },
+        explorer_port.map(|p| (explorer_bind.unwrap_or_else(|| "0.0.0.0".to_string()), p)),
+        faucet.map(parse_eqf),
     ));
         }
 
@@ -209,6 +514,9 @@ fn main() {
                 println!("  Mempool:    {}", r["mempool"]);
                 println!("  Banned:     {}", r["banned"]);
                 println!("  Reward:     {} EQF", r["block_reward"]);
+                if r["pruned"].as_bool() == Some(true) {
+                    println!("  Pruned:     blocks below #{} deleted", r["pruneheight"]);
+                }
             } else {
                 let chain = open_chain(data_dir);
                 println!("📊 EquiForge (from disk)");
@@ -217,6 +525,89 @@ fn main() {
                 println!("  Difficulty: {:.2}", chain.fractional_difficulty());
                 println!("  UTXOs:      {}", chain.utxo_set.len());
                 println!("  Reward:     {} EQF", format_eqf(block_reward(chain.height)));
+                if chain.is_pruned() {
+                    println!("  Pruned:     blocks below #{} deleted", chain.prune_height());
+                }
+            }
+        }
+
+        Commands::VerifyChain { depth } => {
+            let r = rpc::try_rpc_call(rpc_port(port), "verifychain", serde_json::json!([depth])).unwrap_or_else(|| {
+                println!("🔍 Verifying chain on disk...");
+                rpc::verify_chain_json(&open_chain(data_dir), depth)
+            });
+            if r["ok"].as_bool() != Some(true) {
+                eprintln!("❌ Chain inconsistent at block #{}: {}", r["height"], r["reason"].as_str().unwrap_or("?"));
+                std::process::exit(1);
+            }
+            println!("✅ Chain OK: heights {}..={} ({} blocks fully re-checked)",
+                r["start_height"], r["end_height"], r["blocks_checked"]);
+            if r["utxo_set_checked"].as_bool() == Some(true) {
+                println!("  UTXO set matches replay ({} entries)", r["utxos"]);
+            } else {
+                println!("  UTXO set not checked (chain is pruned)");
+            }
+        }
+
+        Commands::SignMessage { address, message } => {
+            let signed = match rpc::try_rpc_call(rpc_port(port), "signmessage", serde_json::json!([address, message])) {
+                Some(r) => r.as_str().map(String::from).ok_or_else(|| "unexpected RPC response".to_string()),
+                None => load_wallet(data_dir, pw).sign_message(&address, &message),
+            };
+            match signed {
+                Ok(sig) => println!("{}", sig),
+                Err(e) => { eprintln!("❌ {}", e); std::process::exit(1); }
+            }
+        }
+
+        Commands::VerifyMessage { address, message, signature } => {
+            match wallet::verify_message(&address, &message, &signature) {
+                Ok(true) => println!("✅ Signature is valid for {}", address),
+                Ok(false) => { println!("❌ Signature is NOT valid for {}", address); std::process::exit(1); }
+                Err(e) => { eprintln!("❌ {}", e); std::process::exit(1); }
+            }
+        }
+
+        Commands::DecodeTx { hex } => {
+            let hex = hex.unwrap_or_else(|| {
+                let path = PathBuf::from(data_dir).join("pending_tx.json");
+                let tx = std::fs::read_to_string(&path).ok()
+                    .and_then(|json| serde_json::from_str::<equiforge::core::types::Transaction>(&json).ok())
+                    .unwrap_or_else(|| { eprintln!("❌ No raw tx given and no readable {}", path.display()); std::process::exit(1); });
+                rpc::raw_tx_hex(&tx)
+            });
+            // A decode error from the node is reproduced by the local fallback
+            let r = rpc::try_rpc_call(rpc_port(port), "decoderawtransaction", serde_json::json!([hex])).unwrap_or_else(|| {
+                match rpc::decode_raw_tx(&hex) {
+                    Ok(tx) => rpc::decoded_tx_json(&tx, &open_chain(data_dir).utxo_set),
+                    Err(e) => { eprintln!("❌ {}", e); std::process::exit(1); }
+                }
+            });
+            println!("🧾 TX {}", r["txid"].as_str().unwrap_or("?"));
+            println!("  Size:    {} bytes, version {}, lock_time {}", r["size"], r["version"], r["lock_time"]);
+            for input in r["inputs"].as_array().into_iter().flatten() {
+                match input["amount_base"].as_u64() {
+                    Some(amount) => println!("  In:      {}:{}  {} EQF  {}", input["txid"].as_str().unwrap_or("?"), input["vout"],
+                        format_eqf(amount), input["address"].as_str().unwrap_or("")),
+                    None if input["type"] == "coinbase" => println!("  In:      coinbase"),
+                    None => println!("  In:      {}:{}  (not in the UTXO set)", input["txid"].as_str().unwrap_or("?"), input["vout"]),
+                }
+            }
+            for output in r["outputs"].as_array().into_iter().flatten() {
+                println!("  Out {}:   {} EQF  {} ({})", output["vout"], format_eqf(output["amount_base"].as_u64().unwrap_or(0)),
+                    output["address"].as_str().unwrap_or("?"), output["type"].as_str().unwrap_or("?"));
+            }
+            match r["fee_base"].as_u64() {
+                Some(fee) => println!("  Fee:     {} EQF ({:.2} per byte)", format_eqf(fee), r["fee_rate"].as_f64().unwrap_or(0.0)),
+                None if r["is_coinbase"] == true => println!("  Fee:     none (coinbase)"),
+                None => println!("  Fee:     unknown (some inputs are not in the UTXO set)"),
+            }
+        }
+
+        Commands::Stop => {
+            match rpc::rpc_call(rpc_port(port), "stop", serde_json::json!([])) {
+                Ok(r) => println!("🛑 {}", r.as_str().unwrap_or("stopping")),
+                Err(e) => { eprintln!("❌ {}", e); std::process::exit(1); }
             }
         }
 
@@ -229,10 +620,14 @@ fn main() {
                         } else {
                             println!("🌐 Connected peers ({}):", arr.len());
                             for p in arr {
-                                println!("  {} v{} height={}",
+                                let dir = if p["inbound"].as_bool().unwrap_or(false) { "in " } else { "out" };
+                                let ping = p["ping_ms"].as_u64().map(|ms| format!("{}ms", ms)).unwrap_or_else(|| "-".into());
+                                println!("  [{}] {} v{} height={} ping={}",
+                                    dir,
                                     p["address"].as_str().unwrap_or("?"),
                                     p["version"],
-                                    p["best_height"]);
+                                    p["best_height"],
+                                    ping);
                             }
                         }
                     }
@@ -258,29 +653,35 @@ fn main() {
                     let wallet = load_wallet(data_dir, pw);
                     let use_rpc = rpc::try_rpc_call(rpc_port(port), "getinfo", serde_json::json!([])).is_some();
                     println!("💰 Wallet:");
+                    let chain = if use_rpc { None } else { Chain::open(data_dir).ok() };
+                    let balance_of = |hash: &Hash256| match &chain {
+                        Some(c) => c.utxo_set.balance_of(hash),
+                        None => rpc::try_rpc_call(rpc_port(port), "getbalance", serde_json::json!([wallet::pubkey_hash_to_address(hash)]))
+                            .and_then(|r| r["balance_base"].as_u64()).unwrap_or(0),
+                    };
                     let mut total: u64 = 0;
                     for (i, kp) in wallet.keypairs.iter().enumerate() {
-                        let addr = kp.address();
-                        let bal = if use_rpc {
-                            rpc::try_rpc_call(rpc_port(port), "getbalance", serde_json::json!([addr]))
-                                .and_then(|r| r["balance_base"].as_u64()).unwrap_or(0)
-                        } else {
-                            match Chain::open(data_dir) {
-                                Ok(c) => c.utxo_set.balance_of(&kp.pubkey_hash()),
-                                Err(_) => 0,
-                            }
-                        };
+                        let bal = balance_of(&kp.pubkey_hash());
                         total += bal;
                         if bal > 0 || i == 0 {
-                            println!("  {} {} EQF{}", addr, format_eqf(bal), if i == 0 { " (primary)" } else { "" });
+                            println!("  {} {} EQF{}", kp.address(), format_eqf(bal), if i == 0 { " (primary)" } else { "" });
                         }
                     }
                     println!("  Total: {} EQF", format_eqf(total));
+                    let mut watched: u64 = 0;
+                    for hash in wallet.watch_only() {
+                        let bal = balance_of(hash);
+                        watched += bal;
+                        println!("  {} {} EQF (watch-only)", wallet::pubkey_hash_to_address(hash), format_eqf(bal));
+                    }
+                    if !wallet.watch_only().is_empty() {
+                        println!("  Watch-only total: {} EQF", format_eqf(watched));
+                    }
                 }
             }
         }
 
-        Commands::Send { to, amount, fee } => {
+        Commands::Send { to, amount, fee, fee_rate, coin_selection } => {
             let wallet = load_wallet(data_dir, pw);
             let recipient_hash = match wallet::address_to_pubkey_hash(&to) {
                 Some(h) => h,
@@ -288,6 +689,10 @@ fn main() {
             };
             let amount_base = parse_eqf(amount);
             let fee_base = parse_eqf(fee);
+            let build = |utxo_set: &equiforge::core::chain::UtxoSet, current_height: u64| match fee_rate {
+                Some(rate) => wallet.create_send_tx_with_fee_rate(utxo_set, recipient_hash, amount_base, rate, current_height, coin_selection),
+                None => wallet.create_send_tx(utxo_set, recipient_hash, amount_base, fee_base, current_height, coin_selection),
+            };
 
             if let Some(info) = rpc::try_rpc_call(rpc_port(port), "getinfo", serde_json::json!([])) {
                 let current_height = info["height"].as_u64().unwrap_or(0);
@@ -319,11 +724,14 @@ fn main() {
                     }
                 }
 
-                let tx = match wallet.create_send_tx(&utxo_set, recipient_hash, amount_base, fee_base, current_height) {
+                let tx = match build(&utxo_set, current_height) {
                     Ok(tx) => tx,
                     Err(e) => { eprintln!("❌ {}", e); std::process::exit(1); }
                 };
-                println!("📤 Sending {} EQF to {} (fee: {} EQF)", format_eqf(amount_base), to, format_eqf(fee_base));
+                let input_total: u64 = tx.inputs.iter()
+                    .filter_map(|i| utxo_set.get(&i.previous_output)).map(|e| e.output.amount).sum();
+                let paid_fee = input_total - tx.total_output();
+                println!("📤 Sending {} EQF to {} (fee: {} EQF)", format_eqf(amount_base), to, format_eqf(paid_fee));
                 let tx_json = serde_json::to_value(&tx).unwrap();
                 match rpc::rpc_call(rpc_port(port), "sendrawtransaction", serde_json::json!([tx_json])) {
                     Ok(r) => println!("  ✅ TX: {}", r["txid"].as_str().unwrap_or("?")),
@@ -332,7 +740,7 @@ fn main() {
             } else {
                 let chain = open_chain(data_dir);
                 let current_height = chain.height;
-                let tx = match wallet.create_send_tx(&chain.utxo_set, recipient_hash, amount_base, fee_base, current_height) {
+                let tx = match build(&chain.utxo_set, current_height) {
                     Ok(tx) => tx,
                     Err(e) => { eprintln!("❌ {}", e); std::process::exit(1); }
                 };
@@ -343,17 +751,190 @@ fn main() {
             }
         }
 
+        Commands::CreateUnsigned { to, amount, fee, change, coin_selection, output } => {
+            let wallet = load_wallet(data_dir, pw);
+            let (Some(recipient_hash), Some(change_hash)) = (
+                wallet::address_to_pubkey_hash(&to),
+                change.as_deref().map_or(Some(None), |c| wallet::address_to_pubkey_hash(c).map(Some)),
+            ) else {
+                eprintln!("❌ Invalid address");
+                std::process::exit(1);
+            };
+            let (amount_base, fee_base) = (parse_eqf(amount), parse_eqf(fee));
+
+            // The node's UTXO set if one is running, otherwise the chain on disk
+            let mut candidates: Vec<(OutPoint, equiforge::core::chain::UtxoEntry)> = Vec::new();
+            let current_height = match rpc::try_rpc_call(rpc_port(port), "getinfo", serde_json::json!([])) {
+                Some(info) => {
+                    for hash in wallet.pubkey_hashes() {
+                        let utxos = rpc::try_rpc_call(rpc_port(port), "listunspent", serde_json::json!([wallet::pubkey_hash_to_address(&hash)]));
+                        candidates.extend(utxos.and_then(|r| r.as_array().cloned()).unwrap_or_default().iter().filter_map(|u| {
+                            let txid = hex::decode(u["txid"].as_str()?).ok()?.try_into().ok()?;
+                            Some((OutPoint { txid, vout: u["vout"].as_u64()? as u32 }, equiforge::core::chain::UtxoEntry {
+                                output: TxOutput { amount: u["amount_base"].as_u64()?, pubkey_hash: hash, script_pubkey: vec![] },
+                                height: u["height"].as_u64()?, is_coinbase: u["coinbase"].as_bool().unwrap_or(false),
+                            }))
+                        }));
+                    }
+                    info["height"].as_u64().unwrap_or(0)
+                }
+                None => {
+                    let chain = open_chain(data_dir);
+                    for hash in wallet.pubkey_hashes() {
+                        candidates.extend(chain.utxo_set.utxos_for(&hash).into_iter().map(|(op, e)| (op, e.clone())));
+                    }
+                    chain.height
+                }
+            };
+
+            let unsigned = wallet::select_coins(candidates, amount_base, fee_base, current_height, coin_selection)
+                .and_then(|inputs| {
+                    let change_hash = change_hash.or(inputs.first().map(|(_, e)| e.output.pubkey_hash)).unwrap_or(recipient_hash);
+                    wallet::build_unsigned_tx(&inputs, recipient_hash, amount_base, fee_base, change_hash)
+                });
+            let unsigned = match unsigned {
+                Ok(u) => u,
+                Err(e) => { eprintln!("❌ {}", e); std::process::exit(1); }
+            };
+            let mut export = serde_json::to_value(&unsigned).unwrap();
+            export["sighashes"] = serde_json::json!(unsigned.sighashes().iter().map(hex::encode).collect::<Vec<_>>());
+            std::fs::write(&output, serde_json::to_string_pretty(&export).unwrap()).unwrap();
+            println!("📝 Unsigned TX: {} EQF to {} (fee: {} EQF, {} inputs)",
+                format_eqf(amount_base), to, format_eqf(unsigned.fee()), unsigned.tx.inputs.len());
+            println!("  Saved to {}. Sign it offline with `equiforge signtx {}`.", output, output);
+        }
+
+        Commands::SignTx { input, output } => {
+            let export: serde_json::Value = match std::fs::read_to_string(&input).map_err(|e| e.to_string())
+                .and_then(|s| serde_json::from_str(&s).map_err(|e| e.to_string())) {
+                Ok(v) => v,
+                Err(e) => { eprintln!("❌ Cannot read {}: {}", input, e); std::process::exit(1); }
+            };
+            let unsigned: wallet::PartialTx = match serde_json::from_value(export.clone()) {
+                Ok(u) => u,
+                Err(e) => { eprintln!("❌ {} is not an unsigned transaction: {}", input, e); std::process::exit(1); }
+            };
+            // A mismatch means the file was altered or made by an incompatible version
+            let expected: Vec<String> = unsigned.sighashes().iter().map(hex::encode).collect();
+            if let Some(listed) = export["sighashes"].as_array() {
+                if listed.iter().map(|h| h.as_str().unwrap_or("")).ne(expected.iter().map(String::as_str)) {
+                    eprintln!("❌ Sighashes in {} don't match the transaction", input);
+                    std::process::exit(1);
+                }
+            }
+            for out in &unsigned.tx.outputs {
+                println!("  Pay:     {} EQF to {}", format_eqf(out.amount), wallet::pubkey_hash_to_address(&out.pubkey_hash));
+            }
+            println!("  Fee:     {} EQF", format_eqf(unsigned.fee()));
+
+            let wallet = load_wallet(data_dir, pw);
+            let tx = match wallet.sign_tx(&unsigned) {
+                Ok(tx) => tx,
+                Err(e) => { eprintln!("❌ {}", e); std::process::exit(1); }
+            };
+            std::fs::write(&output, serde_json::to_string_pretty(&tx).unwrap()).unwrap();
+            println!("✍️  Signed TX {} saved to {}", hex::encode(equiforge::crypto::txid::txid_v1(&tx)), output);
+            println!("  Broadcast it from an online machine with `equiforge broadcast {}`.", output);
+        }
+
+        Commands::Broadcast { input } => {
+            let tx = std::fs::read_to_string(&input).ok()
+                .and_then(|s| serde_json::from_str::<equiforge::core::types::Transaction>(&s).ok())
+                .unwrap_or_else(|| { eprintln!("❌ {} is not a signed transaction", input); std::process::exit(1); });
+            match rpc::rpc_call(rpc_port(port), "sendrawtransaction", serde_json::json!([serde_json::to_value(&tx).unwrap()])) {
+                Ok(r) => println!("📤 TX: {}", r["txid"].as_str().unwrap_or("?")),
+                Err(e) => { eprintln!("❌ {}", e); std::process::exit(1); }
+            }
+        }
+
         Commands::Wallet { action } => {
             match action {
                 WalletAction::Show => {
                     let wallet = load_wallet(data_dir, pw);
                     println!("🔑 Wallet: {}", wallet_path(data_dir).display());
                     println!("  Encrypted: {}", wallet.is_encrypted());
+                    match wallet.seed() {
+                        Some(_) => println!("  HD:        yes ({} derived)", wallet.hd_index()),
+                        None => println!("  HD:        no (random keys — back up after every new address)"),
+                    }
                     println!("  Addresses: {}", wallet.keypairs.len());
                     for (i, kp) in wallet.keypairs.iter().enumerate() {
-                        println!("  [{}] {}{}", i, kp.address(), if i == 0 { " (primary)" } else { "" });
+                        let label = wallet.label_of(&kp.pubkey_hash()).map(|l| format!(" \"{}\"", l)).unwrap_or_default();
+                        println!("  [{}] {}{}{}", i, kp.address(), if i == 0 { " (primary)" } else { "" }, label);
                         println!("      Pubkey hash (hex): {}", hex::encode(kp.pubkey_hash()));
                     }
+                    for hash in wallet.watch_only() {
+                        let label = wallet.label_of(hash).map(|l| format!(" \"{}\"", l)).unwrap_or_default();
+                        println!("  [watch] {}{}", wallet::pubkey_hash_to_address(hash), label);
+                    }
+                }
+                WalletAction::Watch { address } => {
+                    let Some(hash) = wallet::address_to_pubkey_hash(&address) else {
+                        eprintln!("❌ Invalid address: {}", address);
+                        std::process::exit(1);
+                    };
+                    let mut wallet = load_wallet(data_dir, pw);
+                    if wallet.add_watch_only(hash) {
+                        println!("👁  Watching {} (watch-only, can't be spent from)", address);
+                    } else {
+                        println!("ℹ️  {} is already in the wallet", address);
+                    }
+                }
+                WalletAction::Sweep { secret, dest, fee_rate } => {
+                    let key = match hex::decode(secret.trim()).ok().and_then(|b| <[u8; 32]>::try_from(b.as_slice()).ok()) {
+                        Some(bytes) => wallet::Keypair::from_secret_bytes(&bytes),
+                        None => { eprintln!("❌ Secret must be 32 bytes of hex"); std::process::exit(1); }
+                    };
+                    let Some(dest_hash) = wallet::address_to_pubkey_hash(&dest) else {
+                        eprintln!("❌ Invalid address: {}", dest);
+                        std::process::exit(1);
+                    };
+                    let Some(info) = rpc::try_rpc_call(rpc_port(port), "getinfo", serde_json::json!([])) else {
+                        eprintln!("❌ Sweeping needs a running node (its UTXO set is queried over RPC)");
+                        std::process::exit(1);
+                    };
+                    let current_height = info["height"].as_u64().unwrap_or(0);
+                    let fee_rate = fee_rate.unwrap_or_else(|| {
+                        rpc::try_rpc_call(rpc_port(port), "estimatefee", serde_json::json!([6]))
+                            .and_then(|r| r["feerate"].as_f64())
+                            .unwrap_or(equiforge::core::fees::FALLBACK_FEE_RATE)
+                    });
+                    let utxos: Vec<(OutPoint, equiforge::core::chain::UtxoEntry)> =
+                        rpc::try_rpc_call(rpc_port(port), "listunspent", serde_json::json!([key.address()]))
+                            .and_then(|r| r.as_array().cloned()).unwrap_or_default().iter()
+                            .filter_map(|u| {
+                                let txid = hex::decode(u["txid"].as_str()?).ok()?.try_into().ok()?;
+                                Some((OutPoint { txid, vout: u["vout"].as_u64()? as u32 }, equiforge::core::chain::UtxoEntry {
+                                    output: TxOutput { amount: u["amount_base"].as_u64()?, pubkey_hash: key.pubkey_hash(), script_pubkey: vec![] },
+                                    height: u["height"].as_u64()?, is_coinbase: u["coinbase"].as_bool().unwrap_or(false),
+                                }))
+                            })
+                            .collect();
+                    let sweep = match wallet::build_sweep_tx(&key, &utxos, dest_hash, fee_rate, current_height) {
+                        Ok(s) => s,
+                        Err(e) => { eprintln!("❌ {}", e); std::process::exit(1); }
+                    };
+                    if sweep.skipped_immature > 0 {
+                        println!("⚠️  Skipping {} EQF in immature coinbase outputs; sweep again after maturity", format_eqf(sweep.skipped_immature));
+                    }
+                    println!("🧹 Sweeping {} EQF from {} to {} (fee: {} EQF)",
+                        format_eqf(sweep.swept), key.address(), dest, format_eqf(sweep.fee));
+                    match rpc::rpc_call(rpc_port(port), "sendrawtransaction", serde_json::json!([serde_json::to_value(&sweep.tx).unwrap()])) {
+                        Ok(r) => println!("  ✅ TX: {}", r["txid"].as_str().unwrap_or("?")),
+                        Err(e) => { eprintln!("  ❌ {}", e); std::process::exit(1); }
+                    }
+                }
+                WalletAction::Label { address, name } => {
+                    let Some(hash) = wallet::address_to_pubkey_hash(&address) else {
+                        eprintln!("❌ Invalid address: {}", address);
+                        std::process::exit(1);
+                    };
+                    let mut wallet = load_wallet(data_dir, pw);
+                    match wallet.set_label(hash, &name) {
+                        Ok(()) if name.trim().is_empty() => println!("🏷  Removed label from {}", address),
+                        Ok(()) => println!("🏷  {} labeled \"{}\"", address, name.trim()),
+                        Err(e) => { eprintln!("❌ {}", e); std::process::exit(1); }
+                    }
                 }
                 WalletAction::NewAddress => {
                     let mut wallet = load_wallet(data_dir, pw);
@@ -374,6 +955,63 @@ fn main() {
                     wallet.remove_password();
                     println!("🔓 Wallet decrypted. Keys are now stored in plaintext.");
                 }
+                WalletAction::Mnemonic => {
+                    if pw.is_none() && wallet::wallet_file_is_encrypted(&wallet_path(data_dir)) {
+                        eprintln!("❌ Wallet is encrypted. Pass --password to reveal the recovery phrase.");
+                        std::process::exit(1);
+                    }
+                    let wallet = load_wallet(data_dir, pw);
+                    match wallet.to_mnemonic() {
+                        Some(phrase) => {
+                            println!("🔑 Recovery phrase (anyone with these words controls your funds):");
+                            println!("  {}", phrase);
+                        }
+                        None => {
+                            eprintln!("❌ This wallet predates HD derivation and has no seed. Back up {} instead.",
+                                wallet_path(data_dir).display());
+                            std::process::exit(1);
+                        }
+                    }
+                }
+                WalletAction::Restore { mnemonic, seed, count } => {
+                    let path = wallet_path(data_dir);
+                    if path.exists() {
+                        eprintln!("❌ {} already exists. Move it away before restoring.", path.display());
+                        std::process::exit(1);
+                    }
+                    let seed: [u8; 32] = match (mnemonic, seed) {
+                        (Some(phrase), _) => wallet::mnemonic_to_seed(&phrase)
+                            .unwrap_or_else(|e| { eprintln!("❌ {}", e); std::process::exit(1); }),
+                        (None, Some(hex_seed)) => match hex::decode(hex_seed.trim()).ok().and_then(|b| b.try_into().ok()) {
+                            Some(s) => s,
+                            None => { eprintln!("❌ Seed must be 64 hex characters"); std::process::exit(1); }
+                        },
+                        (None, None) => unreachable!("clap requires --mnemonic or --seed"),
+                    };
+                    let mut wallet = match count {
+                        Some(n) => Wallet::restore_from_seed(&seed, n),
+                        None => {
+                            println!("🔍 Scanning for used addresses (gap limit {})...", wallet::RESTORE_GAP_LIMIT);
+                            let use_rpc = rpc::try_rpc_call(rpc_port(port), "getinfo", serde_json::json!([])).is_some();
+                            let chain = if use_rpc { None } else { Chain::open(data_dir).ok() };
+                            Wallet::restore_scanning(&seed, wallet::RESTORE_GAP_LIMIT, |hash| {
+                                match &chain {
+                                    Some(c) => c.utxo_set.balance_of(hash) > 0,
+                                    None => rpc::try_rpc_call(rpc_port(port), "getbalance",
+                                            serde_json::json!([wallet::pubkey_hash_to_address(hash)]))
+                                        .and_then(|r| r["balance_base"].as_u64()).unwrap_or(0) > 0,
+                                }
+                            })
+                        }
+                    };
+                    wallet.label = "node".to_string();
+                    wallet.path = Some(path);
+                    match pw {
+                        Some(p) => wallet.set_password(p),
+                        None => wallet.save(),
+                    }
+                    println!("🔑 Restored {} address(es), primary {}", wallet.keypairs.len(), wallet.primary_address());
+                }
             }
         }
 
@@ -382,47 +1020,36 @@ fn main() {
             let chain = open_chain(data_dir);
             let height = chain.height;
 
-            // Collect all blocks from genesis to tip in order
-            let mut blocks: Vec<Block> = Vec::new();
-            for h in 0..=height {
-                if let Some(block) = chain.block_at_height(h) {
-                    blocks.push(block.clone());
-                } else {
-                    eprintln!("❌ Missing block at height {}! Chain data corrupted.", h);
-                    std::process::exit(1);
-                }
-            }
-
-            // Serialize: [version:u32][height:u64][block_count:u64][blocks...]
-            let mut data: Vec<u8> = Vec::new();
-            // Snapshot format version
-            data.extend_from_slice(&1u32.to_le_bytes());
-            // Chain height
-            data.extend_from_slice(&height.to_le_bytes());
-            // Block count
-            data.extend_from_slice(&(blocks.len() as u64).to_le_bytes());
-            // Genesis hash for verification
-            let genesis_hash = chain.genesis_hash();
-            data.extend_from_slice(&genesis_hash);
-
-            for block in &blocks {
-                let encoded = bincode::serialize(block).unwrap();
-                data.extend_from_slice(&(encoded.len() as u32).to_le_bytes());
-                data.extend_from_slice(&encoded);
+            // Every height must have a body before anything is written
+            if let Some(h) = (0..=height).find(|&h| chain.block_at_height(h).is_none()) {
+                eprintln!("❌ Missing block at height {}! Chain data corrupted.", h);
+                std::process::exit(1);
             }
 
-            // Compress with gzip
-            use std::io::Write;
-            let file = std::fs::File::create(&output).unwrap();
-            let mut encoder = flate2::write::GzEncoder::new(file, flate2::Compression::fast());
-            encoder.write_all(&data).unwrap();
-            encoder.finish().unwrap();
+            // Stream: [version:u32][height:u64][block_count:u64][genesis:32][checksum:32][blocks...]
+            // Blocks use the stable encoding so snapshots survive struct changes,
+            // and go through gzip one at a time instead of being buffered.
+            let file = std::fs::File::create(&output).unwrap_or_else(|e| {
+                eprintln!("❌ Cannot create {}: {}", output, e);
+                std::process::exit(1);
+            });
+            let mut encoder = flate2::write::GzEncoder::new(std::io::BufWriter::new(file), flate2::Compression::fast());
+            let written = snapshot::write_snapshot(&mut encoder, height, chain.genesis_hash(),
+                || (0..=height).filter_map(|h| chain.block_at_height(h)))
+                .and_then(|counts| encoder.finish().and_then(|mut w| std::io::Write::flush(&mut w)).map(|_| counts));
+            let (block_count, raw_bytes) = match written {
+                Ok(counts) => counts,
+                Err(e) => {
+                    eprintln!("❌ Snapshot write failed: {}", e);
+                    std::process::exit(1);
+                }
+            };
 
             let file_size = std::fs::metadata(&output).unwrap().len();
-            println!("  ✅ Exported {} blocks (height {}) to {}", blocks.len(), height, output);
+            println!("  ✅ Exported {} blocks (height {}) to {}", block_count, height, output);
             println!("  📦 File size: {:.1} MB ({} bytes raw → {} bytes compressed)",
                 file_size as f64 / 1_048_576.0,
-                data.len(),
+                raw_bytes,
                 file_size);
             println!("\n  Share this file so others can run:");
             println!("    equiforge import-snapshot -i {}", output);
@@ -436,28 +1063,28 @@ fn main() {
 
             println!("📸 Importing chain snapshot from {}...", input);
 
-            // Decompress
-            use std::io::Read;
-            let file = std::fs::File::open(&input).unwrap();
-            let mut decoder = flate2::read::GzDecoder::new(file);
-            let mut data = Vec::new();
-            decoder.read_to_end(&mut data).unwrap();
-
-            // Parse header
-            let mut offset = 0;
-            let snap_version = u32::from_le_bytes(data[offset..offset+4].try_into().unwrap());
-            offset += 4;
-            if snap_version != 1 {
-                eprintln!("❌ Unknown snapshot version: {}", snap_version);
-                std::process::exit(1);
-            }
-            let height = u64::from_le_bytes(data[offset..offset+8].try_into().unwrap());
-            offset += 8;
-            let block_count = u64::from_le_bytes(data[offset..offset+8].try_into().unwrap());
-            offset += 8;
-            let mut snap_genesis = [0u8; 32];
-            snap_genesis.copy_from_slice(&data[offset..offset+32]);
-            offset += 32;
+            let open_snapshot = || {
+                let file = std::fs::File::open(&input).unwrap_or_else(|e| {
+                    eprintln!("❌ Cannot open {}: {}", input, e);
+                    std::process::exit(1);
+                });
+                flate2::read::GzDecoder::new(std::io::BufReader::new(file))
+            };
+
+            // First pass: parse the header and hash the body, block data is
+            // discarded as it streams past. Nothing is touched until it checks out.
+            let (header, checksummed) = match snapshot::verify_snapshot(open_snapshot()) {
+                Ok(v) => v,
+                Err(e) => {
+                    eprintln!("❌ {}", e);
+                    eprintln!("   Try re-downloading.");
+                    std::process::exit(1);
+                }
+            };
+            let snap_version = header.version;
+            let height = header.height;
+            let block_count = header.block_count;
+            let snap_genesis = header.genesis_hash;
 
             // Verify genesis matches
             let fresh_chain = Chain::new();
@@ -470,9 +1097,15 @@ fn main() {
                 std::process::exit(1);
             }
 
-            println!("  📊 Snapshot: {} blocks (height {})", block_count, height);
+            println!("  📊 Snapshot: {} blocks (height {}, format v{})", block_count, height, snap_version);
             println!("  ✅ Genesis verified");
 
+            if checksummed {
+                println!("  ✅ Checksum verified");
+            } else {
+                println!("  ⚠️  v{} snapshot has no checksum — blocks are only checked as they replay", snap_version);
+            }
+
             // Wipe existing data and import fresh
             let db_path = std::path::PathBuf::from(data_dir);
             if db_path.exists() {
@@ -512,21 +1145,23 @@ fn main() {
             let mut imported = 0u64;
             let start = std::time::Instant::now();
 
-            for i in 0..block_count {
-                if offset + 4 > data.len() {
-                    eprintln!("❌ Snapshot truncated at block {}", i);
+            // Second pass: decode and connect one block at a time
+            let mut reader = match snapshot::SnapshotReader::new(open_snapshot()) {
+                Ok(r) => r,
+                Err(e) => {
+                    eprintln!("❌ {}", e);
                     std::process::exit(1);
                 }
-                let block_len = u32::from_le_bytes(data[offset..offset+4].try_into().unwrap()) as usize;
-                offset += 4;
-
-                if offset + block_len > data.len() {
-                    eprintln!("❌ Snapshot truncated at block {} (need {} bytes)", i, block_len);
-                    std::process::exit(1);
-                }
-
-                let block: Block = bincode::deserialize(&data[offset..offset+block_len]).unwrap();
-                offset += block_len;
+            };
+            for i in 0..block_count {
+                let block = match reader.next_block() {
+                    Ok(Some(b)) => b,
+                    Ok(None) => unreachable!("reader yields block_count blocks"),
+                    Err(e) => {
+                        eprintln!("❌ Block {} could not be read: {}", i, e);
+                        std::process::exit(1);
+                    }
+                };
 
                 // Skip genesis (already loaded)
                 if block.header.height == 0 {
@@ -565,9 +1200,13 @@ fn main() {
             let wallet = Wallet::new("test");
             let config = MinerConfig {
                 miner_pubkey_hash: wallet.primary_pubkey_hash(),
-                community_fund_hash: [0xCF; 32],
+                community_fund_hash: community_fund_hash(),
                 threads: num_cpus::get().max(1),
                 miner_tag: String::new(),
+                payout_targets: Vec::new(),
+                version_bits: 0,
+                min_fee_rate: 0.0,
+                max_txs: MAX_TXS_PER_BLOCK - 1,
             };
             let start = std::time::Instant::now();
             for i in 0..count {
@@ -591,6 +1230,26 @@ fn main() {
                 format_eqf(bal), chain.fractional_difficulty());
         }
 
+        Commands::Benchmark { seconds, threads } => {
+            let threads = if threads == 0 { num_cpus::get().max(1) } else { threads };
+            println!("⏱  Benchmarking EquiHash-X: {} thread(s) for {}s...", threads, seconds);
+            let result = equiforge::pow::benchmark(std::time::Duration::from_secs(seconds), threads);
+            println!("  Hashes:     {}", result.total_hashes());
+            println!("  Elapsed:    {:.2}s", result.elapsed.as_secs_f64());
+            for (i, rate) in result.thread_hashrates().iter().enumerate() {
+                println!("  Thread {:>3}: {:.2} H/s", i, rate);
+            }
+            println!("  Total:      {:.2} H/s", result.hashrate());
+            if let Some(r) = rpc::try_rpc_call(rpc_port(port), "getmininginfo", serde_json::json!([])) {
+                let expected = r["estimated_hashes"].as_f64().unwrap_or(0.0);
+                if result.hashrate() > 0.0 && expected > 0.0 {
+                    let secs = expected / result.hashrate();
+                    println!("  Difficulty: {} (≈{:.0} hashes per block)", r["difficulty"], expected);
+                    println!("  Expected time to block (solo): {}", format_duration(secs));
+                }
+            }
+        }
+
         Commands::PoolMine { pool, address, worker, threads } => {
             let worker_name = worker.unwrap_or_else(|| {
                 hostname::get()
@@ -616,7 +1275,7 @@ fn open_chain(data_dir: &str) -> Chain {
     Chain::open(data_dir).unwrap_or_else(|e| { eprintln!("❌ {}", e); std::process::exit(1); })
 }
 
-use equiforge::core::types::{Block, OutPoint, TxOutput};
+use equiforge::core::types::{Hash256, OutPoint, TxOutput};
 
 // ─── Node ───────────────────────────────────────────────────────────
 
@@ -628,23 +1287,58 @@ async fn run_node(
     threads: usize,
     pool: bool,
     pool_port: u16,
+    pool_solo: bool,
     pw: Option<&str>,
     miner_tag: String,
+    payout_targets: Vec<(Hash256, u64)>,
+    version_bits: u32,
+    min_fee_rate: f64,
+    max_block_txs: usize,
+    proxy: Option<String>,
+    onion: Option<String>,
+    chain_options: ChainOptions,
+    rpc_bind: String,
+    rpc_auth: rpc::RpcAuth,
+    explorer: Option<(String, u16)>,
+    faucet: Option<u64>,
 ) {
-    let state = NodeState::open(data_dir, port);
+    let state = NodeState::open_with_options(data_dir, port, chain_options);
+    *state.proxy.write().unwrap() = proxy.clone();
+    *state.onion_address.write().unwrap() = onion.clone();
     let wallet = load_wallet(data_dir, pw);
 
     let (height, tip, _, _) = network::get_node_info(&state).await;
     println!("🚀 EquiForge Node v{}", PROTOCOL_VERSION);
     println!("  Data:      {}", data_dir);
     println!("  P2P:       0.0.0.0:{}", port);
-    println!("  RPC:       127.0.0.1:{}", rpc_port(port));
-    println!("  Explorer:  http://127.0.0.1:{}", rpc_port(port));
+    println!("  RPC:       {}", network::join_host_port(&rpc_bind, rpc_port(port)));
+    if rpc_auth.token.is_some() {
+        println!("  RPC auth:  bearer token{}", if rpc_auth.protect_get { " (GETs too)" } else { "" });
+    }
+    match &explorer {
+        Some((bind, p)) => println!("  Explorer:  http://{}", network::join_host_port(bind, *p)),
+        None => println!("  Explorer:  http://127.0.0.1:{}", rpc_port(port)),
+    }
     println!("  Chain:     height={} tip={}", height, &hex::encode(tip)[..16]);
     println!("  Wallet:    {}", wallet.primary_address());
     println!("  Encrypted: {}", wallet.is_encrypted());
     println!("  Mining:    {}", if mine { "enabled" } else { "disabled" });
     if !miner_tag.is_empty() { println!("  Tag:       {}", miner_tag); }
+    for (hash, weight) in &payout_targets {
+        println!("  Payout:    {} (weight {})", wallet::pubkey_hash_to_address(hash), weight);
+    }
+    if version_bits != 0 { println!("  Signaling: version bits {:#x}", version_bits); }
+    if min_fee_rate > 0.0 { println!("  Fee floor: {} per byte", min_fee_rate); }
+    if max_block_txs < MAX_TXS_PER_BLOCK - 1 { println!("  Max txs:   {} per block", max_block_txs); }
+    if let Some(proxy) = &proxy { println!("  Proxy:     socks5://{}", proxy); }
+    if let Some(onion) = &onion { println!("  Onion:     {}", onion); }
+    if let Some(amount) = faucet {
+        println!("  Faucet:    {} EQF per request at /faucet", format_eqf(amount));
+        *state.faucet.lock().await = Some(rpc::faucet::Faucet::new(amount));
+    }
+    if !chain_options.tx_index { println!("  TxIndex:   disabled"); }
+    if chain_options.address_index { println!("  AddrIndex: enabled"); }
+    if let Some(keep) = chain_options.prune_depth { println!("  Pruning:   keep last {} blocks", keep); }
     if !seed_nodes().is_empty() { println!("  Seeds:     {} hardcoded", seed_nodes().len()); }
     if is_testnet() { println!("  Network:   TESTNET"); }
 
@@ -664,7 +1358,7 @@ async fn run_node(
         }
     }
 
-    let stop = Arc::new(AtomicBool::new(false));
+    let stop = state.shutdown.clone();
     let stop_clone = stop.clone();
     ctrlc::set_handler(move || {
         println!("\n🛑 Shutting down gracefully...");
@@ -672,8 +1366,11 @@ async fn run_node(
     }).expect("Ctrl-C");
 
     // RPC
-    { let s = state.clone(); let rp = rpc_port(port);
-      tokio::spawn(async move { rpc::start_rpc_server(s, rp).await; }); }
+    { let s = state.clone(); let rp = rpc_port(port); let bind = rpc_bind.clone();
+      tokio::spawn(async move {
+          let explorer = explorer.as_ref().map(|(b, p)| (b.as_str(), *p));
+          rpc::start_rpc_server(s, &bind, rp, rpc_auth, explorer).await;
+      }); }
 
     if pool {
         let pool_state = state.clone();
@@ -685,6 +1382,9 @@ async fn run_node(
             pplns_window: 10_000,
             pool_payout_hash: wallet.primary_pubkey_hash(),  // pool operator = node operator
             pool_name: if miner_tag.is_empty() { String::from("EquiForge-Pool") } else { miner_tag.clone() },
+            min_payout: equiforge::pool::DEFAULT_MIN_PAYOUT,
+            solo: pool_solo,
+            data_dir: Some(data_dir.to_string()),
         };
         tokio::spawn(async move {
             if let Err(e) = equiforge::pool::start_pool_server(pool_state, pool_config).await {
@@ -693,13 +1393,25 @@ async fn run_node(
         });
     }
 
+    // Hand the wallet to the node for wallet RPCs; mining only needs the payout hash
+    let miner_hash = wallet.primary_pubkey_hash();
+    *state.wallet.lock().await = Some(wallet);
+
     // Mining
     if mine {
         let s = state.clone(); let st = stop.clone();
         let t = if threads == 0 { num_cpus::get().max(1) } else { threads };
-        let tag = miner_tag.clone();
+        let cfg = MinerConfig {
+            miner_pubkey_hash: miner_hash,
+            community_fund_hash: community_fund_hash(), threads: t,
+            miner_tag: miner_tag.clone(),
+            payout_targets,
+            version_bits,
+            min_fee_rate,
+            max_txs: max_block_txs,
+        };
         println!("  Threads:   {}", t);
-        tokio::spawn(async move { mining_task(s, wallet, t, st, tag).await; });
+        tokio::spawn(async move { mining_task(s, st, cfg).await; });
     }
 
     // Status
@@ -749,20 +1461,16 @@ async fn run_node(
     }
 }
 
-async fn mining_task(state: Arc<NodeState>, wallet: Wallet, threads: usize, stop: Arc<AtomicBool>, miner_tag: String) {
-    tracing::info!("⛏️  Mining to {}", wallet.primary_address());
+async fn mining_task(state: Arc<NodeState>, stop: Arc<AtomicBool>, cfg: MinerConfig) {
+    let threads = cfg.threads;
+    tracing::info!("⛏️  Mining to {}", wallet::pubkey_hash_to_address(&cfg.miner_pubkey_hash));
     loop {
         if stop.load(Ordering::Relaxed) { break; }
         let tpl = {
             let chain = state.chain.read().await;
             let mp = state.mempool.lock().await;
-            let pending = mp.get_pending();
+            let pending = mp.select_for_block(MAX_BLOCK_SIZE, cfg.max_txs);
             drop(mp);
-            let cfg = MinerConfig {
-                miner_pubkey_hash: wallet.primary_pubkey_hash(),
-                community_fund_hash: [0xCF; 32], threads,
-                miner_tag: miner_tag.clone(),
-            };
             let height = chain.height + 1;
             let diff = chain.next_difficulty();
             let t = miner::create_block_template(&chain, &pending, &cfg);
@@ -812,15 +1520,26 @@ async fn status_task(state: Arc<NodeState>, stop: Arc<AtomicBool>) {
     let mut interval = tokio::time::interval(std::time::Duration::from_secs(30));
     let mut stuck_count: u32 = 0;
     let mut last_height: u64 = 0;
+    let mut last_warning: Option<String> = None;
     loop {
         interval.tick().await;
         if stop.load(Ordering::Relaxed) { break; }
         let (h, tip, u, p) = network::get_node_info(&state).await;
-        let fd = state.chain.read().await.fractional_difficulty();
+        let (fd, warning) = {
+            let chain = state.chain.read().await;
+            (chain.fractional_difficulty(), chain.anomaly_warning())
+        };
         let bans = state.scoreboard.lock().await.ban_count();
         tracing::info!("📊 height={} diff={:.1} tip={} utxos={} peers={} banned={}",
             h, fd, &hex::encode(tip)[..16], u, p, bans);
 
+        // ─── Anomaly Detection ───
+        // Advisory only; logged once per distinct warning
+        if let Some(w) = &warning {
+            if last_warning.as_ref() != Some(w) { tracing::warn!("⚠️  Chain anomaly: {}", w); }
+        }
+        last_warning = warning;
+
         // ─── Stuck Sync Detection ───
         // Check if peers are ahead but our height isn't moving
         let best_peer_height = {
@@ -852,3 +1571,82 @@ async fn status_task(state: Arc<NodeState>, stop: Arc<AtomicBool>) {
         last_height = h;
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FULL_CONFIG: &str = r#"
+        data_dir = "/var/lib/equiforge"
+        port = 19000
+        testnet = true
+        connect = ["44.55.66.77:19333"]
+        mine = true
+        threads = 4
+        rpc_bind = "10.0.0.1"
+        rpc_token = "from-file"
+        rpc_cors = ["https://wallet.example"]
+        explorer_port = 8080
+        explorer_bind = "10.0.0.2"
+    "#;
+
+    fn configured(args: &[&str]) -> Cli {
+        let mut cli = Cli::try_parse_from(args).unwrap();
+        toml::from_str::<Config>(FULL_CONFIG).unwrap().apply(&mut cli);
+        cli
+    }
+
+    #[test]
+    fn test_config_parses_every_key() {
+        let c: Config = toml::from_str(FULL_CONFIG).unwrap();
+        assert_eq!((c.data_dir.as_deref(), c.port, c.testnet), (Some("/var/lib/equiforge"), Some(19000), Some(true)));
+        assert_eq!((c.connect, c.mine, c.threads), (Some(vec!["44.55.66.77:19333".to_string()]), Some(true), Some(4)));
+        assert_eq!((c.rpc_bind.as_deref(), c.rpc_token.as_deref()), (Some("10.0.0.1"), Some("from-file")));
+        assert_eq!(c.rpc_cors, Some(vec!["https://wallet.example".to_string()]));
+        assert_eq!((c.explorer_port, c.explorer_bind.as_deref()), (Some(8080), Some("10.0.0.2")));
+
+        assert!(toml::from_str::<Config>("").unwrap().port.is_none());
+        assert!(toml::from_str::<Config>("mining = true").is_err());
+        assert!(toml::from_str::<Config>("port = \"9333\"").is_err());
+    }
+
+    #[test]
+    fn test_config_fills_unset_flags() {
+        let cli = configured(&["equiforge", "node"]);
+        assert_eq!((cli.data_dir.as_deref(), cli.port, cli.testnet), (Some("/var/lib/equiforge"), Some(19000), true));
+        assert_eq!(cli.rpc_token.as_deref(), Some("from-file"));
+        let Commands::Node { connect, mine, threads, rpc_bind, rpc_cors, explorer_port, explorer_bind, .. } = cli.command else { panic!() };
+        assert_eq!((connect, mine, threads), (vec!["44.55.66.77:19333".to_string()], true, Some(4)));
+        assert_eq!((rpc_bind.as_deref(), rpc_cors), (Some("10.0.0.1"), vec!["https://wallet.example".to_string()]));
+        assert_eq!((explorer_port, explorer_bind.as_deref()), (Some(8080), Some("10.0.0.2")));
+
+        // Keys for `node` leave other commands alone
+        let cli = configured(&["equiforge", "init"]);
+        assert!(matches!(cli.command, Commands::Init));
+        assert_eq!(cli.port, Some(19000));
+    }
+
+    #[test]
+    fn test_command_line_beats_config() {
+        let cli = configured(&[
+            "equiforge", "--data-dir", "/tmp/eqf", "--port", "9999", "--no-testnet", "--rpc-token", "from-cli",
+            "node", "--connect", "1.2.3.4:9333", "--no-mine", "--threads", "2", "--rpc-bind", "0.0.0.0",
+            "--rpc-cors", "*", "--explorer-port", "8081", "--explorer-bind", "127.0.0.1",
+        ]);
+        assert_eq!((cli.data_dir.as_deref(), cli.port, cli.testnet), (Some("/tmp/eqf"), Some(9999), false));
+        assert_eq!(cli.rpc_token.as_deref(), Some("from-cli"));
+        let Commands::Node { connect, mine, threads, rpc_bind, rpc_cors, explorer_port, explorer_bind, .. } = cli.command else { panic!() };
+        assert_eq!((connect, mine, threads), (vec!["1.2.3.4:9333".to_string()], false, Some(2)));
+        assert_eq!((rpc_bind.as_deref(), rpc_cors), (Some("0.0.0.0"), vec!["*".to_string()]));
+        assert_eq!((explorer_port, explorer_bind.as_deref()), (Some(8081), Some("127.0.0.1")));
+
+        // Switches turned off in the file are turned on from the command line
+        let mut cli = Cli::try_parse_from(["equiforge", "--testnet", "node", "--mine"]).unwrap();
+        toml::from_str::<Config>("testnet = false\nmine = false").unwrap().apply(&mut cli);
+        assert!(cli.testnet);
+        assert!(matches!(cli.command, Commands::Node { mine: true, .. }));
+
+        assert!(Cli::try_parse_from(["equiforge", "node", "--mine", "--no-mine"]).is_err());
+        assert!(Cli::try_parse_from(["equiforge", "--testnet", "--no-testnet", "init"]).is_err());
+    }
+}
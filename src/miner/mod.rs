@@ -15,15 +15,46 @@ pub struct MinerConfig {
     /// Number of mining threads
     pub threads: usize,
     pub miner_tag: String,
+    /// Optional weighted split of the miner's share: (pubkey_hash, weight).
+    /// When empty, the whole miner share goes to `miner_pubkey_hash`.
+    pub payout_targets: Vec<(Hash256, u64)>,
+    /// Version bits to signal (bit N = readiness for deployment N).
+    /// When zero, blocks carry the plain PROTOCOL_VERSION.
+    pub version_bits: u32,
+    /// Leave out transactions paying less than this (base units per byte)
+    pub min_fee_rate: f64,
+    /// Most non-coinbase transactions per template; zero mines empty blocks
+    pub max_txs: usize,
+}
+
+impl MinerConfig {
+    /// Header version for new blocks, with any configured version bits set
+    pub fn block_version(&self) -> u32 {
+        let bits = self.version_bits & !VERSIONBITS_TOP_MASK;
+        if bits == 0 { PROTOCOL_VERSION } else { VERSIONBITS_TOP_BITS | bits }
+    }
+
+    /// Payout targets for the coinbase, falling back to `miner_pubkey_hash`
+    pub fn payouts(&self) -> Vec<(Hash256, u64)> {
+        if self.payout_targets.iter().any(|(_, w)| *w > 0) {
+            self.payout_targets.clone()
+        } else {
+            vec![(self.miner_pubkey_hash, 1)]
+        }
+    }
 }
 
 impl Default for MinerConfig {
     fn default() -> Self {
         Self {
             miner_pubkey_hash: [0u8; 32],
-            community_fund_hash: [0xCF; 32],
+            community_fund_hash: community_fund_hash(),
             threads: 1,
             miner_tag: String::from("EquiForge-Miner"),
+            payout_targets: Vec::new(),
+            version_bits: 0,
+            min_fee_rate: 0.0,
+            max_txs: MAX_TXS_PER_BLOCK - 1,
         }
     }
 }
@@ -57,12 +88,14 @@ pub fn create_block_template(
     let mut total_fees: u64 = 0;
     let mut valid_txs: Vec<Transaction> = Vec::new();
     let mut block_size: usize = 0;
+    let mut block_sigops: usize = 0;
 
     for tx in pending_txs {
         if tx.is_coinbase() { continue; }
         let tx_size = tx.size();
-        if block_size + tx_size > MAX_BLOCK_SIZE { break; }
-        if valid_txs.len() + 1 >= MAX_TXS_PER_BLOCK { break; }
+        if block_size + tx_size > MAX_BLOCK_SIZE { continue; }
+        if block_sigops + tx.sigops() > MAX_BLOCK_SIGOPS { continue; }
+        if valid_txs.len() + 1 >= MAX_TXS_PER_BLOCK || valid_txs.len() >= config.max_txs { break; }
 
         // Try to calculate fee from UTXO set
         let mut input_sum: u64 = 0;
@@ -79,16 +112,18 @@ pub fn create_block_template(
         if output_sum > input_sum { continue; }
 
         let fee = input_sum - output_sum;
+        if (fee as f64) < config.min_fee_rate * tx_size as f64 { continue; }
         total_fees += fee;
         valid_txs.push(tx.clone());
         block_size += tx_size;
+        block_sigops += tx.sigops();
     }
 
     // Create coinbase with reward + fees
-    let coinbase = Transaction::new_coinbase(
+    let coinbase = Transaction::new_coinbase_weighted(
         height,
         reward + total_fees,
-        config.miner_pubkey_hash,
+        &config.payouts(),
         config.community_fund_hash,
         &config.miner_tag,
     );
@@ -99,7 +134,7 @@ pub fn create_block_template(
     // Build block with placeholder nonce
     let mut block = Block {
         header: BlockHeader {
-            version: PROTOCOL_VERSION,
+            version: config.block_version(),
             prev_hash,
             merkle_root: NULL_HASH,
             timestamp,
@@ -142,7 +177,7 @@ pub fn mine_block(mut block: Block, stop: Arc<AtomicBool>) -> MineResult {
 
         block.header.nonce = nonce;
 
-        if block.header.meets_difficulty() {
+        if leading_zero_bits(&block.header.compute_hash()) >= difficulty {
             let elapsed = start.elapsed().as_secs_f64();
             let hashrate = if elapsed > 0.0 {
                 hashes as f64 / elapsed
@@ -223,7 +258,7 @@ pub fn mine_block_parallel(block: Block, threads: usize, stop: Arc<AtomicBool>)
                     }
 
                     thread_block.header.nonce = nonce;
-                    if thread_block.header.meets_difficulty() {
+                    if leading_zero_bits(&thread_block.header.compute_hash()) >= difficulty {
                         let _ = tx.send(thread_block);
                         stop.store(true, Ordering::Relaxed);
                         return;
@@ -308,6 +343,7 @@ mod tests {
 
     #[test]
     fn test_create_block_template() {
+        let _ = std::panic::catch_unwind(|| init_network(false));
         let chain = Chain::new();
         let config = MinerConfig::default();
         let template = create_block_template(&chain, &[], &config);
@@ -316,11 +352,12 @@ mod tests {
         assert_eq!(template.header.prev_hash, chain.tip);
         assert_eq!(template.transactions.len(), 1);
         assert!(template.transactions[0].is_coinbase());
-        assert_eq!(template.&header.difficulty_target, initial_target());
+        assert_eq!(template.header.difficulty_target, INITIAL_DIFFICULTY);
     }
 
     #[test]
     fn test_mine_single_block() {
+        let _ = std::panic::catch_unwind(|| init_network(false));
         let chain = Chain::new();
         let config = MinerConfig::default();
         let template = create_block_template(&chain, &[], &config);
@@ -335,4 +372,101 @@ mod tests {
             MineResult::Cancelled => panic!("should not be cancelled"),
         }
     }
+
+    #[test]
+    fn test_template_version_bits() {
+        let _ = std::panic::catch_unwind(|| init_network(false));
+        let chain = Chain::new();
+        let plain = create_block_template(&chain, &[], &MinerConfig::default());
+        assert_eq!(plain.header.version, PROTOCOL_VERSION);
+
+        let config = MinerConfig { version_bits: (1 << 3) | (1 << 7), ..MinerConfig::default() };
+        let template = create_block_template(&chain, &[], &config);
+        assert_eq!(template.header.version, VERSIONBITS_TOP_BITS | (1 << 3) | (1 << 7));
+        assert_eq!(crate::core::chain::version_signal_bits(template.header.version), (1 << 3) | (1 << 7));
+    }
+
+    #[test]
+    fn test_template_fee_floor_and_tx_cap() {
+        let _ = std::panic::catch_unwind(|| init_network(false));
+        let mut chain = Chain::new();
+        // Three spends of 10_000 paying 100, 2_000 and 5_000 in fees
+        let pending: Vec<Transaction> = [100u64, 2_000, 5_000].iter().enumerate().map(|(i, fee)| {
+            let outpoint = OutPoint { txid: [0x50 + i as u8; 32], vout: 0 };
+            chain.utxo_set.add(outpoint.clone(), crate::core::chain::UtxoEntry {
+                output: TxOutput { amount: 10_000, pubkey_hash: [1; 32], script_pubkey: vec![] }, height: 0, is_coinbase: false,
+            });
+            Transaction {
+                version: 1,
+                inputs: vec![TxInput { previous_output: outpoint, signature: vec![], pubkey: vec![], script_sig: vec![], sequence: 0 }],
+                outputs: vec![TxOutput { amount: 10_000 - fee, pubkey_hash: [2; 32], script_pubkey: vec![] }],
+                lock_time: 0,
+            }
+        }).collect();
+        let coinbase_total = |config: &MinerConfig| create_block_template(&chain, &pending, config).transactions[0].total_output();
+
+        // Defaults include everything
+        let template = create_block_template(&chain, &pending, &MinerConfig::default());
+        assert_eq!(template.transactions.len(), 4);
+        assert_eq!(coinbase_total(&MinerConfig::default()), block_reward(1) + 7_100);
+
+        // The floor drops the 100-fee spend only
+        let rate = 1_000.0 / pending[0].size() as f64;
+        let floor = MinerConfig { min_fee_rate: rate, ..MinerConfig::default() };
+        assert_eq!(create_block_template(&chain, &pending, &floor).transactions.len(), 3);
+        assert_eq!(coinbase_total(&floor), block_reward(1) + 7_000);
+
+        // Capped at one, then empty
+        let capped = MinerConfig { max_txs: 1, ..MinerConfig::default() };
+        assert_eq!(create_block_template(&chain, &pending, &capped).transactions.len(), 2);
+        let empty = MinerConfig { max_txs: 0, ..MinerConfig::default() };
+        let template = create_block_template(&chain, &pending, &empty);
+        assert_eq!(template.transactions.len(), 1);
+        assert_eq!(template.transactions[0].total_output(), block_reward(1));
+    }
+
+    #[test]
+    fn test_template_sigops_limit() {
+        let _ = std::panic::catch_unwind(|| init_network(false));
+        let mut chain = Chain::new();
+        // Two spends that each fit, but not together; a small one still does
+        let pending: Vec<Transaction> = [MAX_BLOCK_SIGOPS / 2 + 1, MAX_BLOCK_SIGOPS / 2 + 1, 1].iter().enumerate().map(|(i, &n)| {
+            let inputs = (0..n).map(|vout| {
+                let outpoint = OutPoint { txid: [0x60 + i as u8; 32], vout: vout as u32 };
+                chain.utxo_set.add(outpoint.clone(), crate::core::chain::UtxoEntry {
+                    output: TxOutput { amount: 10, pubkey_hash: [1; 32], script_pubkey: vec![] }, height: 0, is_coinbase: false,
+                });
+                TxInput { previous_output: outpoint, signature: vec![], pubkey: vec![], script_sig: vec![], sequence: 0 }
+            }).collect();
+            Transaction { version: 1, inputs, outputs: vec![TxOutput { amount: 1, pubkey_hash: [2; 32], script_pubkey: vec![] }], lock_time: 0 }
+        }).collect();
+
+        let template = create_block_template(&chain, &pending, &MinerConfig::default());
+        assert_eq!(template.transactions.len(), 3);
+        assert_eq!(template.sigops(), MAX_BLOCK_SIGOPS / 2 + 2);
+        assert_eq!(template.transactions[2], pending[2]);
+    }
+
+    #[test]
+    fn test_weighted_coinbase_block_accepted() {
+        let _ = std::panic::catch_unwind(|| init_network(false));
+        let mut chain = Chain::new();
+        let config = MinerConfig {
+            payout_targets: vec![([0xA1; 32], 2), ([0xB2; 32], 1)],
+            ..MinerConfig::default()
+        };
+        let template = create_block_template(&chain, &[], &config);
+        let coinbase = &template.transactions[0];
+        assert_eq!(coinbase.outputs.len(), 3);
+        assert_eq!(coinbase.total_output(), block_reward(1));
+
+        let stop = Arc::new(AtomicBool::new(false));
+        let block = match mine_block(template, stop) {
+            MineResult::Found(b) => b,
+            MineResult::Cancelled => panic!("mining cancelled"),
+        };
+        chain.add_block(block).unwrap();
+        assert_eq!(chain.height, 1);
+        assert_eq!(chain.utxo_set.balance_of(&[0xB2; 32]), miner_reward(1) / 3);
+    }
 }
@@ -0,0 +1,250 @@
+//! Parallel block download during sync.
+//!
+//! Once a batch of headers validates, the missing blocks are split into
+//! contiguous chunks and spread round-robin over every peer that advertises
+//! the chunk's top height. Blocks arrive out of order, so each one waits here
+//! until its parent has connected and `take_ready` hands them back in chain
+//! order. A chunk whose peer disconnects or doesn't answer within
+//! `DOWNLOAD_TIMEOUT` is handed to another peer.
+
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use crate::core::types::{Block, Hash256};
+
+/// Most blocks per `GetBlockData` (peers cap their replies at this)
+pub const MAX_BLOCKS_PER_REQUEST: usize = 100;
+
+/// How long a peer gets to deliver its chunk before it's reassigned
+pub const DOWNLOAD_TIMEOUT: Duration = Duration::from_secs(60);
+
+struct InFlight {
+    peer: String,
+    height: u64,
+    requested: Instant,
+}
+
+#[derive(Default)]
+pub struct BlockDownloader {
+    in_flight: HashMap<Hash256, InFlight>,
+    /// Delivered blocks waiting for their parent, by hash
+    buffered: HashMap<Hash256, Block>,
+}
+
+impl BlockDownloader {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Split `wanted` (hash, height), ascending by height, across `peers`
+    /// (address, best height). Hashes already requested or delivered are
+    /// skipped. Returns the `GetBlockData` request for each peer.
+    pub fn assign(&mut self, wanted: &[(Hash256, u64)], peers: &[(String, u64)], now: Instant) -> Vec<(String, Vec<Hash256>)> {
+        let wanted: Vec<(Hash256, u64)> = wanted.iter()
+            .filter(|(h, _)| !self.in_flight.contains_key(h) && !self.buffered.contains_key(h))
+            .copied()
+            .collect();
+        if wanted.is_empty() || peers.is_empty() {
+            return Vec::new();
+        }
+
+        let chunk_size = wanted.len().div_ceil(peers.len()).clamp(1, MAX_BLOCKS_PER_REQUEST);
+        let mut requests: Vec<(String, Vec<Hash256>)> = Vec::new();
+        let mut next_peer = 0;
+        for chunk in wanted.chunks(chunk_size) {
+            let top = chunk.last().map(|(_, h)| *h).unwrap_or(0);
+            // Next peer in rotation that can serve the whole chunk
+            let Some(offset) = (0..peers.len()).find(|i| peers[(next_peer + i) % peers.len()].1 >= top) else {
+                continue;
+            };
+            let peer = &peers[(next_peer + offset) % peers.len()].0;
+            next_peer = (next_peer + offset + 1) % peers.len();
+            for &(hash, height) in chunk {
+                self.in_flight.insert(hash, InFlight { peer: peer.clone(), height, requested: now });
+            }
+            let hashes = chunk.iter().map(|(h, _)| *h);
+            match requests.iter_mut().find(|(p, _)| p == peer) {
+                Some((_, list)) => list.extend(hashes),
+                None => requests.push((peer.clone(), hashes.collect())),
+            }
+        }
+        // A peer may have picked up several chunks; keep each message under the cap
+        requests.into_iter()
+            .flat_map(|(peer, hashes)| {
+                hashes.chunks(MAX_BLOCKS_PER_REQUEST).map(|c| (peer.clone(), c.to_vec())).collect::<Vec<_>>()
+            })
+            .collect()
+    }
+
+    /// Record a delivered block. Blocks we never asked for come back as Err.
+    pub fn receive(&mut self, hash: Hash256, block: Block) -> Result<(), Block> {
+        if self.in_flight.remove(&hash).is_none() {
+            return Err(block);
+        }
+        self.buffered.insert(hash, block);
+        Ok(())
+    }
+
+    /// Buffered blocks that can connect now, parents first. `has_block`
+    /// reports whether the chain already has a block. Buffered blocks whose
+    /// parent is nowhere (not in the chain, buffered or in flight) are dropped.
+    pub fn take_ready(&mut self, has_block: impl Fn(&Hash256) -> bool) -> Vec<Block> {
+        let mut pending: Vec<(Hash256, u64)> = self.buffered.iter().map(|(h, b)| (*h, b.header.height)).collect();
+        pending.sort_by_key(|(_, height)| *height);
+
+        let mut ready = Vec::new();
+        let mut connected: std::collections::HashSet<Hash256> = std::collections::HashSet::new();
+        for (hash, _) in pending {
+            let parent = self.buffered[&hash].header.prev_hash;
+            if has_block(&parent) || connected.contains(&parent) {
+                connected.insert(hash);
+                ready.push(self.buffered.remove(&hash).unwrap());
+            } else if !self.buffered.contains_key(&parent) && !self.in_flight.contains_key(&parent) {
+                self.buffered.remove(&hash);
+            }
+        }
+        ready
+    }
+
+    /// Hand requests that are overdue or whose peer left to other peers
+    fn reassign(&mut self, stale: impl Fn(&InFlight) -> bool, peers: &[(String, u64)], now: Instant) -> Vec<(String, Vec<Hash256>)> {
+        let mut dropped: Vec<(Hash256, u64, String)> = self.in_flight.iter()
+            .filter(|(_, f)| stale(f))
+            .map(|(h, f)| (*h, f.height, f.peer.clone()))
+            .collect();
+        if dropped.is_empty() {
+            return Vec::new();
+        }
+        dropped.sort_by_key(|(_, height, _)| *height);
+        for (hash, _, _) in &dropped {
+            self.in_flight.remove(hash);
+        }
+        // Prefer anyone but the peers that just failed
+        let failed: std::collections::HashSet<&str> = dropped.iter().map(|(_, _, p)| p.as_str()).collect();
+        let others: Vec<(String, u64)> = peers.iter().filter(|(p, _)| !failed.contains(p.as_str())).cloned().collect();
+        let wanted: Vec<(Hash256, u64)> = dropped.iter().map(|(h, height, _)| (*h, *height)).collect();
+        self.assign(&wanted, if others.is_empty() { peers } else { &others }, now)
+    }
+
+    /// `peer` disconnected: move its outstanding requests elsewhere
+    pub fn peer_gone(&mut self, peer: &str, peers: &[(String, u64)], now: Instant) -> Vec<(String, Vec<Hash256>)> {
+        self.reassign(|f| f.peer == peer, peers, now)
+    }
+
+    /// Move requests older than `DOWNLOAD_TIMEOUT` elsewhere
+    pub fn expire(&mut self, peers: &[(String, u64)], now: Instant) -> Vec<(String, Vec<Hash256>)> {
+        self.reassign(|f| now.duration_since(f.requested) >= DOWNLOAD_TIMEOUT, peers, now)
+    }
+
+    /// Requested from a peer or delivered and waiting for its parent
+    pub fn is_pending(&self, hash: &Hash256) -> bool {
+        self.in_flight.contains_key(hash) || self.buffered.contains_key(hash)
+    }
+
+    /// Nothing requested or waiting to connect
+    pub fn is_idle(&self) -> bool {
+        self.in_flight.is_empty() && self.buffered.is_empty()
+    }
+
+    pub fn in_flight_count(&self) -> usize {
+        self.in_flight.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::types::{BlockHeader, NULL_HASH};
+
+    /// Block at `height` whose id is [height; 32], linked to [height - 1; 32]
+    fn block(height: u8) -> (Hash256, Block) {
+        let header = BlockHeader {
+            version: 1, prev_hash: [height - 1; 32], merkle_root: NULL_HASH,
+            timestamp: height as u64, difficulty_target: 0, nonce: 0, height: height as u64,
+        };
+        ([height; 32], Block { header, transactions: vec![] })
+    }
+
+    fn wanted(range: std::ops::RangeInclusive<u8>) -> Vec<(Hash256, u64)> {
+        range.map(|h| ([h; 32], h as u64)).collect()
+    }
+
+    #[test]
+    fn test_assign_splits_across_peers() {
+        let mut dl = BlockDownloader::new();
+        let now = Instant::now();
+        let peers = vec![("a:1".to_string(), 30), ("b:1".to_string(), 30), ("c:1".to_string(), 10)];
+        let requests = dl.assign(&wanted(1..=30), &peers, now);
+
+        // 10-block chunks in rotation; "c" can't serve the third (top height 30), so "a" takes it
+        let by_peer = |p: &str| -> Vec<u8> {
+            requests.iter().filter(|(q, _)| q == p).flat_map(|(_, h)| h.iter().map(|h| h[0])).collect()
+        };
+        assert_eq!(by_peer("a:1"), [(1..=10).collect::<Vec<u8>>(), (21..=30).collect()].concat());
+        assert_eq!(by_peer("b:1"), (11..=20).collect::<Vec<u8>>());
+        assert!(by_peer("c:1").is_empty());
+        assert_eq!(dl.in_flight_count(), 30);
+
+        // Already requested: nothing new
+        assert!(dl.assign(&wanted(1..=30), &peers, now).is_empty());
+        // Nobody advertises height 40
+        assert!(dl.assign(&wanted(40..=40), &peers, now).is_empty());
+    }
+
+    #[test]
+    fn test_out_of_order_delivery_reassembles() {
+        let mut dl = BlockDownloader::new();
+        let peers = vec![("a:1".to_string(), 4), ("b:1".to_string(), 4)];
+        dl.assign(&wanted(1..=4), &peers, Instant::now());
+
+        // The second chunk lands first and has to wait for its parent
+        for h in [4, 3] {
+            let (hash, b) = block(h);
+            dl.receive(hash, b).unwrap();
+        }
+        let genesis = |h: &Hash256| *h == [0; 32];
+        assert!(dl.take_ready(genesis).is_empty());
+        assert!(!dl.is_idle());
+
+        for h in [2, 1] {
+            let (hash, b) = block(h);
+            dl.receive(hash, b).unwrap();
+        }
+        let heights: Vec<u64> = dl.take_ready(genesis).iter().map(|b| b.header.height).collect();
+        assert_eq!(heights, vec![1, 2, 3, 4]);
+        assert!(dl.is_idle());
+
+        // Unrequested blocks are handed back
+        let (hash, b) = block(9);
+        assert!(dl.receive(hash, b).is_err());
+    }
+
+    #[test]
+    fn test_failed_chunks_are_reassigned() {
+        let mut dl = BlockDownloader::new();
+        let start = Instant::now();
+        let peers = vec![("a:1".to_string(), 20), ("b:1".to_string(), 20)];
+        let requests = dl.assign(&wanted(1..=20), &peers, start);
+        let a_hashes = requests.iter().find(|(p, _)| p == "a:1").unwrap().1.clone();
+
+        // "a" disconnects: its chunk moves to "b"
+        let remaining = vec![("b:1".to_string(), 20)];
+        let moved = dl.peer_gone("a:1", &remaining, start);
+        assert_eq!(moved, vec![("b:1".to_string(), a_hashes)]);
+        assert_eq!(dl.in_flight_count(), 20);
+
+        // "b" never answers; with a new peer around, everything goes to "c"
+        let peers = vec![("b:1".to_string(), 20), ("c:1".to_string(), 20)];
+        assert!(dl.expire(&peers, start + DOWNLOAD_TIMEOUT / 2).is_empty());
+        let moved = dl.expire(&peers, start + DOWNLOAD_TIMEOUT);
+        assert!(moved.iter().all(|(p, _)| p == "c:1"));
+        assert_eq!(moved.iter().map(|(_, h)| h.len()).sum::<usize>(), 20);
+
+        // A delivered block whose parent is abandoned is dropped, not kept forever
+        let (hash, b) = block(20);
+        dl.receive(hash, b).unwrap();
+        dl.in_flight.clear();
+        assert!(dl.take_ready(|_| false).is_empty());
+        assert!(dl.is_idle());
+    }
+}
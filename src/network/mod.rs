@@ -1,15 +1,21 @@
 use serde::{Deserialize, Serialize};
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeSet, HashMap, HashSet};
 use std::sync::Arc;
 use std::time::{SystemTime, UNIX_EPOCH};
 use tokio::io::{AsyncReadExt, AsyncWriteExt};
 use tokio::net::{TcpListener, TcpStream};
 use tokio::sync::{broadcast, Mutex, RwLock};
 
-use crate::core::chain::Chain;
+use crate::core::chain::{Chain, ChainOptions};
 use crate::core::params::*;
 use crate::core::types::*;
 
+pub mod download;
+pub mod orphans;
+pub mod socks5;
+use download::BlockDownloader;
+use orphans::OrphanPool;
+
 // ─── Message Types ───────────────────────────────────────────────────
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -25,7 +31,7 @@ pub enum NetMessage {
     Pong(u64),
     GetPeers,
     Peers(Vec<String>),
-    VersionV2 { version: u32, best_height: u64, best_hash: Hash256, genesis_hash: Hash256, timestamp: u64, listen_port: u16 },
+    VersionV2 { version: u32, best_height: u64, best_hash: Hash256, genesis_hash: Hash256, timestamp: u64, listen_port: u16, nonce: u64 },
     // ─── Headers-first sync ───
     GetHeaders { start_height: u64, count: u32 },
     GetHeadersFrom { locator: Vec<Hash256>, count: u32 },
@@ -36,6 +42,30 @@ pub enum NetMessage {
     CompactBlock { header: BlockHeader, short_txids: Vec<Hash256>, coinbase: Transaction },
     GetTransactions(Vec<Hash256>), // Request missing txs for compact block
     TransactionBatch(Vec<Transaction>),
+    // ─── Inventory relay ───
+    Inv(Vec<InvItem>),      // Announce hashes we have
+    GetData(Vec<InvItem>),  // Request full data for announced hashes
+    GetMempool,             // Ask for an Inv of the peer's pending txids
+    // ─── Address advertisement ───
+    AddrSelf(String),       // Our reachable .onion listen address (sent only with --onion)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum InvKind {
+    Block,
+    Tx,
+}
+
+/// Announced object: a block hash or a transaction's `txid_v1`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct InvItem {
+    pub kind: InvKind,
+    pub hash: Hash256,
+}
+
+impl InvItem {
+    pub fn block(hash: Hash256) -> Self { Self { kind: InvKind::Block, hash } }
+    pub fn tx(txid: Hash256) -> Self { Self { kind: InvKind::Tx, hash: txid } }
 }
 
 
@@ -65,6 +95,11 @@ pub fn encode_message(msg: &NetMessage) -> Vec<u8> {
 }
 
 async fn read_message(stream: &mut TcpStream) -> Result<NetMessage, String> {
+    read_message_sized(stream).await.map(|(msg, _)| msg)
+}
+
+/// Read one message, also returning its size on the wire (header + payload)
+async fn read_message_sized(stream: &mut TcpStream) -> Result<(NetMessage, usize), String> {
     let mut header = [0u8; HEADER_SIZE];
     stream.read_exact(&mut header).await.map_err(|e| format!("read header: {}", e))?;
     if header[0..4] != magic_bytes() { return Err("invalid magic bytes".into()); }
@@ -72,13 +107,24 @@ async fn read_message(stream: &mut TcpStream) -> Result<NetMessage, String> {
     if length > MAX_MESSAGE_SIZE { return Err(format!("message too large: {} bytes", length)); }
     let mut payload = vec![0u8; length];
     stream.read_exact(&mut payload).await.map_err(|e| format!("read payload: {}", e))?;
-    bincode::deserialize(&payload).map_err(|e| format!("deserialize: {}", e))
+    let msg = bincode::deserialize(&payload).map_err(|e| format!("deserialize: {}", e))?;
+    Ok((msg, HEADER_SIZE + length))
 }
 
-async fn write_message(stream: &mut TcpStream, msg: &NetMessage) -> Result<(), String> {
+/// Write one message, returning the number of bytes sent
+async fn write_message(stream: &mut TcpStream, msg: &NetMessage) -> Result<usize, String> {
     let data = encode_message(msg);
     stream.write_all(&data).await.map_err(|e| format!("write: {}", e))?;
     stream.flush().await.map_err(|e| format!("flush: {}", e))?;
+    Ok(data.len())
+}
+
+/// `write_message` that also counts the bytes against the peer's send budget
+/// and notes any replies the message asks for
+async fn write_metered(stream: &mut TcpStream, limiter: &mut PeerRateLimiter, msg: &NetMessage) -> Result<(), String> {
+    let sent = write_message(stream, msg).await?;
+    limiter.record_send(sent as u64);
+    limiter.expect_replies(msg);
     Ok(())
 }
 
@@ -120,42 +166,112 @@ fn build_locator(chain: &Chain, max: usize) -> Vec<Hash256> {
 
 
 // ─── Per-Peer Rate Limiter ──────────────────────────────────────────
-// TODO: Wire into handle_connection — create per-connection instance,
-//       call record_send/record_recv on each message, disconnect if limited.
 
-#[allow(dead_code)]
+/// Default per-peer bandwidth cap, each direction (10 MB/s)
+pub const DEFAULT_PEER_RATE_LIMIT: u64 = 10 * 1024 * 1024;
+/// Length of a rate-limiter accounting window in seconds
+const RATE_WINDOW_SECS: u64 = 10;
+
+/// Per-peer bandwidth caps in bytes per second. Sends are averaged over a
+/// window; receives drain a bucket holding `RATE_WINDOW_SECS` worth of bytes,
+/// and never less than one maximum-size message
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RateLimits {
+    pub max_send_rate: u64,
+    pub max_recv_rate: u64,
+}
+
+impl Default for RateLimits {
+    fn default() -> Self {
+        Self { max_send_rate: DEFAULT_PEER_RATE_LIMIT, max_recv_rate: DEFAULT_PEER_RATE_LIMIT }
+    }
+}
+
+/// One per connection. A peer that sends faster than `max_recv_rate` is
+/// struck and dropped; while we're over `max_send_rate` towards a peer we
+/// stop relaying unsolicited blocks and transactions to it. Replies to our
+/// own requests (block batches during sync, say) aren't charged.
 struct PeerRateLimiter {
     /// Bytes sent in current window
     bytes_sent: u64,
-    /// Bytes received in current window
-    bytes_recv: u64,
     /// Window start time
     window_start: u64,
+    /// Inbound bytes the peer may still send; below zero it's over the cap
+    recv_allowance: i64,
+    /// When `recv_allowance` was last topped up
+    recv_refilled: std::time::Instant,
+    /// Replies to our requests still to come
+    solicited: u32,
     /// Max bytes per second (outbound)
     max_send_rate: u64,
     /// Max bytes per second (inbound)
     max_recv_rate: u64,
+    /// When we last answered this peer's GetMempool
+    last_mempool_reply: Option<u64>,
 }
 
-#[allow(dead_code)]
 impl PeerRateLimiter {
-    fn new() -> Self {
+    fn new(limits: RateLimits) -> Self {
         Self {
-            bytes_sent: 0, bytes_recv: 0,
+            bytes_sent: 0,
             window_start: SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs(),
-            max_send_rate: 10 * 1024 * 1024, // 10 MB/s default
-            max_recv_rate: 10 * 1024 * 1024,
+            recv_allowance: Self::recv_burst(limits.max_recv_rate),
+            recv_refilled: std::time::Instant::now(),
+            solicited: 0,
+            max_send_rate: limits.max_send_rate,
+            max_recv_rate: limits.max_recv_rate,
+            last_mempool_reply: None,
         }
     }
 
+    /// True (and starts a new interval) if a GetMempool may be answered now
+    fn allow_mempool_reply(&mut self) -> bool {
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
+        if self.last_mempool_reply.is_some_and(|t| now < t + MEMPOOL_REQUEST_INTERVAL) {
+            return false;
+        }
+        self.last_mempool_reply = Some(now);
+        true
+    }
+
     fn record_send(&mut self, bytes: u64) {
         self.maybe_reset_window();
         self.bytes_sent += bytes;
     }
 
-    fn record_recv(&mut self, bytes: u64) {
-        self.maybe_reset_window();
-        self.bytes_recv += bytes;
+    /// Most inbound bytes the bucket holds
+    fn recv_burst(max_recv_rate: u64) -> i64 {
+        max_recv_rate.saturating_mul(RATE_WINDOW_SECS).max(MAX_MESSAGE_SIZE as u64).min(i64::MAX as u64) as i64
+    }
+
+    /// Note the replies `msg`, sent to the peer, asks for
+    fn expect_replies(&mut self, msg: &NetMessage) {
+        let replies = match msg {
+            NetMessage::GetBlocks { .. } | NetMessage::GetBlock(_) | NetMessage::GetHeaders { .. }
+            | NetMessage::GetHeadersFrom { .. } | NetMessage::GetBlockData(_) | NetMessage::GetTransactions(_) => 1,
+            NetMessage::GetData(items) => items.len() as u32,
+            _ => 0,
+        };
+        self.solicited = self.solicited.saturating_add(replies);
+    }
+
+    /// Charge a received message of `bytes` unless it answers one of our requests
+    fn record_recv(&mut self, msg: &NetMessage, bytes: u64) {
+        let is_reply = matches!(msg,
+            NetMessage::Blocks(_) | NetMessage::BlockData(_) | NetMessage::Headers(_) | NetMessage::NewBlock(_)
+            | NetMessage::NewTransaction(_) | NetMessage::TransactionBatch(_));
+        if is_reply && self.solicited > 0 {
+            self.solicited -= 1;
+            return;
+        }
+        let elapsed_ms = self.recv_refilled.elapsed().as_millis() as u64;
+        let refill = self.max_recv_rate.saturating_mul(elapsed_ms) / 1000;
+        if refill > 0 {
+            let burst = Self::recv_burst(self.max_recv_rate);
+            self.recv_allowance = self.recv_allowance.saturating_add(refill.min(i64::MAX as u64) as i64).min(burst);
+            self.recv_refilled = std::time::Instant::now();
+        }
+        self.recv_allowance = self.recv_allowance.saturating_sub(bytes.min(i64::MAX as u64) as i64);
     }
 
     fn is_send_limited(&self) -> bool {
@@ -164,8 +280,7 @@ impl PeerRateLimiter {
     }
 
     fn is_recv_limited(&self) -> bool {
-        let elapsed = self.elapsed_secs().max(1);
-        self.bytes_recv / elapsed > self.max_recv_rate
+        self.recv_allowance < 0
     }
 
     fn elapsed_secs(&self) -> u64 {
@@ -174,14 +289,46 @@ impl PeerRateLimiter {
     }
 
     fn maybe_reset_window(&mut self) {
-        if self.elapsed_secs() >= 10 {
+        if self.elapsed_secs() >= RATE_WINDOW_SECS {
             self.bytes_sent = 0;
-            self.bytes_recv = 0;
             self.window_start = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
         }
     }
 }
 
+// ─── Known Inventory ───────────────────────────────────────────────
+
+/// Inventory remembered per peer before the oldest entries are forgotten
+const MAX_KNOWN_INVENTORY: usize = 50_000;
+/// Most items honoured from a single Inv or GetData message
+const MAX_INV_ITEMS: usize = 1000;
+/// Minimum seconds between GetMempool requests we answer from one peer
+const MEMPOOL_REQUEST_INTERVAL: u64 = 60;
+
+/// What one peer is known to have: everything it announced or sent us, and
+/// everything we announced or sent it. Used so we never announce an object
+/// back to the peer it came from, or to the same peer twice.
+struct KnownInventory {
+    items: HashSet<InvItem>,
+    order: std::collections::VecDeque<InvItem>,
+}
+
+impl KnownInventory {
+    fn new() -> Self {
+        Self { items: HashSet::new(), order: std::collections::VecDeque::new() }
+    }
+
+    /// Remember an item; returns false if it was already known
+    fn insert(&mut self, item: InvItem) -> bool {
+        if !self.items.insert(item) { return false; }
+        self.order.push_back(item);
+        if self.order.len() > MAX_KNOWN_INVENTORY {
+            if let Some(old) = self.order.pop_front() { self.items.remove(&old); }
+        }
+        true
+    }
+}
+
 // ─── Anchor Connections ────────────────────────────────────────────
 
 /// Anchor connections are persistent peers that survive restarts.
@@ -200,12 +347,65 @@ pub fn load_anchors(data_dir: &str) -> Vec<String> {
 
 pub fn save_anchors(data_dir: &str, anchors: &[String]) {
     let path = std::path::PathBuf::from(data_dir).join(ANCHOR_FILE);
-    let limited: Vec<&String> = anchors.iter().take(MAX_ANCHORS).collect();
+    let mut limited: Vec<String> = Vec::new();
+    for addr in anchors.iter().filter_map(|a| normalize_addr(a)) {
+        if limited.len() == MAX_ANCHORS { break; }
+        if !limited.contains(&addr) { limited.push(addr); }
+    }
     if let Ok(json) = serde_json::to_string(&limited) {
         let _ = std::fs::write(path, json);
     }
 }
 
+// ─── Peer Addresses ─────────────────────────────────────────────────
+
+/// Split "host:port". IPv6 hosts must be bracketed ("[::1]:9333"); the
+/// brackets are stripped from the returned host.
+pub fn split_host_port(addr: &str) -> Option<(&str, u16)> {
+    let (host, port) = addr.rsplit_once(':')?;
+    let host = match host.strip_prefix('[') {
+        Some(inner) => inner.strip_suffix(']')?,
+        // An unbracketed host with a colon is a bare IPv6 address, not host:port
+        None if host.contains(':') => return None,
+        None => host,
+    };
+    if host.is_empty() { return None; }
+    Some((host, port.parse().ok()?))
+}
+
+/// Host part of a peer address ("[::1]:9333" -> "::1", "seed.example:9333" ->
+/// "seed.example"). Bare hosts pass through. IPs come back in canonical form,
+/// with IPv4-mapped IPv6 unwrapped, so bans and dedup compare equal.
+pub fn host_of(addr: &str) -> String {
+    let host = match addr.parse::<std::net::IpAddr>() {
+        Ok(_) => addr,
+        Err(_) => split_host_port(addr).map_or(addr, |(host, _)| host),
+    };
+    match host.parse::<std::net::IpAddr>() {
+        Ok(ip) => ip.to_canonical().to_string(),
+        Err(_) => host.to_ascii_lowercase(),
+    }
+}
+
+/// "host:port", bracketing IPv6 hosts
+pub fn join_host_port(host: &str, port: u16) -> String {
+    if host.contains(':') { format!("[{}]:{}", host, port) } else { format!("{}:{}", host, port) }
+}
+
+/// Canonical spelling of a peer address, or None if it isn't host:port
+pub fn normalize_addr(addr: &str) -> Option<String> {
+    let (host, port) = split_host_port(addr)?;
+    Some(join_host_port(&host_of(host), port))
+}
+
+/// The peer connected as, or listening on, `addr`
+fn find_peer<'a>(peers: &'a HashMap<String, PeerInfo>, addr: &str) -> Option<&'a PeerInfo> {
+    let addr = normalize_addr(addr).unwrap_or_else(|| addr.to_string());
+    peers.values().find(|p| {
+        [&p.address, &p.listen_address].iter().any(|a| normalize_addr(a).as_deref().unwrap_or(a) == addr)
+    })
+}
+
 // ─── Ban System ─────────────────────────────────────────────────────
 
 /// Tracks misbehavior per peer IP. After enough strikes, the peer is banned.
@@ -220,6 +420,7 @@ enum Offense {
     InvalidTransaction, // 1 strike  — could be a double-spend race
     MalformedMessage,   // 3 strikes — definitely misbehaving
     SpamPing,           // 1 strike
+    RateLimited,        // 3 strikes — flooding us past the bandwidth cap
 }
 
 impl Offense {
@@ -229,35 +430,44 @@ impl Offense {
             Offense::InvalidTransaction => 1,
             Offense::MalformedMessage => 3,
             Offense::SpamPing => 1,
+            Offense::RateLimited => 3,
         }
     }
 }
 
+/// File in the data directory holding strikes and bans across restarts
+const SCOREBOARD_FILE: &str = "banlist.json";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct BanEntry {
     pub banned_until: u64,
     pub reason: String,
 }
 
+/// On-disk form of the scoreboard
+#[derive(Default, Serialize, Deserialize)]
+struct ScoreboardFile {
+    strikes: HashMap<String, u32>,
+    bans: HashMap<String, BanEntry>,
+}
+
 pub struct PeerScoreboard {
     /// Strike count per IP (not per connection, so reconnecting doesn't reset)
     strikes: HashMap<String, u32>,
     /// Banned IPs with expiry
     bans: HashMap<String, BanEntry>,
+    /// Bans issued since startup (strike-based and manual), for `/metrics`
+    bans_issued: u64,
 }
 
 impl PeerScoreboard {
     pub fn new() -> Self {
-        Self { strikes: HashMap::new(), bans: HashMap::new() }
-    }
-
-    /// Extract the IP portion from a "IP:port" address string
-    fn ip_of(addr: &str) -> String {
-        addr.split(':').next().unwrap_or(addr).to_string()
+        Self { strikes: HashMap::new(), bans: HashMap::new(), bans_issued: 0 }
     }
 
     /// Record an offense. Returns true if the peer should be banned.
     pub fn record_offense(&mut self, addr: &str, offense: Offense) -> bool {
-        let ip = Self::ip_of(addr);
+        let ip = host_of(addr);
         let count = self.strikes.entry(ip.clone()).or_insert(0);
         *count += offense.strikes();
 
@@ -268,6 +478,7 @@ impl PeerScoreboard {
                 banned_until: now + BAN_DURATION,
                 reason: format!("{:?}", offense),
             });
+            self.bans_issued += 1;
             return true;
         }
         false
@@ -275,7 +486,7 @@ impl PeerScoreboard {
 
     /// Check if an IP is currently banned
     pub fn is_banned(&self, addr: &str) -> bool {
-        let ip = Self::ip_of(addr);
+        let ip = host_of(addr);
         if let Some(entry) = self.bans.get(&ip) {
             let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
             return now < entry.banned_until;
@@ -302,41 +513,177 @@ impl PeerScoreboard {
         let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
         self.bans.values().filter(|e| now < e.banned_until).count()
     }
+
+    /// Ban an IP outright for `seconds`, regardless of strikes
+    pub fn ban(&mut self, ip: &str, seconds: u64, reason: &str) {
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
+        let ip = host_of(ip);
+        tracing::warn!("🚫 Manually banning {} for {}s ({})", ip, seconds, reason);
+        self.bans.insert(ip, BanEntry { banned_until: now.saturating_add(seconds), reason: reason.to_string() });
+        self.bans_issued += 1;
+    }
+
+    /// Bans issued since startup, including ones that have since expired
+    pub fn bans_issued(&self) -> u64 { self.bans_issued }
+
+    /// Lift every ban and forget all strikes
+    pub fn clear_bans(&mut self) {
+        self.bans.clear();
+        self.strikes.clear();
+    }
+
+    /// Currently active bans by IP
+    pub fn active_bans(&self) -> Vec<(String, BanEntry)> {
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
+        self.bans.iter()
+            .filter(|(_, e)| now < e.banned_until)
+            .map(|(ip, e)| (ip.clone(), e.clone()))
+            .collect()
+    }
+
+    /// Strikes recorded against a peer's IP
+    pub fn strikes_of(&self, addr: &str) -> u32 {
+        self.strikes.get(&host_of(addr)).copied().unwrap_or(0)
+    }
+
+    /// Write strikes and active bans to the data directory
+    pub fn save(&self, data_dir: &str) {
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
+        let expired = |ip: &String| self.bans.get(ip).is_some_and(|e| now >= e.banned_until);
+        let file = ScoreboardFile {
+            // An expired ban wipes the slate, as in `cleanup`
+            strikes: self.strikes.iter()
+                .filter(|(ip, _)| !expired(ip))
+                .map(|(ip, n)| (ip.clone(), *n))
+                .collect(),
+            bans: self.bans.iter()
+                .filter(|(ip, _)| !expired(ip))
+                .map(|(ip, e)| (ip.clone(), e.clone()))
+                .collect(),
+        };
+        let path = std::path::PathBuf::from(data_dir).join(SCOREBOARD_FILE);
+        if let Ok(json) = serde_json::to_string(&file) {
+            let _ = std::fs::write(path, json);
+        }
+    }
+
+    /// Load a saved scoreboard. Bans that expired while we were down are
+    /// dropped along with their strikes, as `cleanup` would have done.
+    pub fn load(data_dir: &str) -> Self {
+        let path = std::path::PathBuf::from(data_dir).join(SCOREBOARD_FILE);
+        let file: ScoreboardFile = std::fs::read_to_string(&path).ok()
+            .and_then(|data| serde_json::from_str(&data).ok())
+            .unwrap_or_default();
+        let mut sb = Self { strikes: file.strikes, bans: file.bans, bans_issued: 0 };
+        sb.cleanup();
+        sb
+    }
 }
 
 // ─── Mempool (Fee-Rate Sorted) ──────────────────────────────────────
 
+/// Outcome of offering a transaction to the mempool
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MempoolAdd {
+    Added,
+    /// Pool was full; the lowest fee-rate entry (this txid) made room
+    AddedByEvicting(Hash256),
+    Duplicate,
+    /// Pool is full and the tx doesn't pay a strictly higher rate than the cheapest entry
+    FullFeeTooLow,
+}
+
+impl MempoolAdd {
+    pub fn is_added(&self) -> bool {
+        matches!(self, MempoolAdd::Added | MempoolAdd::AddedByEvicting(_))
+    }
+}
+
 struct MempoolEntry {
     tx: Transaction,
     fee: u64,
     size: usize,
     /// Fee rate in base units per byte (fee / tx_size)
     fee_rate: f64,
+    /// Unix time the entry was added
+    time: u64,
+}
+
+/// One mempool entry and its in-mempool relatives (see `Mempool::entry_info`)
+#[derive(Debug, Clone, PartialEq)]
+pub struct MempoolEntryInfo {
+    pub size: usize,
+    pub fee: u64,
+    pub fee_rate: f64,
+    pub time: u64,
+    /// Pending txs this one spends from, directly or through other pending txs
+    pub ancestors: Vec<Hash256>,
+    /// Pending txs spending this one, directly or through other pending txs
+    pub descendants: Vec<Hash256>,
 }
 
 pub struct Mempool {
     entries: HashMap<Hash256, MempoolEntry>,
+    /// Which pending tx spends each outpoint
+    spenders: HashMap<OutPoint, Hash256>,
     max_size: usize,
 }
 
 impl Mempool {
     pub fn new(max_size: usize) -> Self {
-        Self { entries: HashMap::new(), max_size }
+        Self { entries: HashMap::new(), spenders: HashMap::new(), max_size }
     }
 
-    /// Add a pre-validated transaction with a known fee
-    pub fn add_with_fee(&mut self, tx: Transaction, fee: u64) -> bool {
+    /// Add a pre-validated transaction with a known fee. When full, the
+    /// cheapest entry by fee rate is evicted if the newcomer pays strictly more.
+    pub fn add_with_fee(&mut self, tx: Transaction, fee: u64) -> MempoolAdd {
         let txid = crate::crypto::txid::txid_v1(&tx);
-        if self.entries.contains_key(&txid) { return false; }
-        if self.entries.len() >= self.max_size { return false; }
+        if self.entries.contains_key(&txid) { return MempoolAdd::Duplicate; }
         let size = tx.size();
         let fee_rate = if size > 0 { fee as f64 / size as f64 } else { 0.0 };
-        self.entries.insert(txid, MempoolEntry { tx, fee, size, fee_rate });
-        true
+
+        let mut result = MempoolAdd::Added;
+        if self.entries.len() >= self.max_size {
+            let cheapest = self.entries.iter()
+                .min_by(|a, b| a.1.fee_rate.partial_cmp(&b.1.fee_rate).unwrap_or(std::cmp::Ordering::Equal))
+                .map(|(id, e)| (*id, e.fee_rate));
+            match cheapest {
+                Some((victim, rate)) if fee_rate > rate => {
+                    // Its descendants would spend outputs that no longer exist
+                    self.remove_with_descendants(&victim);
+                    tracing::debug!("🗑️ Mempool full, evicted {} for a higher fee rate", &hex::encode(victim)[..16]);
+                    result = MempoolAdd::AddedByEvicting(victim);
+                }
+                _ => return MempoolAdd::FullFeeTooLow,
+            }
+        }
+        for input in &tx.inputs {
+            self.spenders.insert(input.previous_output.clone(), txid);
+        }
+        let time = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
+        self.entries.insert(txid, MempoolEntry { tx, fee, size, fee_rate, time });
+        result
+    }
+
+    fn remove_entry(&mut self, txid: &Hash256) -> Option<MempoolEntry> {
+        let entry = self.entries.remove(txid)?;
+        for input in &entry.tx.inputs {
+            if self.spenders.get(&input.previous_output) == Some(txid) {
+                self.spenders.remove(&input.previous_output);
+            }
+        }
+        Some(entry)
+    }
+
+    /// Remove `txid` and every pending tx that depends on it
+    fn remove_with_descendants(&mut self, txid: &Hash256) {
+        for id in self.descendants(txid).into_iter().chain([*txid]) {
+            self.remove_entry(&id);
+        }
     }
 
     /// Add without fee info (legacy, used for pre-validated txs)
-    pub fn add(&mut self, tx: Transaction) -> bool {
+    pub fn add(&mut self, tx: Transaction) -> MempoolAdd {
         self.add_with_fee(tx, 0)
     }
 
@@ -344,7 +691,6 @@ impl Mempool {
     pub fn validate_and_add(&mut self, tx: Transaction, chain: &Chain) -> Result<Hash256, String> {
         let txid = crate::crypto::txid::txid_v1(&tx);
         if self.entries.contains_key(&txid) { return Err("duplicate transaction".into()); }
-        if self.entries.len() >= self.max_size { return Err("mempool full".into()); }
 
         chain.validate_transaction_for_mempool(&tx).map_err(|e| format!("{}", e))?;
 
@@ -356,23 +702,71 @@ impl Mempool {
             }
         }
         let fee = input_sum.saturating_sub(tx.total_output());
-        self.add_with_fee(tx, fee);
-        Ok(txid)
+        match self.add_with_fee(tx, fee) {
+            MempoolAdd::FullFeeTooLow => Err("mempool full: fee rate too low to evict".into()),
+            MempoolAdd::Duplicate => Err("duplicate transaction".into()),
+            _ => Ok(txid),
+        }
     }
 
     pub fn remove_confirmed(&mut self, block: &Block) {
         for tx in &block.transactions {
             if !tx.is_coinbase() {
-                self.entries.remove(&tx.hash());
+                self.remove_entry(&crate::crypto::txid::txid_v1(tx));
             }
         }
-        // Also remove txs that spend now-consumed UTXOs (conflicting txs)
-        let spent_outpoints: HashSet<OutPoint> = block.transactions.iter()
-            .flat_map(|tx| tx.inputs.iter().map(|i| i.previous_output.clone()))
+        // Also remove txs that spend now-consumed UTXOs (conflicting txs), and
+        // whatever built on them
+        let conflicts: Vec<Hash256> = block.transactions.iter()
+            .flat_map(|tx| tx.inputs.iter())
+            .filter_map(|i| self.spenders.get(&i.previous_output).copied())
             .collect();
-        self.entries.retain(|_, entry| {
-            !entry.tx.inputs.iter().any(|i| spent_outpoints.contains(&i.previous_output))
-        });
+        for txid in conflicts {
+            self.remove_with_descendants(&txid);
+        }
+    }
+
+    /// Pending txs `txid` spends from, transitively
+    pub fn ancestors(&self, txid: &Hash256) -> Vec<Hash256> {
+        self.walk_relatives(txid, |mp, id| {
+            mp.entries.get(id).into_iter()
+                .flat_map(|e| e.tx.inputs.iter().map(|i| i.previous_output.txid))
+                .filter(|parent| mp.entries.contains_key(parent))
+                .collect()
+        })
+    }
+
+    /// Pending txs spending `txid`'s outputs, transitively
+    pub fn descendants(&self, txid: &Hash256) -> Vec<Hash256> {
+        self.walk_relatives(txid, |mp, id| {
+            let outputs = mp.entries.get(id).map_or(0, |e| e.tx.outputs.len() as u32);
+            (0..outputs).filter_map(|vout| mp.spenders.get(&OutPoint { txid: *id, vout }).copied()).collect()
+        })
+    }
+
+    /// Breadth-first walk from `txid` along `next`, excluding `txid` itself
+    fn walk_relatives(&self, txid: &Hash256, next: impl Fn(&Self, &Hash256) -> Vec<Hash256>) -> Vec<Hash256> {
+        let mut found = Vec::new();
+        let mut seen: HashSet<Hash256> = HashSet::from([*txid]);
+        let mut queue = std::collections::VecDeque::from([*txid]);
+        while let Some(id) = queue.pop_front() {
+            for relative in next(self, &id) {
+                if seen.insert(relative) {
+                    found.push(relative);
+                    queue.push_back(relative);
+                }
+            }
+        }
+        found
+    }
+
+    /// Size, fee and relatives of one pending tx
+    pub fn entry_info(&self, txid: &Hash256) -> Option<MempoolEntryInfo> {
+        let e = self.entries.get(txid)?;
+        Some(MempoolEntryInfo {
+            size: e.size, fee: e.fee, fee_rate: e.fee_rate, time: e.time,
+            ancestors: self.ancestors(txid), descendants: self.descendants(txid),
+        })
     }
 
     /// Get pending transactions sorted by fee rate (highest first)
@@ -389,10 +783,98 @@ impl Mempool {
         entries.into_iter().map(|e| (e.tx.clone(), e.fee, e.fee_rate)).collect()
     }
 
+    /// Choose transactions for a block that maximize total fees within
+    /// `max_size` bytes and `max_count` transactions.
+    ///
+    /// Two fills are compared: fee-rate order, carrying on past transactions
+    /// that don't fit so smaller ones backfill the leftover space; and a 0/1
+    /// knapsack over sizes rounded up to `max_size / KNAPSACK_SLOTS` bytes,
+    /// whose rounding slack is backfilled the same way. Transactions spending
+    /// another mempool transaction are left out: their parent isn't in the
+    /// UTXO set yet, so the block template would drop them anyway.
+    pub fn select_for_block(&self, max_size: usize, max_count: usize) -> Vec<Transaction> {
+        let mut candidates: Vec<(&Hash256, &MempoolEntry)> = self.entries.iter()
+            .filter(|(_, e)| !e.tx.inputs.iter().any(|i| self.entries.contains_key(&i.previous_output.txid)))
+            .collect();
+        candidates.sort_by(|a, b| b.1.fee_rate.partial_cmp(&a.1.fee_rate).unwrap_or(std::cmp::Ordering::Equal).then(a.0.cmp(b.0)));
+        let candidates: Vec<&MempoolEntry> = candidates.into_iter().map(|(_, e)| e).collect();
+
+        let fees_of = |taken: &[bool]| -> u64 { candidates.iter().zip(taken).filter(|(_, t)| **t).map(|(e, _)| e.fee).sum() };
+        let mut best = fill_by_fee_rate(&candidates, vec![false; candidates.len()], max_size, max_count);
+        // The knapsack ignores the count limit, so it only runs when that can't bind
+        if candidates.len() <= KNAPSACK_MAX_TXS && candidates.len() <= max_count {
+            let knapsack = fill_by_fee_rate(&candidates, knapsack_by_fee(&candidates, max_size), max_size, max_count);
+            if fees_of(&knapsack) > fees_of(&best) { best = knapsack; }
+        }
+        candidates.iter().zip(best).filter(|(_, t)| *t).map(|(e, _)| e.tx.clone()).collect()
+    }
+
+    /// Txids of up to `limit` pending transactions, highest fee rate first
+    pub fn pending_txids(&self, limit: usize) -> Vec<Hash256> {
+        let mut entries: Vec<(&Hash256, &MempoolEntry)> = self.entries.iter().collect();
+        entries.sort_by(|a, b| b.1.fee_rate.partial_cmp(&a.1.fee_rate).unwrap_or(std::cmp::Ordering::Equal));
+        entries.into_iter().take(limit).map(|(txid, _)| *txid).collect()
+    }
+
+    /// Outputs spent by some pending transaction
+    pub fn spent_outpoints(&self) -> HashSet<OutPoint> {
+        self.entries.values().flat_map(|e| e.tx.inputs.iter().map(|i| i.previous_output.clone())).collect()
+    }
+
+    pub fn contains(&self, txid: &Hash256) -> bool { self.entries.contains_key(txid) }
+    pub fn get(&self, txid: &Hash256) -> Option<&Transaction> { self.entries.get(txid).map(|e| &e.tx) }
+
     pub fn len(&self) -> usize { self.entries.len() }
     pub fn is_empty(&self) -> bool { self.entries.is_empty() }
 }
 
+/// `select_for_block` splits the block into at most this many knapsack slots
+const KNAPSACK_SLOTS: usize = 4096;
+/// Above this many candidates only the greedy fill is used
+const KNAPSACK_MAX_TXS: usize = 1000;
+
+/// Add untaken entries in order while they fit. `candidates` is sorted by fee rate.
+fn fill_by_fee_rate(candidates: &[&MempoolEntry], mut taken: Vec<bool>, max_size: usize, max_count: usize) -> Vec<bool> {
+    let mut size: usize = candidates.iter().zip(&taken).filter(|(_, t)| **t).map(|(e, _)| e.size).sum();
+    let mut count = taken.iter().filter(|t| **t).count();
+    for (entry, t) in candidates.iter().zip(taken.iter_mut()) {
+        if *t || count >= max_count || size + entry.size > max_size { continue; }
+        *t = true;
+        size += entry.size;
+        count += 1;
+    }
+    taken
+}
+
+/// 0/1 knapsack maximizing fees. Sizes round up to whole slots, so the
+/// chosen set always fits in `max_size`.
+fn knapsack_by_fee(candidates: &[&MempoolEntry], max_size: usize) -> Vec<bool> {
+    let slot = max_size.div_ceil(KNAPSACK_SLOTS).max(1);
+    let capacity = max_size / slot;
+    let weights: Vec<usize> = candidates.iter().map(|e| e.size.div_ceil(slot)).collect();
+    let mut best = vec![0u64; capacity + 1];
+    // keep[i * (capacity + 1) + c]: entry i is in the best set for capacity c
+    let mut keep = vec![false; candidates.len() * (capacity + 1)];
+    for (i, (entry, &w)) in candidates.iter().zip(&weights).enumerate() {
+        if w > capacity { continue; }
+        for c in (w..=capacity).rev() {
+            if best[c - w] + entry.fee > best[c] {
+                best[c] = best[c - w] + entry.fee;
+                keep[i * (capacity + 1) + c] = true;
+            }
+        }
+    }
+    let mut taken = vec![false; candidates.len()];
+    let mut c = capacity;
+    for i in (0..candidates.len()).rev() {
+        if keep[i * (capacity + 1) + c] {
+            taken[i] = true;
+            c -= weights[i];
+        }
+    }
+    taken
+}
+
 // ─── Shared Node State ──────────────────────────────────────────────
 
 #[derive(Debug, Clone)]
@@ -403,6 +885,12 @@ pub struct PeerInfo {
     pub best_height: u64,
     pub last_seen: u64,
     pub supports_v2: bool,
+    /// True if the peer dialed us
+    pub inbound: bool,
+    /// Whether we relay loose transactions to this peer
+    pub relay_txs: bool,
+    /// Round-trip time of the last keepalive ping, if answered yet
+    pub ping_ms: Option<u64>,
 }
 
 pub struct NodeState {
@@ -418,6 +906,35 @@ pub struct NodeState {
     pub new_block_notify: tokio::sync::Notify,
     /// Compact-block reconstruction state (Monero-like "fluffy blocks")
     pub pending_compacts: tokio::sync::Mutex<HashMap<Hash256, PendingCompact>>,
+    /// Operator's wallet, for wallet RPCs (None until the node attaches one)
+    pub wallet: Mutex<Option<crate::wallet::Wallet>>,
+    /// Mining pool server state, for pool RPCs (None unless `--pool`)
+    pub pool: Mutex<Option<Arc<RwLock<crate::pool::PoolState>>>>,
+    /// Testnet faucet behind `GET /faucet` (None unless `--faucet`)
+    pub faucet: Mutex<Option<crate::rpc::faucet::Faucet>>,
+    /// Blocks that arrived before their parent
+    pub orphans: Mutex<OrphanPool>,
+    /// Sync blocks requested from several peers at once
+    pub downloads: Mutex<BlockDownloader>,
+    /// Asks a connection handler to send `GetBlockData`; carries (peer address, hashes)
+    pub block_request_tx: broadcast::Sender<(String, Vec<Hash256>)>,
+    /// Per-peer bandwidth caps applied to new connections
+    pub rate_limits: std::sync::RwLock<RateLimits>,
+    /// Random per-process nonce sent in our version message; seeing it come
+    /// back means we dialed ourselves
+    pub local_nonce: u64,
+    /// Addresses that turned out to be us (learned from self-connections)
+    pub self_addresses: std::sync::RwLock<HashSet<String>>,
+    /// Asks connection handlers to drop peers; carries an IP or "IP:port"
+    pub disconnect_tx: broadcast::Sender<String>,
+    /// Peers added with the `addnode` RPC; redialed whenever they drop
+    pub added_nodes: std::sync::RwLock<BTreeSet<String>>,
+    /// SOCKS5 proxy every outbound connection goes through (`--proxy`)
+    pub proxy: std::sync::RwLock<Option<String>>,
+    /// Our onion service address, advertised to peers after the handshake
+    pub onion_address: std::sync::RwLock<Option<String>>,
+    /// Set on Ctrl-C or by the `stop` RPC; the node's shutdown watcher exits
+    pub shutdown: Arc<std::sync::atomic::AtomicBool>,
 }
 
 impl NodeState {
@@ -433,13 +950,84 @@ impl NodeState {
             listen_port, block_tx, tx_tx,
             new_block_notify: tokio::sync::Notify::new(),
             pending_compacts: tokio::sync::Mutex::new(HashMap::new()),
+            wallet: Mutex::new(None),
+            pool: Mutex::new(None),
+            faucet: Mutex::new(None),
+            orphans: Mutex::new(OrphanPool::new(MAX_ORPHAN_POOL_BYTES)),
+            downloads: Mutex::new(BlockDownloader::new()),
+            block_request_tx: broadcast::channel(256).0,
+            rate_limits: std::sync::RwLock::new(RateLimits::default()),
+            local_nonce: rand::random(),
+            self_addresses: std::sync::RwLock::new(HashSet::new()),
+            disconnect_tx: broadcast::channel(64).0,
+            added_nodes: std::sync::RwLock::new(BTreeSet::new()),
+            proxy: std::sync::RwLock::new(None),
+            onion_address: std::sync::RwLock::new(None),
+            shutdown: Arc::new(std::sync::atomic::AtomicBool::new(false)),
         })
     }
 
+    /// True if dialing `addr` would reach this node: our port on a loopback
+    /// or unspecified IP, or an address a past self-connection exposed
+    pub fn is_own_address(&self, addr: &str) -> bool {
+        if self.self_addresses.read().unwrap().contains(addr) {
+            return true;
+        }
+        if self.onion_address.read().unwrap().as_deref() == Some(addr) {
+            return true;
+        }
+        match addr.parse::<std::net::SocketAddr>() {
+            Ok(sa) => sa.port() == self.listen_port && (sa.ip().is_loopback() || sa.ip().is_unspecified()),
+            Err(_) => false,
+        }
+    }
+
+    /// Onion addresses are only reachable through the proxy
+    pub fn can_dial(&self, addr: &str) -> bool {
+        self.proxy.read().unwrap().is_some() || !socks5::is_onion(addr)
+    }
+
+    /// Remember `addr` as a manually added peer and dial it
+    pub async fn add_node(self: &Arc<Self>, addr: &str) {
+        let addr = &normalize_addr(addr).unwrap_or_else(|| addr.to_string());
+        self.added_nodes.write().unwrap().insert(addr.to_string());
+        self.known_addresses.write().await.insert(addr.to_string());
+        let state = self.clone();
+        let addr = addr.to_string();
+        tokio::spawn(async move { connect_to_peer(state, &addr).await; });
+    }
+
+    /// Drop the peer connected as (or listening on) `addr` and forget it as
+    /// an added node. Returns the connection address, or None if no such peer.
+    pub async fn disconnect_node(&self, addr: &str) -> Option<String> {
+        self.added_nodes.write().unwrap().remove(&normalize_addr(addr).unwrap_or_else(|| addr.to_string()));
+        let mut peers = self.peers.write().await;
+        let key = find_peer(&peers, addr)?.address.clone();
+        peers.remove(&key);
+        drop(peers);
+        // The connection loop matches on its own address and exits
+        let _ = self.disconnect_tx.send(key.clone());
+        Some(key)
+    }
+
+    /// Each added node with the peer it's currently connected as, if any
+    pub async fn added_node_info(&self) -> Vec<(String, Option<PeerInfo>)> {
+        let added: Vec<String> = self.added_nodes.read().unwrap().iter().cloned().collect();
+        let peers = self.peers.read().await;
+        added.into_iter().map(|addr| {
+            let peer = find_peer(&peers, &addr).cloned();
+            (addr, peer)
+        }).collect()
+    }
+
     pub fn open(data_dir: &str, listen_port: u16) -> Arc<Self> {
+        Self::open_with_options(data_dir, listen_port, ChainOptions::default())
+    }
+
+    pub fn open_with_options(data_dir: &str, listen_port: u16, options: ChainOptions) -> Arc<Self> {
         let (block_tx, _) = broadcast::channel(256);
         let (tx_tx, _) = broadcast::channel(4096);
-        let chain = Chain::open(data_dir).unwrap_or_else(|e| {
+        let chain = Chain::open_with_options(data_dir, options).unwrap_or_else(|e| {
             tracing::error!("Failed to open chain from {}: {}", data_dir, e);
             Chain::new()
         });
@@ -448,10 +1036,24 @@ impl NodeState {
             mempool: Mutex::new(Mempool::new(10_000)),
             peers: RwLock::new(HashMap::new()),
             known_addresses: RwLock::new(HashSet::new()),
-            scoreboard: Mutex::new(PeerScoreboard::new()),
+            scoreboard: Mutex::new(PeerScoreboard::load(data_dir)),
             listen_port, block_tx, tx_tx,
             new_block_notify: tokio::sync::Notify::new(),
             pending_compacts: tokio::sync::Mutex::new(HashMap::new()),
+            wallet: Mutex::new(None),
+            pool: Mutex::new(None),
+            faucet: Mutex::new(None),
+            orphans: Mutex::new(OrphanPool::new(MAX_ORPHAN_POOL_BYTES)),
+            downloads: Mutex::new(BlockDownloader::new()),
+            block_request_tx: broadcast::channel(256).0,
+            rate_limits: std::sync::RwLock::new(RateLimits::default()),
+            local_nonce: rand::random(),
+            self_addresses: std::sync::RwLock::new(HashSet::new()),
+            disconnect_tx: broadcast::channel(64).0,
+            added_nodes: std::sync::RwLock::new(BTreeSet::new()),
+            proxy: std::sync::RwLock::new(None),
+            onion_address: std::sync::RwLock::new(None),
+            shutdown: Arc::new(std::sync::atomic::AtomicBool::new(false)),
         })
     }
 }
@@ -484,6 +1086,7 @@ async fn handle_connection(mut stream: TcpStream, state: Arc<NodeState>, peer_ad
     let version_msg = NetMessage::VersionV2 {
         version: PROTOCOL_VERSION, best_height: our_height, best_hash: our_hash,
         genesis_hash: our_genesis, timestamp: now, listen_port: state.listen_port,
+        nonce: state.local_nonce,
     };
     if let Err(e) = write_message(&mut stream, &version_msg).await {
         tracing::error!("Failed to send version to {}: {}", peer_addr, e);
@@ -494,9 +1097,18 @@ async fn handle_connection(mut stream: TcpStream, state: Arc<NodeState>, peer_ad
     let peer_is_v2;
 
     let peer_height = match read_message(&mut stream).await {
-        Ok(NetMessage::VersionV2 { version, best_height, genesis_hash, listen_port, .. }) => {
+        Ok(NetMessage::VersionV2 { version, best_height, genesis_hash, listen_port, nonce, .. }) => {
             peer_is_v2 = true;
 
+            if nonce == state.local_nonce {
+                tracing::info!("🔁 Connected to ourselves via {}, dropping", peer_addr);
+                if is_outbound {
+                    state.self_addresses.write().unwrap().insert(peer_addr.clone());
+                    state.known_addresses.write().await.remove(&peer_addr);
+                }
+                return;
+            }
+
             // Reject outdated protocol versions
             if version < MIN_PROTOCOL_VERSION {
                 tracing::warn!("🚫 Rejecting peer {} — protocol v{} too old (minimum v{})", 
@@ -520,12 +1132,12 @@ async fn handle_connection(mut stream: TcpStream, state: Arc<NodeState>, peer_ad
 
             tracing::info!("  Peer {} v{} at height {} (genesis verified ✅)", peer_addr, version, best_height);
             {
-                let peer_ip = peer_addr.split(':').next().unwrap_or("127.0.0.1");
-                let listen_addr = format!("{}:{}", peer_ip, listen_port);
+                let listen_addr = join_host_port(&host_of(&peer_addr), listen_port);
                 let mut peers = state.peers.write().await;
                 peers.insert(peer_addr.clone(), PeerInfo {
                     address: peer_addr.clone(), listen_address: listen_addr.clone(),
                     version, best_height, last_seen: now, supports_v2: true,
+                    inbound: !is_outbound, relay_txs: true, ping_ms: None,
                 });
                 drop(peers);
                 let mut known = state.known_addresses.write().await;
@@ -575,9 +1187,21 @@ async fn handle_connection(mut stream: TcpStream, state: Arc<NodeState>, peer_ad
         }).await;
     }
 
+    let onion = state.onion_address.read().unwrap().clone();
+    if let Some(onion) = onion {
+        let _ = write_message(&mut stream, &NetMessage::AddrSelf(onion)).await;
+    }
     let _ = write_message(&mut stream, &NetMessage::GetPeers).await;
+    // Learn what's already waiting to be mined so our templates aren't empty
+    let _ = write_message(&mut stream, &NetMessage::GetMempool).await;
 
+    let limits = *state.rate_limits.read().unwrap();
+    let mut limiter = PeerRateLimiter::new(limits);
+    let mut known = KnownInventory::new();
     let mut block_rx = state.block_tx.subscribe();
+    let mut disconnect_rx = state.disconnect_tx.subscribe();
+    let mut block_request_rx = state.block_request_tx.subscribe();
+    let peer_ip = host_of(&peer_addr);
     let mut tx_rx = state.tx_tx.subscribe();
     let mut peer_exchange = tokio::time::interval(std::time::Duration::from_secs(PEER_EXCHANGE_INTERVAL));
     let mut keepalive = tokio::time::interval(std::time::Duration::from_secs(60));
@@ -586,11 +1210,17 @@ async fn handle_connection(mut stream: TcpStream, state: Arc<NodeState>, peer_ad
         tokio::select! {
             msg_result = tokio::time::timeout(
                 std::time::Duration::from_secs(300), // 5 min read timeout
-                read_message(&mut stream)
+                read_message_sized(&mut stream)
             ) => {
                 match msg_result {
-                    Ok(Ok(msg)) => {
-                        match handle_message(&mut stream, &state, &peer_addr, msg).await {
+                    Ok(Ok((msg, size))) => {
+                        limiter.record_recv(&msg, size as u64);
+                        if limiter.is_recv_limited() {
+                            tracing::warn!("🚫 Peer {} exceeded {} B/s inbound, disconnecting", peer_addr, limiter.max_recv_rate);
+                            state.scoreboard.lock().await.record_offense(&peer_addr, Offense::RateLimited);
+                            break;
+                        }
+                        match handle_message(&mut stream, &mut limiter, &mut known, &state, &peer_addr, msg).await {
                             Ok(()) => {}
                             Err(e) => {
                                 tracing::error!("Error from {}: {}", peer_addr, e);
@@ -616,57 +1246,105 @@ async fn handle_connection(mut stream: TcpStream, state: Arc<NodeState>, peer_ad
             }
             block_result = block_rx.recv() => {
                 if let Ok(block) = block_result {
+                    if limiter.is_send_limited() { continue; }
+                    // Skip peers that sent us the block or already heard about it
+                    if !known.insert(InvItem::block(block.header.hash())) { continue; }
                     if peer_is_v2 {
-                        // Send compact block: full coinbase + hashes of remaining txs
+                        // Send compact block: full coinbase + txids of remaining txs
                         let tx_hashes: Vec<Hash256> = block.transactions[1..].iter()
-                            .map(|tx| tx.hash())
+                            .map(crate::crypto::txid::txid_v1)
                             .collect();
-                        let _ = write_message(&mut stream, &NetMessage::CompactBlock {
+                        let _ = write_metered(&mut stream, &mut limiter, &NetMessage::CompactBlock {
                             header: block.header.clone(),
                             short_txids: tx_hashes,
                             coinbase: block.transactions[0].clone(),
                         }).await;
                     } else {
-                        let _ = write_message(&mut stream, &NetMessage::NewBlock(block)).await;
+                        // Legacy peers get an announcement and fetch the block if they want it
+                        let inv = vec![InvItem::block(block.header.hash())];
+                        let _ = write_metered(&mut stream, &mut limiter, &NetMessage::Inv(inv)).await;
                     }
                 }
             }
             tx_result = tx_rx.recv() => {
                 if let Ok(tx) = tx_result {
-                    let _ = write_message(&mut stream, &NetMessage::NewTransaction(tx)).await;
+                    if limiter.is_send_limited() { continue; }
+                    let item = InvItem::tx(crate::crypto::txid::txid_v1(&tx));
+                    if !known.insert(item) { continue; }
+                    let _ = write_metered(&mut stream, &mut limiter, &NetMessage::Inv(vec![item])).await;
+                }
+            }
+            target = disconnect_rx.recv() => {
+                if let Ok(target) = target {
+                    if target == peer_addr || host_of(&target) == peer_ip {
+                        tracing::info!("🔌 Disconnecting {} on request", peer_addr);
+                        break;
+                    }
+                }
+            }
+            request = block_request_rx.recv() => {
+                if let Ok((target, hashes)) = request {
+                    if target == peer_addr {
+                        let _ = write_metered(&mut stream, &mut limiter, &NetMessage::GetBlockData(hashes)).await;
+                    }
                 }
             }
             _ = peer_exchange.tick() => {
-                let _ = write_message(&mut stream, &NetMessage::GetPeers).await;
+                let _ = write_metered(&mut stream, &mut limiter, &NetMessage::GetPeers).await;
             }
             _ = keepalive.tick() => {
-                let nonce = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
-                if write_message(&mut stream, &NetMessage::Ping(nonce)).await.is_err() {
+                // Nonce is our send time in ms; the Pong echoes it back for latency
+                let nonce = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_millis() as u64;
+                if write_metered(&mut stream, &mut limiter, &NetMessage::Ping(nonce)).await.is_err() {
                     tracing::info!("🔌 Peer {} unreachable (ping failed)", peer_addr);
                     break;
                 }
                 // Update last_seen
                 let mut peers = state.peers.write().await;
                 if let Some(peer) = peers.get_mut(&peer_addr) {
-                    peer.last_seen = nonce;
+                    peer.last_seen = nonce / 1000;
                 }
             }
         }
     }
 
     { state.peers.write().await.remove(&peer_addr); }
+    // Whatever this peer still owed us goes to someone else
+    let peers = download_peers(&state).await;
+    let moved = state.downloads.lock().await.peer_gone(&peer_addr, &peers, std::time::Instant::now());
+    dispatch_block_requests(&state, moved);
     tracing::info!("🔌 Cleaned up peer {}", peer_addr);
 }
 
+// ─── Parallel Download ──────────────────────────────────────────────
+
+/// Connected peers with their advertised heights, in a stable order
+async fn download_peers(state: &NodeState) -> Vec<(String, u64)> {
+    let mut peers: Vec<(String, u64)> = state.peers.read().await.values()
+        .map(|p| (p.address.clone(), p.best_height))
+        .collect();
+    peers.sort();
+    peers
+}
+
+/// Hand `GetBlockData` requests to the owning connection handlers
+fn dispatch_block_requests(state: &NodeState, requests: Vec<(String, Vec<Hash256>)>) {
+    for request in requests {
+        let _ = state.block_request_tx.send(request);
+    }
+}
+
 // ─── Message Handler ────────────────────────────────────────────────
 
 async fn handle_message(
-    stream: &mut TcpStream, state: &Arc<NodeState>, peer_addr: &str, msg: NetMessage,
+    stream: &mut TcpStream, limiter: &mut PeerRateLimiter, known: &mut KnownInventory,
+    state: &Arc<NodeState>, peer_addr: &str, msg: NetMessage,
 ) -> Result<(), String> {
     match msg {
         NetMessage::NewBlock(block) => {
             let height = block.header.height;
             let hash = block.header.hash();
+            known.insert(InvItem::block(hash));
             let mut chain = state.chain.write().await;
             match chain.add_block(block.clone()) {
                 Ok(_) => {
@@ -683,17 +1361,23 @@ async fn handle_message(
                         peer.best_height = peer.best_height.max(height);
                         peer.last_seen = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
                     }
+                    drop(peers);
+                    resolve_orphans(state, hash).await;
                 }
                 Err(crate::core::chain::BlockError::OrphanBlock) => {
                     let our_height = chain.height;
                     drop(chain);
+                    if hold_orphan(state, peer_addr, hash, block).await {
+                        tracing::debug!("📥 Block #{} from {} is orphan, parent already on its way", height, peer_addr);
+                        return Ok(());
+                    }
                     tracing::info!("📥 Block #{} is orphan, locator-syncing from {} (we're at {})", height, peer_addr, our_height);
                     // Use locator to handle forks correctly — never assume linear chain
                     let locator = {
                         let chain = state.chain.read().await;
                         build_locator(&chain, 32)
                     };
-                    write_message(stream, &NetMessage::GetHeadersFrom {
+                    write_metered(stream, limiter, &NetMessage::GetHeadersFrom {
                         locator,
                         count: 2000,
                     }).await?;
@@ -732,12 +1416,13 @@ async fn handle_message(
             drop(chain);
 
             if !headers.is_empty() {
-                write_message(stream, &NetMessage::Headers(headers)).await?;
+                write_metered(stream, limiter, &NetMessage::Headers(headers)).await?;
             }
         }
 
 
         NetMessage::NewTransaction(tx) => {
+            known.insert(InvItem::tx(crate::crypto::txid::txid_v1(&tx)));
             if tx.is_coinbase() {
                 let mut sb = state.scoreboard.lock().await;
                 sb.record_offense(peer_addr, Offense::InvalidTransaction);
@@ -766,13 +1451,21 @@ async fn handle_message(
             let mut blocks = Vec::new();
             let end = (start_height + capped_count as u64).min(chain.height + 1);
             for h in start_height..end {
-                if let Some(block) = chain.block_at_height(h) { blocks.push(block.clone()); }
+                // Stop at a pruned block rather than send a batch with gaps
+                match chain.block_at_height(h) {
+                    Some(block) => blocks.push(block.clone()),
+                    None => break,
+                }
             }
             let send_count = blocks.len();
+            let pruned = send_count == 0 && start_height < end && start_height < chain.prune_height();
             drop(chain);
+            if pruned {
+                tracing::debug!("✂️  {} asked for pruned blocks from #{}", peer_addr, start_height);
+            }
             if send_count > 0 {
                 tracing::info!("📤 Sending {} blocks to {} ({}→{})", send_count, peer_addr, start_height, start_height + send_count as u64 - 1);
-                write_message(stream, &NetMessage::Blocks(blocks)).await?;
+                write_metered(stream, limiter, &NetMessage::Blocks(blocks)).await?;
             }
         }
 
@@ -839,7 +1532,7 @@ async fn handle_message(
                         let chain = state.chain.read().await;
                         build_locator(&chain, 32)
                     };
-                    write_message(stream, &NetMessage::GetHeadersFrom {
+                    write_metered(stream, limiter, &NetMessage::GetHeadersFrom {
                         locator, count: 2000,
                     }).await?;
                 }
@@ -853,7 +1546,7 @@ async fn handle_message(
                 .cloned();
             drop(chain);
             if let Some(block) = block {
-                write_message(stream, &NetMessage::NewBlock(block)).await?;
+                write_metered(stream, limiter, &NetMessage::NewBlock(block)).await?;
             }
         }
 
@@ -861,11 +1554,10 @@ async fn handle_message(
             let peers = state.peers.read().await;
             let addrs: Vec<String> = peers.values().map(|p| p.listen_address.clone()).collect();
             drop(peers);
-            write_message(stream, &NetMessage::Peers(addrs)).await?;
+            write_metered(stream, limiter, &NetMessage::Peers(addrs)).await?;
         }
 
         NetMessage::Peers(addrs) => {
-            let our_addr = format!("127.0.0.1:{}", state.listen_port);
             let mut known = state.known_addresses.write().await;
             let connected: HashSet<String> = {
                 let peers = state.peers.read().await;
@@ -873,7 +1565,8 @@ async fn handle_message(
             };
             let mut new_count = 0u32;
             for addr in addrs {
-                if addr == our_addr || connected.contains(&addr) { continue; }
+                let Some(addr) = normalize_addr(&addr) else { continue };
+                if state.is_own_address(&addr) || connected.contains(&addr) { continue; }
                 if known.insert(addr) { new_count += 1; }
             }
             drop(known);
@@ -882,14 +1575,29 @@ async fn handle_message(
             }
         }
 
+        NetMessage::AddrSelf(addr) => {
+            // Only onion addresses: anything else is already visible from the socket
+            if socks5::is_onion(&addr) && split_host_port(&addr).is_some() && !state.is_own_address(&addr) {
+                if let Some(peer) = state.peers.write().await.get_mut(peer_addr) {
+                    peer.listen_address = addr.clone();
+                }
+                state.known_addresses.write().await.insert(addr);
+            }
+        }
+
         NetMessage::Ping(nonce) => {
-            write_message(stream, &NetMessage::Pong(nonce)).await?;
+            write_metered(stream, limiter, &NetMessage::Pong(nonce)).await?;
         }
 
-        NetMessage::Pong(_) => {
+        NetMessage::Pong(nonce) => {
+            let now_ms = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_millis() as u64;
             let mut peers = state.peers.write().await;
             if let Some(peer) = peers.get_mut(peer_addr) {
-                peer.last_seen = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
+                peer.last_seen = now_ms / 1000;
+                // Only trust echoes of a ping we could plausibly have sent (within the last minute)
+                if nonce <= now_ms && now_ms - nonce < 60_000 {
+                    peer.ping_ms = Some(now_ms - nonce);
+                }
             }
         }
 
@@ -905,7 +1613,7 @@ async fn handle_message(
             if !headers.is_empty() {
                 tracing::info!("📤 Sending {} headers to {} ({}→{})",
                     headers.len(), peer_addr, start_height, start_height + headers.len() as u64 - 1);
-                write_message(stream, &NetMessage::Headers(headers)).await?;
+                write_metered(stream, limiter, &NetMessage::Headers(headers)).await?;
             }
         }
 
@@ -934,16 +1642,17 @@ async fn handle_message(
                     build_locator(&chain, 32)
                 };
 
-                write_message(stream, &NetMessage::GetHeadersFrom { locator, count: 2000 }).await?;
+                write_metered(stream, limiter, &NetMessage::GetHeadersFrom { locator, count: 2000 }).await?;
                 return Ok(());
             }
 
-            // Filter to hashes we don't have full blocks for
-            let need_blocks: Vec<Hash256> = {
+            // Filter to hashes we don't have full blocks for. Valid hashes are
+            // a prefix of `headers`, so zipping pairs each with its height.
+            let need_blocks: Vec<(Hash256, u64)> = {
                 let chain = state.chain.read().await;
-                valid_hashes.iter()
-                    .filter(|h| chain.block_by_hash(h).is_none())
-                    .copied()
+                valid_hashes.iter().zip(&headers)
+                    .filter(|(h, _)| chain.block_by_hash(h).is_none())
+                    .map(|(h, header)| (*h, header.height))
                     .collect()
             };
 
@@ -958,11 +1667,24 @@ async fn handle_message(
                 }
             }
 
-            // Request full block data for validated headers
+            // Request full block data, spread over every peer that has it
             if !need_blocks.is_empty() {
-                // Request in batches of 100
-                for chunk in need_blocks.chunks(100) {
-                    write_message(stream, &NetMessage::GetBlockData(chunk.to_vec())).await?;
+                let mut peers = download_peers(state).await;
+                // The peer that sent the headers gets the first (most urgent) chunk
+                if let Some(i) = peers.iter().position(|(p, _)| p == peer_addr) {
+                    peers[..=i].rotate_right(1);
+                }
+                let requests = state.downloads.lock().await.assign(&need_blocks, &peers, std::time::Instant::now());
+                let helpers = requests.iter().filter(|(p, _)| p != peer_addr).count();
+                if helpers > 0 {
+                    tracing::debug!("📥 Fetching {} blocks with help from {} other peer requests", need_blocks.len(), helpers);
+                }
+                for (peer, hashes) in requests {
+                    if peer == peer_addr {
+                        write_metered(stream, limiter, &NetMessage::GetBlockData(hashes)).await?;
+                    } else {
+                        dispatch_block_requests(state, vec![(peer, hashes)]);
+                    }
                 }
             }
 
@@ -971,7 +1693,7 @@ async fn handle_message(
             if let Some(peer) = peers.get(peer_addr) {
                 if peer.best_height > last_height {
                     drop(peers);
-                    write_message(stream, &NetMessage::GetHeaders {
+                    write_metered(stream, limiter, &NetMessage::GetHeaders {
                         start_height: last_height + 1, count: 2000,
                     }).await?;
                 }
@@ -983,54 +1705,96 @@ async fn handle_message(
             let chain = state.chain.read().await;
             let blocks = chain.blocks_by_hashes(capped);
             drop(chain);
+            if blocks.len() < capped.len() {
+                tracing::debug!("📤 {} of {} blocks requested by {} unavailable (pruned or unknown)",
+                    capped.len() - blocks.len(), capped.len(), peer_addr);
+            }
             if !blocks.is_empty() {
                 tracing::info!("📤 Sending {} block data to {}", blocks.len(), peer_addr);
-                write_message(stream, &NetMessage::BlockData(blocks)).await?;
+                write_metered(stream, limiter, &NetMessage::BlockData(blocks)).await?;
             }
         }
 
         NetMessage::BlockData(blocks) => {
             let count = blocks.len();
+            let last_height = blocks.last().map(|b| b.header.height);
+
+            // Requested blocks wait in the downloader until their parent has
+            // connected; anything we didn't ask for is tried right away
+            let mut batch = Vec::new();
+            {
+                let mut downloads = state.downloads.lock().await;
+                for block in blocks {
+                    let hash = block.header.hash();
+                    if let Err(block) = downloads.receive(hash, block) {
+                        batch.push(block);
+                    }
+                }
+            }
+
             let mut accepted = 0;
             let mut last_reject_reason = String::new();
+            let mut processed = Vec::new();
+            let mut accepted_hashes = Vec::new();
             let chunk_size = 25;
-            for chunk in blocks.chunks(chunk_size) {
-                let mut chain = state.chain.write().await;
-                chain.set_batch_mode(true);
-                for block in chunk {
-                    match chain.add_block(block.clone()) {
-                        Ok(_) => accepted += 1,
-                        Err(e) => {
-                            last_reject_reason = format!("{}", e);
-                            tracing::warn!("❌ BlockData #{} rejected from {}: {}", block.header.height, peer_addr, e);
+            loop {
+                if batch.is_empty() {
+                    let chain = state.chain.read().await;
+                    batch = state.downloads.lock().await.take_ready(|h| chain.block_by_hash(h).is_some());
+                    if batch.is_empty() { break; }
+                }
+                for chunk in batch.chunks(chunk_size) {
+                    let mut chain = state.chain.write().await;
+                    chain.set_batch_mode(true);
+                    for block in chunk {
+                        match chain.add_block(block.clone()) {
+                            Ok(_) => {
+                                accepted += 1;
+                                accepted_hashes.push(block.header.hash());
+                            }
+                            Err(e) => {
+                                last_reject_reason = format!("{}", e);
+                                tracing::warn!("❌ BlockData #{} rejected from {}: {}", block.header.height, peer_addr, e);
+                            }
                         }
                     }
+                    chain.set_batch_mode(false);
+                    chain.flush_batch();
+                    drop(chain);
+                    tokio::task::yield_now().await;
                 }
-                chain.set_batch_mode(false);
-                chain.flush_batch();
-                drop(chain);
-                tokio::task::yield_now().await;
+                processed.append(&mut batch);
             }
             if accepted > 0 {
                 let mut mempool = state.mempool.lock().await;
-                for block in &blocks {
+                for block in &processed {
                     mempool.remove_confirmed(block);
                 }
                 drop(mempool);
                 state.new_block_notify.notify_waiters();
             }
+            // Announced blocks that raced ahead of the sync may be waiting on these
+            for hash in accepted_hashes {
+                resolve_orphans(state, hash).await;
+            }
             let our_height = state.chain.read().await.height;
             tracing::info!("📥 BlockData: accepted {}/{} from {} (height: {})", accepted, count, peer_addr, our_height);
 
             // Update peer's advertised height based on blocks received
-            if let Some(last_block) = blocks.last() {
+            if let Some(last_height) = last_height {
                 let mut peers = state.peers.write().await;
                 if let Some(peer) = peers.get_mut(peer_addr) {
-                    peer.best_height = peer.best_height.max(last_block.header.height);
+                    peer.best_height = peer.best_height.max(last_height);
                 }
                 drop(peers);
             }
 
+            // Other peers' chunks are still on the way; whoever delivers the
+            // last one picks the header sync back up
+            if !state.downloads.lock().await.is_idle() {
+                return Ok(());
+            }
+
             // Continue syncing if peer has more blocks
             let peer_best = {
                 let peers = state.peers.read().await;
@@ -1046,8 +1810,8 @@ async fn handle_message(
                         build_locator(&chain, 32)
                     };
 
-                    write_message(
-                        stream,
+                    write_metered(
+                        stream, limiter,
                         &NetMessage::GetHeadersFrom { locator, count: 2000 },
                     ).await?;
 
@@ -1057,7 +1821,7 @@ async fn handle_message(
                             let chain = state.chain.read().await;
                             build_locator(&chain, 32)
                         };
-                        write_message(stream, &NetMessage::GetHeadersFrom {
+                        write_metered(stream, limiter, &NetMessage::GetHeadersFrom {
                             locator, count: 2000,
                         }).await?;
                     }
@@ -1070,6 +1834,7 @@ async fn handle_message(
         NetMessage::CompactBlock { header, short_txids, coinbase } => {
             // Monero-like "fluffy block": try reconstruct from mempool, request only missing txs.
             let block_hash = header.hash();
+            known.insert(InvItem::block(block_hash));
 
             // Fast-path: if we already have this block, ignore.
             {
@@ -1128,16 +1893,20 @@ async fn handle_message(
                         let _ = state.block_tx.send(block);
                         state.new_block_notify.notify_waiters();
                         tracing::info!("📦 Compact block from {} ({})", peer_addr, &hex::encode(block_hash)[..16]);
+                        resolve_orphans(state, block_hash).await;
                     }
                     Err(crate::core::chain::BlockError::OrphanBlock) => {
                         let our_height = chain.height;
                         drop(chain);
+                        if hold_orphan(state, peer_addr, block_hash, block).await {
+                            return Ok(());
+                        }
                         tracing::info!("📥 Compact block is orphan, locator-syncing from {} (we're at {})", peer_addr, our_height);
                         let locator = {
                             let chain = state.chain.read().await;
                             build_locator(&chain, 32)
                         };
-                        write_message(stream, &NetMessage::GetHeadersFrom {
+                        write_metered(stream, limiter, &NetMessage::GetHeadersFrom {
                             locator,
                             count: 2000,
                         }).await?;
@@ -1171,7 +1940,7 @@ async fn handle_message(
             }
 
             let missing_list: Vec<Hash256> = missing.into_iter().collect();
-            write_message(stream, &NetMessage::GetTransactions(missing_list)).await?;
+            write_metered(stream, limiter, &NetMessage::GetTransactions(missing_list)).await?;
 
 
         }
@@ -1188,7 +1957,7 @@ async fn handle_message(
                 .filter_map(|h| pending_map.get(h).cloned())
                 .collect();
             if !found.is_empty() {
-                write_message(stream, &NetMessage::TransactionBatch(found)).await?;
+                write_metered(stream, limiter, &NetMessage::TransactionBatch(found)).await?;
             }
         }
 
@@ -1243,6 +2012,11 @@ async fn handle_message(
                                 let _ = state.block_tx.send(block);
                                 state.new_block_notify.notify_waiters();
                                 tracing::info!("✅ Reconstructed block {} from compact+missing txs", &hex::encode(bh)[..16]);
+                                resolve_orphans(state, bh).await;
+                            }
+                            Err(crate::core::chain::BlockError::OrphanBlock) => {
+                                drop(chain);
+                                hold_orphan(state, peer_addr, bh, block).await;
                             }
                             Err(e) => {
                                 tracing::warn!("❌ Reconstructed block rejected: {:?}", e);
@@ -1253,12 +2027,129 @@ async fn handle_message(
             }
 
         }
+
+        // ─── Inventory Relay ───
+
+        NetMessage::Inv(items) => {
+            if items.len() > MAX_INV_ITEMS {
+                let mut sb = state.scoreboard.lock().await;
+                sb.record_offense(peer_addr, Offense::MalformedMessage);
+                return Ok(());
+            }
+            let mut wanted = Vec::new();
+            {
+                let chain = state.chain.read().await;
+                let mempool = state.mempool.lock().await;
+                let orphans = state.orphans.lock().await;
+                for item in items {
+                    known.insert(item);
+                    let have = match item.kind {
+                        InvKind::Block => chain.block_by_hash(&item.hash).is_some() || orphans.contains(&item.hash),
+                        InvKind::Tx => mempool.contains(&item.hash),
+                    };
+                    if !have { wanted.push(item); }
+                }
+            }
+            if !wanted.is_empty() {
+                write_metered(stream, limiter, &NetMessage::GetData(wanted)).await?;
+            }
+        }
+
+        NetMessage::GetMempool => {
+            if !limiter.allow_mempool_reply() {
+                let mut sb = state.scoreboard.lock().await;
+                sb.record_offense(peer_addr, Offense::SpamPing);
+                return Ok(());
+            }
+            let txids = state.mempool.lock().await.pending_txids(MAX_INV_ITEMS);
+            let items: Vec<InvItem> = txids.into_iter()
+                .map(InvItem::tx)
+                .filter(|item| known.insert(*item))
+                .collect();
+            if !items.is_empty() {
+                write_metered(stream, limiter, &NetMessage::Inv(items)).await?;
+            }
+        }
+
+        NetMessage::GetData(items) => {
+            if items.len() > MAX_INV_ITEMS {
+                let mut sb = state.scoreboard.lock().await;
+                sb.record_offense(peer_addr, Offense::MalformedMessage);
+                return Ok(());
+            }
+            let mut replies = Vec::new();
+            {
+                let chain = state.chain.read().await;
+                let mempool = state.mempool.lock().await;
+                for item in items {
+                    let reply = match item.kind {
+                        InvKind::Block => chain.block_by_hash(&item.hash).cloned().map(NetMessage::NewBlock),
+                        InvKind::Tx => mempool.get(&item.hash).cloned().map(NetMessage::NewTransaction),
+                    };
+                    if let Some(reply) = reply {
+                        known.insert(item);
+                        replies.push(reply);
+                    }
+                }
+            }
+            for reply in replies {
+                write_metered(stream, limiter, &reply).await?;
+            }
+        }
     }
     Ok(())
 }
 
 // ─── Public API ─────────────────────────────────────────────────────
 
+/// Seed entries with this prefix name a DNS seed whose A/AAAA records are peers
+pub const DNS_SEED_PREFIX: &str = "dnsseed:";
+
+/// "dnsseed:host[:port]" -> "host:port" (default P2P port if none given)
+fn dns_seed_target(entry: &str) -> Option<String> {
+    let host = entry.strip_prefix(DNS_SEED_PREFIX)?.trim();
+    if host.is_empty() { return None; }
+    if split_host_port(host).is_some() { Some(host.to_string()) } else { Some(join_host_port(host, default_port())) }
+}
+
+/// Resolve every DNS seed, add the results to `known_addresses`, and return
+/// them (deduplicated, in resolution order). Nothing is looked up behind a
+/// proxy, as the query would go out through the local resolver.
+async fn resolve_dns_seeds(state: &Arc<NodeState>, dns_seeds: &[String]) -> Vec<String> {
+    if state.proxy.read().unwrap().is_some() {
+        tracing::debug!("Skipping DNS seeds: lookups would bypass the proxy");
+        return Vec::new();
+    }
+    let mut resolved = Vec::new();
+    for entry in dns_seeds {
+        let Some(target) = dns_seed_target(entry) else {
+            tracing::warn!("Ignoring malformed DNS seed {:?}", entry);
+            continue;
+        };
+        let addrs: Vec<std::net::SocketAddr> = match tokio::net::lookup_host(target.as_str()).await {
+            Ok(addrs) => addrs.collect(),
+            Err(e) => { tracing::warn!("DNS seed {} failed: {}", target, e); continue; }
+        };
+        tracing::info!("🌱 DNS seed {} returned {} addresses", target, addrs.len());
+        for addr in addrs {
+            let addr = addr.to_string();
+            if !resolved.contains(&addr) { resolved.push(addr); }
+        }
+    }
+    resolved.retain(|a| !state.is_own_address(a));
+    let mut known = state.known_addresses.write().await;
+    known.extend(resolved.iter().cloned());
+    resolved
+}
+
+/// Dial up to `MAX_OUTBOUND_PEERS` of the given addresses
+fn dial_addresses(state: &Arc<NodeState>, addrs: Vec<String>) {
+    for addr in addrs.into_iter().take(MAX_OUTBOUND_PEERS) {
+        let state = state.clone();
+        tokio::spawn(async move { connect_to_peer(state, &addr).await; });
+    }
+}
+
 pub async fn start_node(
     state: Arc<NodeState>, seed_peers: Vec<String>,
 ) -> Result<(), Box<dyn std::error::Error>> {
@@ -1271,6 +2162,8 @@ pub async fn start_node(
         let s = seed.to_string();
         if !all_seeds.contains(&s) { all_seeds.push(s); }
     }
+    let (dns_seeds, all_seeds): (Vec<String>, Vec<String>) =
+        all_seeds.into_iter().partition(|s| s.starts_with(DNS_SEED_PREFIX));
 
     for addr in &all_seeds {
         let state = state.clone();
@@ -1281,6 +2174,15 @@ pub async fn start_node(
         });
     }
 
+    if !dns_seeds.is_empty() {
+        let state = state.clone();
+        let dns_seeds = dns_seeds.clone();
+        tokio::spawn(async move {
+            let resolved = resolve_dns_seeds(&state, &dns_seeds).await;
+            dial_addresses(&state, resolved);
+        });
+    }
+
     // Maintenance task
     {
         let state = state.clone();
@@ -1290,8 +2192,28 @@ pub async fn start_node(
             loop {
                 interval.tick().await;
 
-                // Clean up expired bans
-                { state.scoreboard.lock().await.cleanup(); }
+                // Clean up expired bans and persist the rest
+                {
+                    let mut sb = state.scoreboard.lock().await;
+                    sb.cleanup();
+                    sb.save(data_dir());
+                }
+
+                // Reassign block downloads a peer sat on for too long
+                {
+                    let peers = download_peers(&state).await;
+                    let moved = state.downloads.lock().await.expire(&peers, std::time::Instant::now());
+                    if !moved.is_empty() {
+                        tracing::info!("📥 Reassigning {} overdue block requests", moved.len());
+                    }
+                    dispatch_block_requests(&state, moved);
+                }
+
+                // Drop orphans whose parent never arrived
+                let expired = state.orphans.lock().await.expire(std::time::Instant::now());
+                if expired > 0 {
+                    tracing::debug!("🗑️ Expired {} orphan blocks", expired);
+                }
 
                 // Expire stale pending compact blocks (>30s old)
                 {
@@ -1325,6 +2247,20 @@ pub async fn start_node(
                     }
                 }
 
+                // Added nodes are kept connected regardless of the peer count
+                for (addr, peer) in state.added_node_info().await {
+                    if peer.is_none() {
+                        let state = state.clone();
+                        tokio::spawn(async move { connect_to_peer(state, &addr).await; });
+                    }
+                }
+
+                // DNS seed IPs may have changed since startup: look them up again
+                if peer_count == 0 && !dns_seeds.is_empty() {
+                    let resolved = resolve_dns_seeds(&state, &dns_seeds).await;
+                    dial_addresses(&state, resolved);
+                }
+
                 // Try discovered peers if below target
                 if peer_count > 0 && peer_count < MAX_OUTBOUND_PEERS {
                     let known = state.known_addresses.read().await;
@@ -1332,11 +2268,10 @@ pub async fn start_node(
                         let peers = state.peers.read().await;
                         peers.values().map(|p| p.listen_address.clone()).collect()
                     };
-                    let our_addr = format!("127.0.0.1:{}", state.listen_port);
                     let sb = state.scoreboard.lock().await;
 
                     let candidates: Vec<String> = known.iter()
-                        .filter(|a| *a != &our_addr && !connected.contains(*a) && !sb.is_banned(a))
+                        .filter(|a| state.can_dial(a) && !state.is_own_address(a) && !connected.contains(*a) && !sb.is_banned(a))
                         .take(3)
                         .cloned()
                         .collect();
@@ -1403,16 +2338,26 @@ pub async fn connect_to_peer(state: Arc<NodeState>, addr: &str) {
         let sb = state.scoreboard.lock().await;
         if sb.is_banned(addr) { return; }
     }
+    if state.is_own_address(addr) { return; }
     {
         let peers = state.peers.read().await;
-        if peers.values().any(|p| p.listen_address == addr || p.address == addr) { return; }
-    }
-    tracing::info!("🔗 Connecting to {}...", addr);
-    // 10 second connection timeout to prevent hanging on dead peers
-    match tokio::time::timeout(
-        std::time::Duration::from_secs(10),
-        TcpStream::connect(addr)
-    ).await {
+        if find_peer(&peers, addr).is_some() { return; }
+    }
+    if !state.can_dial(addr) {
+        tracing::debug!("Skipping {}: onion addresses need --proxy", addr);
+        return;
+    }
+    let proxy = state.proxy.read().unwrap().clone();
+    tracing::info!("🔗 Connecting to {}{}...", addr, if proxy.is_some() { " via proxy" } else { "" });
+    let connecting = async {
+        match &proxy {
+            Some(proxy) => socks5::connect(proxy, addr).await,
+            None => TcpStream::connect(addr).await,
+        }
+    };
+    // Connection timeout to prevent hanging on dead peers; Tor circuits take longer to build
+    let timeout = if proxy.is_some() { 30 } else { 10 };
+    match tokio::time::timeout(std::time::Duration::from_secs(timeout), connecting).await {
         Ok(Ok(stream)) => handle_connection(stream, state, addr.to_string(), true).await,
         Ok(Err(e)) => tracing::debug!("Failed to connect to {}: {}", addr, e),
         Err(_) => tracing::debug!("Connection to {} timed out", addr),
@@ -1430,6 +2375,7 @@ pub async fn broadcast_block(state: &Arc<NodeState>, block: Block) {
             let _ = state.block_tx.send(block);
             state.new_block_notify.notify_waiters();
             tracing::info!("📡 Broadcast block #{} ({})", height, hex::encode(block_hash));
+            resolve_orphans(state, block_hash).await;
         }
         Err(crate::core::chain::BlockError::DuplicateBlock) => {
             tracing::debug!("Mined block already known (race with peer), discarding");
@@ -1441,6 +2387,67 @@ pub async fn broadcast_block(state: &Arc<NodeState>, block: Block) {
     }
 }
 
+/// Buffer an orphan block. Returns true if no header resync is needed: its
+/// parent is already held as an orphan or being downloaded, or the block was
+/// refused for lacking the work its height calls for (and the peer struck),
+/// so free junk can't push real orphans out of the pool.
+async fn hold_orphan(state: &Arc<NodeState>, peer_addr: &str, hash: Hash256, block: Block) -> bool {
+    let floor = state.chain.read().await.difficulty_floor_at(block.header.height);
+    if !block.header.meets_difficulty() || block.header.difficulty_target < floor {
+        tracing::debug!("Orphan {} from {} lacks proof of work, dropped", &hex::encode(hash)[..16], peer_addr);
+        state.scoreboard.lock().await.record_offense(peer_addr, Offense::InvalidBlock);
+        return true;
+    }
+    let parent = block.header.prev_hash;
+    let mut orphans = state.orphans.lock().await;
+    orphans.insert(hash, block);
+    let parent_known = orphans.contains(&parent);
+    drop(orphans);
+    parent_known || state.downloads.lock().await.is_pending(&parent)
+}
+
+/// Connect buffered orphans that descend from `parent`. The first chunk runs
+/// inline; anything deeper continues in a background task that releases the
+/// chain lock between chunks.
+pub async fn resolve_orphans(state: &Arc<NodeState>, parent: Hash256) {
+    let mut queue = std::collections::VecDeque::from([parent]);
+    if resolve_orphan_chunk(state, &mut queue).await == 0 || queue.is_empty() {
+        return;
+    }
+    let state = state.clone();
+    tokio::spawn(async move {
+        loop {
+            tokio::task::yield_now().await;
+            if resolve_orphan_chunk(&state, &mut queue).await == 0 || queue.is_empty() { break; }
+        }
+    });
+}
+
+/// One bounded pass of orphan resolution under a single chain write lock
+async fn resolve_orphan_chunk(state: &Arc<NodeState>, queue: &mut std::collections::VecDeque<Hash256>) -> usize {
+    let mut accepted = Vec::new();
+    let attempted = {
+        let mut orphans = state.orphans.lock().await;
+        if orphans.is_empty() { return 0; }
+        let mut chain = state.chain.write().await;
+        orphans.resolve(queue, MAX_ORPHANS_RESOLVED_PER_BLOCK, |hash, block| {
+            match chain.add_block(block.clone()) {
+                Ok(_) => { accepted.push(block); true }
+                Err(e) => { tracing::debug!("Orphan {} rejected: {}", &hex::encode(hash)[..16], e); false }
+            }
+        })
+    };
+    if !accepted.is_empty() {
+        let mut mempool = state.mempool.lock().await;
+        for block in &accepted { mempool.remove_confirmed(block); }
+        drop(mempool);
+        tracing::info!("🧩 Connected {} orphan block(s)", accepted.len());
+        for block in accepted { let _ = state.block_tx.send(block); }
+        state.new_block_notify.notify_waiters();
+    }
+    attempted
+}
+
 pub async fn get_node_info(state: &Arc<NodeState>) -> (u64, Hash256, usize, usize) {
     let chain = state.chain.read().await;
     let h = chain.height; let t = chain.tip; let u = chain.utxo_set.len();
@@ -1476,18 +2483,60 @@ mod tests {
     fn test_ban_system() {
         let mut sb = PeerScoreboard::new();
         assert!(!sb.is_banned("1.2.3.4:9333"));
-        // 5 strikes needed
+        // BAN_THRESHOLD strikes needed
         sb.record_offense("1.2.3.4:9333", Offense::InvalidTransaction); // 1
         assert!(!sb.is_banned("1.2.3.4:9333"));
-        sb.record_offense("1.2.3.4:9333", Offense::InvalidTransaction); // 2
-        sb.record_offense("1.2.3.4:9333", Offense::InvalidBlock);       // 4
-        assert!(!sb.is_banned("1.2.3.4:9333"));
-        sb.record_offense("1.2.3.4:9333", Offense::InvalidTransaction); // 5 -> banned
+        for _ in 0..(BAN_THRESHOLD - 2) / 2 {
+            sb.record_offense("1.2.3.4:9333", Offense::InvalidBlock);   // +2
+        }
+        assert!(!sb.is_banned("1.2.3.4:9333"));                          // threshold - 1
+        sb.record_offense("1.2.3.4:9333", Offense::InvalidTransaction); // -> banned
         assert!(sb.is_banned("1.2.3.4:9333"));
         // Different port same IP also banned
         assert!(sb.is_banned("1.2.3.4:1234"));
     }
 
+    #[test]
+    fn test_manual_ban_and_clear() {
+        let mut sb = PeerScoreboard::new();
+        sb.ban("10.0.0.5", 60, "spam");
+        assert!(sb.is_banned("10.0.0.5:9333"));
+        assert_eq!(sb.active_bans()[0].1.reason, "spam");
+        sb.record_offense("10.0.0.6:1", Offense::InvalidBlock);
+        sb.clear_bans();
+        assert!(!sb.is_banned("10.0.0.5:9333"));
+        assert_eq!(sb.strikes_of("10.0.0.6:1"), 0);
+    }
+
+    #[test]
+    fn test_scoreboard_persists_active_bans_only() {
+        let dir = std::env::temp_dir().join(format!("eqf_banlist_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let dir = dir.to_str().unwrap();
+
+        let mut sb = PeerScoreboard::new();
+        sb.record_offense("1.2.3.4:9333", Offense::InvalidTransaction);
+        for _ in 0..BAN_THRESHOLD { sb.record_offense("5.6.7.8:9333", Offense::InvalidTransaction); }
+        // A ban that has already run out
+        sb.bans.insert("9.9.9.9".into(), BanEntry { banned_until: 1, reason: "old".into() });
+        sb.strikes.insert("9.9.9.9".into(), BAN_THRESHOLD);
+        sb.save(dir);
+
+        let loaded = PeerScoreboard::load(dir);
+        assert!(loaded.is_banned("5.6.7.8:1"));
+        assert!(!loaded.is_banned("9.9.9.9:1"));
+        assert_eq!(loaded.strikes_of("9.9.9.9:1"), 0);
+        assert_eq!(loaded.strikes_of("1.2.3.4:1"), 1);
+        let bans = loaded.active_bans();
+        assert_eq!(bans.len(), 1);
+        assert_eq!(bans[0].0, "5.6.7.8");
+        assert_eq!(bans[0].1.reason, "InvalidTransaction");
+
+        // Nothing saved yet: empty scoreboard
+        let _ = std::fs::remove_dir_all(dir);
+        assert_eq!(PeerScoreboard::load(dir).ban_count(), 0);
+    }
+
     #[test]
     fn test_mempool_fee_sorting() {
         let mut mp = Mempool::new(100);
@@ -1503,9 +2552,443 @@ mod tests {
         assert_eq!(pending[0].hash(), tx2.hash());
     }
 
+    #[test]
+    fn test_select_for_block_beats_greedy() {
+        // Transaction of exactly `size` bytes
+        let tx = |tag: u8, size: usize| {
+            let mut tx = Transaction {
+                version: 1, inputs: vec![], outputs: vec![TxOutput { amount: 100, pubkey_hash: [tag; 32], script_pubkey: vec![] }], lock_time: 0,
+            };
+            let base = tx.size();
+            tx.outputs[0].script_pubkey = vec![0; size - base];
+            assert_eq!(tx.size(), size);
+            tx
+        };
+        // One large high-rate tx crowds out two mid-rate ones that fill the block exactly
+        let crafted = [(tx(0, 600), 700u64), (tx(1, 500), 550), (tx(2, 500), 550), (tx(3, 100), 50)];
+        let mut mp = Mempool::new(100);
+        for (t, fee) in &crafted { mp.add_with_fee(t.clone(), *fee); }
+        let fee_of = |t: &Transaction| crafted.iter().find(|(c, _)| c.hash() == t.hash()).unwrap().1;
+
+        // Plain greedy: fee-rate order, stop at the first misfit
+        let (mut greedy_fees, mut size) = (0, 0);
+        for t in mp.get_pending() {
+            if size + t.size() > 1000 { break; }
+            size += t.size();
+            greedy_fees += fee_of(&t);
+        }
+        assert_eq!(greedy_fees, 700);
+
+        let chosen = mp.select_for_block(1000, 100);
+        assert!(chosen.iter().map(|t| t.size()).sum::<usize>() <= 1000);
+        assert_eq!(chosen.iter().map(fee_of).sum::<u64>(), 1100);
+
+        // Count-limited: best rate first, then backfill by rate
+        let chosen = mp.select_for_block(1000, 2);
+        assert_eq!(chosen.iter().map(fee_of).sum::<u64>(), 750);
+
+        // A child of a mempool transaction waits for its parent to confirm
+        let mut child = tx(4, 120);
+        child.inputs.push(TxInput {
+            previous_output: OutPoint { txid: crate::crypto::txid::txid_v1(&crafted[0].0), vout: 0 },
+            signature: vec![], pubkey: vec![], script_sig: vec![], sequence: 0,
+        });
+        mp.add_with_fee(child.clone(), 10_000);
+        assert!(!mp.select_for_block(10_000, 100).iter().any(|t| t.hash() == child.hash()));
+    }
+
+    #[test]
+    fn test_full_mempool_evicts_lowest_fee_rate() {
+        let tx = |i: u8| Transaction {
+            version: 1, inputs: vec![], outputs: vec![TxOutput { amount: 100, pubkey_hash: [i; 32], script_pubkey: vec![] }], lock_time: 0,
+        };
+        let txid = |i: u8| crate::crypto::txid::txid_v1(&tx(i));
+        let mut mp = Mempool::new(2);
+        assert_eq!(mp.add_with_fee(tx(0), 100), MempoolAdd::Added);
+        assert_eq!(mp.add_with_fee(tx(1), 300), MempoolAdd::Added);
+        assert_eq!(mp.add_with_fee(tx(1), 300), MempoolAdd::Duplicate);
+
+        // Full: equal to the cheapest rate isn't enough
+        assert_eq!(mp.add_with_fee(tx(2), 100), MempoolAdd::FullFeeTooLow);
+        assert_eq!(mp.len(), 2);
+        assert!(!mp.contains(&txid(2)));
+
+        // Strictly higher displaces the cheapest entry
+        assert_eq!(mp.add_with_fee(tx(3), 200), MempoolAdd::AddedByEvicting(txid(0)));
+        assert_eq!(mp.len(), 2);
+        assert!(mp.contains(&txid(3)) && mp.contains(&txid(1)) && !mp.contains(&txid(0)));
+    }
+
+    #[test]
+    fn test_mempool_parent_child() {
+        let spend = |prev: Option<&Transaction>, tag: u8| Transaction {
+            version: 1,
+            inputs: prev.map(|p| TxInput {
+                previous_output: OutPoint { txid: crate::crypto::txid::txid_v1(p), vout: 0 },
+                signature: vec![], pubkey: vec![], script_sig: vec![], sequence: 0,
+            }).into_iter().collect(),
+            outputs: vec![TxOutput { amount: 100, pubkey_hash: [tag; 32], script_pubkey: vec![] }],
+            lock_time: 0,
+        };
+        let parent = spend(None, 1);
+        let child = spend(Some(&parent), 2);
+        let grandchild = spend(Some(&child), 3);
+        let [p, c, g] = [&parent, &child, &grandchild].map(crate::crypto::txid::txid_v1);
+
+        let mut mp = Mempool::new(100);
+        for tx in [&parent, &child, &grandchild] { mp.add_with_fee(tx.clone(), 1000); }
+        let info = mp.entry_info(&c).unwrap();
+        assert_eq!((info.ancestors, info.descendants), (vec![p], vec![g]));
+        assert_eq!((info.fee, info.size), (1000, child.size()));
+        assert!(info.time > 0);
+        assert_eq!(mp.ancestors(&g), vec![c, p]);
+        assert_eq!(mp.descendants(&p), vec![c, g]);
+        assert!(mp.entry_info(&[0xAB; 32]).is_none());
+
+        // Confirming the parent leaves the child with no pending ancestors
+        let block = |txs: Vec<Transaction>| Block {
+            header: BlockHeader { version: 1, prev_hash: NULL_HASH, merkle_root: NULL_HASH, timestamp: 0, difficulty_target: 0, nonce: 0, height: 1 },
+            transactions: txs,
+        };
+        mp.remove_confirmed(&block(vec![parent.clone()]));
+        assert!(mp.ancestors(&c).is_empty());
+        assert_eq!(mp.descendants(&c), vec![g]);
+
+        // A conflicting spend of the child's input takes the grandchild with it
+        let mut conflict = spend(Some(&parent), 9);
+        conflict.outputs[0].amount = 50;
+        mp.remove_confirmed(&block(vec![conflict]));
+        assert!(mp.is_empty());
+        assert!(mp.spenders.is_empty());
+    }
+
+    #[test]
+    fn test_full_mempool_evicts_descendants() {
+        let parent = Transaction {
+            version: 1, inputs: vec![], outputs: vec![TxOutput { amount: 100, pubkey_hash: [1; 32], script_pubkey: vec![] }], lock_time: 0,
+        };
+        let child = Transaction {
+            version: 1,
+            inputs: vec![TxInput {
+                previous_output: OutPoint { txid: crate::crypto::txid::txid_v1(&parent), vout: 0 },
+                signature: vec![], pubkey: vec![], script_sig: vec![], sequence: 0,
+            }],
+            outputs: vec![TxOutput { amount: 90, pubkey_hash: [2; 32], script_pubkey: vec![] }],
+            lock_time: 0,
+        };
+        let other = |tag: u8| Transaction {
+            version: 1, inputs: vec![], outputs: vec![TxOutput { amount: 100, pubkey_hash: [tag; 32], script_pubkey: vec![] }], lock_time: 0,
+        };
+        let mut mp = Mempool::new(3);
+        mp.add_with_fee(parent.clone(), 10);
+        mp.add_with_fee(child, 5000);
+        mp.add_with_fee(other(3), 1000);
+        // Evicting the cheap parent can't leave its child behind
+        let parent_id = crate::crypto::txid::txid_v1(&parent);
+        assert_eq!(mp.add_with_fee(other(4), 1000), MempoolAdd::AddedByEvicting(parent_id));
+        assert_eq!(mp.len(), 2);
+        assert_eq!(mp.spenders.len(), 0);
+    }
+
+    #[test]
+    fn test_mempool_sync_reply() {
+        let mut mp = Mempool::new(100);
+        let txs: Vec<Transaction> = (0..3u8).map(|i| Transaction {
+            version: 1, inputs: vec![], outputs: vec![TxOutput { amount: 100, pubkey_hash: [i; 32], script_pubkey: vec![] }], lock_time: 0,
+        }).collect();
+        for (tx, fee) in txs.iter().zip([100, 5000, 1000]) { mp.add_with_fee(tx.clone(), fee); }
+        let txid = |i: usize| crate::crypto::txid::txid_v1(&txs[i]);
+        assert_eq!(mp.pending_txids(10), vec![txid(1), txid(2), txid(0)]);
+        assert_eq!(mp.pending_txids(1), vec![txid(1)]);
+
+        // One answer per interval
+        let mut limiter = PeerRateLimiter::new(RateLimits::default());
+        assert!(limiter.allow_mempool_reply());
+        assert!(!limiter.allow_mempool_reply());
+        limiter.last_mempool_reply = Some(0);
+        assert!(limiter.allow_mempool_reply());
+    }
+
+    #[test]
+    fn test_remove_confirmed_uses_canonical_txid() {
+        let mut mp = Mempool::new(100);
+        // No inputs, so only the txid lookup (not the conflict sweep) can evict it
+        let tx = Transaction { version: 1, inputs: vec![], outputs: vec![TxOutput { amount: 100, pubkey_hash: [0; 32], script_pubkey: vec![] }], lock_time: 0 };
+        let txid = crate::crypto::txid::txid_v1(&tx);
+        assert_ne!(txid, tx.hash());
+        assert!(mp.add_with_fee(tx.clone(), 100).is_added());
+        assert!(mp.contains(&txid));
+
+        let coinbase = Transaction::new_coinbase(1, 50, [1; 32], [0; 32], "");
+        let mut block = Block {
+            header: BlockHeader { version: 1, prev_hash: NULL_HASH, merkle_root: NULL_HASH, timestamp: 0, difficulty_target: 1, nonce: 0, height: 1 },
+            transactions: vec![coinbase, tx],
+        };
+        block.header.merkle_root = block.compute_merkle_root();
+        mp.remove_confirmed(&block);
+        assert_eq!(mp.len(), 0);
+    }
+
     #[tokio::test]
     async fn test_node_state() {
         let state = NodeState::new(9333);
         assert_eq!(state.chain.read().await.height, 0);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_peer_address_parsing() {
+        assert_eq!(split_host_port("1.2.3.4:9333"), Some(("1.2.3.4", 9333)));
+        assert_eq!(split_host_port("[::1]:9333"), Some(("::1", 9333)));
+        assert_eq!(split_host_port("Seed.Example:1"), Some(("Seed.Example", 1)));
+        assert_eq!(split_host_port("::1"), None);
+        assert_eq!(split_host_port("[::1"), None);
+        assert_eq!(split_host_port(":9333"), None);
+        assert_eq!(split_host_port("host:99999"), None);
+
+        assert_eq!(host_of("1.2.3.4:9333"), "1.2.3.4");
+        assert_eq!(host_of("1.2.3.4"), "1.2.3.4");
+        assert_eq!(host_of("[2001:db8:0::1]:9333"), "2001:db8::1");
+        assert_eq!(host_of("2001:db8::1"), "2001:db8::1");
+        assert_eq!(host_of("[::ffff:1.2.3.4]:5555"), "1.2.3.4");
+        assert_eq!(host_of("Seed.Example:9333"), "seed.example");
+
+        assert_eq!(join_host_port("::1", 9333), "[::1]:9333");
+        assert_eq!(join_host_port("1.2.3.4", 9333), "1.2.3.4:9333");
+        assert_eq!(normalize_addr("[2001:DB8:0::1]:9333").as_deref(), Some("[2001:db8::1]:9333"));
+        assert_eq!(normalize_addr("seed.example:9333").as_deref(), Some("seed.example:9333"));
+        assert_eq!(normalize_addr("garbage"), None);
+    }
+
+    #[test]
+    fn test_ipv6_bans_and_peer_dedup() {
+        let mut sb = PeerScoreboard::new();
+        sb.ban("[2001:db8::1]:9333", 60, "test");
+        assert!(sb.is_banned("[2001:db8::1]:40000"));
+        assert!(sb.is_banned("2001:db8:0:0::1"));
+        assert!(!sb.is_banned("[2001:db8::2]:9333"));
+        // Before, every IPv6 address collapsed to "[" and shared one ban
+        assert!(!sb.is_banned("[::1]:9333"));
+        sb.ban("seed.example:9333", 60, "test");
+        assert!(sb.is_banned("SEED.example:1"));
+
+        let mut peers = HashMap::new();
+        for (address, listen_address) in [("[2001:db8::5]:50123", "[2001:db8::5]:9333"), ("10.0.0.1:9333", "10.0.0.1:9333")] {
+            peers.insert(address.to_string(), PeerInfo {
+                address: address.into(), listen_address: listen_address.into(), version: PROTOCOL_VERSION,
+                best_height: 0, last_seen: 0, supports_v2: true, inbound: true, relay_txs: true, ping_ms: None,
+            });
+        }
+        assert_eq!(find_peer(&peers, "[2001:db8:0::5]:9333").map(|p| p.address.as_str()), Some("[2001:db8::5]:50123"));
+        assert!(find_peer(&peers, "[::ffff:10.0.0.1]:9333").is_some());
+        assert!(find_peer(&peers, "[2001:db8::5]:9334").is_none());
+
+        let dir = std::env::temp_dir().join(format!("eqf_anchors_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let dir_str = dir.to_str().unwrap();
+        save_anchors(dir_str, &["[2001:db8::5]:9333".into(), "[2001:db8:0::5]:9333".into(), "bogus".into()]);
+        assert_eq!(load_anchors(dir_str), vec!["[2001:db8::5]:9333".to_string()]);
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[tokio::test]
+    async fn test_added_nodes_and_disconnect() {
+        let _ = std::panic::catch_unwind(|| init_network(false));
+        let state = NodeState::new(9333);
+        // Nothing listens on port 1, so the dial fails quietly
+        state.add_node("127.0.0.1:1").await;
+        state.added_nodes.write().unwrap().insert("10.0.0.5:9333".to_string());
+        assert!(state.known_addresses.read().await.contains("127.0.0.1:1"));
+
+        // 10.0.0.5 dialed us from an ephemeral port
+        let peer = PeerInfo {
+            address: "10.0.0.5:50123".into(), listen_address: "10.0.0.5:9333".into(), version: PROTOCOL_VERSION,
+            best_height: 0, last_seen: 0, supports_v2: true, inbound: true, relay_txs: true, ping_ms: None,
+        };
+        state.peers.write().await.insert(peer.address.clone(), peer);
+        let info = state.added_node_info().await;
+        assert_eq!(info.len(), 2);
+        assert_eq!(info[0].0, "10.0.0.5:9333");
+        assert_eq!(info[0].1.as_ref().map(|p| p.address.as_str()), Some("10.0.0.5:50123"));
+        assert!(info[1].1.is_none());
+
+        let mut rx = state.disconnect_tx.subscribe();
+        assert_eq!(state.disconnect_node("10.0.0.5:9333").await.as_deref(), Some("10.0.0.5:50123"));
+        assert_eq!(rx.recv().await.unwrap(), "10.0.0.5:50123");
+        assert!(state.peers.read().await.is_empty());
+        assert_eq!(state.added_node_info().await.len(), 1);
+        assert_eq!(state.disconnect_node("10.0.0.5:9333").await, None);
+    }
+
+    #[tokio::test]
+    async fn test_rate_limiter_trips_on_flood() {
+        let _ = std::panic::catch_unwind(|| init_network(false));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        // Fake peer: ~2 MB of pings in one burst
+        let flood = tokio::spawn(async move {
+            let mut stream = TcpStream::connect(addr).await.unwrap();
+            let data = encode_message(&NetMessage::Ping(7)).repeat(100_000);
+            let _ = stream.write_all(&data).await;
+        });
+
+        let (mut stream, _) = listener.accept().await.unwrap();
+        let limits = RateLimits { max_send_rate: DEFAULT_PEER_RATE_LIMIT, max_recv_rate: 64 * 1024 };
+        let mut limiter = PeerRateLimiter::new(limits);
+        // A full-size burst is allowed once; it leaves nothing for the flood
+        limiter.record_recv(&NetMessage::Ping(0), MAX_MESSAGE_SIZE as u64);
+        assert!(!limiter.is_recv_limited());
+        let mut read = 0;
+        while !limiter.is_recv_limited() {
+            let (msg, size) = read_message_sized(&mut stream).await.unwrap();
+            assert!(matches!(msg, NetMessage::Ping(7)));
+            limiter.record_recv(&msg, size as u64);
+            read += 1;
+        }
+        assert!(read < 100_000, "limiter never tripped");
+        assert!(!limiter.is_send_limited());
+        drop(stream);
+        let _ = flood.await;
+
+        // The default cap doesn't trip on normal traffic
+        let mut relaxed = PeerRateLimiter::new(RateLimits::default());
+        relaxed.record_recv(&NetMessage::Ping(0), 1024 * 1024);
+        assert!(!relaxed.is_recv_limited());
+        let mut sb = PeerScoreboard::new();
+        for _ in 0..BAN_THRESHOLD / Offense::RateLimited.strikes() { sb.record_offense("5.6.7.8:1", Offense::RateLimited); }
+        assert!(!sb.is_banned("5.6.7.8:1"));
+        sb.record_offense("5.6.7.8:1", Offense::RateLimited);
+        assert!(sb.is_banned("5.6.7.8:1"));
+    }
+
+    #[test]
+    fn test_requested_batches_not_charged() {
+        let limits = RateLimits { max_send_rate: DEFAULT_PEER_RATE_LIMIT, max_recv_rate: 64 * 1024 };
+        let mut limiter = PeerRateLimiter::new(limits);
+        let batch = NetMessage::BlockData(vec![]);
+
+        // Full-size replies to our requests during sync, back to back
+        for _ in 0..3 {
+            limiter.expect_replies(&NetMessage::GetBlockData(vec![[1; 32]]));
+            limiter.record_recv(&batch, MAX_MESSAGE_SIZE as u64);
+            assert!(!limiter.is_recv_limited());
+        }
+        limiter.expect_replies(&NetMessage::GetData(vec![InvItem::block([2; 32]), InvItem::tx([3; 32])]));
+        limiter.record_recv(&NetMessage::Ping(0), 1024);
+        assert_eq!(limiter.solicited, 2);
+
+        // Once the requests are answered the same data is charged
+        limiter.record_recv(&batch, MAX_MESSAGE_SIZE as u64);
+        limiter.record_recv(&batch, MAX_MESSAGE_SIZE as u64);
+        assert!(!limiter.is_recv_limited());
+        limiter.record_recv(&batch, MAX_MESSAGE_SIZE as u64);
+        assert!(limiter.is_recv_limited());
+    }
+
+    #[test]
+    fn test_known_inventory_and_inv_roundtrip() {
+        let _ = std::panic::catch_unwind(|| init_network(false));
+        let mut known = KnownInventory::new();
+        let tx = InvItem::tx([1; 32]);
+        assert!(known.insert(tx));
+        assert!(!known.insert(tx), "re-announcing a known item");
+        // Same hash, different kind is a different object
+        assert!(!known.items.contains(&InvItem::block([1; 32])));
+
+        // Oldest entries are forgotten past the cap
+        for i in 0..MAX_KNOWN_INVENTORY as u32 {
+            let mut hash = [0u8; 32];
+            hash[..4].copy_from_slice(&i.to_le_bytes());
+            hash[31] = 0xFF;
+            known.insert(InvItem::block(hash));
+        }
+        assert!(!known.items.contains(&tx));
+        assert_eq!(known.items.len(), MAX_KNOWN_INVENTORY);
+
+        let msg = NetMessage::GetData(vec![InvItem::block([2; 32]), tx]);
+        let encoded = encode_message(&msg);
+        match bincode::deserialize::<NetMessage>(&encoded[HEADER_SIZE..]).unwrap() {
+            NetMessage::GetData(items) => assert_eq!(items, vec![InvItem::block([2; 32]), tx]),
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_self_connection_detected() {
+        let _ = std::panic::catch_unwind(|| init_network(false));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        // Advertise a different port so the address check alone doesn't catch it
+        let state = NodeState::new(1);
+
+        let inbound = {
+            let state = state.clone();
+            tokio::spawn(async move {
+                let (stream, peer) = listener.accept().await.unwrap();
+                handle_connection(stream, state, peer.to_string(), false).await;
+            })
+        };
+        let stream = TcpStream::connect(&addr).await.unwrap();
+        handle_connection(stream, state.clone(), addr.clone(), true).await;
+        inbound.await.unwrap();
+
+        assert!(state.peers.read().await.is_empty());
+        assert!(state.is_own_address(&addr));
+        assert!(!state.is_own_address("10.0.0.1:1234"));
+        // Loopback or unspecified IP on our own listen port
+        assert!(state.is_own_address("127.0.0.1:1"));
+        assert!(state.is_own_address("0.0.0.0:1"));
+        assert!(!state.is_own_address("127.0.0.1:2"));
+    }
+
+    #[tokio::test]
+    async fn test_orphans_need_proof_of_work() {
+        let state = NodeState::new(1);
+        let floor = state.chain.read().await.difficulty_floor_at(5);
+        let orphan = |difficulty_target| Block {
+            header: BlockHeader {
+                version: 4, prev_hash: [0xEE; 32], merkle_root: NULL_HASH,
+                timestamp: 1_700_000_000, difficulty_target, nonce: 0, height: 5,
+            },
+            transactions: vec![],
+        };
+
+        // Free to forge: below the floor for its height, or missing its own target
+        let cheap = orphan(0);
+        assert!(hold_orphan(&state, "10.0.0.1:1", cheap.header.hash(), cheap).await);
+        let mut unmined = orphan(floor);
+        while unmined.header.meets_difficulty() { unmined.header.nonce += 1; }
+        assert!(hold_orphan(&state, "10.0.0.1:1", unmined.header.hash(), unmined).await);
+        assert!(state.orphans.lock().await.is_empty());
+        assert_eq!(state.scoreboard.lock().await.strikes_of("10.0.0.1:1"), 2 * Offense::InvalidBlock.strikes());
+
+        let mut mined = orphan(floor);
+        while !mined.header.meets_difficulty() { mined.header.nonce += 1; }
+        let hash = mined.header.hash();
+        assert!(!hold_orphan(&state, "10.0.0.2:1", hash, mined).await);
+        assert!(state.orphans.lock().await.contains(&hash));
+        assert_eq!(state.scoreboard.lock().await.strikes_of("10.0.0.2:1"), 0);
+    }
+
+    #[tokio::test]
+    async fn test_dns_seed_resolution() {
+        let _ = std::panic::catch_unwind(|| init_network(false));
+        assert_eq!(dns_seed_target("dnsseed:seed.example.org"), Some(format!("seed.example.org:{}", default_port())));
+        assert_eq!(dns_seed_target("dnsseed:seed.example.org:1234"), Some("seed.example.org:1234".into()));
+        assert_eq!(dns_seed_target("dnsseed:"), None);
+        assert_eq!(dns_seed_target("1.2.3.4:9333"), None);
+
+        let state = NodeState::new(1);
+        let seeds = vec!["dnsseed:localhost:4321".to_string(), "dnsseed:".to_string()];
+        let resolved = resolve_dns_seeds(&state, &seeds).await;
+        assert!(resolved.iter().any(|a| a == "127.0.0.1:4321" || a == "[::1]:4321"), "{:?}", resolved);
+        let known = state.known_addresses.read().await;
+        assert!(resolved.iter().all(|a| known.contains(a)));
+
+        // Behind a proxy the local resolver is never asked
+        let proxied = NodeState::new(1);
+        *proxied.proxy.write().unwrap() = Some("127.0.0.1:9050".into());
+        assert!(resolve_dns_seeds(&proxied, &seeds).await.is_empty());
+        assert!(proxied.known_addresses.read().await.is_empty());
+    }
+}
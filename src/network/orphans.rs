@@ -0,0 +1,221 @@
+//! Orphan block buffer.
+//!
+//! Blocks whose parent we don't have yet are held here, keyed by parent hash,
+//! until the parent connects. Resolution is chunked: at most
+//! `MAX_ORPHANS_RESOLVED_PER_BLOCK` orphans are connected per pass so a deep
+//! orphan chain can't hold the chain write lock in one long burst. Orphans
+//! whose parent never shows up are dropped after `ORPHAN_EXPIRY`.
+
+use std::collections::{HashMap, VecDeque};
+use std::time::{Duration, Instant};
+
+use crate::core::params::MAX_ORPHAN_BLOCKS;
+use crate::core::types::*;
+
+/// How long an orphan waits for its parent before it's dropped
+pub const ORPHAN_EXPIRY: Duration = Duration::from_secs(20 * 60);
+
+struct OrphanEntry {
+    block: Block,
+    size: usize,
+    /// Insertion order, for oldest-first eviction
+    seq: u64,
+    received: Instant,
+}
+
+/// One held orphan (see `OrphanPool::list`)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OrphanInfo {
+    pub hash: Hash256,
+    pub height: u64,
+    pub prev_hash: Hash256,
+    pub size: usize,
+    pub waiting: Duration,
+}
+
+pub struct OrphanPool {
+    blocks: HashMap<Hash256, OrphanEntry>,
+    by_parent: HashMap<Hash256, Vec<Hash256>>,
+    bytes: usize,
+    max_bytes: usize,
+    next_seq: u64,
+}
+
+impl OrphanPool {
+    pub fn new(max_bytes: usize) -> Self {
+        Self { blocks: HashMap::new(), by_parent: HashMap::new(), bytes: 0, max_bytes, next_seq: 0 }
+    }
+
+    pub fn len(&self) -> usize { self.blocks.len() }
+    pub fn is_empty(&self) -> bool { self.blocks.is_empty() }
+    pub fn bytes(&self) -> usize { self.bytes }
+    pub fn contains(&self, hash: &Hash256) -> bool { self.blocks.contains_key(hash) }
+
+    /// Buffer an orphan, evicting the oldest entries to stay under the memory cap.
+    /// Returns false if it was already held or is larger than the whole pool.
+    pub fn insert(&mut self, hash: Hash256, block: Block) -> bool {
+        let size = block.size();
+        if self.blocks.contains_key(&hash) || size > self.max_bytes {
+            return false;
+        }
+        while self.bytes + size > self.max_bytes || self.blocks.len() >= MAX_ORPHAN_BLOCKS {
+            let Some(oldest) = self.blocks.iter().min_by_key(|(_, e)| e.seq).map(|(h, _)| *h) else { break };
+            self.remove(&oldest);
+        }
+        self.by_parent.entry(block.header.prev_hash).or_default().push(hash);
+        self.blocks.insert(hash, OrphanEntry { block, size, seq: self.next_seq, received: Instant::now() });
+        self.next_seq += 1;
+        self.bytes += size;
+        true
+    }
+
+    pub fn remove(&mut self, hash: &Hash256) -> Option<Block> {
+        let entry = self.blocks.remove(hash)?;
+        self.bytes -= entry.size;
+        let parent = entry.block.header.prev_hash;
+        if let Some(children) = self.by_parent.get_mut(&parent) {
+            children.retain(|h| h != hash);
+            if children.is_empty() { self.by_parent.remove(&parent); }
+        }
+        Some(entry.block)
+    }
+
+    /// Every held orphan, lowest height first
+    pub fn list(&self, now: Instant) -> Vec<OrphanInfo> {
+        let mut list: Vec<OrphanInfo> = self.blocks.iter().map(|(hash, e)| OrphanInfo {
+            hash: *hash, height: e.block.header.height, prev_hash: e.block.header.prev_hash,
+            size: e.size, waiting: now.saturating_duration_since(e.received),
+        }).collect();
+        list.sort_by_key(|o| (o.height, o.hash));
+        list
+    }
+
+    /// Drop orphans held for `ORPHAN_EXPIRY` or longer as of `now`; returns how many
+    pub fn expire(&mut self, now: Instant) -> usize {
+        let stale: Vec<Hash256> = self.blocks.iter()
+            .filter(|(_, e)| now.saturating_duration_since(e.received) >= ORPHAN_EXPIRY)
+            .map(|(h, _)| *h)
+            .collect();
+        for hash in &stale {
+            self.remove(hash);
+        }
+        stale.len()
+    }
+
+    /// Connect orphans descending from the hashes in `queue`, at most `limit` per call.
+    ///
+    /// `connect` attempts to add a block to the chain and reports success; an accepted
+    /// block's hash is queued so its own orphans follow. Returns how many orphans
+    /// were attempted. Work left over stays in `queue` for the next pass.
+    pub fn resolve(&mut self, queue: &mut VecDeque<Hash256>, limit: usize, mut connect: impl FnMut(Hash256, Block) -> bool) -> usize {
+        let mut attempted = 0;
+        while attempted < limit {
+            let Some(parent) = queue.front().copied() else { break };
+            let Some(child) = self.by_parent.get(&parent).and_then(|c| c.first().copied()) else {
+                queue.pop_front();
+                continue;
+            };
+            let block = self.remove(&child).expect("indexed orphan present");
+            attempted += 1;
+            if connect(child, block) {
+                queue.push_back(child);
+            }
+        }
+        attempted
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Linked chain of bare blocks (no PoW — `hold_orphan` checks it before insert)
+    fn orphan_chain(len: usize) -> Vec<(Hash256, Block)> {
+        let mut prev = [0xEE; 32];
+        (0..len).map(|i| {
+            let header = BlockHeader {
+                version: 4, prev_hash: prev, merkle_root: NULL_HASH,
+                timestamp: 1_700_000_000 + i as u64, difficulty_target: 0, nonce: i as u64, height: i as u64 + 1,
+            };
+            let hash = header.id_hash();
+            prev = hash;
+            (hash, Block { header, transactions: vec![] })
+        }).collect()
+    }
+
+    #[test]
+    fn test_deep_orphan_chain_resolves_in_chunks() {
+        let chain = orphan_chain(40);
+        let mut pool = OrphanPool::new(usize::MAX);
+        for (hash, block) in chain.iter().rev() {
+            assert!(pool.insert(*hash, block.clone()));
+        }
+        assert_eq!(pool.len(), 40);
+
+        let mut connected = Vec::new();
+        let mut queue = VecDeque::from([[0xEE; 32]]);
+        let mut passes = Vec::new();
+        while !queue.is_empty() {
+            let n = pool.resolve(&mut queue, 16, |hash, _| { connected.push(hash); true });
+            if n == 0 { break; }
+            passes.push(n);
+        }
+
+        assert_eq!(passes, vec![16, 16, 8]);
+        assert!(pool.is_empty());
+        let expected: Vec<Hash256> = chain.iter().map(|(h, _)| *h).collect();
+        assert_eq!(connected, expected);
+    }
+
+    #[test]
+    fn test_rejected_orphan_stops_its_branch() {
+        let chain = orphan_chain(5);
+        let mut pool = OrphanPool::new(usize::MAX);
+        for (hash, block) in &chain { pool.insert(*hash, block.clone()); }
+
+        // Reject the third block: its descendants never get queued
+        let bad = chain[2].0;
+        let mut queue = VecDeque::from([[0xEE; 32]]);
+        let n = pool.resolve(&mut queue, 16, |hash, _| hash != bad);
+        assert_eq!(n, 3);
+        assert!(queue.is_empty());
+        assert_eq!(pool.len(), 2);
+    }
+
+    #[test]
+    fn test_memory_cap_evicts_oldest() {
+        let chain = orphan_chain(4);
+        let size = chain[0].1.size();
+        let mut pool = OrphanPool::new(size * 3);
+        for (hash, block) in &chain { assert!(pool.insert(*hash, block.clone())); }
+        assert_eq!(pool.len(), 3);
+        assert!(pool.bytes() <= size * 3);
+        assert!(!pool.contains(&chain[0].0));
+        assert!(pool.contains(&chain[3].0));
+        assert!(!pool.insert(chain[3].0, chain[3].1.clone()));
+
+        // The count cap holds even when the byte cap has room
+        let many = orphan_chain(MAX_ORPHAN_BLOCKS + 5);
+        let mut pool = OrphanPool::new(usize::MAX);
+        for (hash, block) in &many { pool.insert(*hash, block.clone()); }
+        assert_eq!(pool.len(), MAX_ORPHAN_BLOCKS);
+        assert!(!pool.contains(&many[0].0));
+    }
+
+    #[test]
+    fn test_orphans_expire() {
+        let chain = orphan_chain(3);
+        let mut pool = OrphanPool::new(usize::MAX);
+        for (hash, block) in &chain { pool.insert(*hash, block.clone()); }
+        let now = Instant::now();
+        let listed = pool.list(now + Duration::from_secs(5));
+        assert_eq!(listed.iter().map(|o| o.height).collect::<Vec<_>>(), vec![1, 2, 3]);
+        assert_eq!(listed[1].prev_hash, chain[0].0);
+        assert!(listed.iter().all(|o| o.waiting >= Duration::from_secs(5)));
+        assert_eq!(pool.expire(now), 0);
+        assert_eq!(pool.expire(now + ORPHAN_EXPIRY), 3);
+        assert!(pool.is_empty());
+        assert_eq!(pool.bytes(), 0);
+        assert!(pool.by_parent.is_empty());
+    }
+}
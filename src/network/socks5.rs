@@ -0,0 +1,149 @@
+//! Outbound connections through a SOCKS5 proxy (RFC 1928), e.g. Tor.
+//!
+//! Only the no-authentication method and the CONNECT command are used.
+//! Hostnames are passed to the proxy unresolved (address type 3), which is
+//! what lets Tor reach `.onion` services and keeps DNS lookups off the
+//! local resolver.
+
+use std::io::{Error, ErrorKind};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+
+use super::split_host_port;
+
+const SOCKS_VERSION: u8 = 5;
+const METHOD_NO_AUTH: u8 = 0;
+const CMD_CONNECT: u8 = 1;
+const ATYP_IPV4: u8 = 1;
+const ATYP_DOMAIN: u8 = 3;
+const ATYP_IPV6: u8 = 4;
+
+/// True if `addr` ("host:port" or a bare host) names a Tor onion service
+pub fn is_onion(addr: &str) -> bool {
+    let host = addr.rsplit_once(':').map_or(addr, |(host, _)| host);
+    host.len() > ".onion".len() && host.to_ascii_lowercase().ends_with(".onion")
+}
+
+/// CONNECT request for `host:port`
+pub fn connect_request(host: &str, port: u16) -> std::io::Result<Vec<u8>> {
+    let mut req = vec![SOCKS_VERSION, CMD_CONNECT, 0];
+    match host.parse::<std::net::IpAddr>() {
+        Ok(std::net::IpAddr::V4(ip)) => { req.push(ATYP_IPV4); req.extend_from_slice(&ip.octets()); }
+        Ok(std::net::IpAddr::V6(ip)) => { req.push(ATYP_IPV6); req.extend_from_slice(&ip.octets()); }
+        Err(_) => {
+            if host.len() > u8::MAX as usize {
+                return Err(Error::new(ErrorKind::InvalidInput, "hostname too long for SOCKS5"));
+            }
+            req.push(ATYP_DOMAIN);
+            req.push(host.len() as u8);
+            req.extend_from_slice(host.as_bytes());
+        }
+    }
+    req.extend_from_slice(&port.to_be_bytes());
+    Ok(req)
+}
+
+fn reply_error(code: u8) -> Error {
+    let reason = match code {
+        1 => "general failure",
+        2 => "connection not allowed by ruleset",
+        3 => "network unreachable",
+        4 => "host unreachable",
+        5 => "connection refused",
+        6 => "TTL expired",
+        7 => "command not supported",
+        8 => "address type not supported",
+        _ => "unknown error",
+    };
+    Error::other(format!("SOCKS5 proxy: {} ({})", reason, code))
+}
+
+/// Open a TCP stream to `target` ("host:port") through the proxy at `proxy`
+pub async fn connect(proxy: &str, target: &str) -> std::io::Result<TcpStream> {
+    let (host, port) = split_host_port(target)
+        .ok_or_else(|| Error::new(ErrorKind::InvalidInput, "target must be host:port"))?;
+    let request = connect_request(host, port)?;
+
+    let mut stream = TcpStream::connect(proxy).await?;
+    stream.write_all(&[SOCKS_VERSION, 1, METHOD_NO_AUTH]).await?;
+    let mut choice = [0u8; 2];
+    stream.read_exact(&mut choice).await?;
+    if choice != [SOCKS_VERSION, METHOD_NO_AUTH] {
+        return Err(Error::other("SOCKS5 proxy requires authentication"));
+    }
+
+    stream.write_all(&request).await?;
+    let mut head = [0u8; 4];
+    stream.read_exact(&mut head).await?;
+    if head[0] != SOCKS_VERSION {
+        return Err(Error::new(ErrorKind::InvalidData, "not a SOCKS5 proxy"));
+    }
+    if head[1] != 0 {
+        return Err(reply_error(head[1]));
+    }
+    // Skip the bound address the proxy reports; peers never need it
+    let addr_len = match head[3] {
+        ATYP_IPV4 => 4,
+        ATYP_IPV6 => 16,
+        ATYP_DOMAIN => stream.read_u8().await? as usize,
+        _ => return Err(Error::new(ErrorKind::InvalidData, "bad SOCKS5 address type")),
+    };
+    let mut bound = vec![0u8; addr_len + 2];
+    stream.read_exact(&mut bound).await?;
+    Ok(stream)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// One-shot proxy: checks the CONNECT request, answers with `reply_code`,
+    /// then writes "hi" down the tunnel
+    async fn mock_proxy(expected: Vec<u8>, reply_code: u8) -> String {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        tokio::spawn(async move {
+            let (mut s, _) = listener.accept().await.unwrap();
+            let mut greeting = [0u8; 3];
+            s.read_exact(&mut greeting).await.unwrap();
+            assert_eq!(greeting, [5, 1, 0]);
+            s.write_all(&[5, 0]).await.unwrap();
+            let mut req = vec![0u8; expected.len()];
+            s.read_exact(&mut req).await.unwrap();
+            assert_eq!(req, expected);
+            s.write_all(&[5, reply_code, 0, ATYP_IPV4, 127, 0, 0, 1, 0x24, 0x75]).await.unwrap();
+            s.write_all(b"hi").await.unwrap();
+        });
+        addr
+    }
+
+    #[test]
+    fn test_addresses_and_requests() {
+        assert!(is_onion("abcdefghijklmnop.onion:9333"));
+        assert!(is_onion("ABCDEF.ONION"));
+        assert!(!is_onion(".onion:9333"));
+        assert!(!is_onion("10.0.0.1:9333"));
+
+        assert_eq!(connect_request("10.0.0.1", 9333).unwrap(), vec![5, 1, 0, 1, 10, 0, 0, 1, 0x24, 0x75]);
+        assert_eq!(connect_request("::1", 1).unwrap()[3..4], [ATYP_IPV6]);
+        let domain = connect_request("ab.onion", 80).unwrap();
+        assert_eq!(domain[..5], [5, 1, 0, ATYP_DOMAIN, 8]);
+        assert_eq!(&domain[5..13], b"ab.onion");
+        assert!(connect_request(&"a".repeat(256), 1).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_connect_through_proxy() {
+        let target = "expyuzz4wqqyqhjn.onion:9333";
+        let proxy = mock_proxy(connect_request("expyuzz4wqqyqhjn.onion", 9333).unwrap(), 0).await;
+        let mut stream = connect(&proxy, target).await.unwrap();
+        let mut buf = [0u8; 2];
+        stream.read_exact(&mut buf).await.unwrap();
+        assert_eq!(&buf, b"hi");
+
+        // Host unreachable
+        let proxy = mock_proxy(connect_request("expyuzz4wqqyqhjn.onion", 9333).unwrap(), 4).await;
+        let err = connect(&proxy, target).await.unwrap_err();
+        assert!(err.to_string().contains("host unreachable"), "{}", err);
+    }
+}
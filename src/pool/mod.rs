@@ -6,7 +6,9 @@
 //! The pool server creates block templates with the pool operator's address,
 //! distributes headers to workers, validates shares, and submits found blocks.
 //! Pool miners need only this protocol + the PoW function — no blockchain.
+//! Workers are paid PPLNS from matured pool coinbases (see `payout`).
 
+pub mod payout;
 pub mod pool_miner;
 
 use serde::{Deserialize, Serialize};
@@ -36,6 +38,8 @@ pub enum PoolMessage {
     Register {
         worker_name: String,
         payout_address: String,
+        /// Token from an earlier `Registered`, to resume that session
+        session_token: Option<String>,
     },
     /// Submit a nonce that meets share_target.
     SubmitShare {
@@ -44,6 +48,13 @@ pub enum PoolMessage {
     },
 
     // ── Server → Miner ──
+    /// Registration accepted. Send `session_token` with the next `Register`
+    /// to pick up where this connection left off.
+    Registered {
+        session_token: String,
+        /// True if an earlier session was restored
+        resumed: bool,
+    },
     /// New mining job.
     Job {
         job_id: u64,
@@ -53,6 +64,9 @@ pub enum PoolMessage {
         share_target: u32,
         /// Actual network difficulty — hash meeting this is a real block.
         network_target: u32,
+        /// This worker's nonce range: the high 32 bits of every nonce it
+        /// submits (see `nonce_in_range`).
+        extranonce: u32,
     },
     /// Current job cancelled — stop mining, wait for next Job.
     JobCancel,
@@ -82,6 +96,17 @@ pub enum PoolMessage {
 
 const MAX_POOL_MSG: usize = 1024 * 1024;
 
+/// Workers get disjoint nonce ranges: the high 32 bits are the assigned
+/// extranonce, the low 32 bits are the worker's to search.
+pub fn nonce_in_range(nonce: u64, extranonce: u32) -> bool {
+    (nonce >> 32) as u32 == extranonce
+}
+
+/// Nonce at `offset` within an extranonce's range
+pub fn range_nonce(extranonce: u32, offset: u32) -> u64 {
+    ((extranonce as u64) << 32) | offset as u64
+}
+
 pub async fn read_pool_msg(stream: &mut TcpStream) -> Result<PoolMessage, String> {
     let mut len_buf = [0u8; 4];
     stream.read_exact(&mut len_buf).await.map_err(|e| format!("read len: {}", e))?;
@@ -114,34 +139,66 @@ struct Worker {
     shares_accepted: u64,
     shares_submitted: u64,
     connected_at: u64,
-    recent_share_times: Vec<u64>,
+    /// (time, share target) of recent accepted shares
+    recent_shares: Vec<(u64, u32)>,
+    /// Vardiff: this worker's share target
+    share_target: u32,
+    /// When `share_target` was last reconsidered
+    last_retarget: u64,
+    shares_since_retarget: u64,
+    /// High 32 bits of every nonce this worker may submit
+    extranonce: u32,
+    session_token: String,
 }
 
 impl Worker {
-    fn hashrate_estimate(&self, share_diff: u32) -> f64 {
-        let buf = &self.recent_share_times;
+    fn hashrate_estimate(&self) -> f64 {
+        let buf = &self.recent_shares;
         if buf.len() < 2 {
             return 0.0;
         }
         let window = buf.len().min(30);
         let recent = &buf[buf.len() - window..];
-        let elapsed = recent.last().unwrap().saturating_sub(*recent.first().unwrap());
+        let elapsed = recent.last().unwrap().0.saturating_sub(recent.first().unwrap().0);
         if elapsed == 0 {
             return 0.0;
         }
-        let hashes_per_share = (1u64 << share_diff.min(63)) as f64;
-        (window as f64 * hashes_per_share) / elapsed as f64
+        // Shares differ in difficulty once vardiff kicks in
+        let hashes: f64 = recent.iter().map(|(_, target)| (1u64 << (*target).min(63)) as f64).sum();
+        hashes / elapsed as f64
     }
 
     fn record_share(&mut self, now: u64) {
         self.shares_accepted += 1;
-        self.recent_share_times.push(now);
-        if self.recent_share_times.len() > 120 {
-            self.recent_share_times.drain(0..self.recent_share_times.len() - 120);
+        self.shares_since_retarget += 1;
+        self.recent_shares.push((now, self.share_target));
+        if self.recent_shares.len() > 120 {
+            self.recent_shares.drain(0..self.recent_shares.len() - 120);
         }
     }
 }
 
+// ─── Vardiff ────────────────────────────────────────────────────────
+
+/// Vardiff aims for one share per worker this often
+pub const VARDIFF_TARGET_SECS: u64 = 10;
+/// Minimum time between a worker's retargets
+pub const VARDIFF_RETARGET_SECS: u64 = 30;
+
+/// Share target for a worker that found `shares` shares in `elapsed` seconds at
+/// `current`. Each bit doubles the work per share, so the step is log2 of how
+/// far the share rate is off, rounded. A worker with no shares is treated as
+/// having one, so slow miners step down over a few retargets.
+pub fn vardiff_retarget(current: u32, shares: u64, elapsed: u64, min: u32, max: u32) -> u32 {
+    let max = max.max(min);
+    if elapsed == 0 {
+        return current.clamp(min, max);
+    }
+    let rate_ratio = (VARDIFF_TARGET_SECS * shares.max(1)) as f64 / elapsed as f64;
+    let step = rate_ratio.log2().round() as i64;
+    (current as i64 + step).clamp(min as i64, max as i64) as u32
+}
+
 // ─── Pool Configuration ─────────────────────────────────────────────
 
 #[derive(Debug, Clone)]
@@ -153,8 +210,23 @@ pub struct PoolConfig {
     pub pplns_window: usize,
     pub pool_payout_hash: Hash256,
     pub pool_name: String,
+    /// Smallest balance paid out; less rolls forward to the next payout
+    pub min_payout: u64,
+    /// Solo mode: shares are blocks, and each worker mines a coinbase paying
+    /// its own address. No PPLNS window, vardiff or payouts.
+    pub solo: bool,
+    /// Where the payout ledger is saved; None keeps it in memory only
+    pub data_dir: Option<String>,
 }
 
+/// Default `PoolConfig::min_payout` (0.1 EQF)
+pub const DEFAULT_MIN_PAYOUT: u64 = COIN / 10;
+
+/// Defaults are a shared (PPLNS) pool: shares at `share_diff_offset` bits below
+/// the network target, found blocks pay `pool_payout_hash`, and workers are
+/// paid from matured coinbases. With `solo` set instead, the share target is
+/// the network target, the pool address and fee are never used, and a block
+/// pays whichever worker found it, directly in the coinbase.
 impl Default for PoolConfig {
     fn default() -> Self {
         Self {
@@ -165,13 +237,17 @@ impl Default for PoolConfig {
             pplns_window: 10_000,
             pool_payout_hash: [0xFE; 32],
             pool_name: String::from("EquiForge-Pool"),
+            min_payout: DEFAULT_MIN_PAYOUT,
+            solo: false,
+            data_dir: None,
         }
     }
 }
 
 // ─── Pool State ─────────────────────────────────────────────────────
 
-struct PoolState {
+/// Everything the pool server tracks; shared with the RPC server for stats
+pub struct PoolState {
     config: PoolConfig,
     workers: HashMap<String, Worker>,
     job_id: u64,
@@ -179,14 +255,22 @@ struct PoolState {
     /// we clone this, set the nonce, and submit. No re-creation needed.
     current_template: Option<Block>,
     network_target: u32,
+    /// Starting share target for newly connected workers
     share_target: u32,
+    /// Safety dedup only: extranonce ranges already keep workers apart
     used_nonces: std::collections::HashSet<u64>,
-    pplns_window: Vec<(String, Hash256)>,
+    /// Next extranonce handed to a registering worker
+    next_extranonce: u32,
+    /// Disconnected workers by session token, with when they dropped
+    idle_sessions: HashMap<String, (Worker, u64)>,
+    pplns_window: Vec<payout::WindowShare>,
     blocks_found: u64,
+    payouts: payout::PayoutLedger,
 }
 
 impl PoolState {
     fn new(config: PoolConfig) -> Self {
+        let payouts = config.data_dir.as_deref().map(payout::PayoutLedger::load).unwrap_or_default();
         Self {
             config,
             workers: HashMap::new(),
@@ -195,12 +279,25 @@ impl PoolState {
             network_target: 0,
             share_target: 0,
             used_nonces: std::collections::HashSet::new(),
+            next_extranonce: 0,
+            idle_sessions: HashMap::new(),
             pplns_window: Vec::new(),
             blocks_found: 0,
+            payouts,
+        }
+    }
+
+    /// Persist the payout ledger after it changes
+    fn save_payouts(&self) {
+        if let Some(dir) = &self.config.data_dir {
+            self.payouts.save(dir);
         }
     }
 
     fn compute_share_target(&self, network_diff: u32) -> u32 {
+        if self.config.solo {
+            return network_diff;
+        }
         network_diff
             .saturating_sub(self.config.share_diff_offset)
             .max(self.config.min_share_difficulty)
@@ -208,24 +305,182 @@ impl PoolState {
 
     fn record_share(&mut self, worker_name: &str, payout_hash: Hash256) {
         let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
-        if let Some(w) = self.workers.get_mut(worker_name) {
-            w.record_share(now);
+        let Some(w) = self.workers.get_mut(worker_name) else { return };
+        w.record_share(now);
+        if self.config.solo {
+            return;
         }
-        self.pplns_window.push((worker_name.to_string(), payout_hash));
+        let share_target = w.share_target;
+        self.pplns_window.push(payout::WindowShare { worker: worker_name.to_string(), payout_hash, share_target });
         if self.pplns_window.len() > self.config.pplns_window {
             let excess = self.pplns_window.len() - self.config.pplns_window;
             self.pplns_window.drain(0..excess);
         }
     }
 
+    /// Share target a worker mines at (the pool default until it registers)
+    fn worker_share_target(&self, worker_name: &str) -> u32 {
+        if self.config.solo {
+            return self.network_target;
+        }
+        self.workers.get(worker_name).map(|w| w.share_target).unwrap_or(self.share_target)
+    }
+
+    /// The block a worker is mining. In solo mode that's the shared template
+    /// with the coinbase's miner output redirected to the worker.
+    fn worker_template(&self, worker_name: &str) -> Option<Block> {
+        let mut block = self.current_template.clone()?;
+        if self.config.solo {
+            let payout_hash = self.workers.get(worker_name)?.payout_hash;
+            block.transactions[0].outputs[0].pubkey_hash = payout_hash;
+            block.header.merkle_root = block.compute_merkle_root();
+        }
+        Some(block)
+    }
+
+    /// Reconsider a worker's share target once `VARDIFF_RETARGET_SECS` have
+    /// passed. Returns the new target if it changed.
+    fn retarget_worker(&mut self, worker_name: &str, now: u64) -> Option<u32> {
+        if self.config.solo {
+            return None;
+        }
+        let (min, max) = (self.config.min_share_difficulty, self.network_target);
+        let w = self.workers.get_mut(worker_name)?;
+        let elapsed = now.saturating_sub(w.last_retarget);
+        if elapsed < VARDIFF_RETARGET_SECS {
+            return None;
+        }
+        let new_target = vardiff_retarget(w.share_target, w.shares_since_retarget, elapsed, min, max);
+        w.last_retarget = now;
+        w.shares_since_retarget = 0;
+        if new_target == w.share_target {
+            return None;
+        }
+        tracing::debug!("⛏️  Vardiff: '{}' share target {} → {}", worker_name, w.share_target, new_target);
+        w.share_target = new_target;
+        Some(new_target)
+    }
+
     fn pool_hashrate(&self) -> f64 {
         self.workers
             .values()
-            .map(|w| w.hashrate_estimate(self.share_target))
+            .map(|w| w.hashrate_estimate())
             .sum()
     }
 }
 
+// ─── Sessions ───────────────────────────────────────────────────────
+//
+// A worker that drops and reconnects within `SESSION_IDLE_SECS`, presenting
+// the token it got at registration, gets its `Worker` back: share counters,
+// hashrate history, vardiff target and extranonce (so shares for the current
+// job stay valid). Its PPLNS shares never leave the window either way.
+
+/// How long a disconnected worker's session can be resumed
+pub const SESSION_IDLE_SECS: u64 = 600;
+
+impl PoolState {
+    /// Register a worker, resuming its idle session if `token` names one with
+    /// the same worker name and payout address. Returns (token, resumed).
+    fn open_session(&mut self, name: &str, payout_hash: Hash256, token: Option<&str>, now: u64) -> (String, bool) {
+        self.idle_sessions.retain(|_, (_, since)| now.saturating_sub(*since) < SESSION_IDLE_SECS);
+
+        let resumable = token
+            .and_then(|t| self.idle_sessions.get(t))
+            .is_some_and(|(w, _)| w.name == name && w.payout_hash == payout_hash);
+        if let (true, Some(token)) = (resumable, token) {
+            let (worker, _) = self.idle_sessions.remove(token).expect("checked above");
+            self.workers.insert(name.to_string(), worker);
+            return (token.to_string(), true);
+        }
+
+        let session_token = hex::encode(rand::random::<[u8; 16]>());
+        let extranonce = self.next_extranonce;
+        self.next_extranonce = self.next_extranonce.wrapping_add(1);
+        self.workers.insert(name.to_string(), Worker {
+            name: name.to_string(),
+            payout_hash,
+            shares_accepted: 0,
+            shares_submitted: 0,
+            connected_at: now,
+            recent_shares: Vec::new(),
+            share_target: self.share_target,
+            last_retarget: now,
+            shares_since_retarget: 0,
+            extranonce,
+            session_token: session_token.clone(),
+        });
+        (session_token, false)
+    }
+
+    /// Park a disconnecting worker so it can resume
+    fn close_session(&mut self, name: &str, now: u64) {
+        if let Some(worker) = self.workers.remove(name) {
+            self.idle_sessions.insert(worker.session_token.clone(), (worker, now));
+        }
+    }
+}
+
+// ─── Stats ──────────────────────────────────────────────────────────
+
+/// Pool-wide figures for `getpoolstats`
+#[derive(Debug, Clone, Serialize)]
+pub struct PoolSummary {
+    pub pool_name: String,
+    pub connected_miners: usize,
+    pub pool_hashrate: f64,
+    pub blocks_found: u64,
+    pub job_id: u64,
+    pub network_difficulty: u32,
+    /// Starting share difficulty; vardiff moves each worker from here
+    pub share_difficulty: u32,
+    pub fee_percent: f64,
+    pub pplns_shares: usize,
+}
+
+/// One connected worker for `getpoolworkers`
+#[derive(Debug, Clone, Serialize)]
+pub struct WorkerSummary {
+    pub name: String,
+    pub payout_hash: String,
+    pub shares_accepted: u64,
+    pub shares_submitted: u64,
+    pub hashrate: f64,
+    pub share_difficulty: u32,
+    pub connected_secs: u64,
+}
+
+impl PoolState {
+    pub fn summary(&self) -> PoolSummary {
+        PoolSummary {
+            pool_name: self.config.pool_name.clone(),
+            connected_miners: self.workers.len(),
+            pool_hashrate: self.pool_hashrate(),
+            blocks_found: self.blocks_found,
+            job_id: self.job_id,
+            network_difficulty: self.network_target,
+            share_difficulty: self.share_target,
+            fee_percent: self.config.fee_percent,
+            pplns_shares: self.pplns_window.len(),
+        }
+    }
+
+    /// Connected workers sorted by name; `now` is unix seconds
+    pub fn worker_summaries(&self, now: u64) -> Vec<WorkerSummary> {
+        let mut workers: Vec<WorkerSummary> = self.workers.values().map(|w| WorkerSummary {
+            name: w.name.clone(),
+            payout_hash: hex::encode(w.payout_hash),
+            shares_accepted: w.shares_accepted,
+            shares_submitted: w.shares_submitted,
+            hashrate: w.hashrate_estimate(),
+            share_difficulty: w.share_target,
+            connected_secs: now.saturating_sub(w.connected_at),
+        }).collect();
+        workers.sort_by(|a, b| a.name.cmp(&b.name));
+        workers
+    }
+}
+
 // ─── Pool Server Entry Point ────────────────────────────────────────
 
 pub async fn start_pool_server(
@@ -235,17 +490,22 @@ pub async fn start_pool_server(
     let addr = format!("0.0.0.0:{}", config.port);
     let listener = TcpListener::bind(&addr).await?;
     tracing::info!("⛏️  Pool server on port {}", config.port);
-    tracing::info!(
-        "    Fee: {}%  |  Share offset: -{} bits  |  PPLNS window: {}",
-        config.fee_percent, config.share_diff_offset, config.pplns_window
-    );
+    if config.solo {
+        tracing::info!("    Solo mode: shares are blocks, each paying its finder");
+    } else {
+        tracing::info!(
+            "    Fee: {}%  |  Share offset: -{} bits  |  PPLNS window: {}",
+            config.fee_percent, config.share_diff_offset, config.pplns_window
+        );
+    }
 
     let pool = Arc::new(RwLock::new(PoolState::new(config)));
+    *node_state.pool.lock().await = Some(pool.clone());
 
     // Create initial job template
     refresh_template(&node_state, &pool).await;
 
-    // Job updater — watches for new blocks
+    // Job updater — watches for new blocks, paying out anything that matured
     {
         let ns = node_state.clone();
         let p = pool.clone();
@@ -253,6 +513,7 @@ pub async fn start_pool_server(
             loop {
                 ns.new_block_notify.notified().await;
                 refresh_template(&ns, &p).await;
+                process_payouts(&ns, &p).await;
             }
         });
     }
@@ -298,16 +559,20 @@ pub async fn start_pool_server(
 async fn refresh_template(node_state: &Arc<NodeState>, pool: &Arc<RwLock<PoolState>>) {
     let chain = node_state.chain.read().await;
     let mp = node_state.mempool.lock().await;
-    let pending = mp.get_pending();
+    let pending = mp.select_for_block(MAX_BLOCK_SIZE, MAX_TXS_PER_BLOCK - 1);
     drop(mp);
 
     let network_diff = chain.next_difficulty();
     let pool_hash = pool.read().await.config.pool_payout_hash;
     let miner_cfg = miner::MinerConfig {
         miner_pubkey_hash: pool_hash,
-        community_fund_hash: [0xCF; 32],
+        community_fund_hash: community_fund_hash(),
         threads: 1,
         miner_tag: format!("pool:{}", pool.read().await.config.pool_name),
+        payout_targets: Vec::new(),
+        version_bits: 0,
+        min_fee_rate: 0.0,
+        max_txs: MAX_TXS_PER_BLOCK - 1,
     };
     let template = miner::create_block_template(&chain, &pending, &miner_cfg);
     let height = template.header.height;
@@ -317,6 +582,10 @@ async fn refresh_template(node_state: &Arc<NodeState>, pool: &Arc<RwLock<PoolSta
     ps.job_id += 1;
     ps.network_target = network_diff;
     ps.share_target = ps.compute_share_target(network_diff);
+    let min_share = ps.config.min_share_difficulty;
+    for w in ps.workers.values_mut() {
+        w.share_target = w.share_target.clamp(min_share, network_diff.max(min_share));
+    }
     ps.current_template = Some(template);
     ps.used_nonces.clear();
 
@@ -326,15 +595,71 @@ async fn refresh_template(node_state: &Arc<NodeState>, pool: &Arc<RwLock<PoolSta
     );
 }
 
-fn make_job_msg(ps: &PoolState) -> Option<PoolMessage> {
-    ps.current_template.as_ref().map(|tpl| PoolMessage::Job {
+fn make_job_msg(ps: &PoolState, worker_name: &str) -> Option<PoolMessage> {
+    let extranonce = ps.workers.get(worker_name)?.extranonce;
+    ps.worker_template(worker_name).map(|tpl| PoolMessage::Job {
         job_id: ps.job_id,
-        header: tpl.header.clone(),
-        share_target: ps.share_target,
+        header: tpl.header,
+        share_target: ps.worker_share_target(worker_name),
         network_target: ps.network_target,
+        extranonce,
     })
 }
 
+// ─── Payouts ────────────────────────────────────────────────────────
+
+/// Credit matured pool blocks and broadcast a payout transaction if any
+/// worker's balance is due. Signed with the node wallet's key for
+/// `pool_payout_hash`.
+async fn process_payouts(node_state: &Arc<NodeState>, pool: &Arc<RwLock<PoolState>>) {
+    {
+        let chain = node_state.chain.read().await;
+        let mut ps = pool.write().await;
+        ps.payouts.mature(&chain.utxo_set, chain.height);
+        ps.save_payouts();
+    }
+
+    // The wallet lock is never held together with the chain lock
+    let built = {
+        let wallet = node_state.wallet.lock().await;
+        let Some(wallet) = wallet.as_ref() else { return };
+        let mut ps = pool.write().await;
+        let (operator, min_payout) = (ps.config.pool_payout_hash, ps.config.min_payout);
+        let built = ps.payouts.build_payout(wallet, operator, min_payout);
+        ps.save_payouts();
+        built
+    };
+    let payout = match built {
+        Ok(Some(payout)) => payout,
+        Ok(None) => return,
+        Err(e) => {
+            tracing::warn!("⛏️  Pool payout failed: {}", e);
+            return;
+        }
+    };
+
+    let accepted = {
+        let chain = node_state.chain.read().await;
+        node_state.mempool.lock().await.validate_and_add(payout.tx.clone(), &chain)
+    };
+    match accepted {
+        Ok(txid) => {
+            let total: u64 = payout.payouts.iter().map(|(_, a)| a).sum();
+            tracing::info!(
+                "💸 Pool payout {}: {} worker(s), {:.8} EQF",
+                &hex::encode(txid)[..16], payout.payouts.len(), total as f64 / COIN as f64
+            );
+            let _ = node_state.tx_tx.send(payout.tx);
+        }
+        Err(e) => {
+            tracing::warn!("⛏️  Pool payout rejected: {} — balances kept for the next block", e);
+            let mut ps = pool.write().await;
+            ps.payouts.restore(payout);
+            ps.save_payouts();
+        }
+    }
+}
+
 // ─── Per-Worker Handler ─────────────────────────────────────────────
 
 async fn handle_worker(
@@ -346,7 +671,7 @@ async fn handle_worker(
     let _ = stream.set_nodelay(true);
 
     // ── Registration ──
-    let (name, payout_hash) = match tokio::time::timeout(
+    let (name, payout_hash, session_token) = match tokio::time::timeout(
         std::time::Duration::from_secs(10),
         read_pool_msg(&mut stream),
     )
@@ -355,6 +680,7 @@ async fn handle_worker(
         Ok(Ok(PoolMessage::Register {
             worker_name,
             payout_address,
+            session_token,
         })) => match hex::decode(&payout_address) {
             Ok(bytes) if bytes.len() == 32 => {
                 let mut h = [0u8; 32];
@@ -365,7 +691,7 @@ async fn handle_worker(
                     peer,
                     &payout_address[..16]
                 );
-                (worker_name, h)
+                (worker_name, h, session_token)
             }
             _ => {
                 let _ = write_pool_msg(
@@ -383,30 +709,23 @@ async fn handle_worker(
         }
     };
 
-    // Add worker
+    // Add (or resume) the worker
     {
         let now = SystemTime::now()
             .duration_since(UNIX_EPOCH)
             .unwrap()
             .as_secs();
-        let mut ps = pool.write().await;
-        ps.workers.insert(
-            name.clone(),
-            Worker {
-                name: name.clone(),
-                payout_hash,
-                shares_accepted: 0,
-                shares_submitted: 0,
-                connected_at: now,
-                recent_share_times: Vec::new(),
-            },
-        );
+        let (session_token, resumed) = pool.write().await.open_session(&name, payout_hash, session_token.as_deref(), now);
+        if resumed {
+            tracing::info!("⛏️  Worker '{}' resumed its session", name);
+        }
+        let _ = write_pool_msg(&mut stream, &PoolMessage::Registered { session_token, resumed }).await;
     }
 
     // Send initial job
     {
         let ps = pool.read().await;
-        if let Some(job) = make_job_msg(&ps) {
+        if let Some(job) = make_job_msg(&ps, &name) {
             let _ = write_pool_msg(&mut stream, &job).await;
         }
     }
@@ -417,12 +736,15 @@ async fn handle_worker(
     // ── Main loop ──
     loop {
         tokio::select! {
-            // New block → cancel + send fresh job
+            // New block → cancel + send fresh job. Also where a worker too slow
+            // to submit shares gets its difficulty lowered.
             _ = block_rx.recv() => {
                 tokio::time::sleep(std::time::Duration::from_millis(50)).await;
                 let _ = write_pool_msg(&mut stream, &PoolMessage::JobCancel).await;
-                let ps = pool.read().await;
-                if let Some(job) = make_job_msg(&ps) {
+                let mut ps = pool.write().await;
+                let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
+                ps.retarget_worker(&name, now);
+                if let Some(job) = make_job_msg(&ps, &name) {
                     let _ = write_pool_msg(&mut stream, &job).await;
                 }
             }
@@ -453,7 +775,8 @@ async fn handle_worker(
         }
     }
 
-    pool.write().await.workers.remove(&name);
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
+    pool.write().await.close_session(&name, now);
     tracing::info!("⛏️  Worker '{}' disconnected", name);
 }
 
@@ -469,16 +792,17 @@ async fn process_share(
     pool: &Arc<RwLock<PoolState>>,
 ) {
     // Take a snapshot of what we need under a read lock
-    let (header, share_target, network_target, current_job_id) = {
+    let (header, share_target, network_target, current_job_id, extranonce) = {
         let ps = pool.read().await;
-        match ps.current_template {
-            Some(ref tpl) => (
-                tpl.header.clone(),
-                ps.share_target,
+        match (ps.worker_template(worker_name), ps.workers.get(worker_name)) {
+            (Some(tpl), Some(w)) => (
+                tpl.header,
+                ps.worker_share_target(worker_name),
                 ps.network_target,
                 ps.job_id,
+                w.extranonce,
             ),
-            None => {
+            _ => {
                 let _ = write_pool_msg(
                     stream,
                     &PoolMessage::ShareRejected {
@@ -507,6 +831,18 @@ async fn process_share(
         return;
     }
 
+    // Someone else's nonce range?
+    if !nonce_in_range(nonce, extranonce) {
+        let _ = write_pool_msg(
+            stream,
+            &PoolMessage::ShareRejected {
+                reason: format!("nonce {:#x} outside assigned range (extranonce {})", nonce, extranonce),
+            },
+        )
+        .await;
+        return;
+    }
+
     // Duplicate nonce?
     {
         let mut ps = pool.write().await;
@@ -554,15 +890,15 @@ async fn process_share(
     }
 
     // ── Valid share ──
-    let (accepted, hashrate) = {
+    let (accepted, hashrate, retarget_job) = {
         let mut ps = pool.write().await;
         ps.record_share(worker_name, payout_hash);
         let w = ps.workers.get(worker_name);
         let acc = w.map(|w| w.shares_accepted).unwrap_or(0);
-        let hr = w
-            .map(|w| w.hashrate_estimate(ps.share_target))
-            .unwrap_or(0.0);
-        (acc, hr)
+        let hr = w.map(|w| w.hashrate_estimate()).unwrap_or(0.0);
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
+        let job = ps.retarget_worker(worker_name, now).and_then(|_| make_job_msg(&ps, worker_name));
+        (acc, hr, job)
     };
 
     let _ = write_pool_msg(
@@ -579,6 +915,11 @@ async fn process_share(
         worker_name, nonce, zeros, accepted
     );
 
+    // Difficulty changed: same job, new share target
+    if let Some(job) = retarget_job {
+        let _ = write_pool_msg(stream, &job).await;
+    }
+
     // ── Check if it's a REAL BLOCK ──
     if zeros >= network_target {
         tracing::info!(
@@ -590,8 +931,7 @@ async fn process_share(
 
         let block = {
             let ps = pool.read().await;
-            ps.current_template.as_ref().map(|tpl| {
-                let mut block = tpl.clone();
+            ps.worker_template(worker_name).map(|mut block| {
                 block.header.nonce = nonce;
                 block
             })
@@ -600,10 +940,16 @@ async fn process_share(
         if let Some(block) = block {
             let block_hash = block.header.hash();
             if block_hash == hash {
-                network::broadcast_block(node_state, block).await;
+                network::broadcast_block(node_state, block.clone()).await;
 
                 let mut ps = pool.write().await;
                 ps.blocks_found += 1;
+                if !ps.config.solo {
+                    let fee_percent = ps.config.fee_percent;
+                    let PoolState { payouts, pplns_window, .. } = &mut *ps;
+                    payouts.record_block(&block, pplns_window, fee_percent);
+                    ps.save_payouts();
+                }
                 tracing::info!(
                     "🎉 Pool block #{} submitted! Lifetime total: {}",
                     check.height,
@@ -626,4 +972,115 @@ async fn process_share(
             }
         }
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_vardiff_retarget() {
+        // On target: one share per VARDIFF_TARGET_SECS keeps the difficulty
+        assert_eq!(vardiff_retarget(12, 3, 30, 4, 20), 12);
+        // 8x too many shares: three bits harder
+        assert_eq!(vardiff_retarget(12, 24, 30, 4, 20), 15);
+        // No shares for two minutes: easier, but never below the minimum
+        assert_eq!(vardiff_retarget(12, 0, 120, 4, 20), 8);
+        assert_eq!(vardiff_retarget(5, 0, 600, 4, 20), 4);
+        // Never above the network target
+        assert_eq!(vardiff_retarget(19, 1000, 30, 4, 20), 20);
+        assert_eq!(vardiff_retarget(12, 5, 0, 4, 20), 12);
+    }
+
+    #[test]
+    fn test_worker_retarget_and_summaries() {
+        let mut ps = PoolState::new(PoolConfig::default());
+        ps.network_target = 24;
+        ps.share_target = 20;
+        ps.open_session("w", [1; 32], None, 1000);
+        assert_eq!(ps.retarget_worker("w", 1000 + VARDIFF_RETARGET_SECS - 1), None);
+        assert_eq!(ps.retarget_worker("w", 1000 + 4 * VARDIFF_RETARGET_SECS), Some(16));
+        assert_eq!(ps.worker_share_target("w"), 16);
+        assert_eq!(ps.worker_share_target("unknown"), 20);
+
+        let summary = ps.summary();
+        assert_eq!((summary.connected_miners, summary.network_difficulty, summary.share_difficulty), (1, 24, 20));
+        let workers = ps.worker_summaries(1060);
+        assert_eq!(workers.len(), 1);
+        assert_eq!((workers[0].share_difficulty, workers[0].connected_secs), (16, 60));
+        assert_eq!(workers[0].payout_hash, hex::encode([1u8; 32]));
+    }
+
+    #[test]
+    fn test_session_resume_and_expiry() {
+        let mut ps = PoolState::new(PoolConfig::default());
+        ps.share_target = 10;
+        let (token, resumed) = ps.open_session("w", [1; 32], None, 100);
+        assert!(!resumed);
+        ps.record_share("w", [1; 32]);
+        ps.workers.get_mut("w").unwrap().share_target = 14;
+        let extranonce = ps.workers["w"].extranonce;
+        ps.close_session("w", 200);
+        assert!(ps.workers.is_empty());
+
+        // Wrong payout address or unknown token: a fresh worker
+        let (other, resumed) = ps.open_session("w", [2; 32], Some(&token), 210);
+        assert!(!resumed && other != token);
+        assert_eq!(ps.workers["w"].shares_accepted, 0);
+        ps.close_session("w", 210);
+
+        let (again, resumed) = ps.open_session("w", [1; 32], Some(&token), 220);
+        assert!(resumed);
+        assert_eq!(again, token);
+        let w = &ps.workers["w"];
+        assert_eq!((w.shares_accepted, w.share_target, w.extranonce, w.connected_at), (1, 14, extranonce, 100));
+        assert_eq!(ps.pplns_window.len(), 1);
+
+        // Too long idle: the session is gone
+        ps.close_session("w", 300);
+        let (_, resumed) = ps.open_session("w", [1; 32], Some(&token), 300 + SESSION_IDLE_SECS);
+        assert!(!resumed);
+    }
+
+    #[test]
+    fn test_extranonce_ranges_are_disjoint() {
+        assert!(nonce_in_range(range_nonce(3, 0), 3));
+        assert!(nonce_in_range(range_nonce(3, u32::MAX), 3));
+        assert!(!nonce_in_range(range_nonce(4, 0), 3));
+        assert_eq!(range_nonce(3, u32::MAX) + 1, range_nonce(4, 0));
+    }
+
+    #[test]
+    fn test_solo_template_pays_worker() {
+        let config = PoolConfig { solo: true, ..PoolConfig::default() };
+        let mut ps = PoolState::new(config.clone());
+        ps.network_target = 18;
+        ps.share_target = ps.compute_share_target(18);
+        assert_eq!(ps.share_target, 18);
+
+        let coinbase = Transaction::new_coinbase(5, 50 * COIN, config.pool_payout_hash, COMMUNITY_FUND_HASH, "pool");
+        let mut template = Block {
+            header: BlockHeader {
+                version: 4, prev_hash: NULL_HASH, merkle_root: NULL_HASH,
+                timestamp: 0, difficulty_target: 18, nonce: 0, height: 5,
+            },
+            transactions: vec![coinbase],
+        };
+        template.header.merkle_root = template.compute_merkle_root();
+        ps.current_template = Some(template.clone());
+        ps.open_session("w", [7; 32], None, 0);
+
+        let mine = ps.worker_template("w").unwrap();
+        assert_eq!(mine.transactions[0].outputs[0].pubkey_hash, [7; 32]);
+        assert_eq!(mine.header.merkle_root, mine.compute_merkle_root());
+        assert_ne!(mine.header.merkle_root, template.header.merkle_root);
+        assert_eq!(ps.worker_share_target("w"), 18);
+        assert_eq!(ps.retarget_worker("w", 1_000_000), None);
+
+        ps.record_share("w", [7; 32]);
+        assert!(ps.pplns_window.is_empty());
+
+        // Shared pool: everyone mines the pool's coinbase
+        ps.config.solo = false;
+        assert_eq!(ps.worker_template("w").unwrap().header.merkle_root, template.header.merkle_root);
+    }
+}
@@ -0,0 +1,438 @@
+//! PPLNS payouts.
+//!
+//! When a pool block is found, its miner output is split across the shares in
+//! the PPLNS window at that moment, after the pool fee. Those credits wait for
+//! the coinbase to mature (`COINBASE_MATURITY`); then one transaction spends
+//! the matured coinbase outputs and pays every worker whose balance reached
+//! `min_payout`. Smaller balances roll forward to a later payout: what they
+//! are owed, plus a fee to pay them, goes back to the pool in a carry output
+//! that joins the payout funds once it confirms. Only the fee and rounding dust return to the
+//! operator as change.
+//!
+//! A reward whose coinbase output is gone or belongs to another height when it
+//! matures (block orphaned, or a coinbase txid collision overwrote it) is
+//! dropped without crediting anyone. The ledger is saved to `pool_ledger.json`
+//! in the data directory so balances survive a restart.
+
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+
+use crate::core::chain::{UtxoEntry, UtxoSet};
+use crate::core::fees::FALLBACK_FEE_RATE;
+use crate::core::params::*;
+use crate::core::script;
+use crate::core::types::*;
+use crate::wallet::Wallet;
+
+/// One accepted share in the PPLNS window
+#[derive(Debug, Clone)]
+pub struct WindowShare {
+    pub worker: String,
+    pub payout_hash: Hash256,
+    /// Leading zero bits the share had to meet (vardiff makes this per worker)
+    pub share_target: u32,
+}
+
+/// Split `amount` across the window in proportion to each payout address's
+/// share work (a share at target t counts 2^t), after taking `fee_percent`.
+/// Amounts round down; the remainder stays with the pool. Sorted by payout hash.
+pub fn pplns_split(window: &[WindowShare], amount: u64, fee_percent: f64) -> Vec<(Hash256, u64)> {
+    let Some(easiest) = window.iter().map(|s| s.share_target).min() else { return Vec::new() };
+    let fee = (amount as f64 * fee_percent.clamp(0.0, 100.0) / 100.0).ceil() as u64;
+    let distributable = amount.saturating_sub(fee) as u128;
+
+    // Work relative to the easiest share, capped so the products fit in u128
+    let mut work: BTreeMap<Hash256, u128> = BTreeMap::new();
+    for share in window {
+        *work.entry(share.payout_hash).or_default() += 1u128 << (share.share_target - easiest).min(32);
+    }
+    let total: u128 = work.values().sum();
+    work.into_iter()
+        .map(|(hash, w)| (hash, (distributable * w / total) as u64))
+        .filter(|(_, credit)| *credit > 0)
+        .collect()
+}
+
+/// A found block whose coinbase hasn't matured yet
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct PendingReward {
+    height: u64,
+    outpoint: OutPoint,
+    credits: Vec<(Hash256, u64)>,
+}
+
+/// File in the data directory holding the ledger across restarts
+const LEDGER_FILE: &str = "pool_ledger.json";
+
+/// On-disk form of the ledger (JSON map keys must be strings)
+#[derive(Default, Serialize, Deserialize)]
+struct LedgerFile {
+    pending: Vec<PendingReward>,
+    funds: Vec<(OutPoint, UtxoEntry)>,
+    carries: Vec<OutPoint>,
+    balances: Vec<(Hash256, u64)>,
+}
+
+/// Tracks rewards waiting for maturity, matured coinbase outputs not yet
+/// spent by a payout, carry outputs of broadcast payouts, and what each
+/// payout address is owed.
+#[derive(Debug, Default)]
+pub struct PayoutLedger {
+    pending: Vec<PendingReward>,
+    funds: Vec<(OutPoint, UtxoEntry)>,
+    /// Payout outputs holding rolled-forward balances, until they confirm
+    carries: Vec<OutPoint>,
+    balances: HashMap<Hash256, u64>,
+}
+
+impl PayoutLedger {
+    /// Load the saved ledger, or an empty one if there is none
+    pub fn load(data_dir: &str) -> Self {
+        let path = std::path::PathBuf::from(data_dir).join(LEDGER_FILE);
+        let file: LedgerFile = std::fs::read_to_string(&path).ok()
+            .and_then(|data| serde_json::from_str(&data).ok())
+            .unwrap_or_default();
+        Self {
+            pending: file.pending,
+            funds: file.funds,
+            carries: file.carries,
+            balances: file.balances.into_iter().collect(),
+        }
+    }
+
+    /// Write the ledger to the data directory (temp file, then rename, so a
+    /// crash mid-write keeps the previous copy)
+    pub fn save(&self, data_dir: &str) {
+        let mut balances: Vec<(Hash256, u64)> = self.balances.iter().map(|(h, b)| (*h, *b)).collect();
+        balances.sort();
+        let file = LedgerFile {
+            pending: self.pending.clone(),
+            funds: self.funds.clone(),
+            carries: self.carries.clone(),
+            balances,
+        };
+        let dir = std::path::PathBuf::from(data_dir);
+        let tmp = dir.join(format!("{}.tmp", LEDGER_FILE));
+        let Ok(json) = serde_json::to_string(&file) else { return };
+        if let Err(e) = std::fs::write(&tmp, json).and_then(|_| std::fs::rename(&tmp, dir.join(LEDGER_FILE))) {
+            tracing::warn!("⛏️  Could not save the pool ledger: {}", e);
+        }
+    }
+
+    /// Credit the window for a block the pool just found. Output 0 of the
+    /// coinbase is the pool's.
+    pub fn record_block(&mut self, block: &Block, window: &[WindowShare], fee_percent: f64) {
+        let Some(coinbase) = block.transactions.first() else { return };
+        let Some(output) = coinbase.outputs.first() else { return };
+        self.pending.push(PendingReward {
+            height: block.header.height,
+            outpoint: OutPoint { txid: crate::crypto::txid::txid_v1(coinbase), vout: 0 },
+            credits: pplns_split(window, output.amount, fee_percent),
+        });
+    }
+
+    /// Move rewards whose coinbase matured at `tip_height` into balances and
+    /// their outputs into the payout funds. Returns how many matured.
+    pub fn mature(&mut self, utxo_set: &UtxoSet, tip_height: u64) -> usize {
+        // The operator may have spent a matured coinbase through the wallet
+        self.funds.retain(|(op, e)| utxo_set.get(op).is_some_and(|u| u.height == e.height));
+        // Carry outputs become spendable funds once their payout confirms
+        let carries = std::mem::take(&mut self.carries);
+        for outpoint in carries {
+            match utxo_set.get(&outpoint) {
+                Some(entry) => self.funds.push((outpoint, entry.clone())),
+                None => self.carries.push(outpoint),
+            }
+        }
+
+        let (ready, waiting): (Vec<_>, Vec<_>) = self.pending.drain(..)
+            .partition(|r| tip_height.saturating_sub(r.height) >= COINBASE_MATURITY);
+        self.pending = waiting;
+
+        let mut matured = 0;
+        for reward in ready {
+            match utxo_set.get(&reward.outpoint) {
+                Some(entry) if entry.is_coinbase && entry.height == reward.height => {
+                    for (hash, credit) in &reward.credits {
+                        *self.balances.entry(*hash).or_default() += credit;
+                    }
+                    self.funds.push((reward.outpoint, entry.clone()));
+                    matured += 1;
+                }
+                _ => tracing::warn!("⛏️  Pool block #{} coinbase is no longer spendable — reward dropped", reward.height),
+            }
+        }
+        matured
+    }
+
+    /// What `payout_hash` is owed but hasn't been paid
+    pub fn balance(&self, payout_hash: &Hash256) -> u64 {
+        self.balances.get(payout_hash).copied().unwrap_or(0)
+    }
+
+    /// Build and sign a transaction spending the payout funds to every balance
+    /// of at least `min_payout` that fits, largest first. What the remaining
+    /// balances are owed goes to a carry output the ledger keeps, not to the
+    /// operator's change. Paid balances and the spent funds leave the ledger;
+    /// call `restore` if the transaction doesn't make it into the mempool.
+    /// None when nobody is due a payout.
+    pub fn build_payout(
+        &mut self,
+        wallet: &Wallet,
+        operator_hash: Hash256,
+        min_payout: u64,
+    ) -> Result<Option<PayoutTx>, String> {
+        let available: u64 = self.funds.iter().map(|(_, e)| e.output.amount).sum();
+        let min_payout = min_payout.max(DUST_THRESHOLD);
+
+        let mut due: Vec<(Hash256, u64)> = self.balances.iter()
+            .filter(|(_, b)| **b >= min_payout)
+            .map(|(h, b)| (*h, *b))
+            .collect();
+        due.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+
+        // A bigger payout list means a bigger tx, so settle the fee first
+        let mut fee = MIN_TX_FEE;
+        for _ in 0..8 {
+            let mut budget = available.saturating_sub(fee);
+            let payouts: Vec<(Hash256, u64)> = due.iter()
+                .filter(|(_, amount)| {
+                    let fits = *amount <= budget;
+                    if fits { budget -= amount; }
+                    fits
+                })
+                .copied()
+                .collect();
+            if payouts.is_empty() {
+                return Ok(None);
+            }
+
+            let paid: u64 = payouts.iter().map(|(_, a)| a).sum();
+            let owed = self.balances.values().sum::<u64>() - paid;
+            let (tx, carry) = self.payout_tx(wallet, operator_hash, &payouts, owed, available, fee)?;
+            let needed = ((FALLBACK_FEE_RATE * tx.size() as f64).ceil() as u64).max(MIN_TX_FEE);
+            if needed > fee {
+                fee = needed;
+                continue;
+            }
+
+            for (hash, amount) in &payouts {
+                if let Some(b) = self.balances.get_mut(hash) {
+                    *b -= amount;
+                    if *b == 0 { self.balances.remove(hash); }
+                }
+            }
+            let funds = std::mem::take(&mut self.funds);
+            let carry = carry.map(|vout| OutPoint { txid: crate::crypto::txid::txid_v1(&tx), vout });
+            self.carries.extend(carry.clone());
+            return Ok(Some(PayoutTx { tx, payouts, funds, carry }));
+        }
+        Err("could not settle on a payout fee".into())
+    }
+
+    /// Put a payout that wasn't accepted back on the books
+    pub fn restore(&mut self, payout: PayoutTx) {
+        for (hash, amount) in payout.payouts {
+            *self.balances.entry(hash).or_default() += amount;
+        }
+        self.funds.extend(payout.funds);
+        self.carries.retain(|op| Some(op) != payout.carry.as_ref());
+    }
+
+    /// The payout transaction and the index of its carry output, if any. The
+    /// carry also holds this payout's fee toward the next one, and is at
+    /// least `DUST_THRESHOLD` so it can be relayed.
+    fn payout_tx(
+        &self,
+        wallet: &Wallet,
+        operator_hash: Hash256,
+        payouts: &[(Hash256, u64)],
+        owed: u64,
+        available: u64,
+        fee: u64,
+    ) -> Result<(Transaction, Option<u32>), String> {
+        let p2pkh = |hash: Hash256, amount: u64| TxOutput { amount, pubkey_hash: hash, script_pubkey: script::script_p2pkh(&hash) };
+        let mut outputs: Vec<TxOutput> = payouts.iter().map(|(h, a)| p2pkh(*h, *a)).collect();
+        let leftover = available - fee - payouts.iter().map(|(_, a)| a).sum::<u64>();
+        let carry = if owed == 0 { 0 } else { (owed + fee).max(DUST_THRESHOLD).min(leftover) };
+        let carry_vout = (carry >= DUST_THRESHOLD).then(|| {
+            outputs.push(p2pkh(operator_hash, carry));
+            outputs.len() as u32 - 1
+        });
+        let change = leftover - carry;
+        if change >= DUST_THRESHOLD {
+            outputs.push(p2pkh(operator_hash, change));
+        }
+        let inputs = self.funds.iter().map(|(op, _)| TxInput {
+            previous_output: op.clone(),
+            signature: vec![],
+            pubkey: vec![],
+            script_sig: vec![],
+            sequence: 0xFFFFFFFF,
+        }).collect();
+        let mut tx = Transaction { version: CURRENT_TX_VERSION, inputs, outputs, lock_time: 0 };
+        let prev_outputs: Vec<TxOutput> = self.funds.iter().map(|(_, e)| e.output.clone()).collect();
+        wallet.sign_p2pkh_inputs(&mut tx, &prev_outputs)?;
+        Ok((tx, carry_vout))
+    }
+}
+
+/// A signed payout and what it took off the ledger
+#[derive(Debug)]
+pub struct PayoutTx {
+    pub tx: Transaction,
+    pub payouts: Vec<(Hash256, u64)>,
+    funds: Vec<(OutPoint, UtxoEntry)>,
+    carry: Option<OutPoint>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn window(counts: &[(u8, usize)]) -> Vec<WindowShare> {
+        counts.iter()
+            .flat_map(|&(id, n)| std::iter::repeat_n(
+                WindowShare { worker: format!("w{}", id), payout_hash: [id; 32], share_target: 8 }, n))
+            .collect()
+    }
+
+    #[test]
+    fn test_pplns_split_is_proportional() {
+        // 3:1 split of 99% of 1000
+        let split = pplns_split(&window(&[(1, 3), (2, 1)]), 1000, 1.0);
+        assert_eq!(split, vec![([1; 32], 742), ([2; 32], 247)]);
+
+        // Rounding never pays out more than the post-fee amount
+        let split = pplns_split(&window(&[(1, 1), (2, 1), (3, 1)]), 100, 0.0);
+        assert_eq!(split.iter().map(|(_, a)| a).sum::<u64>(), 99);
+        assert!(split.iter().all(|(_, a)| *a == 33));
+
+        // Workers sharing a payout address are paid together
+        let mut shared = window(&[(5, 2)]);
+        shared[1].worker = "b".into();
+        assert_eq!(pplns_split(&shared, 50 * COIN, 0.0), vec![([5; 32], 50 * COIN)]);
+
+        // A share two bits harder is worth four easy ones
+        let mut mixed = window(&[(1, 4), (2, 1)]);
+        mixed[4].share_target += 2;
+        assert_eq!(pplns_split(&mixed, 800, 0.0), vec![([1; 32], 400), ([2; 32], 400)]);
+
+        assert!(pplns_split(&[], 1000, 1.0).is_empty());
+        assert!(pplns_split(&window(&[(1, 1)]), 1000, 100.0).is_empty());
+    }
+
+    #[test]
+    fn test_payout_after_maturity_with_threshold() {
+        let wallet = Wallet::new("pool");
+        let operator = wallet.primary_pubkey_hash();
+        let reward = 50 * COIN;
+        let coinbase = Transaction::new_coinbase(10, reward, operator, COMMUNITY_FUND_HASH, "pool");
+        let block = Block {
+            header: BlockHeader {
+                version: 4, prev_hash: NULL_HASH, merkle_root: NULL_HASH,
+                timestamp: 0, difficulty_target: 1, nonce: 0, height: 10,
+            },
+            transactions: vec![coinbase.clone()],
+        };
+        let mut utxos = UtxoSet::new();
+        let outpoint = OutPoint { txid: crate::crypto::txid::txid_v1(&coinbase), vout: 0 };
+        utxos.add(outpoint.clone(), UtxoEntry { output: coinbase.outputs[0].clone(), height: 10, is_coinbase: true });
+
+        // Worker 1 has 999 shares, worker 2 a single one: below the threshold
+        let mut ledger = PayoutLedger::default();
+        ledger.record_block(&block, &window(&[(1, 999), (2, 1)]), 1.0);
+        assert_eq!(ledger.mature(&utxos, 10 + COINBASE_MATURITY - 1), 0);
+        assert_eq!(ledger.mature(&utxos, 10 + COINBASE_MATURITY), 1);
+
+        let miner_amount = coinbase.outputs[0].amount;
+        let small = ledger.balance(&[2; 32]);
+        assert_eq!(small, pplns_split(&window(&[(1, 999), (2, 1)]), miner_amount, 1.0)[1].1);
+
+        let payout = ledger.build_payout(&wallet, operator, small + 1).unwrap().unwrap();
+        assert_eq!(payout.payouts.len(), 1);
+        assert_eq!(payout.payouts[0].0, [1; 32]);
+        assert_eq!(ledger.balance(&[1; 32]), 0);
+        assert_eq!(ledger.balance(&[2; 32]), small, "small balance rolls forward");
+
+        // The rolled-forward balance and a fee stay with the pool; only the
+        // pool fee and rounding go to the operator
+        let fee = miner_amount - payout.tx.total_output();
+        let [_, carry, change] = &payout.tx.outputs[..] else { panic!("expected payout, carry and change") };
+        assert_eq!(carry.amount, small + fee);
+        assert_eq!(change.pubkey_hash, operator);
+        assert_eq!(change.amount, miner_amount - payout.payouts[0].1 - small - 2 * fee);
+        assert!(change.amount < miner_amount / 50);
+
+        // Funds are spent and the carry hasn't confirmed: nothing more to pay
+        assert!(ledger.build_payout(&wallet, operator, 1).unwrap().is_none());
+
+        // A rejected payout goes back on the books
+        ledger.restore(payout);
+        assert!(ledger.balance(&[1; 32]) > 0);
+        assert!(ledger.carries.is_empty());
+        let payout = ledger.build_payout(&wallet, operator, small + 1).unwrap().unwrap();
+
+        // Once the payout confirms, its carry pays the small balance
+        let txid = crate::crypto::txid::txid_v1(&payout.tx);
+        utxos.spend(&outpoint);
+        for (vout, output) in payout.tx.outputs.iter().enumerate() {
+            utxos.add(OutPoint { txid, vout: vout as u32 }, UtxoEntry { output: output.clone(), height: 111, is_coinbase: false });
+        }
+        ledger.mature(&utxos, 111);
+        let second = ledger.build_payout(&wallet, operator, 1).unwrap().unwrap();
+        assert_eq!(second.payouts, vec![([2; 32], small)]);
+        assert_eq!(ledger.balance(&[2; 32]), 0);
+    }
+
+    #[test]
+    fn test_ledger_survives_restart() {
+        let dir = std::env::temp_dir().join(format!("eqf_pool_ledger_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let dir = dir.to_str().unwrap();
+
+        let coinbase = Transaction::new_coinbase(3, 50 * COIN, [9; 32], COMMUNITY_FUND_HASH, "pool");
+        let block = Block {
+            header: BlockHeader {
+                version: 4, prev_hash: NULL_HASH, merkle_root: NULL_HASH,
+                timestamp: 0, difficulty_target: 1, nonce: 0, height: 3,
+            },
+            transactions: vec![coinbase.clone()],
+        };
+        let mut utxos = UtxoSet::new();
+        let outpoint = OutPoint { txid: crate::crypto::txid::txid_v1(&coinbase), vout: 0 };
+        utxos.add(outpoint, UtxoEntry { output: coinbase.outputs[0].clone(), height: 3, is_coinbase: true });
+
+        let mut ledger = PayoutLedger::default();
+        ledger.record_block(&block, &window(&[(1, 1)]), 1.0);
+        ledger.save(dir);
+        let mut loaded = PayoutLedger::load(dir);
+        assert_eq!(loaded.mature(&utxos, 3 + COINBASE_MATURITY), 1);
+        assert!(loaded.balance(&[1; 32]) > 0);
+
+        loaded.save(dir);
+        let reloaded = PayoutLedger::load(dir);
+        assert_eq!(reloaded.balance(&[1; 32]), loaded.balance(&[1; 32]));
+        assert_eq!(reloaded.funds.len(), 1);
+
+        // Nothing saved yet: empty ledger
+        std::fs::remove_dir_all(dir).unwrap();
+        assert_eq!(PayoutLedger::load(dir).balance(&[1; 32]), 0);
+    }
+
+    #[test]
+    fn test_orphaned_reward_is_dropped() {
+        let coinbase = Transaction::new_coinbase(7, 50 * COIN, [9; 32], COMMUNITY_FUND_HASH, "pool");
+        let block = Block {
+            header: BlockHeader {
+                version: 4, prev_hash: NULL_HASH, merkle_root: NULL_HASH,
+                timestamp: 0, difficulty_target: 1, nonce: 0, height: 7,
+            },
+            transactions: vec![coinbase],
+        };
+        let mut ledger = PayoutLedger::default();
+        ledger.record_block(&block, &window(&[(1, 1)]), 1.0);
+        assert_eq!(ledger.mature(&UtxoSet::new(), 7 + COINBASE_MATURITY), 0);
+        assert_eq!(ledger.balance(&[1; 32]), 0);
+    }
+}
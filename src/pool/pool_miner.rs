@@ -28,10 +28,12 @@ struct MiningJob {
     header: BlockHeader,
     share_target: u32,
     network_target: u32,
+    extranonce: u32,
 }
 
-/// Nonce offset — seeded randomly on first use so each process start
-/// explores different nonce space. Prevents "duplicate nonce" on reconnect.
+/// Where to start inside the assigned extranonce range — seeded randomly on
+/// first use and advanced per job, so a job resent with the same id (after a
+/// vardiff retarget) doesn't rescan nonces already submitted.
 static NONCE_OFFSET: AtomicU64 = AtomicU64::new(0);
 static NONCE_INITIALIZED: AtomicBool = AtomicBool::new(false);
 
@@ -52,8 +54,10 @@ fn mine_job(
     stop: Arc<AtomicBool>,
 ) -> Option<(u64, [u8; 32])> {
     init_nonce_offset();
-    let offset = NONCE_OFFSET.fetch_add(1_000_000_000, Ordering::Relaxed);
-    let nonce_range = u64::MAX / threads as u64;
+    let offset = NONCE_OFFSET.fetch_add(1_000_000_000, Ordering::Relaxed) as u32;
+    // Threads split the low 32 bits; the high 32 are the pool's extranonce
+    let nonce_range = u32::MAX / threads.clamp(1, u32::MAX as usize) as u32;
+    let extranonce = job.extranonce;
     let (tx, rx) = std::sync::mpsc::channel();
 
     let handles: Vec<_> = (0..threads)
@@ -62,14 +66,15 @@ fn mine_job(
             let share_target = job.share_target;
             let stop = stop.clone();
             let tx = tx.clone();
-            let base = (i as u64).wrapping_mul(nonce_range);
+            let base = (i as u32).wrapping_mul(nonce_range);
             let start = base.wrapping_add(offset);
 
             std::thread::spawn(move || {
-                let mut nonce = start;
-                let mut count: u64 = 0;
+                let mut low = start;
+                let mut count: u32 = 0;
                 loop {
                     if stop.load(Ordering::Relaxed) { return; }
+                    let nonce = super::range_nonce(extranonce, low);
                     header.nonce = nonce;
                     let serialized = bincode::serialize(&header).expect("serialize");
                     let hash = pow::equihash_x(&serialized);
@@ -78,7 +83,7 @@ fn mine_job(
                         stop.store(true, Ordering::Relaxed);
                         return;
                     }
-                    nonce = nonce.wrapping_add(1);
+                    low = low.wrapping_add(1);
                     count += 1;
                     if count >= nonce_range { return; }
                 }
@@ -171,6 +176,8 @@ pub async fn run_pool_miner(config: PoolMinerConfig) {
     println!();
 
     let mut consecutive_failures: u32 = 0;
+    // Session token per pool, so a dropped connection resumes its stats
+    let mut sessions: std::collections::HashMap<String, String> = std::collections::HashMap::new();
 
     loop {
         // ── Probe all pools ──
@@ -192,7 +199,7 @@ pub async fn run_pool_miner(config: PoolMinerConfig) {
         for probe in &reachable {
             println!("🔗 Connecting to {} ({}ms latency)...", probe.addr, probe.latency_ms);
 
-            match connect_and_mine(&probe.addr, &config).await {
+            match connect_and_mine(&probe.addr, &config, &mut sessions).await {
                 Ok(()) => {
                     // Clean disconnect (pool shut down gracefully).
                     // Re-probe to find another pool.
@@ -227,7 +234,11 @@ pub async fn run_pool_miner(config: PoolMinerConfig) {
 
 // ─── Single-Pool Mining Session ─────────────────────────────────────
 
-async fn connect_and_mine(pool_addr: &str, config: &PoolMinerConfig) -> Result<(), String> {
+async fn connect_and_mine(
+    pool_addr: &str,
+    config: &PoolMinerConfig,
+    sessions: &mut std::collections::HashMap<String, String>,
+) -> Result<(), String> {
     let mut stream = TcpStream::connect(pool_addr)
         .await
         .map_err(|e| format!("connect: {}", e))?;
@@ -238,7 +249,18 @@ async fn connect_and_mine(pool_addr: &str, config: &PoolMinerConfig) -> Result<(
     write_pool_msg(&mut stream, &PoolMessage::Register {
         worker_name: config.worker_name.clone(),
         payout_address: config.payout_address.clone(),
+        session_token: sessions.get(pool_addr).cloned(),
     }).await?;
+    match tokio::time::timeout(std::time::Duration::from_secs(10), read_pool_msg(&mut stream)).await {
+        Ok(Ok(PoolMessage::Registered { session_token, resumed })) => {
+            if resumed { println!("🔁 Resumed previous session"); }
+            sessions.insert(pool_addr.to_string(), session_token);
+        }
+        Ok(Ok(PoolMessage::ShareRejected { reason })) => return Err(format!("registration refused: {}", reason)),
+        Ok(Ok(_)) => return Err("unexpected reply to registration".into()),
+        Ok(Err(e)) => return Err(e),
+        Err(_) => return Err("registration timed out".into()),
+    }
 
     let mut current_job: Option<MiningJob> = None;
     let mut total_shares: u64 = 0;
@@ -253,10 +275,11 @@ async fn connect_and_mine(pool_addr: &str, config: &PoolMinerConfig) -> Result<(
             let job_id = job.job_id;
             let share_target = job.share_target;
             let network_target = job.network_target;
+            let extranonce = job.extranonce;
             let height = job.header.height;
             let threads = config.threads;
             let mining_job = MiningJob {
-                job_id, header: job.header.clone(), share_target, network_target,
+                job_id, header: job.header.clone(), share_target, network_target, extranonce,
             };
 
             let mine_handle = tokio::task::spawn_blocking(move || {
@@ -298,9 +321,9 @@ async fn connect_and_mine(pool_addr: &str, config: &PoolMinerConfig) -> Result<(
                                     current_job = None;
                                     drain_until_job(&mut stream, &mut current_job).await?;
                                 }
-                                Ok(Ok(PoolMessage::Job { job_id, header, share_target, network_target })) => {
+                                Ok(Ok(PoolMessage::Job { job_id, header, share_target, network_target, extranonce })) => {
                                     println!("📋 Job #{}: height={} diff={}/{}", job_id, header.height, share_target, network_target);
-                                    current_job = Some(MiningJob { job_id, header, share_target, network_target });
+                                    current_job = Some(MiningJob { job_id, header, share_target, network_target, extranonce });
                                 }
                                 Ok(Ok(_)) => {}
                                 Ok(Err(e)) => return Err(e),
@@ -321,9 +344,9 @@ async fn connect_and_mine(pool_addr: &str, config: &PoolMinerConfig) -> Result<(
                             current_job = None;
                             drain_until_job(&mut stream, &mut current_job).await?;
                         }
-                        Ok(PoolMessage::Job { job_id, header, share_target, network_target }) => {
+                        Ok(PoolMessage::Job { job_id, header, share_target, network_target, extranonce }) => {
                             println!("📋 Job #{}: height={} diff={}/{}", job_id, header.height, share_target, network_target);
-                            current_job = Some(MiningJob { job_id, header, share_target, network_target });
+                            current_job = Some(MiningJob { job_id, header, share_target, network_target, extranonce });
                         }
                         Ok(PoolMessage::BlockFound { height, hash, finder }) => {
                             println!("🎉 Block #{} by {}! ({}…)", height, finder, &hash[..16.min(hash.len())]);
@@ -342,9 +365,9 @@ async fn connect_and_mine(pool_addr: &str, config: &PoolMinerConfig) -> Result<(
                 std::time::Duration::from_secs(30),
                 read_pool_msg(&mut stream),
             ).await {
-                Ok(Ok(PoolMessage::Job { job_id, header, share_target, network_target })) => {
+                Ok(Ok(PoolMessage::Job { job_id, header, share_target, network_target, extranonce })) => {
                     println!("📋 Job #{}: height={} diff={}/{}", job_id, header.height, share_target, network_target);
-                    current_job = Some(MiningJob { job_id, header, share_target, network_target });
+                    current_job = Some(MiningJob { job_id, header, share_target, network_target, extranonce });
                 }
                 Ok(Ok(PoolMessage::PoolStats { connected_miners, pool_hashrate, blocks_found, current_height })) => {
                     println!("📊 Pool: {} miners, {:.1} H/s, {} blocks, height {}",
@@ -366,9 +389,9 @@ async fn drain_until_job(
         std::time::Duration::from_secs(10),
         read_pool_msg(stream),
     ).await {
-        Ok(Ok(PoolMessage::Job { job_id, header, share_target, network_target })) => {
+        Ok(Ok(PoolMessage::Job { job_id, header, share_target, network_target, extranonce })) => {
             println!("📋 Job #{}: height={} diff={}/{}", job_id, header.height, share_target, network_target);
-            *current_job = Some(MiningJob { job_id, header, share_target, network_target });
+            *current_job = Some(MiningJob { job_id, header, share_target, network_target, extranonce });
         }
         Ok(Ok(PoolMessage::JobCancel)) => { *current_job = None; }
         Ok(Ok(_)) => {}
@@ -12,7 +12,7 @@
 //!     The scratchpad is filled in 64-byte chunks using Blake3 keyed with
 //!     successive counter values. This is sequential and memory-bandwidth bound.
 //!
-//!   Phase 2 — MIX: Perform N_ITERATIONS rounds of memory-hard mixing.
+//!   Phase 2 — MIX: Perform `iterations` rounds of memory-hard mixing.
 //!     Each round:
 //!       1. Compute a mix index from the current state (data-dependent addressing)
 //!       2. Read 64 bytes from scratchpad at that index
@@ -26,11 +26,11 @@
 //!   Phase 3 — SQUEEZE: Compress the final state into a 32-byte hash
 //!     using double SHA-256 (compatible with existing difficulty system).
 //!
-//! Parameters:
-//!   SCRATCHPAD_SIZE = 4 MB (4,194,304 bytes)
-//!   N_ITERATIONS = 64
-//!   CHUNK_SIZE = 64 bytes
-//!   N_CHUNKS = 65,536
+//! Parameters (mainnet; set per network in `core::params::PowParams`):
+//!   scratchpad_size = 4 MB (4,194,304 bytes)
+//!   iterations = 64
+//!   CHUNK_SIZE = 64 bytes (fixed: a chunk is exactly the 8 x u64 mixing state)
+//!   chunks = 65,536
 //!
 //! Performance expectations (per core):
 //!   Modern CPU: ~50-200 hashes/second
@@ -38,34 +38,37 @@
 //!   ASIC: impractical (4 MB SRAM per hash unit is uneconomical)
 
 use sha2::{Digest, Sha256};
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Mutex, OnceLock};
 
-/// Scratchpad size in bytes (4 MB)
-const SCRATCHPAD_SIZE: usize = 4 * 1024 * 1024;
+use crate::core::params::{pow_params, PowParams};
 
 /// Size of each scratchpad chunk in bytes
 const CHUNK_SIZE: usize = 64;
 
-/// Number of chunks in the scratchpad
-const N_CHUNKS: usize = SCRATCHPAD_SIZE / CHUNK_SIZE;
-
-/// Number of mixing iterations
-const N_ITERATIONS: usize = 64;
-
 /// Compute the EquiHash-X proof-of-work hash for a block header.
 ///
 /// Input: serialized block header bytes (includes nonce)
 /// Output: 32-byte hash suitable for difficulty comparison
 ///
 /// This function is deterministic: same input always produces same output.
-/// Both miners and validators call this exact function.
+/// Both miners and validators call this exact function, with the active
+/// network's parameters.
 pub fn equihash_x(header_bytes: &[u8]) -> [u8; 32] {
+    equihash_x_with(header_bytes, &pow_params())
+}
+
+/// `equihash_x` with explicit scratchpad size and iteration count
+pub fn equihash_x_with(header_bytes: &[u8], params: &PowParams) -> [u8; 32] {
+    let n_chunks = params.chunks();
+
     // ─── Phase 1: FILL scratchpad ───────────────────────────────────
     //
     // Generate the scratchpad deterministically from the header.
     // We use Blake3 in keyed mode for speed (Blake3 is ~3x faster than SHA-256
     // for bulk data, which is fine since the memory-hardness comes from Phase 2).
 
-    let mut scratchpad = vec![0u8; SCRATCHPAD_SIZE];
+    let mut scratchpad = vec![0u8; n_chunks * CHUNK_SIZE];
 
     // Derive a 32-byte seed from the header
     let seed = blake3::hash(header_bytes);
@@ -73,7 +76,7 @@ pub fn equihash_x(header_bytes: &[u8]) -> [u8; 32] {
 
     // Fill scratchpad in 64-byte chunks
     // Each chunk = Blake3(seed || chunk_index)
-    for i in 0..N_CHUNKS {
+    for i in 0..n_chunks {
         let mut input = Vec::with_capacity(36);
         input.extend_from_slice(seed_bytes);
         input.extend_from_slice(&(i as u32).to_le_bytes());
@@ -109,9 +112,9 @@ pub fn equihash_x(header_bytes: &[u8]) -> [u8; 32] {
         state[4 + i] = u64::from_le_bytes(header_hash[i * 8..(i + 1) * 8].try_into().unwrap());
     }
 
-    for round in 0..N_ITERATIONS {
+    for round in 0..params.iterations {
         // 1. Compute read index from state (data-dependent addressing)
-        let read_idx = (state[0].wrapping_add(state[round % 8]) as usize) % N_CHUNKS;
+        let read_idx = (state[0].wrapping_add(state[round % 8]) as usize) % n_chunks;
         let read_offset = read_idx * CHUNK_SIZE;
 
         // 2. Read 64 bytes from scratchpad
@@ -164,7 +167,7 @@ pub fn equihash_x(header_bytes: &[u8]) -> [u8; 32] {
         }
 
         // 4. Write mixed state back to a different scratchpad location
-        let write_idx = (state[1].wrapping_mul(state[3]) as usize) % N_CHUNKS;
+        let write_idx = (state[1].wrapping_mul(state[3]) as usize) % n_chunks;
         let write_offset = write_idx * CHUNK_SIZE;
         for j in 0..8 {
             scratchpad[write_offset + j * 8..write_offset + (j + 1) * 8]
@@ -189,9 +192,198 @@ pub fn equihash_x(header_bytes: &[u8]) -> [u8; 32] {
     result
 }
 
+// ─── Verification Cache ─────────────────────────────────────────────
+//
+// Sync, reorgs and locator resyncs hash the same headers over and over. The
+// cache is keyed by the exact serialized header and only ever filled with the
+// result of a full `equihash_x` run, so a lookup can't return anything the
+// full computation wouldn't. Mining doesn't go through it: every nonce is a
+// new header and would just evict useful entries.
+
+/// Headers remembered by `equihash_x_cached`
+pub const POW_CACHE_SIZE: usize = 8192;
+
+struct PowCache {
+    hashes: HashMap<Vec<u8>, [u8; 32]>,
+    order: VecDeque<Vec<u8>>,
+    capacity: usize,
+    /// Parameters the cached hashes were computed with
+    params: Option<PowParams>,
+}
+
+impl PowCache {
+    fn new(capacity: usize) -> Self {
+        Self { hashes: HashMap::new(), order: VecDeque::new(), capacity, params: None }
+    }
+
+    /// Forget everything cached under different parameters (i.e. before
+    /// `init_network` picked the network)
+    fn use_params(&mut self, params: PowParams) {
+        if self.params != Some(params) {
+            self.hashes.clear();
+            self.order.clear();
+            self.params = Some(params);
+        }
+    }
+
+    fn get(&self, header_bytes: &[u8]) -> Option<[u8; 32]> {
+        self.hashes.get(header_bytes).copied()
+    }
+
+    /// Remember a computed hash, evicting the oldest entry when full
+    fn insert(&mut self, header_bytes: &[u8], hash: [u8; 32]) {
+        if self.hashes.insert(header_bytes.to_vec(), hash).is_some() {
+            return;
+        }
+        self.order.push_back(header_bytes.to_vec());
+        while self.order.len() > self.capacity {
+            if let Some(old) = self.order.pop_front() {
+                self.hashes.remove(&old);
+            }
+        }
+    }
+}
+
+fn pow_cache() -> &'static Mutex<PowCache> {
+    static CACHE: OnceLock<Mutex<PowCache>> = OnceLock::new();
+    CACHE.get_or_init(|| Mutex::new(PowCache::new(POW_CACHE_SIZE)))
+}
+
+/// `equihash_x` with recent results memoized, for validation.
+/// The lock isn't held while hashing, so two threads may both compute a
+/// missing entry; they get the same result.
+pub fn equihash_x_cached(header_bytes: &[u8]) -> [u8; 32] {
+    let params = pow_params();
+    let cached = {
+        let mut cache = pow_cache().lock().unwrap_or_else(|e| e.into_inner());
+        cache.use_params(params);
+        cache.get(header_bytes)
+    };
+    if let Some(hash) = cached {
+        return hash;
+    }
+    let hash = equihash_x_with(header_bytes, &params);
+    let mut cache = pow_cache().lock().unwrap_or_else(|e| e.into_inner());
+    cache.use_params(params);
+    cache.insert(header_bytes, hash);
+    hash
+}
+
+// ─── Benchmark ──────────────────────────────────────────────────────
+
+/// Outcome of `benchmark`
+#[derive(Debug, Clone)]
+pub struct BenchmarkResult {
+    /// Hashes completed by each thread
+    pub hashes_per_thread: Vec<u64>,
+    pub elapsed: std::time::Duration,
+}
+
+impl BenchmarkResult {
+    pub fn total_hashes(&self) -> u64 {
+        self.hashes_per_thread.iter().sum()
+    }
+
+    /// Aggregate hashes per second
+    pub fn hashrate(&self) -> f64 {
+        let secs = self.elapsed.as_secs_f64();
+        if secs > 0.0 { self.total_hashes() as f64 / secs } else { 0.0 }
+    }
+
+    /// Hashes per second of each thread
+    pub fn thread_hashrates(&self) -> Vec<f64> {
+        let secs = self.elapsed.as_secs_f64();
+        self.hashes_per_thread.iter().map(|&n| if secs > 0.0 { n as f64 / secs } else { 0.0 }).collect()
+    }
+}
+
+/// Hash a dummy header with incrementing nonces on `threads` threads for
+/// `duration`, the way the miner does (uncached `equihash_x`)
+pub fn benchmark(duration: std::time::Duration, threads: usize) -> BenchmarkResult {
+    use crate::core::types::{BlockHeader, NULL_HASH};
+
+    let threads = threads.max(1);
+    let start = std::time::Instant::now();
+    let hashes_per_thread = std::thread::scope(|scope| {
+        let handles: Vec<_> = (0..threads).map(|t| {
+            scope.spawn(move || {
+                let mut header = BlockHeader {
+                    version: 1, prev_hash: NULL_HASH, merkle_root: NULL_HASH,
+                    timestamp: 0, difficulty_target: 0, nonce: (t as u64) << 48, height: 0,
+                };
+                let mut hashes = 0u64;
+                while start.elapsed() < duration {
+                    std::hint::black_box(header.compute_hash());
+                    header.nonce += 1;
+                    hashes += 1;
+                }
+                hashes
+            })
+        }).collect();
+        handles.into_iter().map(|h| h.join().unwrap_or(0)).collect()
+    });
+    BenchmarkResult { hashes_per_thread, elapsed: start.elapsed() }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::core::params::{MAINNET_POW, TESTNET_POW};
+
+    #[test]
+    fn test_cache_matches_full_compute_and_is_bounded() {
+        let header = b"cached header bytes";
+        assert_eq!(equihash_x_cached(header), equihash_x(header));
+        assert_eq!(equihash_x_cached(header), equihash_x(header));
+
+        let mut cache = PowCache::new(2);
+        cache.insert(b"a", [1; 32]);
+        cache.insert(b"b", [2; 32]);
+        cache.insert(b"a", [1; 32]);
+        assert_eq!(cache.order.len(), 2);
+        cache.insert(b"c", [3; 32]);
+        assert_eq!(cache.get(b"a"), None);
+        assert_eq!(cache.get(b"b"), Some([2; 32]));
+        assert_eq!(cache.get(b"c"), Some([3; 32]));
+        assert_eq!(cache.hashes.len(), 2);
+    }
+
+    #[test]
+    fn test_mainnet_hash_unchanged() {
+        // Mainnet parameters must keep the genesis and existing block hashes
+        let hash = equihash_x_with(b"equiforge mainnet pow", &MAINNET_POW);
+        assert_eq!(hex::encode(hash), "98a2770016227ca6f033282bf71a078b1b52b4a58dcea7806459173110db6e05");
+    }
+
+    #[test]
+    fn test_small_scratchpad_deterministic_with_avalanche() {
+        let header = b"testnet header nonce=7";
+        assert_eq!(equihash_x_with(header, &TESTNET_POW), equihash_x_with(header, &TESTNET_POW));
+        assert_ne!(equihash_x_with(header, &TESTNET_POW), equihash_x_with(header, &MAINNET_POW));
+
+        // Flipping one input bit should flip about half of the output bits
+        let base = [0x5Au8; 80];
+        let base_hash = equihash_x_with(&base, &TESTNET_POW);
+        let mut total = 0u32;
+        for bit in 0..8 {
+            let mut flipped = base;
+            flipped[bit * 10] ^= 1 << bit;
+            let hash = equihash_x_with(&flipped, &TESTNET_POW);
+            total += base_hash.iter().zip(hash.iter()).map(|(a, b)| (a ^ b).count_ones()).sum::<u32>();
+        }
+        let avg = total / 8;
+        assert!((96..=160).contains(&avg), "poor avalanche: {} bits", avg);
+    }
+
+    #[test]
+    fn test_benchmark_counts_every_thread() {
+        let result = benchmark(std::time::Duration::from_millis(200), 2);
+        assert_eq!(result.hashes_per_thread.len(), 2);
+        assert!(result.hashes_per_thread.iter().all(|&n| n > 0));
+        assert!(result.elapsed >= std::time::Duration::from_millis(200));
+        let per_thread: f64 = result.thread_hashrates().iter().sum();
+        assert!((per_thread - result.hashrate()).abs() < 1e-6);
+    }
 
     #[test]
     fn test_deterministic() {
@@ -0,0 +1,125 @@
+//! Testnet faucet (`--faucet`).
+//!
+//! `GET /faucet?address=...` pays a fixed amount from the node wallet to the
+//! address. Each client IP and each address can be paid at most once per
+//! `FAUCET_COOLDOWN`, so the wallet can't be drained by one requester. IPv6
+//! clients are counted per /64, the block a single host is usually handed,
+//! and at most `FAUCET_MAX_DRIPS` are paid per cooldown across everyone.
+
+use std::collections::HashMap;
+use std::net::IpAddr;
+
+use crate::core::types::Hash256;
+
+/// Seconds an IP or address waits between drips
+pub const FAUCET_COOLDOWN: u64 = 24 * 3600;
+
+/// Drips paid across all clients within one `FAUCET_COOLDOWN`
+pub const FAUCET_MAX_DRIPS: usize = 500;
+
+/// Cooldown key for a client: the /64 prefix for IPv6, else the IP itself
+fn client_key(ip: &str) -> String {
+    match ip.parse::<IpAddr>() {
+        Ok(IpAddr::V6(v6)) if v6.to_ipv4_mapped().is_none() => {
+            let prefix = u128::from(v6) & !(u64::MAX as u128);
+            format!("{}/64", std::net::Ipv6Addr::from(prefix))
+        }
+        _ => ip.to_string(),
+    }
+}
+
+pub struct Faucet {
+    /// Base units paid per drip
+    pub amount: u64,
+    /// When each IP (IPv6 /64) / address was last paid
+    by_ip: HashMap<String, u64>,
+    by_address: HashMap<Hash256, u64>,
+}
+
+impl Faucet {
+    pub fn new(amount: u64) -> Self {
+        Self { amount, by_ip: HashMap::new(), by_address: HashMap::new() }
+    }
+
+    /// Reserve a drip for `ip` paying `address`, or return the seconds until
+    /// both are allowed another one (or, with the budget spent, until the
+    /// oldest drip expires)
+    pub fn claim(&mut self, ip: &str, address: Hash256, now: u64) -> Result<(), u64> {
+        let ip = client_key(ip);
+        // Forget expired entries so the maps stay as small as the last day's traffic
+        self.by_ip.retain(|_, t| *t + FAUCET_COOLDOWN > now);
+        self.by_address.retain(|_, t| *t + FAUCET_COOLDOWN > now);
+
+        let wait = [self.by_ip.get(&ip), self.by_address.get(&address)].into_iter()
+            .flatten()
+            .map(|t| t + FAUCET_COOLDOWN - now)
+            .max();
+        if let Some(wait) = wait {
+            return Err(wait);
+        }
+        // Every drip in the window holds one address entry
+        if self.by_address.len() >= FAUCET_MAX_DRIPS {
+            let oldest = self.by_address.values().min().copied().unwrap_or(now);
+            return Err(oldest + FAUCET_COOLDOWN - now);
+        }
+        self.by_ip.insert(ip, now);
+        self.by_address.insert(address, now);
+        Ok(())
+    }
+
+    /// Hand back a claim whose payment failed
+    pub fn release(&mut self, ip: &str, address: &Hash256) {
+        self.by_ip.remove(&client_key(ip));
+        self.by_address.remove(address);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cooldown_per_ip_and_address() {
+        let mut faucet = Faucet::new(1000);
+        let now = 1_000_000;
+        assert!(faucet.claim("1.2.3.4", [1; 32], now).is_ok());
+        // Same IP, new address; new IP, same address: both wait
+        assert_eq!(faucet.claim("1.2.3.4", [2; 32], now + 10), Err(FAUCET_COOLDOWN - 10));
+        assert_eq!(faucet.claim("5.6.7.8", [1; 32], now + 10), Err(FAUCET_COOLDOWN - 10));
+        assert!(faucet.claim("5.6.7.8", [2; 32], now + 10).is_ok());
+
+        // A failed payment doesn't use up the allowance
+        faucet.release("5.6.7.8", &[2; 32]);
+        assert!(faucet.claim("5.6.7.8", [2; 32], now + 20).is_ok());
+
+        assert!(faucet.claim("1.2.3.4", [1; 32], now + FAUCET_COOLDOWN).is_ok());
+        assert_eq!(faucet.by_ip.len(), 2);
+        assert!(faucet.claim("9.9.9.9", [3; 32], now + 3 * FAUCET_COOLDOWN).is_ok());
+        assert_eq!(faucet.by_ip.len(), 1, "expired entries are pruned");
+    }
+
+    #[test]
+    fn test_ipv6_per_prefix_and_global_budget() {
+        assert_eq!(client_key("2001:db8:1:2:aaaa::1"), "2001:db8:1:2::/64");
+        assert_eq!(client_key("::ffff:1.2.3.4"), "::ffff:1.2.3.4");
+        assert_eq!(client_key("1.2.3.4"), "1.2.3.4");
+
+        let mut faucet = Faucet::new(1000);
+        let now = 1_000_000;
+        assert!(faucet.claim("2001:db8:1:2::1", [1; 32], now).is_ok());
+        // Another address in the same /64 waits; the next /64 doesn't
+        assert_eq!(faucet.claim("2001:db8:1:2:ffff::9", [2; 32], now), Err(FAUCET_COOLDOWN));
+        assert!(faucet.claim("2001:db8:1:3::1", [2; 32], now).is_ok());
+        faucet.release("2001:db8:1:3::2", &[2; 32]);
+        assert!(faucet.claim("2001:db8:1:3::3", [2; 32], now).is_ok());
+
+        // Spread over many IPs, the budget still runs out for the window
+        for i in 2..FAUCET_MAX_DRIPS {
+            let mut address = [0xAA; 32];
+            address[..8].copy_from_slice(&(i as u64).to_le_bytes());
+            assert!(faucet.claim(&format!("10.0.{}.{}", i / 256, i % 256), address, now + 5).is_ok());
+        }
+        assert_eq!(faucet.claim("10.9.9.9", [0xFF; 32], now + 5), Err(FAUCET_COOLDOWN - 5));
+        assert!(faucet.claim("10.9.9.9", [0xFF; 32], now + FAUCET_COOLDOWN).is_ok());
+    }
+}
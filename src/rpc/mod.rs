@@ -9,10 +9,19 @@ use crate::network::NodeState;
 use crate::wallet;
 use crate::miner;
 use crate::network;
+use crate::storage::snapshot;
 use crate::core::params::COINBASE_MATURITY;
 
+pub mod faucet;
+pub mod websocket;
+
 pub const RPC_PORT_OFFSET: u16 = 1;
 
+/// Longest a `waitfornewblock` call may block (also its default)
+pub const MAX_WAIT_FOR_BLOCK_MS: u64 = 5 * 60 * 1000;
+/// Most blocks `getdifficultyhistory` returns in one call
+pub const MAX_DIFFICULTY_HISTORY: u64 = 2_000;
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct RpcRequest { pub method: String, #[serde(default)] pub params: serde_json::Value, #[serde(default)] pub id: u64 }
 #[derive(Debug, Serialize, Deserialize)]
@@ -23,19 +32,126 @@ pub struct RpcError { pub code: i32, pub message: String }
 fn success(id: u64, result: serde_json::Value) -> RpcResponse { RpcResponse { result: Some(result), error: None, id } }
 fn error(id: u64, code: i32, msg: &str) -> RpcResponse { RpcResponse { result: None, error: Some(RpcError { code, message: msg.to_string() }), id } }
 
-pub async fn start_rpc_server(state: Arc<NodeState>, rpc_port: u16) {
-    let addr = format!("0.0.0.0:{}", rpc_port);
+/// Access control for the HTTP server (`--rpc-token`, `--rpc-auth-get`)
+#[derive(Debug, Clone, Default)]
+pub struct RpcAuth {
+    /// Required as `Authorization: Bearer <token>` on JSON-RPC calls; None leaves RPC open
+    pub token: Option<String>,
+    /// Also require the token for GETs (explorer, snapshot, metrics, websocket)
+    pub protect_get: bool,
+    /// Browser origins allowed to make JSON-RPC calls (`--rpc-cors`); "*" allows any.
+    /// POSTs from other origins get 403. GET routes and the explorer's own
+    /// calls are open to every origin.
+    pub cors_origins: Vec<String>,
+}
+
+impl RpcAuth {
+    /// Whether a request presenting this bearer token (if any) may proceed
+    fn permits(&self, presented: Option<&str>, is_get: bool) -> bool {
+        let Some(token) = &self.token else { return true };
+        if is_get && !self.protect_get { return true; }
+        presented.is_some_and(|p| constant_time_eq(p.as_bytes(), token.as_bytes()))
+    }
+
+    /// Whether pages from `origin` may make JSON-RPC calls (`--rpc-cors`)
+    fn allows_origin(&self, origin: &str) -> bool {
+        self.cors_origins.iter().any(|a| a == "*" || a == origin)
+    }
+
+    /// CORS header lines for a JSON-RPC response: the request's origin echoed
+    /// back if allowlisted, nothing (so browsers refuse it) otherwise
+    fn cors_headers(&self, origin: Option<&str>) -> String {
+        match origin {
+            Some(o) if self.allows_origin(o) => format!("Access-Control-Allow-Origin: {}\r\nVary: Origin\r\n", o),
+            _ => String::new(),
+        }
+    }
+}
+
+/// Whether `origin` is the page served from `host` itself (the explorer on a combined port)
+fn same_origin(origin: &str, host: Option<&str>) -> bool {
+    let origin = origin.strip_prefix("http://").or_else(|| origin.strip_prefix("https://")).unwrap_or(origin);
+    host.is_some_and(|h| h.eq_ignore_ascii_case(origin))
+}
+
+/// The value of `header_line` if it's the header `name` (case-insensitive)
+fn header_value<'a>(header_line: &'a str, name: &str) -> Option<&'a str> {
+    let (n, value) = header_line.split_once(':')?;
+    n.trim().eq_ignore_ascii_case(name).then(|| value.trim())
+}
+
+/// The token in an `Authorization: Bearer <token>` header line, None for any other header
+fn bearer_token(header_line: &str) -> Option<&str> {
+    let (scheme, token) = header_value(header_line, "authorization")?.split_once(' ')?;
+    scheme.eq_ignore_ascii_case("bearer").then(|| token.trim())
+}
+
+const CORS_ANY: &str = "Access-Control-Allow-Origin: *\r\n";
+
+/// Compare without an early exit, so response timing doesn't leak the token
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+const UNAUTHORIZED: &[u8] = b"HTTP/1.1 401 Unauthorized\r\nWWW-Authenticate: Bearer\r\nAccess-Control-Allow-Origin: *\r\nContent-Length: 0\r\n\r\n";
+const FORBIDDEN: &[u8] = b"HTTP/1.1 403 Forbidden\r\nContent-Length: 0\r\n\r\n";
+
+/// Which requests a listener answers. With `--explorer-port` the explorer
+/// gets its own listener and the RPC port stops serving it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Role {
+    /// Everything on one port (no `--explorer-port`)
+    Combined,
+    /// JSON-RPC, `/metrics` and the websocket
+    Rpc,
+    /// Explorer page, snapshot, websocket and the read-only calls the page makes
+    Explorer,
+}
+
+/// Methods the explorer page calls; the only POSTs its port accepts
+const EXPLORER_METHODS: &[&str] = &["getinfo", "getblock", "gettx", "getaddress", "getmempool", "getrichlist", "getpeerinfo"];
+
+const NOT_FOUND: &[u8] = b"HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\n\r\n";
+
+/// Serve JSON-RPC on `bind:rpc_port`, plus the explorer either on the same
+/// port or, if `explorer` is given, on that (bind, port) alone
+pub async fn start_rpc_server(state: Arc<NodeState>, bind: &str, rpc_port: u16, auth: RpcAuth, explorer: Option<(&str, u16)>) {
+    let addr = crate::network::join_host_port(bind, rpc_port);
     let listener = match TcpListener::bind(&addr).await { Ok(l) => l, Err(e) => { tracing::error!("Failed to bind RPC on {}: {}", addr, e); return; } };
-    tracing::info!("🌐 RPC server on http://0.0.0.0:{}", rpc_port);
+    tracing::info!("🌐 RPC server on http://{}", addr);
+    let loopback = listener.local_addr().is_ok_and(|a| a.ip().is_loopback());
+    if !loopback && auth.token.is_none() {
+        tracing::warn!("⚠️  RPC is reachable beyond localhost without --rpc-token");
+    }
+    let auth = Arc::new(auth);
+    let role = match explorer {
+        Some((explorer_bind, explorer_port)) => {
+            let explorer_addr = crate::network::join_host_port(explorer_bind, explorer_port);
+            match TcpListener::bind(&explorer_addr).await {
+                Ok(l) => {
+                    tracing::info!("🔎 Explorer on http://{}", explorer_addr);
+                    tokio::spawn(serve_http(l, state.clone(), auth.clone(), Role::Explorer));
+                }
+                Err(e) => tracing::error!("Failed to bind explorer on {}: {}", explorer_addr, e),
+            }
+            Role::Rpc
+        }
+        None => Role::Combined,
+    };
+    serve_http(listener, state, auth, role).await;
+}
+
+async fn serve_http(listener: TcpListener, state: Arc<NodeState>, auth: Arc<RpcAuth>, role: Role) {
     loop {
         match listener.accept().await {
-            Ok((stream, _)) => { let state = state.clone(); tokio::spawn(async move { handle_http(stream, state).await }); }
+            Ok((stream, _)) => { let state = state.clone(); let auth = auth.clone(); tokio::spawn(async move { handle_http(stream, state, &auth, role).await }); }
             Err(e) => tracing::error!("RPC accept error: {}", e),
         }
     }
 }
 
-async fn handle_http(mut stream: tokio::net::TcpStream, state: Arc<NodeState>) {
+async fn handle_http(mut stream: tokio::net::TcpStream, state: Arc<NodeState>, auth: &RpcAuth, role: Role) {
+    let peer_ip = stream.peer_addr().map(|a| a.ip().to_string()).unwrap_or_default();
     let (reader, mut writer) = stream.split();
     let mut reader = BufReader::new(reader);
     let mut request_line = String::new();
@@ -43,45 +159,89 @@ async fn handle_http(mut stream: tokio::net::TcpStream, state: Arc<NodeState>) {
 
     if request_line.starts_with("GET") {
         let path = request_line.split_whitespace().nth(1).unwrap_or("/").to_string();
-        loop { let mut line = String::new(); if reader.read_line(&mut line).await.is_err() { break; } if line.trim().is_empty() { break; } }
+        let (mut upgrade, mut ws_key, mut presented) = (false, None, None);
+        loop {
+            let mut line = String::new();
+            if reader.read_line(&mut line).await.is_err() { break; }
+            if line.trim().is_empty() { break; }
+            let lower = line.trim().to_lowercase();
+            if let Some(val) = lower.strip_prefix("upgrade:") { upgrade = val.trim() == "websocket"; }
+            if lower.starts_with("sec-websocket-key:") { ws_key = line.trim().split_once(':').map(|(_, v)| v.trim().to_string()); }
+            if let Some(token) = bearer_token(&line) { presented = Some(token.to_string()); }
+        }
+        if !auth.permits(presented.as_deref(), true) {
+            let _ = writer.write_all(UNAUTHORIZED).await;
+            return;
+        }
+        if upgrade {
+            if path != "/ws" {
+                let _ = writer.write_all(NOT_FOUND).await;
+                return;
+            }
+            match ws_key {
+                Some(key) => websocket::serve(reader, writer, &key, state).await,
+                None => { let _ = writer.write_all(b"HTTP/1.1 400 Bad Request\r\nContent-Length: 0\r\n\r\n").await; }
+            }
+            return;
+        }
+        let explorer_path = path != "/metrics";
+        if (role == Role::Rpc && explorer_path) || (role == Role::Explorer && !explorer_path) {
+            let _ = writer.write_all(NOT_FOUND).await;
+            return;
+        }
         if path == "/snapshot" || path == "/snapshot.bin" {
             tracing::info!("📸 Snapshot download requested");
             let chain = state.chain.read().await;
             let height = chain.height;
-            let mut data: Vec<u8> = Vec::new();
-            data.extend_from_slice(&1u32.to_le_bytes());
-            data.extend_from_slice(&height.to_le_bytes());
-            data.extend_from_slice(&((height + 1) as u64).to_le_bytes());
-            let genesis_hash = chain.genesis_hash();
-            data.extend_from_slice(&genesis_hash);
-            for h in 0..=height {
-                if let Some(block) = chain.block_at_height(h) {
-                    let encoded = bincode::serialize(block).unwrap();
-                    data.extend_from_slice(&(encoded.len() as u32).to_le_bytes());
-                    data.extend_from_slice(&encoded);
-                }
-            }
-            drop(chain);
-            use std::io::Write as IoWrite;
+            // Only the compressed bytes are buffered (Content-Length needs them up front)
             let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::fast());
-            encoder.write_all(&data).unwrap();
-            let compressed = encoder.finish().unwrap();
+            let compressed = snapshot::write_snapshot(&mut encoder, height, chain.genesis_hash(),
+                || (0..=height).filter_map(|h| chain.block_at_height(h)))
+                .and_then(|_| encoder.finish());
+            drop(chain);
+            let Ok(compressed) = compressed else {
+                let _ = writer.write_all(b"HTTP/1.1 500 Internal Server Error\r\nContent-Length: 0\r\n\r\n").await;
+                return;
+            };
             let response = format!("HTTP/1.1 200 OK\r\nContent-Type: application/octet-stream\r\nContent-Disposition: attachment; filename=\"snapshot.bin\"\r\nAccess-Control-Allow-Origin: *\r\nContent-Length: {}\r\n\r\n", compressed.len());
             let _ = writer.write_all(response.as_bytes()).await;
             let _ = writer.write_all(&compressed).await;
             return;
         }
+        if path == "/faucet" || path.starts_with("/faucet?") {
+            let (status, body) = faucet_drip(&state, &peer_ip, path.split_once('?').map_or("", |(_, q)| q)).await;
+            let body = body.to_string();
+            let response = format!("HTTP/1.1 {}\r\nContent-Type: application/json\r\n{}Content-Length: {}\r\n\r\n{}", status, CORS_ANY, body.len(), body);
+            let _ = writer.write_all(response.as_bytes()).await;
+            return;
+        }
+        if path == "/metrics" {
+            let body = render_metrics(&state).await;
+            let response = format!("HTTP/1.1 200 OK\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\n\r\n{}", body.len(), body);
+            let _ = writer.write_all(response.as_bytes()).await;
+            return;
+        }
         let html = include_str!("explorer.html");
         let response = format!("HTTP/1.1 200 OK\r\nContent-Type: text/html; charset=utf-8\r\nAccess-Control-Allow-Origin: *\r\nContent-Length: {}\r\n\r\n{}", html.len(), html);
         let _ = writer.write_all(response.as_bytes()).await;
         return;
     }
     if request_line.starts_with("OPTIONS") {
-        loop { let mut line = String::new(); if reader.read_line(&mut line).await.is_err() { break; } if line.trim().is_empty() { break; } }
-        let _ = writer.write_all(b"HTTP/1.1 204 No Content\r\nAccess-Control-Allow-Origin: *\r\nAccess-Control-Allow-Methods: POST, GET, OPTIONS\r\nAccess-Control-Allow-Headers: Content-Type\r\nAccess-Control-Max-Age: 86400\r\n\r\n").await;
+        let mut origin = None;
+        loop {
+            let mut line = String::new();
+            if reader.read_line(&mut line).await.is_err() { break; }
+            if line.trim().is_empty() { break; }
+            if let Some(o) = header_value(&line, "origin") { origin = Some(o.to_string()); }
+        }
+        // Preflights come ahead of cross-origin POSTs, so they follow the POST policy
+        let cors = if role == Role::Explorer { CORS_ANY.to_string() } else { auth.cors_headers(origin.as_deref()) };
+        let response = format!("HTTP/1.1 204 No Content\r\n{}Access-Control-Allow-Methods: POST, GET, OPTIONS\r\nAccess-Control-Allow-Headers: Content-Type, Authorization\r\nAccess-Control-Max-Age: 86400\r\n\r\n", cors);
+        let _ = writer.write_all(response.as_bytes()).await;
         return;
     }
     let mut content_length: usize = 0;
+    let (mut presented, mut origin, mut host) = (None, None, None);
     loop {
         let mut header_line = String::new();
         if reader.read_line(&mut header_line).await.is_err() { return; }
@@ -89,19 +249,140 @@ async fn handle_http(mut stream: tokio::net::TcpStream, state: Arc<NodeState>) {
         if trimmed.is_empty() { break; }
         let lower = trimmed.to_lowercase();
         if let Some(val) = lower.strip_prefix("content-length:") { content_length = val.trim().parse().unwrap_or(0); }
+        if let Some(token) = bearer_token(trimmed) { presented = Some(token.to_string()); }
+        if let Some(o) = header_value(trimmed, "origin") { origin = Some(o.to_string()); }
+        if let Some(h) = header_value(trimmed, "host") { host = Some(h.to_string()); }
+    }
+    // Browsers send simple cross-origin POSTs without a preflight, so a page
+    // from an origin off the allowlist must not get its call run at all
+    if role != Role::Explorer && origin.as_deref().is_some_and(|o| !auth.allows_origin(o) && !same_origin(o, host.as_deref())) {
+        let _ = writer.write_all(FORBIDDEN).await;
+        return;
+    }
+    // The explorer's read-only calls are gated like its GETs
+    if !auth.permits(presented.as_deref(), role == Role::Explorer) {
+        let _ = writer.write_all(UNAUTHORIZED).await;
+        return;
     }
     let mut body = vec![0u8; content_length];
     if content_length > 0 { if reader.read_exact(&mut body).await.is_err() { return; } }
     let response = match serde_json::from_slice::<RpcRequest>(&body) {
-        Ok(req) => handle_rpc(req, &state).await,
+        Ok(req) if role == Role::Explorer && !EXPLORER_METHODS.contains(&req.method.as_str()) => {
+            error(req.id, -32601, &format!("method '{}' is not available on the explorer port", req.method))
+        }
+        Ok(req) => handle_rpc(req, &state, auth).await,
         Err(e) => error(0, -32700, &format!("parse error: {}", e)),
     };
     let response_json = serde_json::to_string(&response).unwrap();
-    let http_response = format!("HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nAccess-Control-Allow-Origin: *\r\n\r\n{}", response_json.len(), response_json);
+    let cors = if role == Role::Explorer { CORS_ANY.to_string() } else { auth.cors_headers(origin.as_deref()) };
+    let http_response = format!("HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\n{}\r\n{}", response_json.len(), cors, response_json);
     let _ = writer.write_all(http_response.as_bytes()).await;
 }
 
-async fn handle_rpc(req: RpcRequest, state: &Arc<NodeState>) -> RpcResponse {
+/// Serve `GET /faucet?address=...`: an HTTP status line and a JSON body
+async fn faucet_drip(state: &NodeState, ip: &str, query: &str) -> (&'static str, serde_json::Value) {
+    let Some(amount) = state.faucet.lock().await.as_ref().map(|f| f.amount).filter(|_| is_testnet()) else {
+        return ("404 Not Found", json!({"error": "no faucet on this node"}));
+    };
+    let address = query.split('&').find_map(|kv| kv.strip_prefix("address=")).unwrap_or("");
+    let Some(hash) = wallet::address_to_pubkey_hash(address) else {
+        return ("400 Bad Request", json!({"error": "invalid address"}));
+    };
+    let now = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+    // Claimed before sending so concurrent requests can't both get through
+    if let Some(Err(wait)) = state.faucet.lock().await.as_mut().map(|f| f.claim(ip, hash, now)) {
+        return ("429 Too Many Requests", json!({"error": "faucet already used; try again later", "retry_after": wait}));
+    }
+    match send_from_wallet(state, &[(hash, amount)], DEFAULT_SEND_FEE).await {
+        Ok(txid) => ("200 OK", json!({"txid": hex::encode(txid), "amount": amount as f64 / COIN as f64, "address": address})),
+        Err(e) => {
+            if let Some(f) = state.faucet.lock().await.as_mut() { f.release(ip, &hash); }
+            tracing::warn!("🚰 Faucet payment to {} failed: {}", address, e);
+            ("503 Service Unavailable", json!({"error": "faucet can't pay right now"}))
+        }
+    }
+}
+
+/// Node counters in the Prometheus text exposition format
+async fn render_metrics(state: &NodeState) -> String {
+    use std::fmt::Write;
+    let chain = state.chain.read().await;
+    let peers = state.peers.read().await.len();
+    let mempool = state.mempool.lock().await.len();
+    let sb = state.scoreboard.lock().await;
+    let m = chain.metrics();
+
+    let mut out = String::new();
+    let mut metric = |name: &str, kind: &str, help: &str, samples: &[(String, u64)]| {
+        let _ = writeln!(out, "# HELP {} {}\n# TYPE {} {}", name, help, name, kind);
+        for (labels, value) in samples {
+            let _ = writeln!(out, "{}{} {}", name, labels, value);
+        }
+    };
+    let one = |v: u64| [(String::new(), v)];
+    metric("equiforge_blocks_accepted_total", "counter", "Blocks stored on the active or a side chain", &one(m.accepted));
+    let rejected: Vec<(String, u64)> = m.rejected.iter().map(|(r, n)| (format!("{{reason=\"{}\"}}", r), *n)).collect();
+    metric("equiforge_blocks_rejected_total", "counter", "Blocks refused by add_block", &rejected);
+    metric("equiforge_reorgs_total", "counter", "Completed chain reorganizations", &one(m.reorgs));
+    metric("equiforge_reorg_depth_blocks_sum", "counter", "Blocks disconnected across all reorgs", &one(m.reorg_depth_sum));
+    metric("equiforge_reorg_depth_blocks_max", "gauge", "Deepest reorg since startup", &one(m.reorg_depth_max));
+    metric("equiforge_chain_height", "gauge", "Height of the active chain tip", &one(chain.height));
+    metric("equiforge_peers", "gauge", "Connected peers", &one(peers as u64));
+    metric("equiforge_mempool_transactions", "gauge", "Transactions in the mempool", &one(mempool as u64));
+    metric("equiforge_bans_total", "counter", "Peer bans issued", &one(sb.bans_issued()));
+    metric("equiforge_banned_peers", "gauge", "Currently banned IPs", &one(sb.ban_count() as u64));
+    out
+}
+
+/// The wallet's confirmed outputs that no pending transaction spends yet
+fn wallet_utxos(w: &wallet::Wallet, chain: &crate::core::chain::Chain, mempool: &crate::network::Mempool) -> crate::core::chain::UtxoSet {
+    let pending = mempool.spent_outpoints();
+    let mut utxos = crate::core::chain::UtxoSet::new();
+    for hash in w.spendable_pubkey_hashes() {
+        for (outpoint, entry) in chain.utxo_set.utxos_for(&hash) {
+            if !pending.contains(&outpoint) { utxos.add(outpoint, entry.clone()); }
+        }
+    }
+    utxos
+}
+
+/// An EQF amount as base units, None unless a number from 0 to `MAX_SUPPLY`
+fn eqf_amount(v: &serde_json::Value) -> Option<u64> {
+    v.as_f64().map(|a| (a * COIN as f64).round())
+        .filter(|a| *a >= 0.0 && *a <= MAX_SUPPLY as f64)
+        .map(|a| a as u64)
+}
+
+/// Fee the node wallet pays unless told otherwise (as `equiforge send`)
+const DEFAULT_SEND_FEE: u64 = COIN / 10_000;
+
+/// Fee for the wallet send RPCs: the given EQF amount, or `DEFAULT_SEND_FEE`
+fn send_fee(v: Option<&serde_json::Value>) -> Option<u64> {
+    match v {
+        None | Some(serde_json::Value::Null) => Some(DEFAULT_SEND_FEE),
+        Some(v) => eqf_amount(v),
+    }
+}
+
+/// Pay `recipients` from the node wallet in one transaction, add it to the
+/// mempool and relay it
+async fn send_from_wallet(state: &NodeState, recipients: &[(Hash256, u64)], fee: u64) -> Result<Hash256, String> {
+    let chain = state.chain.read().await;
+    let mut mempool = state.mempool.lock().await;
+    let guard = state.wallet.lock().await;
+    let w = guard.as_ref().ok_or("no wallet loaded")?;
+    let utxos = wallet_utxos(w, &chain, &mempool);
+    let tx = w.create_multi_send_tx(&utxos, recipients, fee, chain.height, wallet::CoinSelection::LargestFirst)?;
+    drop(guard);
+    let txid = mempool.validate_and_add(tx.clone(), &chain).map_err(|reason| format!("rejected: {}", reason))?;
+    drop(mempool); drop(chain);
+    let total: u64 = recipients.iter().map(|(_, a)| a).sum();
+    tracing::info!("📤 Sent {} EQF to {} recipient(s) from the node wallet ({})", total as f64 / COIN as f64, recipients.len(), &hex::encode(txid)[..16]);
+    let _ = state.tx_tx.send(tx);
+    Ok(txid)
+}
+
+async fn handle_rpc(req: RpcRequest, state: &Arc<NodeState>, auth: &RpcAuth) -> RpcResponse {
     match req.method.as_str() {
         "getinfo" | "getblockchaininfo" => {
             let chain = state.chain.read().await;
@@ -109,8 +390,7 @@ async fn handle_rpc(req: RpcRequest, state: &Arc<NodeState>) -> RpcResponse {
             let mempool = state.mempool.lock().await;
             let sb = state.scoreboard.lock().await;
             let height = chain.height;
-            let mut total_supply: u64 = 0;
-            for h in 0..=height { total_supply += block_reward(h); }
+            let total_supply = chain.money_supply();
             let tip = chain.tip_header();
             let avg_block_time = if height >= 10 {
                 if let Some(older) = chain.block_at_height(height.saturating_sub(10)) {
@@ -119,8 +399,7 @@ async fn handle_rpc(req: RpcRequest, state: &Arc<NodeState>) -> RpcResponse {
                 } else { TARGET_BLOCK_TIME as f64 }
             } else { TARGET_BLOCK_TIME as f64 };
             let diff = chain.next_difficulty();
-            let est_hashes = estimated_hashes_for_difficulty(diff);
-            let hashrate = if avg_block_time > 0.0 { est_hashes as f64 / avg_block_time } else { 0.0 };
+            let hashrate = chain.network_hashps(10);
             success(req.id, json!({
                 "height": height, "tip": hex::encode(chain.tip),
                 "difficulty": diff, "fractional_difficulty": chain.fractional_difficulty(),
@@ -128,14 +407,70 @@ async fn handle_rpc(req: RpcRequest, state: &Arc<NodeState>) -> RpcResponse {
                 "peers": peers.len(), "mempool": mempool.len(), "banned": sb.ban_count(),
                 "block_reward": block_reward(height) as f64 / COIN as f64,
                 "persistent": chain.is_persistent(),
+                "pruned": chain.is_pruned(), "pruneheight": chain.prune_height(),
                 "total_supply": total_supply as f64 / COIN as f64,
                 "max_supply": MAX_SUPPLY as f64 / COIN as f64,
                 "avg_block_time": avg_block_time, "hashrate": hashrate,
-                "last_block_time": tip.timestamp,
+                "last_block_time": tip.timestamp, "time": tip.timestamp,
+                "mediantime": chain.median_time_past(),
+                "moneysupply": total_supply as f64 / COIN as f64, "moneysupply_base": total_supply,
                 "network": if is_testnet() { "testnet" } else { "mainnet" },
+                "warning": chain.anomaly_warning(),
             }))
         }
         "getblockcount" | "getheight" => { let chain = state.chain.read().await; success(req.id, json!(chain.height)) }
+        "getchaintips" => {
+            let chain = state.chain.read().await;
+            let tips: Vec<serde_json::Value> = chain.chain_tips().iter().map(|t| json!({
+                "height": t.height, "hash": hex::encode(t.hash),
+                "branchlen": t.branch_len, "status": t.status.as_str(),
+            })).collect();
+            success(req.id, json!(tips))
+        }
+        "verifychain" => {
+            let depth = req.params.get(0).or_else(|| req.params.get("depth")).and_then(|v| v.as_u64());
+            let chain = state.chain.read().await;
+            success(req.id, verify_chain_json(&chain, depth))
+        }
+        "getdbinfo" => {
+            let chain = state.chain.read().await;
+            let Some(storage) = chain.storage() else { return error(req.id, -32000, "chain is not persistent") };
+            let size = match storage.size_on_disk() {
+                Ok(size) => size,
+                Err(e) => return error(req.id, -32000, &e.to_string()),
+            };
+            let counts = storage.key_counts();
+            success(req.id, json!({
+                "size_on_disk": size,
+                "blocks": counts.blocks, "headers": counts.headers, "utxos": counts.utxos,
+                "undo": counts.undo, "txindex": counts.tx_index, "addressindex": counts.address_index,
+                "pruned": chain.is_pruned(),
+            }))
+        }
+        "waitfornewblock" => {
+            let timeout_ms = req.params.get(0).or_else(|| req.params.get("timeout")).and_then(|v| v.as_u64())
+                .filter(|ms| *ms > 0).unwrap_or(MAX_WAIT_FOR_BLOCK_MS).min(MAX_WAIT_FOR_BLOCK_MS);
+            let deadline = tokio::time::Instant::now() + std::time::Duration::from_millis(timeout_ms);
+            let start_tip = state.chain.read().await.tip;
+            let timed_out = loop {
+                // Register before checking the tip so a block landing in between isn't missed;
+                // notify_waiters wakes every registered waiter
+                let notified = state.new_block_notify.notified();
+                tokio::pin!(notified);
+                notified.as_mut().enable();
+                if state.chain.read().await.tip != start_tip { break false; }
+                if tokio::time::timeout_at(deadline, notified).await.is_err() { break true; }
+            };
+            let chain = state.chain.read().await;
+            success(req.id, json!({"height": chain.height, "hash": hex::encode(chain.tip), "timed_out": timed_out}))
+        }
+        "stop" => {
+            // Anyone who can reach an open server could otherwise take the node down
+            if auth.token.is_none() { return error(req.id, -32000, "stop needs the node to run with --rpc-token"); }
+            tracing::info!("🛑 Shutdown requested over RPC");
+            state.shutdown.store(true, std::sync::atomic::Ordering::SeqCst);
+            success(req.id, json!("EquiForge node stopping"))
+        }
         "getbestblockhash" => { let chain = state.chain.read().await; success(req.id, json!(hex::encode(chain.tip))) }
         "getbalance" => {
             let address = req.params.get(0).or_else(|| req.params.get("address")).and_then(|v| v.as_str()).unwrap_or("");
@@ -161,6 +496,68 @@ async fn handle_rpc(req: RpcRequest, state: &Arc<NodeState>) -> RpcResponse {
                 None => error(req.id, -32602, "invalid address"),
             }
         }
+        "gettxout" => {
+            let txid = req.params.get(0).or_else(|| req.params.get("txid")).and_then(|v| v.as_str()).and_then(parse_hash);
+            let vout = req.params.get(1).or_else(|| req.params.get("vout")).and_then(|v| v.as_u64());
+            let (txid, vout) = match (txid, vout) {
+                (Some(t), Some(v)) if v <= u32::MAX as u64 => (t, v as u32),
+                (None, _) => return error(req.id, -32602, "invalid txid (expected 64 hex chars)"),
+                _ => return error(req.id, -32602, "invalid vout"),
+            };
+            let chain = state.chain.read().await;
+            match chain.utxo_set.get(&OutPoint { txid, vout }) {
+                Some(e) => success(req.id, json!({
+                    "txid": hex::encode(txid), "vout": vout,
+                    "amount": e.output.amount as f64 / COIN as f64, "amount_base": e.output.amount,
+                    "pubkey_hash": hex::encode(e.output.pubkey_hash),
+                    "address": wallet::pubkey_hash_to_address(&e.output.pubkey_hash),
+                    "height": e.height, "coinbase": e.is_coinbase,
+                    "confirmations": chain.height - e.height + 1,
+                    "bestblock": hex::encode(chain.tip),
+                })),
+                None => success(req.id, serde_json::Value::Null),
+            }
+        }
+        "getemission" => {
+            let tip_height = state.chain.read().await.height;
+            let height = req.params.get(0).or_else(|| req.params.get("height")).and_then(|v| v.as_u64()).unwrap_or(tip_height);
+            let emitted = total_emission(height);
+            let cap = emission_cap();
+            let next_change = next_reward_change(height);
+            success(req.id, json!({
+                "height": height,
+                "block_reward": block_reward(height) as f64 / COIN as f64, "block_reward_base": block_reward(height),
+                "community_fund": required_community_fund(height) as f64 / COIN as f64,
+                "next_reward_change": next_change,
+                "blocks_until_change": next_change.map(|h| h - height),
+                "next_block_reward": next_change.map(|h| block_reward(h) as f64 / COIN as f64),
+                "emitted": emitted as f64 / COIN as f64, "emitted_base": emitted,
+                "emission_cap": cap as f64 / COIN as f64, "emission_cap_base": cap,
+                "percent_emitted": emitted as f64 * 100.0 / cap as f64,
+                "max_supply": MAX_SUPPLY as f64 / COIN as f64,
+            }))
+        }
+        "gettxoutsetinfo" => {
+            let chain = state.chain.read().await;
+            let info = chain.utxo_set_info();
+            let emitted = chain.money_supply();
+            drop(chain);
+            // Coinbases may claim less than reward + fees but never more, so
+            // unspent outputs can only exceed emission through a consensus bug
+            let consistent = info.total_amount <= emitted;
+            if !consistent {
+                tracing::error!("UTXO set holds {} base units but only {} were emitted by height {}",
+                    info.total_amount, emitted, info.height);
+            }
+            success(req.id, json!({
+                "height": info.height, "bestblock": hex::encode(info.tip),
+                "txouts": info.utxos, "digest": hex::encode(info.digest),
+                "total_amount": info.total_amount as f64 / COIN as f64, "total_amount_base": info.total_amount,
+                "expected_emission": emitted as f64 / COIN as f64, "expected_emission_base": emitted,
+                "unclaimed_base": emitted.saturating_sub(info.total_amount),
+                "consistent": consistent,
+            }))
+        }
         "gettx" => {
             let txid_str = req.params.get(0).or_else(|| req.params.get("txid")).and_then(|v| v.as_str()).unwrap_or("");
             if txid_str.len() != 64 { return error(req.id, -32602, "invalid txid"); }
@@ -168,7 +565,7 @@ async fn handle_rpc(req: RpcRequest, state: &Arc<NodeState>) -> RpcResponse {
             for h in (0..=chain.height).rev() {
                 if let Some(block) = chain.block_at_height(h) {
                     for (tx_idx, tx) in block.transactions.iter().enumerate() {
-                        let this_txid = hex::encode(tx.hash());
+                        let this_txid = hex::encode(crate::crypto::txid::txid_v1(tx));
                         if this_txid != txid_str { continue; }
                         let inputs: Vec<serde_json::Value> = if tx.is_coinbase() {
                             vec![json!({"type":"coinbase","amount": tx.total_output() as f64 / COIN as f64})]
@@ -179,7 +576,7 @@ async fn handle_rpc(req: RpcRequest, state: &Arc<NodeState>) -> RpcResponse {
                                 'outer: for ph in (0..=chain.height).rev() {
                                     if let Some(pb) = chain.block_at_height(ph) {
                                         for ptx in &pb.transactions {
-                                            if hex::encode(ptx.hash()) == prev_txid {
+                                            if hex::encode(crate::crypto::txid::txid_v1(ptx)) == prev_txid {
                                                 if let Some(out) = ptx.outputs.get(inp.previous_output.vout as usize) {
                                                     pa = out.amount; paddr = wallet::pubkey_hash_to_address(&out.pubkey_hash);
                                                 } break 'outer;
@@ -192,7 +589,7 @@ async fn handle_rpc(req: RpcRequest, state: &Arc<NodeState>) -> RpcResponse {
                         };
                         let outputs: Vec<serde_json::Value> = tx.outputs.iter().enumerate().map(|(vout, out)| {
                             let addr = wallet::pubkey_hash_to_address(&out.pubkey_hash);
-                            let op = OutPoint { txid: tx.hash(), vout: vout as u32 };
+                            let op = OutPoint { txid: crate::crypto::txid::txid_v1(tx), vout: vout as u32 };
                             let spent = !chain.utxo_set.contains(&op);
                             json!({"vout":vout,"amount":out.amount as f64/COIN as f64,"address":addr,"spent":spent})
                         }).collect();
@@ -209,6 +606,106 @@ async fn handle_rpc(req: RpcRequest, state: &Arc<NodeState>) -> RpcResponse {
                 }
             }
             error(req.id, -32602, "transaction not found")
+        }
+        "gettransaction" => {
+            let Some(txid) = req.params.get(0).or_else(|| req.params.get("txid")).and_then(|v| v.as_str()).and_then(parse_hash) else {
+                return error(req.id, -32602, "invalid txid");
+            };
+            let with_proof = req.params.get(1).or_else(|| req.params.get("merkle_proof")).and_then(|v| v.as_bool()).unwrap_or(false);
+            let chain = state.chain.read().await;
+            match chain.get_transaction(&txid) {
+                Some((block, tx)) => {
+                    let mut result = confirmed_tx_to_json(tx, block, &chain);
+                    if with_proof {
+                        // Verify against `getblockheader`: hash `leaf` up through `branch`
+                        let branch: Vec<serde_json::Value> = block.merkle_proof(&txid).unwrap_or_default().iter()
+                            .map(|(hash, is_left)| json!({"hash": hex::encode(hash), "left": is_left}))
+                            .collect();
+                        result["merkle_proof"] = json!({
+                            "leaf": hex::encode(tx.hash()), "branch": branch,
+                            "merkle_root": hex::encode(block.header.merkle_root),
+                            "block_hash": hex::encode(block.header.hash()),
+                        });
+                    }
+                    success(req.id, result)
+                }
+                None => error(req.id, -5, "no confirmed transaction with that txid"),
+            }
+        }
+        "getrawtransaction" => {
+            let Some(txid) = req.params.get(0).or_else(|| req.params.get("txid")).and_then(|v| v.as_str()).and_then(parse_hash) else {
+                return error(req.id, -32602, "invalid txid");
+            };
+            let verbose = req.params.get(1).or_else(|| req.params.get("verbose")).and_then(|v| v.as_bool()).unwrap_or(false);
+            let unconfirmed = state.mempool.lock().await.get(&txid).cloned();
+            if let Some(tx) = unconfirmed {
+                if !verbose { return success(req.id, json!(raw_tx_hex(&tx))); }
+                let mut result = decoded_tx_json(&tx, &state.chain.read().await.utxo_set);
+                result["in_mempool"] = json!(true);
+                result["confirmations"] = json!(0);
+                return success(req.id, result);
+            }
+            let chain = state.chain.read().await;
+            match chain.get_transaction(&txid) {
+                Some((_, tx)) if !verbose => success(req.id, json!(raw_tx_hex(tx))),
+                Some((block, tx)) => {
+                    let mut result = confirmed_tx_to_json(tx, block, &chain);
+                    result["in_mempool"] = json!(false);
+                    success(req.id, result)
+                }
+                None => error(req.id, -5, "no transaction with that txid in the mempool or tx index"),
+            }
+        }
+        "decoderawtransaction" => {
+            let hex_str = req.params.get(0).or_else(|| req.params.get("hex")).and_then(|v| v.as_str()).unwrap_or("");
+            match decode_raw_tx(hex_str) {
+                Ok(tx) => success(req.id, decoded_tx_json(&tx, &state.chain.read().await.utxo_set)),
+                Err(e) => error(req.id, -22, &e),
+            }
+        }
+        "decodescript" => {
+            let hex_str = req.params.get(0).or_else(|| req.params.get("hex")).and_then(|v| v.as_str()).unwrap_or("");
+            match hex::decode(hex_str.trim()) {
+                Ok(script) => success(req.id, decode_script_json(&script)),
+                Err(e) => error(req.id, -22, &format!("invalid hex: {}", e)),
+            }
+        }
+        "getaddresshistory" => {
+            let address = req.params.get(0).or_else(|| req.params.get("address")).and_then(|v| v.as_str()).unwrap_or("");
+            let Some(hash) = wallet::address_to_pubkey_hash(address) else {
+                return error(req.id, -32602, "invalid address");
+            };
+            let chain = state.chain.read().await;
+            let entries = match chain.address_history(&hash) {
+                Ok(entries) => entries,
+                Err(e) => return error(req.id, -32000, &e),
+            };
+            let (mut received, mut sent) = (0u64, 0u64);
+            let history: Vec<serde_json::Value> = entries.iter().map(|e| {
+                let mut item = json!({
+                    "txid": hex::encode(e.txid), "height": e.height,
+                    "confirmations": chain.height - e.height + 1,
+                    "amount": e.amount as f64 / COIN as f64, "amount_base": e.amount,
+                });
+                if e.sent {
+                    sent += e.amount;
+                    item["category"] = json!("send");
+                    item["vin"] = json!(e.index);
+                } else {
+                    received += e.amount;
+                    let op = OutPoint { txid: e.txid, vout: e.index };
+                    item["category"] = json!("receive");
+                    item["vout"] = json!(e.index);
+                    item["spent"] = json!(chain.utxo_set.get(&op).is_none());
+                }
+                item
+            }).collect();
+            success(req.id, json!({
+                "address": address,
+                "total_received": received as f64 / COIN as f64, "total_received_base": received,
+                "total_sent": sent as f64 / COIN as f64, "total_sent_base": sent,
+                "history": history,
+            }))
         }
          "getaddress" => {
             let address = req.params.get(0).or_else(|| req.params.get("address")).and_then(|v| v.as_str()).unwrap_or("");
@@ -243,7 +740,7 @@ async fn handle_rpc(req: RpcRequest, state: &Arc<NodeState>) -> RpcResponse {
                     for h in 0..=chain.height {
                         if let Some(block) = chain.block_at_height(h) {
                             for tx in &block.transactions {
-                                let txid = tx.hash();
+                                let txid = crate::crypto::txid::txid_v1(tx);
                                 for (vout, out) in tx.outputs.iter().enumerate() {
                                     output_owner.insert((txid, vout as u32), (out.pubkey_hash, out.amount));
                                 }
@@ -288,7 +785,7 @@ async fn handle_rpc(req: RpcRequest, state: &Arc<NodeState>) -> RpcResponse {
                                     let net = received as i64 - sent as i64;
                                     if txs.len() < 50 {
                                         txs.push(json!({
-                                            "txid": hex::encode(tx.hash()),
+                                            "txid": hex::encode(crate::crypto::txid::txid_v1(tx)),
                                             "block_height": h,
                                             "timestamp": block.header.timestamp,
                                             "received": received as f64 / COIN as f64,
@@ -357,21 +854,329 @@ async fn handle_rpc(req: RpcRequest, state: &Arc<NodeState>) -> RpcResponse {
                 None => error(req.id, -32602, "missing tx parameter"),
             }
         }
+        "getnewaddress" => {
+            if auth.token.is_none() { return error(req.id, -32000, "getnewaddress needs the node to run with --rpc-token"); }
+            let label = req.params.get(0).or_else(|| req.params.get("label")).and_then(|v| v.as_str()).unwrap_or("");
+            let mut guard = state.wallet.lock().await;
+            let Some(w) = guard.as_mut() else { return error(req.id, -32000, "no wallet loaded") };
+            // HD wallets derive the next child, so the seed backup covers it
+            let address = w.new_address();
+            if !label.trim().is_empty() {
+                let hash = wallet::address_to_pubkey_hash(&address).expect("fresh address parses");
+                if let Err(e) = w.set_label(hash, label) { return error(req.id, -32000, &e); }
+            }
+            success(req.id, json!(address))
+        }
+        "sendtoaddress" => {
+            // Spends the node's own coins, so never on an unauthenticated server
+            if auth.token.is_none() { return error(req.id, -32000, "sendtoaddress needs the node to run with --rpc-token"); }
+            let address = req.params.get(0).or_else(|| req.params.get("address")).and_then(|v| v.as_str());
+            let amount = req.params.get(1).or_else(|| req.params.get("amount"));
+            let (Some(address), Some(amount)) = (address, amount) else {
+                return error(req.id, -32602, "params: [address, amount, fee?]");
+            };
+            let Some(recipient) = wallet::address_to_pubkey_hash(address) else { return error(req.id, -32602, "invalid address") };
+            let Some(amount) = eqf_amount(amount).filter(|a| *a > 0) else { return error(req.id, -32602, "amount out of range") };
+            let Some(fee) = send_fee(req.params.get(2).or_else(|| req.params.get("fee"))) else { return error(req.id, -32602, "fee out of range") };
+            match send_from_wallet(state, &[(recipient, amount)], fee).await {
+                Ok(txid) => success(req.id, json!(hex::encode(txid))),
+                Err(e) => error(req.id, -32000, &e),
+            }
+        }
+        "sendmany" => {
+            if auth.token.is_none() { return error(req.id, -32000, "sendmany needs the node to run with --rpc-token"); }
+            let Some(targets) = req.params.get(0).or_else(|| req.params.get("amounts")).and_then(|v| v.as_object()).filter(|m| !m.is_empty()) else {
+                return error(req.id, -32602, "params: [{address: amount, ...}, fee?]");
+            };
+            // Check everything before touching the wallet, so a bad entry sends nothing
+            let mut recipients = Vec::with_capacity(targets.len());
+            for (address, amount) in targets {
+                let Some(hash) = wallet::address_to_pubkey_hash(address) else { return error(req.id, -32602, &format!("invalid address: {}", address)) };
+                let Some(amount) = eqf_amount(amount).filter(|a| *a > 0) else { return error(req.id, -32602, &format!("invalid amount for {}", address)) };
+                recipients.push((hash, amount));
+            }
+            let Some(fee) = send_fee(req.params.get(1).or_else(|| req.params.get("fee"))) else { return error(req.id, -32602, "fee out of range") };
+            match send_from_wallet(state, &recipients, fee).await {
+                Ok(txid) => success(req.id, json!(hex::encode(txid))),
+                Err(e) => error(req.id, -32000, &e),
+            }
+        }
+        "createmultisig" => {
+            let required = req.params.get(0).or_else(|| req.params.get("required")).and_then(|v| v.as_u64());
+            let addresses = req.params.get(1).or_else(|| req.params.get("addresses")).and_then(|v| v.as_array());
+            let (Some(required), Some(addresses)) = (required, addresses) else {
+                return error(req.id, -32602, "params: [required, [address, ...]]");
+            };
+            let mut key_hashes = Vec::with_capacity(addresses.len());
+            for a in addresses {
+                match a.as_str().and_then(wallet::address_to_pubkey_hash) {
+                    Some(h) => key_hashes.push(h),
+                    None => return error(req.id, -32602, &format!("invalid address: {}", a)),
+                }
+            }
+            let required = match u8::try_from(required) { Ok(m) => m, Err(_) => return error(req.id, -32602, "required out of range") };
+            match wallet::create_multisig(required, &key_hashes) {
+                Ok((address, script_pubkey)) => success(req.id, json!({
+                    "address": address, "script_pubkey": hex::encode(script_pubkey),
+                    "required": required, "keys": addresses,
+                })),
+                Err(e) => error(req.id, -32602, &e),
+            }
+        }
+        "createpsbt" => {
+            let inputs = req.params.get(0).or_else(|| req.params.get("inputs")).and_then(|v| v.as_array());
+            let outputs = req.params.get(1).or_else(|| req.params.get("outputs")).and_then(|v| v.as_array());
+            let (Some(inputs), Some(outputs)) = (inputs, outputs) else {
+                return error(req.id, -32602, "params: [[{txid, vout}, ...], [{address|script_pubkey, amount}, ...]]");
+            };
+            let chain = state.chain.read().await;
+            let mut tx_inputs = Vec::with_capacity(inputs.len());
+            let mut prev_outputs = Vec::with_capacity(inputs.len());
+            for (i, inp) in inputs.iter().enumerate() {
+                let txid = inp.get("txid").and_then(|v| v.as_str()).and_then(parse_hash);
+                let vout = inp.get("vout").and_then(|v| v.as_u64());
+                let (Some(txid), Some(vout)) = (txid, vout) else {
+                    return error(req.id, -32602, &format!("input {}: need txid and vout", i));
+                };
+                let outpoint = OutPoint { txid, vout: vout as u32 };
+                let Some(entry) = chain.utxo_set.get(&outpoint) else {
+                    return error(req.id, -32602, &format!("input {}: unspent output not found", i));
+                };
+                prev_outputs.push(entry.output.clone());
+                tx_inputs.push(TxInput { previous_output: outpoint, signature: vec![], pubkey: vec![], script_sig: vec![], sequence: 0xFFFFFFFF });
+            }
+            drop(chain);
+            let mut tx_outputs = Vec::with_capacity(outputs.len());
+            for (i, out) in outputs.iter().enumerate() {
+                let Some(amount) = out.get("amount").and_then(|v| v.as_f64()).filter(|a| *a > 0.0) else {
+                    return error(req.id, -32602, &format!("output {}: invalid amount", i));
+                };
+                let amount = (amount * COIN as f64).round() as u64;
+                if let Some(script_hex) = out.get("script_pubkey").and_then(|v| v.as_str()) {
+                    let Ok(script_pubkey) = hex::decode(script_hex) else {
+                        return error(req.id, -32602, &format!("output {}: invalid script hex", i));
+                    };
+                    let pubkey_hash = crate::core::script::parse_script_pubkey_p2pkh(&script_pubkey)
+                        .unwrap_or_else(|_| crate::core::script::multisig_script_hash(&script_pubkey));
+                    tx_outputs.push(TxOutput { amount, pubkey_hash, script_pubkey });
+                } else if let Some(hash) = out.get("address").and_then(|v| v.as_str()).and_then(wallet::address_to_pubkey_hash) {
+                    tx_outputs.push(TxOutput { amount, pubkey_hash: hash, script_pubkey: crate::core::script::script_p2pkh(&hash) });
+                } else {
+                    return error(req.id, -32602, &format!("output {}: need address or script_pubkey", i));
+                }
+            }
+            let tx = Transaction { version: CURRENT_TX_VERSION, inputs: tx_inputs, outputs: tx_outputs, lock_time: 0 };
+            match wallet::PartialTx::new(tx, prev_outputs) {
+                Ok(ptx) => success(req.id, json!({"psbt": ptx.to_hex(), "complete": false})),
+                Err(e) => error(req.id, -32602, &e),
+            }
+        }
+        "validateaddress" => {
+            let Some(address) = req.params.get(0).or_else(|| req.params.get("address")).and_then(|v| v.as_str()) else {
+                return error(req.id, -32602, "missing address parameter");
+            };
+            let Some(hash) = wallet::address_to_pubkey_hash(address) else {
+                return success(req.id, json!({"isvalid": false, "address": address}));
+            };
+            let mut result = json!({"isvalid": true, "address": address, "pubkey_hash": hex::encode(hash)});
+            if let Some(w) = state.wallet.lock().await.as_ref() {
+                result["ismine"] = json!(w.keypair_for_hash(&hash).is_some());
+            }
+            success(req.id, result)
+        }
+        "listlabels" => {
+            let guard = state.wallet.lock().await;
+            let Some(w) = guard.as_ref() else { return error(req.id, -32000, "no wallet loaded") };
+            success(req.id, json!(w.list_labels()))
+        }
+        "getaddressesbylabel" => {
+            let Some(label) = req.params.get(0).or_else(|| req.params.get("label")).and_then(|v| v.as_str()) else {
+                return error(req.id, -32602, "missing label parameter");
+            };
+            let guard = state.wallet.lock().await;
+            let Some(w) = guard.as_ref() else { return error(req.id, -32000, "no wallet loaded") };
+            let addresses: Vec<serde_json::Value> = w.hashes_by_label(label).iter().map(|h| json!({
+                "address": wallet::pubkey_hash_to_address(h), "watch_only": w.is_watch_only(h),
+            })).collect();
+            success(req.id, json!(addresses))
+        }
+        "signmessage" => {
+            // Proves ownership of the node's keys, so token-only like spending
+            if auth.token.is_none() { return error(req.id, -32000, "signmessage needs the node to run with --rpc-token"); }
+            let address = req.params.get(0).or_else(|| req.params.get("address")).and_then(|v| v.as_str()).unwrap_or("");
+            let Some(message) = req.params.get(1).or_else(|| req.params.get("message")).and_then(|v| v.as_str()) else {
+                return error(req.id, -32602, "missing message parameter");
+            };
+            let guard = state.wallet.lock().await;
+            let Some(w) = guard.as_ref() else { return error(req.id, -32000, "no wallet loaded") };
+            match w.sign_message(address, message) {
+                Ok(sig) => success(req.id, json!(sig)),
+                Err(e) => error(req.id, -32602, &e),
+            }
+        }
+        "verifymessage" => {
+            let address = req.params.get(0).or_else(|| req.params.get("address")).and_then(|v| v.as_str()).unwrap_or("");
+            let message = req.params.get(1).or_else(|| req.params.get("message")).and_then(|v| v.as_str());
+            let signature = req.params.get(2).or_else(|| req.params.get("signature")).and_then(|v| v.as_str());
+            let (Some(message), Some(signature)) = (message, signature) else {
+                return error(req.id, -32602, "expected [address, message, signature]");
+            };
+            match wallet::verify_message(address, message, signature) {
+                Ok(valid) => success(req.id, json!(valid)),
+                Err(e) => error(req.id, -32602, &e),
+            }
+        }
+        "walletprocesspsbt" => {
+            // Signs with every node-wallet key: a spend by another route
+            if auth.token.is_none() { return error(req.id, -32000, "walletprocesspsbt needs the node to run with --rpc-token"); }
+            let Some(psbt) = req.params.get(0).or_else(|| req.params.get("psbt")).and_then(|v| v.as_str()) else {
+                return error(req.id, -32602, "missing psbt parameter");
+            };
+            let mut ptx = match wallet::PartialTx::from_hex(psbt) { Ok(p) => p, Err(e) => return error(req.id, -32602, &e) };
+            let guard = state.wallet.lock().await;
+            let Some(w) = guard.as_ref() else { return error(req.id, -32000, "no wallet loaded") };
+            match ptx.sign_with_wallet(w) {
+                Ok(signed) => success(req.id, json!({"psbt": ptx.to_hex(), "signed": signed, "complete": ptx.is_complete()})),
+                Err(e) => error(req.id, -32000, &e),
+            }
+        }
+        "finalizepsbt" => {
+            // Accepts one psbt or an array of co-signers' psbts to combine first
+            let param = req.params.get(0).or_else(|| req.params.get("psbt"));
+            let hexes: Vec<&str> = match param {
+                Some(serde_json::Value::String(s)) => vec![s.as_str()],
+                Some(serde_json::Value::Array(a)) => a.iter().filter_map(|v| v.as_str()).collect(),
+                _ => vec![],
+            };
+            if hexes.is_empty() { return error(req.id, -32602, "missing psbt parameter"); }
+            let mut combined = match wallet::PartialTx::from_hex(hexes[0]) { Ok(p) => p, Err(e) => return error(req.id, -32602, &e) };
+            for h in &hexes[1..] {
+                let other = match wallet::PartialTx::from_hex(h) { Ok(p) => p, Err(e) => return error(req.id, -32602, &e) };
+                if let Err(e) = combined.combine(&other) { return error(req.id, -32602, &e); }
+            }
+            if !combined.is_complete() {
+                return success(req.id, json!({"psbt": combined.to_hex(), "complete": false, "missing_inputs": combined.missing_inputs()}));
+            }
+            match combined.finalize() {
+                Ok(tx) => success(req.id, json!({"complete": true, "txid": hex::encode(crate::crypto::txid::txid_v1(&tx)), "tx": tx})),
+                Err(e) => error(req.id, -32000, &e),
+            }
+        }
+        "estimatefee" => {
+            use crate::core::fees::{self, EstimateMode};
+            let target = req.params.get(0).or_else(|| req.params.get("target")).and_then(|v| v.as_u64()).unwrap_or(6) as usize;
+            if target == 0 || target > fees::MAX_CONF_TARGET {
+                return error(req.id, -32602, &format!("target must be 1..{}", fees::MAX_CONF_TARGET));
+            }
+            let mode_str = req.params.get(1).or_else(|| req.params.get("mode")).and_then(|v| v.as_str()).unwrap_or("conservative");
+            let Some(mode) = EstimateMode::parse(mode_str) else {
+                return error(req.id, -32602, "mode must be 'economical' or 'conservative'");
+            };
+            let chain = state.chain.read().await;
+            let history = chain.recent_fee_rates(mode.window(target));
+            drop(chain);
+            let pending: Vec<(usize, f64)> = state.mempool.lock().await.get_pending_with_fees().iter()
+                .map(|(tx, _, rate)| (tx.size(), *rate)).collect();
+            let from_history = fees::estimate_fee_rate(&history, target, mode);
+            let from_mempool = fees::mempool_fee_rate(&pending, target);
+            // A backlog deeper than the target outranks what recent blocks paid
+            let estimate = match (from_history, from_mempool) {
+                (Some(h), Some(m)) => Some(h.max(m)),
+                (h, m) => h.or(m),
+            };
+            let feerate = estimate.unwrap_or(fees::FALLBACK_FEE_RATE);
+            let typical_fee = ((feerate * fees::TYPICAL_TX_SIZE as f64).ceil() as u64).max(MIN_TX_FEE);
+            success(req.id, json!({
+                "feerate": feerate, "mode": mode.as_str(), "target": target,
+                "blocks": history.len(), "samples": history.iter().map(|b| b.len()).sum::<usize>(),
+                "mempool_txs": pending.len(), "mempool_feerate": from_mempool,
+                "fallback": estimate.is_none(),
+                "typical_fee": typical_fee as f64 / COIN as f64, "typical_fee_base": typical_fee,
+            }))
+        }
         "getmempool" => {
             let mempool = state.mempool.lock().await;
             let entries: Vec<serde_json::Value> = mempool.get_pending_with_fees().iter().map(|(tx, fee, fee_rate)| json!({
-                "txid":hex::encode(tx.hash()),"size":tx.size(),"fee":*fee as f64/COIN as f64,"fee_base":fee,"fee_rate":fee_rate,
+                "txid":hex::encode(crate::crypto::txid::txid_v1(tx)),"size":tx.size(),"fee":*fee as f64/COIN as f64,"fee_base":fee,"fee_rate":fee_rate,
             })).collect();
             success(req.id, json!({"size":entries.len(),"transactions":entries}))
         }
+        "getmempoolentry" => {
+            let txid = match req.params.get(0).or_else(|| req.params.get("txid")).and_then(|v| v.as_str()).and_then(parse_hash) {
+                Some(h) => h, None => return error(req.id, -32602, "params: [\"txid\"]"),
+            };
+            let mempool = state.mempool.lock().await;
+            match mempool.entry_info(&txid) {
+                Some(e) => success(req.id, json!({
+                    "txid": hex::encode(txid), "size": e.size, "fee": e.fee as f64 / COIN as f64, "fee_base": e.fee,
+                    "fee_rate": e.fee_rate, "time": e.time,
+                    "ancestors": e.ancestors.iter().map(hex::encode).collect::<Vec<_>>(),
+                    "descendants": e.descendants.iter().map(hex::encode).collect::<Vec<_>>(),
+                })),
+                None => error(req.id, -5, "transaction not in mempool"),
+            }
+        }
         "getpeerinfo" => {
             let peers = state.peers.read().await;
             let peer_list: Vec<serde_json::Value> = peers.values().map(|p| json!({
                 "address":p.address,"listen_address":p.listen_address,"version":p.version,
-                "best_height":p.best_height,"last_seen":p.last_seen,
+                "best_height":p.best_height,"last_seen":p.last_seen,"inbound":p.inbound,
+                "supports_compact":p.supports_v2,"relay_txs":p.relay_txs,"ping_ms":p.ping_ms,
             })).collect();
             success(req.id, json!(peer_list))
         }
+        "addnode" => {
+            let addr = req.params.get(0).or_else(|| req.params.get("addr")).and_then(|v| v.as_str()).unwrap_or("");
+            if network::split_host_port(addr).is_none() { return error(req.id, -32602, "params: [\"host:port\"]"); }
+            if state.scoreboard.lock().await.is_banned(addr) { return error(req.id, -23, "address is banned"); }
+            state.add_node(addr).await;
+            success(req.id, json!({"added": addr}))
+        }
+        "disconnectnode" => {
+            let addr = req.params.get(0).or_else(|| req.params.get("addr")).and_then(|v| v.as_str()).unwrap_or("");
+            match state.disconnect_node(addr).await {
+                Some(peer) => success(req.id, json!({"disconnected": peer})),
+                None => error(req.id, -29, "node not connected"),
+            }
+        }
+        "getaddednodeinfo" => {
+            let list: Vec<serde_json::Value> = state.added_node_info().await.into_iter().map(|(addr, peer)| json!({
+                "addednode": addr, "connected": peer.is_some(),
+                "address": peer.as_ref().map(|p| p.address.clone()), "inbound": peer.as_ref().map(|p| p.inbound),
+            })).collect();
+            success(req.id, json!(list))
+        }
+        "setban" => {
+            let ip = req.params.get(0).or_else(|| req.params.get("ip")).and_then(|v| v.as_str()).unwrap_or("");
+            if ip.parse::<std::net::IpAddr>().is_err() { return error(req.id, -32602, "invalid IP address"); }
+            let Some(seconds) = req.params.get(1).or_else(|| req.params.get("seconds")).and_then(|v| v.as_u64()).filter(|&s| s > 0) else {
+                return error(req.id, -32602, "seconds must be a positive integer");
+            };
+            let reason = req.params.get(2).or_else(|| req.params.get("reason")).and_then(|v| v.as_str()).unwrap_or("manual");
+            {
+                let mut sb = state.scoreboard.lock().await;
+                sb.ban(ip, seconds, reason);
+                sb.save(data_dir());
+            }
+            let _ = state.disconnect_tx.send(network::host_of(ip));
+            success(req.id, json!({"ip": ip, "seconds": seconds, "reason": reason}))
+        }
+        "listbanned" => {
+            let sb = state.scoreboard.lock().await;
+            let mut bans = sb.active_bans();
+            bans.sort_by_key(|(_, e)| e.banned_until);
+            let list: Vec<serde_json::Value> = bans.iter().map(|(ip, e)| json!({
+                "ip": ip, "banned_until": e.banned_until, "reason": e.reason,
+            })).collect();
+            success(req.id, json!(list))
+        }
+        "clearbanned" => {
+            let mut sb = state.scoreboard.lock().await;
+            let cleared = sb.ban_count();
+            sb.clear_bans();
+            sb.save(data_dir());
+            success(req.id, json!({"cleared": cleared}))
+        }
         "getblock" => {
             let hash_str = req.params.get(0).or_else(|| req.params.get("hash")).and_then(|v| v.as_str()).unwrap_or("");
             let chain = state.chain.read().await;
@@ -388,22 +1193,192 @@ async fn handle_rpc(req: RpcRequest, state: &Arc<NodeState>) -> RpcResponse {
             }
             error(req.id, -32602, "block not found")
         }
+        "getblockhash" => {
+            let Some(height) = req.params.get(0).or_else(|| req.params.get("height")).and_then(|v| v.as_u64()) else {
+                return error(req.id, -32602, "params: [height]");
+            };
+            let chain = state.chain.read().await;
+            match chain.hash_at_height(height) {
+                Some(hash) => success(req.id, json!(hex::encode(hash))),
+                None => error(req.id, -32602, &format!("block height out of range (tip is {})", chain.height)),
+            }
+        }
+        "getblockheader" => {
+            let id = req.params.get(0).or_else(|| req.params.get("hash"));
+            let verbose = req.params.get(1).or_else(|| req.params.get("verbose")).and_then(|v| v.as_bool()).unwrap_or(true);
+            let chain = state.chain.read().await;
+            let hash = match id {
+                Some(v) if v.is_u64() => v.as_u64().and_then(|h| chain.hash_at_height(h)),
+                Some(v) => v.as_str().and_then(|s| match s.parse::<u64>() {
+                    Ok(h) if s.len() != 64 => chain.hash_at_height(h),
+                    _ => parse_hash(s),
+                }),
+                None => return error(req.id, -32602, "params: [hash_or_height, verbose=true]"),
+            };
+            let Some((hash, header)) = hash.and_then(|h| chain.header(&h).map(|hdr| (h, hdr))) else {
+                return error(req.id, -32602, "block not found");
+            };
+            if !verbose {
+                return success(req.id, json!(hex::encode(bincode::serialize(header).unwrap_or_default())));
+            }
+            success(req.id, json!({
+                "hash": hex::encode(hash), "height": header.height, "version": header.version,
+                "prev_hash": hex::encode(header.prev_hash), "merkle_root": hex::encode(header.merkle_root),
+                "timestamp": header.timestamp, "difficulty_target": header.difficulty_target, "nonce": header.nonce,
+                "in_active_chain": chain.hash_at_height(header.height) == Some(hash),
+            }))
+        }
+        "getorphans" => {
+            let list = state.orphans.lock().await.list(std::time::Instant::now());
+            let entries: Vec<serde_json::Value> = list.iter().map(|o| json!({
+                "hash": hex::encode(o.hash), "height": o.height, "prev_hash": hex::encode(o.prev_hash),
+                "size": o.size, "waiting_secs": o.waiting.as_secs(),
+            })).collect();
+            success(req.id, json!({"count": entries.len(), "orphans": entries}))
+        }
+        "getrejectedblocks" => {
+            let list = state.chain.read().await.recent_rejections();
+            let entries: Vec<serde_json::Value> = list.iter().map(|r| json!({
+                "id": hex::encode(r.id), "height": r.height, "reason": r.reason, "rejected_at": r.rejected_at,
+            })).collect();
+            success(req.id, json!({"count": entries.len(), "blocks": entries}))
+        }
+        "getblockstats" => {
+            let chain = state.chain.read().await;
+            let hash = match req.params.get(0).or_else(|| req.params.get("hash")) {
+                Some(v) if v.is_u64() => v.as_u64().and_then(|h| chain.hash_at_height(h)),
+                Some(v) => v.as_str().and_then(|s| match s.parse::<u64>() {
+                    Ok(h) if s.len() != 64 => chain.hash_at_height(h),
+                    _ => parse_hash(s),
+                }),
+                None => return error(req.id, -32602, "params: [hash_or_height]"),
+            };
+            let Some(hash) = hash.filter(|h| chain.header(h).is_some()) else {
+                return error(req.id, -32602, "block not found");
+            };
+            match chain.block_stats(&hash) {
+                Some(s) => success(req.id, json!({
+                    "height": s.height, "hash": hex::encode(s.hash), "size": s.size, "tx_count": s.tx_count,
+                    "total_out": s.total_out as f64 / COIN as f64, "total_out_base": s.total_out,
+                    "total_fees": s.total_fees as f64 / COIN as f64, "total_fees_base": s.total_fees,
+                    "avg_fee_rate": s.avg_fee_rate, "subsidy": s.subsidy as f64 / COIN as f64,
+                    "coinbase_total": s.coinbase_total as f64 / COIN as f64,
+                    "fee_source": if s.fees_from_undo { "undo" } else { "coinbase" },
+                })),
+                None => error(req.id, -1, "block data pruned"),
+            }
+        }
+        "getcommonancestor" => {
+            let hash_a = req.params.get(0).or_else(|| req.params.get("hash_a")).and_then(|v| v.as_str()).and_then(parse_hash);
+            let hash_b = req.params.get(1).or_else(|| req.params.get("hash_b")).and_then(|v| v.as_str()).and_then(parse_hash);
+            let (a, b) = match (hash_a, hash_b) {
+                (Some(a), Some(b)) => (a, b),
+                _ => return error(req.id, -32602, "params: [hash_a, hash_b] (64 hex chars each)"),
+            };
+            let chain = state.chain.read().await;
+            if chain.header(&a).is_none() || chain.header(&b).is_none() {
+                return error(req.id, -32602, "block not found");
+            }
+            match chain.common_ancestor(&a, &b).and_then(|h| chain.header(&h).map(|hdr| (h, hdr.height))) {
+                Some((hash, height)) => success(req.id, json!({"hash":hex::encode(hash),"height":height})),
+                None => success(req.id, serde_json::Value::Null),
+            }
+        }
+        "getversionbitsstats" => {
+            let window = req.params.get(0).or_else(|| req.params.get("blocks")).and_then(|v| v.as_u64())
+                .unwrap_or(VERSIONBITS_WINDOW).clamp(1, 10_000);
+            let chain = state.chain.read().await;
+            let stats = chain.version_bit_stats(window);
+            drop(chain);
+            let bits: Vec<serde_json::Value> = stats.bit_counts.iter().enumerate()
+                .filter(|(bit, count)| **count > 0 || VERSIONBITS_DEPLOYMENTS.iter().any(|(_, b)| *b as usize == *bit))
+                .map(|(bit, count)| json!({
+                    "bit": bit,
+                    "name": VERSIONBITS_DEPLOYMENTS.iter().find(|(_, b)| *b as usize == bit).map(|(n, _)| *n),
+                    "count": count,
+                    "fraction": *count as f64 / stats.blocks.max(1) as f64,
+                })).collect();
+            success(req.id, json!({
+                "blocks": stats.blocks, "start_height": stats.start_height, "end_height": stats.end_height,
+                "signaling_blocks": stats.signaling_blocks, "bits": bits,
+            }))
+        }
+        "getversionbits" => {
+            // Named deployments, or any bit asked for explicitly
+            let bits: Vec<u32> = match req.params.get(0).or_else(|| req.params.get("bit")) {
+                Some(v) => match v.as_u64() {
+                    Some(bit) if bit < VERSIONBITS_NUM_BITS as u64 => vec![bit as u32],
+                    _ => return error(req.id, -32602, &format!("bit must be 0..{}", VERSIONBITS_NUM_BITS - 1)),
+                },
+                None => VERSIONBITS_DEPLOYMENTS.iter().map(|(_, bit)| *bit).collect(),
+            };
+            let chain = state.chain.read().await;
+            let deployments: Vec<serde_json::Value> = bits.into_iter().map(|bit| {
+                let s = chain.deployment_status(bit);
+                json!({
+                    "bit": bit,
+                    "name": VERSIONBITS_DEPLOYMENTS.iter().find(|(_, b)| *b == bit).map(|(n, _)| *n),
+                    "state": s.state.as_str(), "since": s.since,
+                    "period_start": s.period_start, "period_blocks": s.period_blocks,
+                    "period_signaled": s.period_signaled,
+                })
+            }).collect();
+            success(req.id, json!({
+                "period": VERSIONBITS_PERIOD, "threshold": VERSIONBITS_THRESHOLD, "deployments": deployments,
+            }))
+        }
         "getmininginfo" => {
             let chain = state.chain.read().await; let diff = chain.next_difficulty();
             success(req.id, json!({"height":chain.height+1,"difficulty":diff,"fractional_difficulty":chain.fractional_difficulty(),
-                "estimated_hashes":estimated_hashes_for_difficulty(diff),"block_reward":block_reward(chain.height+1) as f64/COIN as f64}))
+                "estimated_hashes":estimated_hashes_for_difficulty(diff),"block_reward":block_reward(chain.height+1) as f64/COIN as f64,
+                "networkhashps":chain.network_hashps(0)}))
+        }
+        "getdifficultyhistory" => {
+            let count = req.params.get(0).or_else(|| req.params.get("count")).and_then(|v| v.as_u64())
+                .unwrap_or(100).clamp(1, MAX_DIFFICULTY_HISTORY);
+            let chain = state.chain.read().await;
+            let start = (chain.height + 1).saturating_sub(count);
+            // One extra header before the window gives the first block its solve time
+            let headers = chain.headers_in_range(start.saturating_sub(1), (count + 1) as u32);
+            let blocks: Vec<serde_json::Value> = headers.iter().enumerate()
+                .filter(|(_, h)| h.height >= start)
+                .map(|(i, h)| {
+                    let solve_time = i.checked_sub(1).map(|p| h.timestamp as i64 - headers[p].timestamp as i64);
+                    json!({"height": h.height, "timestamp": h.timestamp, "difficulty": h.difficulty_target, "solve_time": solve_time})
+                })
+                .collect();
+            success(req.id, json!({"target_block_time": TARGET_BLOCK_TIME, "blocks": blocks}))
+        }
+        "getnetworkhashps" => {
+            let blocks = req.params.get(0).or_else(|| req.params.get("blocks")).and_then(|v| v.as_u64()).unwrap_or(0);
+            let chain = state.chain.read().await;
+            success(req.id, json!(chain.network_hashps(blocks)))
+        }
+        "getpoolstats" => {
+            let pool = state.pool.lock().await.clone();
+            match pool {
+                Some(pool) => success(req.id, json!(pool.read().await.summary())),
+                None => success(req.id, serde_json::Value::Null),
+            }
+        }
+        "getpoolworkers" => {
+            let pool = state.pool.lock().await.clone();
+            match pool {
+                Some(pool) => {
+                    let now = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap().as_secs();
+                    success(req.id, json!(pool.read().await.worker_summaries(now)))
+                }
+                None => success(req.id, json!([])),
+            }
         }
         "getrichlist" => {
             let count = req.params.get(0).and_then(|v| v.as_u64()).unwrap_or(20) as usize;
-            let chain = state.chain.read().await;
-            let mut balances: std::collections::HashMap<Hash256, u64> = std::collections::HashMap::new();
-            for (_op, entry) in chain.utxo_set.iter() { *balances.entry(entry.output.pubkey_hash).or_insert(0) += entry.output.amount; }
-            let mut sorted: Vec<(Hash256, u64)> = balances.into_iter().collect();
-            sorted.sort_by(|a, b| b.1.cmp(&a.1));
-            let list: Vec<serde_json::Value> = sorted.iter().take(count).enumerate().map(|(rank, (hash, amount))| json!({
-                "rank":rank+1,"address":wallet::pubkey_hash_to_address(hash),"balance":*amount as f64/COIN as f64,"balance_base":amount,
+            let rich = state.chain.read().await.rich_list();
+            let list: Vec<serde_json::Value> = rich.entries.iter().take(count).enumerate().map(|(rank, e)| json!({
+                "rank":rank+1,"address":wallet::pubkey_hash_to_address(&e.pubkey_hash),
+                "balance":e.balance as f64/COIN as f64,"balance_base":e.balance,"utxos":e.utxos,
             })).collect();
-            success(req.id, json!({"total_addresses":sorted.len(),"addresses":list}))
+            success(req.id, json!({"tip":hex::encode(rich.tip),"total_addresses":rich.entries.len(),"addresses":list}))
         }
          "getblocktemplate" => {
             let miner_hex = req.params.get(0).and_then(|v| v.as_str()).unwrap_or("");
@@ -422,14 +1397,18 @@ async fn handle_rpc(req: RpcRequest, state: &Arc<NodeState>) -> RpcResponse {
 
             let chain = state.chain.read().await;
             let mp = state.mempool.lock().await;
-            let pending = mp.get_pending();
+            let pending = mp.select_for_block(MAX_BLOCK_SIZE, MAX_TXS_PER_BLOCK - 1);
             drop(mp);
 
             let cfg = miner::MinerConfig {
                 miner_pubkey_hash: miner_hash,
-                community_fund_hash: [0xCF; 32],
+                community_fund_hash: community_fund_hash(),
                 threads: 1,
                 miner_tag: String::new(),
+                payout_targets: Vec::new(),
+                version_bits: 0,
+                min_fee_rate: 0.0,
+                max_txs: MAX_TXS_PER_BLOCK - 1,
             };
             let template = miner::create_block_template(&chain, &pending, &cfg);
             let difficulty = chain.next_difficulty();
@@ -550,6 +1529,138 @@ async fn handle_rpc(req: RpcRequest, state: &Arc<NodeState>) -> RpcResponse {
     }
 }
 
+/// Parse a 64-char hex string into a block/tx hash
+fn parse_hash(s: &str) -> Option<Hash256> {
+    let bytes = hex::decode(s).ok()?;
+    if bytes.len() != 32 { return None; }
+    let mut hash = [0u8; 32]; hash.copy_from_slice(&bytes);
+    Some(hash)
+}
+
+/// Full view of a confirmed transaction; prevouts are resolved through the tx index
+fn confirmed_tx_to_json(tx: &Transaction, block: &Block, chain: &crate::core::chain::Chain) -> serde_json::Value {
+    let txid = crate::crypto::txid::txid_v1(tx);
+    let height = block.header.height;
+    let location = chain.tx_location(&txid);
+    let mut input_total: u64 = 0;
+    let inputs: Vec<serde_json::Value> = if tx.is_coinbase() {
+        vec![json!({"type":"coinbase","amount": tx.total_output() as f64 / COIN as f64, "tag": tx.coinbase_tag()})]
+    } else {
+        tx.inputs.iter().map(|inp| {
+            let prev = chain.get_transaction(&inp.previous_output.txid)
+                .and_then(|(_, ptx)| ptx.outputs.get(inp.previous_output.vout as usize));
+            let (amount, address) = match prev {
+                Some(out) => (out.amount, wallet::pubkey_hash_to_address(&out.pubkey_hash)),
+                None => (0, String::new()),
+            };
+            input_total += amount;
+            json!({"txid":hex::encode(inp.previous_output.txid),"vout":inp.previous_output.vout,
+                "amount":amount as f64/COIN as f64,"amount_base":amount,"address":address})
+        }).collect()
+    };
+    let outputs: Vec<serde_json::Value> = tx.outputs.iter().enumerate().map(|(vout, out)| {
+        let spent = !chain.utxo_set.contains(&OutPoint { txid, vout: vout as u32 });
+        json!({"vout":vout,"amount":out.amount as f64/COIN as f64,"amount_base":out.amount,
+            "address":wallet::pubkey_hash_to_address(&out.pubkey_hash),"spent":spent})
+    }).collect();
+    let output_total = tx.total_output();
+    let fee = if tx.is_coinbase() { 0 } else { input_total.saturating_sub(output_total) };
+    json!({
+        "txid":hex::encode(txid),"hash":hex::encode(tx.hash()),
+        "block_hash":hex::encode(location.map(|l| l.block_hash).unwrap_or_else(|| block.header.hash())),
+        "block_height":height,"tx_index":location.map(|l| l.index).unwrap_or(0),"timestamp":block.header.timestamp,
+        "confirmations":chain.height-height+1,"is_coinbase":tx.is_coinbase(),
+        "inputs":inputs,"outputs":outputs,
+        "input_total":input_total as f64/COIN as f64,"output_total":output_total as f64/COIN as f64,
+        "fee":fee as f64/COIN as f64,"size":tx.size(),
+    })
+}
+
+/// Canonical serialized bytes of a transaction, hex-encoded
+pub fn raw_tx_hex(tx: &Transaction) -> String {
+    hex::encode(bincode::serialize(tx).expect("tx serialization failed"))
+}
+
+/// Parse the output of `raw_tx_hex`; trailing bytes are an error
+pub fn decode_raw_tx(hex_str: &str) -> Result<Transaction, String> {
+    let bytes = hex::decode(hex_str.trim()).map_err(|e| format!("invalid hex: {}", e))?;
+    if bytes.len() > MAX_BLOCK_SIZE {
+        return Err("transaction larger than a block".into());
+    }
+    let tx: Transaction = bincode::deserialize(&bytes).map_err(|e| format!("tx decode failed: {}", e))?;
+    if tx.size() != bytes.len() {
+        return Err(format!("tx decode failed: {} trailing bytes", bytes.len() - tx.size()));
+    }
+    Ok(tx)
+}
+
+/// Structure of an unconfirmed or not-yet-broadcast transaction. Inputs are
+/// priced from `utxos`; `fee` is null unless every one of them was found.
+/// Shared with the CLI's offline `decode-tx`.
+pub fn decoded_tx_json(tx: &Transaction, utxos: &crate::core::chain::UtxoSet) -> serde_json::Value {
+    let mut input_total = Some(0u64);
+    let inputs: Vec<serde_json::Value> = if tx.is_coinbase() {
+        vec![json!({"type":"coinbase","tag": tx.coinbase_tag(),"sequence":tx.inputs.first().map(|i| i.sequence)})]
+    } else {
+        tx.inputs.iter().map(|inp| {
+            let mut entry = json!({"txid":hex::encode(inp.previous_output.txid),"vout":inp.previous_output.vout,"sequence":inp.sequence});
+            match utxos.get(&inp.previous_output) {
+                Some(prev) => {
+                    input_total = input_total.map(|t| t + prev.output.amount);
+                    entry["amount"] = json!(prev.output.amount as f64 / COIN as f64);
+                    entry["amount_base"] = json!(prev.output.amount);
+                    entry["address"] = json!(wallet::pubkey_hash_to_address(&prev.output.pubkey_hash));
+                }
+                None => input_total = None,
+            }
+            entry
+        }).collect()
+    };
+    let outputs: Vec<serde_json::Value> = tx.outputs.iter().enumerate().map(|(vout, out)| json!({
+        "vout":vout,"amount":out.amount as f64/COIN as f64,"amount_base":out.amount,
+        "address":wallet::pubkey_hash_to_address(&out.pubkey_hash),
+        "type":decode_script_json(&out.script_pubkey)["type"],
+    })).collect();
+    let fee = if tx.is_coinbase() { None } else { input_total.map(|t| t.saturating_sub(tx.total_output())) };
+    json!({
+        "txid":hex::encode(crate::crypto::txid::txid_v1(tx)),"hash":hex::encode(tx.hash()),
+        "version":tx.version,"lock_time":tx.lock_time,"size":tx.size(),"is_coinbase":tx.is_coinbase(),
+        "inputs":inputs,"outputs":outputs,"output_total":tx.total_output() as f64/COIN as f64,
+        "fee":fee.map(|f| f as f64 / COIN as f64),"fee_base":fee,
+        "fee_rate":fee.map(|f| f as f64 / tx.size().max(1) as f64),
+    })
+}
+
+/// Lock type and addresses of a script_pubkey
+fn decode_script_json(script: &[u8]) -> serde_json::Value {
+    use crate::core::script;
+    if script.is_empty() {
+        return json!({"type": "pubkeyhash", "note": "implicit: locked to the output's pubkey_hash field"});
+    }
+    if let Ok(hash) = script::parse_script_pubkey_p2pkh(script) {
+        return json!({"type": "pubkeyhash", "address": wallet::pubkey_hash_to_address(&hash)});
+    }
+    match script::parse_script_pubkey_multisig(script) {
+        Ok((required, key_hashes)) => json!({
+            "type": "multisig", "required": required,
+            "address": wallet::pubkey_hash_to_address(&script::multisig_script_hash(script)),
+            "keys": key_hashes.iter().map(wallet::pubkey_hash_to_address).collect::<Vec<_>>(),
+        }),
+        Err(_) => json!({"type": "nonstandard", "size": script.len()}),
+    }
+}
+
+/// `verifychain` result, shared with the CLI's offline fallback
+pub fn verify_chain_json(chain: &crate::core::chain::Chain, depth: Option<u64>) -> serde_json::Value {
+    match chain.verify_chain(depth) {
+        Ok(v) => json!({
+            "ok": true, "start_height": v.start_height, "end_height": v.end_height,
+            "blocks_checked": v.blocks_checked, "utxos": v.utxos, "utxo_set_checked": v.utxo_set_checked,
+        }),
+        Err(e) => json!({"ok": false, "height": e.height, "reason": e.reason}),
+    }
+}
+
 fn block_to_json(block: &Block, chain: &crate::core::chain::Chain) -> serde_json::Value {
     let hash = block.header.hash(); let height = block.header.height;
     let miner_addr = if !block.transactions.is_empty() && !block.transactions[0].outputs.is_empty() {
@@ -566,7 +1677,7 @@ fn block_to_json(block: &Block, chain: &crate::core::chain::Chain) -> serde_json
         let recipients: Vec<serde_json::Value> = tx.outputs.iter().map(|out| json!({
             "address": wallet::pubkey_hash_to_address(&out.pubkey_hash), "amount": out.amount as f64 / COIN as f64,
         })).collect();
-        json!({"txid":hex::encode(tx.hash()),"index":i,"is_coinbase":tx.is_coinbase(),"input_count":tx.inputs.len(),
+        json!({"txid":hex::encode(crate::crypto::txid::txid_v1(tx)),"index":i,"is_coinbase":tx.is_coinbase(),"input_count":tx.inputs.len(),
             "output_count":tx.outputs.len(),"output_total":output_total as f64/COIN as f64,"size":tx.size(),"recipients":recipients})
     }).collect();
     json!({"hash":hex::encode(hash),"height":height,"version":block.header.version,
@@ -578,6 +1689,12 @@ fn block_to_json(block: &Block, chain: &crate::core::chain::Chain) -> serde_json
 }
 
 // ─── RPC Client ────────────────────────────────────────────────────
+
+/// Bearer token the CLI sends with every call (set once from `--rpc-token`)
+static CLIENT_TOKEN: std::sync::OnceLock<String> = std::sync::OnceLock::new();
+
+pub fn set_client_token(token: String) { let _ = CLIENT_TOKEN.set(token); }
+
 pub fn rpc_call(port: u16, method: &str, params: serde_json::Value) -> Result<serde_json::Value, String> {
     let request = RpcRequest { method: method.to_string(), params, id: 1 };
     let body = serde_json::to_string(&request).unwrap();
@@ -585,11 +1702,13 @@ pub fn rpc_call(port: u16, method: &str, params: serde_json::Value) -> Result<se
     let addr = format!("127.0.0.1:{}", port);
     let mut stream = TcpStream::connect(&addr).map_err(|_| format!("cannot connect to node RPC at {}. Is the node running?", addr))?;
     stream.set_read_timeout(Some(std::time::Duration::from_secs(10))).ok();
-    let http_request = format!("POST / HTTP/1.1\r\nHost: 127.0.0.1\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n{}", body.len(), body);
+    let authorization = CLIENT_TOKEN.get().map(|t| format!("Authorization: Bearer {}\r\n", t)).unwrap_or_default();
+    let http_request = format!("POST / HTTP/1.1\r\nHost: 127.0.0.1\r\nContent-Type: application/json\r\n{}Content-Length: {}\r\n\r\n{}", authorization, body.len(), body);
     stream.write_all(http_request.as_bytes()).map_err(|e| format!("write error: {}", e))?;
     let mut response = Vec::new();
     stream.read_to_end(&mut response).map_err(|e| format!("read error: {}", e))?;
     let response_str = String::from_utf8_lossy(&response);
+    if response_str.starts_with("HTTP/1.1 401") { return Err("node RPC rejected the request: pass the node's --rpc-token".into()); }
     let body_start = response_str.find("\r\n\r\n").ok_or("invalid HTTP response")?;
     let json_body = &response_str[body_start + 4..];
     let rpc_response: RpcResponse = serde_json::from_str(json_body).map_err(|e| format!("JSON parse error: {}", e))?;
@@ -597,3 +1716,252 @@ pub fn rpc_call(port: u16, method: &str, params: serde_json::Value) -> Result<se
     rpc_response.result.ok_or("empty result".to_string())
 }
 pub fn try_rpc_call(port: u16, method: &str, params: serde_json::Value) -> Option<serde_json::Value> { rpc_call(port, method, params).ok() }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bearer_token_parsing() {
+        assert_eq!(bearer_token("Authorization: Bearer s3cret\r\n"), Some("s3cret"));
+        assert_eq!(bearer_token("authorization:bearer  s3cret"), Some("s3cret"));
+        assert_eq!(bearer_token("Authorization: Basic dXNlcjpwYXNz"), None);
+        assert_eq!(bearer_token("X-Authorization: Bearer s3cret"), None);
+        assert_eq!(bearer_token("Authorization: Bearer"), None);
+    }
+
+    #[test]
+    fn test_auth_permits() {
+        let open = RpcAuth::default();
+        assert!(open.permits(None, false));
+
+        let mut auth = RpcAuth { token: Some("s3cret".into()), ..Default::default() };
+        assert!(auth.permits(Some("s3cret"), false));
+        assert!(!auth.permits(Some("s3cre"), false));
+        assert!(!auth.permits(Some("S3CRET"), false));
+        assert!(!auth.permits(None, false));
+        assert!(auth.permits(None, true));
+        auth.protect_get = true;
+        assert!(!auth.permits(None, true));
+        assert!(auth.permits(Some("s3cret"), true));
+    }
+
+    /// Serve `handle_http` on an ephemeral localhost port
+    async fn spawn_server(auth: RpcAuth, role: Role) -> std::net::SocketAddr {
+        let _ = std::panic::catch_unwind(|| init_network(false));
+        spawn_server_with(NodeState::new(0), auth, role).await
+    }
+
+    async fn spawn_server_with(state: Arc<NodeState>, auth: RpcAuth, role: Role) -> std::net::SocketAddr {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(serve_http(listener, state, Arc::new(auth), role));
+        addr
+    }
+
+    async fn send(addr: std::net::SocketAddr, request: String) -> String {
+        let mut stream = tokio::net::TcpStream::connect(addr).await.unwrap();
+        stream.write_all(request.as_bytes()).await.unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+        response
+    }
+
+    fn post(method: &str, auth_header: &str) -> String {
+        let body = format!(r#"{{"method":"{}","params":[],"id":1}}"#, method);
+        format!("POST / HTTP/1.1\r\n{}Content-Length: {}\r\n\r\n{}", auth_header, body.len(), body)
+    }
+
+    #[tokio::test]
+    async fn test_websocket_only_on_ws_path() {
+        let addr = spawn_server(RpcAuth::default(), Role::Rpc).await;
+        let upgrade = |path: &str| format!(
+            "GET {} HTTP/1.1\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\n\r\n", path);
+        assert!(send(addr, upgrade("/metrics")).await.starts_with("HTTP/1.1 404"));
+
+        let mut stream = tokio::net::TcpStream::connect(addr).await.unwrap();
+        stream.write_all(upgrade("/ws").as_bytes()).await.unwrap();
+        let mut head = [0u8; 12];
+        stream.read_exact(&mut head).await.unwrap();
+        assert_eq!(&head, b"HTTP/1.1 101");
+    }
+
+    #[test]
+    fn test_cors_allowlist() {
+        let auth = RpcAuth { cors_origins: vec!["https://wallet.example".into()], ..Default::default() };
+        assert_eq!(auth.cors_headers(Some("https://wallet.example")), "Access-Control-Allow-Origin: https://wallet.example\r\nVary: Origin\r\n");
+        assert_eq!(auth.cors_headers(Some("https://evil.example")), "");
+        assert_eq!(auth.cors_headers(None), "");
+        let any = RpcAuth { cors_origins: vec!["*".into()], ..Default::default() };
+        assert!(any.cors_headers(Some("https://evil.example")).contains("https://evil.example"));
+        assert_eq!(RpcAuth::default().cors_headers(Some("https://wallet.example")), "");
+    }
+
+    #[tokio::test]
+    async fn test_cors_on_the_wire() {
+        let _ = std::panic::catch_unwind(|| init_network(false));
+        let state = NodeState::new(0);
+        let auth = RpcAuth { cors_origins: vec!["https://wallet.example".into()], ..Default::default() };
+        let addr = spawn_server_with(state.clone(), auth, Role::Combined).await;
+        let preflight = |origin: &str| format!("OPTIONS / HTTP/1.1\r\nOrigin: {}\r\nAccess-Control-Request-Method: POST\r\n\r\n", origin);
+
+        let allowed = send(addr, preflight("https://wallet.example")).await;
+        assert!(allowed.contains("Access-Control-Allow-Origin: https://wallet.example\r\n"), "{}", allowed);
+        let denied = send(addr, preflight("https://evil.example")).await;
+        assert!(!denied.contains("Access-Control-Allow-Origin"), "{}", denied);
+        // A simple POST needs no preflight, so other origins are refused outright
+        let refused = send(addr, post("stop", "Origin: https://evil.example\r\n")).await;
+        assert!(refused.starts_with("HTTP/1.1 403"), "{}", refused);
+        assert!(!state.shutdown.load(std::sync::atomic::Ordering::SeqCst));
+        let served = send(addr, post("getblockcount", "Origin: https://wallet.example\r\n")).await;
+        assert!(served.starts_with("HTTP/1.1 200") && served.contains("Access-Control-Allow-Origin: https://wallet.example"), "{}", served);
+        // The explorer page served from this port calls it same-origin
+        let own = format!("Origin: http://{}\r\nHost: {}\r\n", addr, addr);
+        assert!(send(addr, post("getblockcount", &own)).await.starts_with("HTTP/1.1 200"));
+        // Explorer GETs stay readable from anywhere
+        assert!(send(addr, "GET / HTTP/1.1\r\nOrigin: https://evil.example\r\n\r\n".into()).await.contains("Access-Control-Allow-Origin: *"));
+    }
+
+    #[tokio::test]
+    async fn test_sendtoaddress() {
+        let _ = std::panic::catch_unwind(|| init_network(false));
+        let state = NodeState::new(0);
+        let w = wallet::Wallet::new("");
+        let funded = OutPoint { txid: [7; 32], vout: 0 };
+        let hash = w.primary_pubkey_hash();
+        state.chain.write().await.utxo_set.add(funded.clone(), crate::core::chain::UtxoEntry {
+            output: TxOutput { amount: COIN, pubkey_hash: hash, script_pubkey: crate::core::script::script_p2pkh(&hash) },
+            height: 0, is_coinbase: false,
+        });
+        *state.wallet.lock().await = Some(w);
+        let to = wallet::pubkey_hash_to_address(&[9; 32]);
+        let call = |auth: RpcAuth| {
+            let (state, to) = (state.clone(), to.clone());
+            async move { handle_rpc(RpcRequest { method: "sendtoaddress".into(), params: json!([to, 0.25]), id: 1 }, &state, &auth).await }
+        };
+        let with_token = RpcAuth { token: Some("s3cret".into()), ..Default::default() };
+
+        assert!(call(RpcAuth::default()).await.error.unwrap().message.contains("--rpc-token"));
+        // Fees no supply could cover are refused before touching the wallet
+        for fee in [json!(-0.1), json!(1e300), json!(MAX_SUPPLY as f64)] {
+            let params = json!([to, 0.25, fee]);
+            let resp = handle_rpc(RpcRequest { method: "sendtoaddress".into(), params, id: 1 }, &state, &with_token).await;
+            assert_eq!(resp.error.unwrap().code, -32602);
+        }
+        assert!(state.mempool.lock().await.is_empty());
+        let txid = call(with_token.clone()).await.result.unwrap();
+        let mempool = state.mempool.lock().await;
+        let tx = mempool.get(&hex::decode(txid.as_str().unwrap()).unwrap().try_into().unwrap()).unwrap().clone();
+        assert_eq!(tx.inputs[0].previous_output, funded);
+        assert_eq!(tx.outputs[0].amount, COIN / 4);
+        drop(mempool);
+
+        // The only coin is now spent in the mempool; it isn't picked twice
+        let again = call(with_token).await.error.unwrap().message;
+        assert!(again.contains("insufficient"), "{}", again);
+    }
+
+    #[tokio::test]
+    async fn test_getnewaddress() {
+        let _ = std::panic::catch_unwind(|| init_network(false));
+        let state = NodeState::new(0);
+        *state.wallet.lock().await = Some(wallet::Wallet::from_seed(&[3; 32]));
+        let call = |auth: RpcAuth, params: serde_json::Value| {
+            let state = state.clone();
+            async move { handle_rpc(RpcRequest { method: "getnewaddress".into(), params, id: 1 }, &state, &auth).await }
+        };
+        let auth = RpcAuth { token: Some("s3cret".into()), ..Default::default() };
+
+        assert!(call(RpcAuth::default(), json!([])).await.error.is_some());
+        let address = call(auth, json!(["deposits"])).await.result.unwrap();
+        let address = address.as_str().unwrap();
+        let guard = state.wallet.lock().await;
+        let w = guard.as_ref().unwrap();
+        // Second HD child of the seed, so it can be restored from the backup
+        assert_eq!(address, wallet::Wallet::restore_from_seed(&[3; 32], 2).addresses()[1]);
+        assert_eq!(w.addresses().len(), 2);
+        assert_eq!(w.label_of(&wallet::address_to_pubkey_hash(address).unwrap()), Some("deposits"));
+    }
+
+    #[tokio::test]
+    async fn test_wallet_signing_needs_token() {
+        let _ = std::panic::catch_unwind(|| init_network(false));
+        let state = NodeState::new(0);
+        let w = wallet::Wallet::from_seed(&[4; 32]);
+        let address = w.addresses()[0].clone();
+        *state.wallet.lock().await = Some(w);
+        let call = |method: &str, params: serde_json::Value, auth: RpcAuth| {
+            let (state, method) = (state.clone(), method.to_string());
+            async move { handle_rpc(RpcRequest { method, params, id: 1 }, &state, &auth).await }
+        };
+        let with_token = RpcAuth { token: Some("s3cret".into()), ..Default::default() };
+
+        let err = call("signmessage", json!([address, "hi"]), RpcAuth::default()).await.error.unwrap().message;
+        assert!(err.contains("--rpc-token"), "{}", err);
+        let sig = call("signmessage", json!([address, "hi"]), with_token.clone()).await.result.unwrap();
+        assert_eq!(wallet::verify_message(&address, "hi", sig.as_str().unwrap()), Ok(true));
+
+        let err = call("walletprocesspsbt", json!(["00"]), RpcAuth::default()).await.error.unwrap().message;
+        assert!(err.contains("--rpc-token"), "{}", err);
+        // With the token it gets as far as parsing the psbt
+        assert_eq!(call("walletprocesspsbt", json!(["00"]), with_token).await.error.unwrap().code, -32602);
+    }
+
+    #[tokio::test]
+    async fn test_stop_needs_token() {
+        let _ = std::panic::catch_unwind(|| init_network(false));
+        let state = NodeState::new(0);
+        let stop = |auth: RpcAuth| {
+            let state = state.clone();
+            async move { handle_rpc(RpcRequest { method: "stop".into(), params: json!([]), id: 1 }, &state, &auth).await }
+        };
+        assert!(stop(RpcAuth::default()).await.error.unwrap().message.contains("--rpc-token"));
+        assert!(!state.shutdown.load(std::sync::atomic::Ordering::SeqCst));
+        assert!(stop(RpcAuth { token: Some("s3cret".into()), ..Default::default() }).await.result.is_some());
+        assert!(state.shutdown.load(std::sync::atomic::Ordering::SeqCst));
+    }
+
+    #[tokio::test]
+    async fn test_sendmany_rejects_bad_recipient_atomically() {
+        let _ = std::panic::catch_unwind(|| init_network(false));
+        let state = NodeState::new(0);
+        *state.wallet.lock().await = Some(wallet::Wallet::new(""));
+        let good = wallet::pubkey_hash_to_address(&[9; 32]);
+        let auth = RpcAuth { token: Some("s3cret".into()), ..Default::default() };
+        let params = json!([{ good.clone(): 0.1, "not-an-address": 0.1 }]);
+        let resp = handle_rpc(RpcRequest { method: "sendmany".into(), params, id: 1 }, &state, &auth).await;
+        assert!(resp.error.unwrap().message.contains("invalid address: not-an-address"));
+        let resp = handle_rpc(RpcRequest { method: "sendmany".into(), params: json!([{ good: 0.1 }]), id: 1 }, &state, &auth).await;
+        assert!(resp.error.unwrap().message.contains("insufficient"));
+        assert!(state.mempool.lock().await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_post_without_token_gets_401() {
+        let addr = spawn_server(RpcAuth { token: Some("s3cret".into()), ..Default::default() }, Role::Combined).await;
+        assert!(send(addr, post("getblockcount", "")).await.starts_with("HTTP/1.1 401"));
+        assert!(send(addr, post("getblockcount", "Authorization: Bearer wrong\r\n")).await.starts_with("HTTP/1.1 401"));
+        assert!(send(addr, post("getblockcount", "Authorization: Bearer s3cret\r\n")).await.starts_with("HTTP/1.1 200"));
+        // The explorer stays public unless GETs are gated too
+        assert!(send(addr, "GET / HTTP/1.1\r\n\r\n".into()).await.starts_with("HTTP/1.1 200"));
+    }
+
+    #[tokio::test]
+    async fn test_explorer_port_split() {
+        let auth = RpcAuth { token: Some("s3cret".into()), ..Default::default() };
+        let rpc = spawn_server(auth.clone(), Role::Rpc).await;
+        let explorer = spawn_server(auth, Role::Explorer).await;
+
+        assert!(send(rpc, "GET / HTTP/1.1\r\n\r\n".into()).await.starts_with("HTTP/1.1 404"));
+        assert!(send(rpc, "GET /metrics HTTP/1.1\r\n\r\n".into()).await.starts_with("HTTP/1.1 200"));
+        assert!(send(rpc, post("getinfo", "")).await.starts_with("HTTP/1.1 401"));
+
+        assert!(send(explorer, "GET / HTTP/1.1\r\n\r\n".into()).await.starts_with("HTTP/1.1 200"));
+        assert!(send(explorer, "GET /metrics HTTP/1.1\r\n\r\n".into()).await.starts_with("HTTP/1.1 404"));
+        // The page's own calls work without the token; nothing else does
+        let info = send(explorer, post("getinfo", "")).await;
+        assert!(info.contains(r#""error":null"#), "{}", info);
+        let stop = send(explorer, post("stop", "Authorization: Bearer s3cret\r\n")).await;
+        assert!(stop.contains("not available on the explorer port"), "{}", stop);
+    }
+}
@@ -0,0 +1,302 @@
+//! WebSocket event stream (RFC 6455, server side only).
+//!
+//! `GET /ws` with `Upgrade: websocket` switches the RPC connection to a
+//! WebSocket. Nothing is pushed until the client subscribes:
+//!
+//!   {"subscribe": ["blocks", "txs"]}     {"unsubscribe": ["txs"]}
+//!
+//! after which new blocks (from `block_tx`) and new mempool transactions
+//! (from `tx_tx`) arrive as `{"event": "block", ...}` / `{"event": "tx", ...}`
+//! text frames. Only unfragmented frames are understood; anything else from
+//! the client is ignored.
+
+use serde_json::json;
+use sha1::{Digest, Sha1};
+use std::sync::Arc;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::sync::broadcast::error::RecvError;
+
+use crate::core::params::COIN;
+use crate::core::types::{Block, Transaction};
+use crate::network::NodeState;
+
+/// Fixed GUID every server appends to the client's key (RFC 6455 §1.3)
+const WS_GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+
+/// Largest client frame accepted; clients only ever send subscriptions
+pub const MAX_CLIENT_FRAME: usize = 64 * 1024;
+
+pub const OP_CONTINUATION: u8 = 0x0;
+pub const OP_TEXT: u8 = 0x1;
+pub const OP_CLOSE: u8 = 0x8;
+pub const OP_PING: u8 = 0x9;
+pub const OP_PONG: u8 = 0xA;
+
+/// `Sec-WebSocket-Accept` value for a client's `Sec-WebSocket-Key`
+pub fn accept_key(client_key: &str) -> String {
+    let mut sha = Sha1::new();
+    sha.update(client_key.trim().as_bytes());
+    sha.update(WS_GUID.as_bytes());
+    base64_encode(&sha.finalize())
+}
+
+fn base64_encode(bytes: &[u8]) -> String {
+    const TABLE: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut out = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let n = (chunk[0] as u32) << 16
+            | (*chunk.get(1).unwrap_or(&0) as u32) << 8
+            | *chunk.get(2).unwrap_or(&0) as u32;
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(TABLE[(n >> (18 - 6 * i) & 0x3F) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+/// One frame as sent by the server: FIN set, never masked
+pub fn encode_frame(opcode: u8, payload: &[u8]) -> Vec<u8> {
+    let mut frame = Vec::with_capacity(payload.len() + 10);
+    frame.push(0x80 | opcode);
+    match payload.len() {
+        n if n < 126 => frame.push(n as u8),
+        n if n <= u16::MAX as usize => {
+            frame.push(126);
+            frame.extend_from_slice(&(n as u16).to_be_bytes());
+        }
+        n => {
+            frame.push(127);
+            frame.extend_from_slice(&(n as u64).to_be_bytes());
+        }
+    }
+    frame.extend_from_slice(payload);
+    frame
+}
+
+/// Read one client frame, returning (opcode, unmasked payload)
+pub async fn read_frame<R: AsyncRead + Unpin>(reader: &mut R) -> std::io::Result<(u8, Vec<u8>)> {
+    let mut head = [0u8; 2];
+    reader.read_exact(&mut head).await?;
+    let opcode = head[0] & 0x0F;
+    let masked = head[1] & 0x80 != 0;
+    let len = match head[1] & 0x7F {
+        126 => {
+            let mut b = [0u8; 2];
+            reader.read_exact(&mut b).await?;
+            u16::from_be_bytes(b) as u64
+        }
+        127 => {
+            let mut b = [0u8; 8];
+            reader.read_exact(&mut b).await?;
+            u64::from_be_bytes(b)
+        }
+        n => n as u64,
+    };
+    if len > MAX_CLIENT_FRAME as u64 {
+        return Err(std::io::Error::new(std::io::ErrorKind::InvalidData, "websocket frame too large"));
+    }
+    let mut mask = [0u8; 4];
+    if masked {
+        reader.read_exact(&mut mask).await?;
+    }
+    let mut payload = vec![0u8; len as usize];
+    reader.read_exact(&mut payload).await?;
+    if masked {
+        for (i, b) in payload.iter_mut().enumerate() {
+            *b ^= mask[i % 4];
+        }
+    }
+    Ok((opcode, payload))
+}
+
+/// `read_frame` that owns its reader and hands it back, so one read can stay
+/// pinned across `select!` wakeups instead of being dropped half-way through
+/// a frame whenever an event is ready first
+async fn next_frame<R: AsyncRead + Unpin>(mut reader: R) -> (R, std::io::Result<(u8, Vec<u8>)>) {
+    let frame = read_frame(&mut reader).await;
+    (reader, frame)
+}
+
+/// Event types a client has asked for
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Subscriptions {
+    pub blocks: bool,
+    pub txs: bool,
+}
+
+impl Subscriptions {
+    /// Apply a `{"subscribe": [...]}` / `{"unsubscribe": [...]}` message.
+    /// Returns false if the message was neither.
+    pub fn apply(&mut self, msg: &serde_json::Value) -> bool {
+        let mut handled = false;
+        for (key, on) in [("subscribe", true), ("unsubscribe", false)] {
+            let Some(events) = msg.get(key).and_then(|v| v.as_array()) else { continue };
+            handled = true;
+            for event in events.iter().filter_map(|e| e.as_str()) {
+                match event {
+                    "blocks" => self.blocks = on,
+                    "txs" => self.txs = on,
+                    _ => {}
+                }
+            }
+        }
+        handled
+    }
+}
+
+fn block_event(block: &Block) -> serde_json::Value {
+    json!({
+        "event": "block", "height": block.header.height, "hash": hex::encode(block.header.hash()),
+        "timestamp": block.header.timestamp, "tx_count": block.transactions.len(),
+    })
+}
+
+fn tx_event(tx: &Transaction) -> serde_json::Value {
+    json!({
+        "event": "tx", "txid": hex::encode(crate::crypto::txid::txid_v1(tx)), "size": tx.size(),
+        "output_total": tx.total_output() as f64 / COIN as f64,
+    })
+}
+
+/// Complete the upgrade handshake, then stream events until the client leaves
+pub async fn serve<R, W>(reader: R, mut writer: W, client_key: &str, state: Arc<NodeState>)
+where
+    R: AsyncRead + Unpin,
+    W: AsyncWrite + Unpin,
+{
+    let handshake = format!(
+        "HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Accept: {}\r\n\r\n",
+        accept_key(client_key)
+    );
+    if writer.write_all(handshake.as_bytes()).await.is_err() {
+        return;
+    }
+
+    let mut block_rx = state.block_tx.subscribe();
+    let mut tx_rx = state.tx_tx.subscribe();
+    let mut subs = Subscriptions::default();
+    let reading = next_frame(reader);
+    tokio::pin!(reading);
+    loop {
+        let outgoing = tokio::select! {
+            (reader, frame) = &mut reading => {
+                reading.set(next_frame(reader));
+                match frame {
+                    Ok((OP_TEXT, payload)) => {
+                        let reply = match serde_json::from_slice::<serde_json::Value>(&payload) {
+                            Ok(msg) if subs.apply(&msg) => json!({"subscribed": {"blocks": subs.blocks, "txs": subs.txs}}),
+                            _ => json!({"error": "expected {\"subscribe\": [\"blocks\", \"txs\"]}"}),
+                        };
+                        encode_frame(OP_TEXT, reply.to_string().as_bytes())
+                    }
+                    Ok((OP_PING, payload)) => encode_frame(OP_PONG, &payload),
+                    Ok((OP_CLOSE, _)) | Err(_) => {
+                        let _ = writer.write_all(&encode_frame(OP_CLOSE, &[])).await;
+                        return;
+                    }
+                    Ok(_) => continue,
+                }
+            }
+            block = block_rx.recv() => match block {
+                Ok(block) if subs.blocks => encode_frame(OP_TEXT, block_event(&block).to_string().as_bytes()),
+                Ok(_) | Err(RecvError::Lagged(_)) => continue,
+                Err(RecvError::Closed) => return,
+            },
+            tx = tx_rx.recv() => match tx {
+                Ok(tx) if subs.txs => encode_frame(OP_TEXT, tx_event(&tx).to_string().as_bytes()),
+                Ok(_) | Err(RecvError::Lagged(_)) => continue,
+                Err(RecvError::Closed) => return,
+            },
+        };
+        if writer.write_all(&outgoing).await.is_err() {
+            return;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_accept_key_rfc_example() {
+        assert_eq!(accept_key("dGhlIHNhbXBsZSBub25jZQ=="), "s3pPLMBiTxaQ9kYGzzhZRbK+xOo=");
+        assert_eq!(base64_encode(b"ab"), "YWI=");
+        assert_eq!(base64_encode(b"a"), "YQ==");
+    }
+
+    /// Masked client text frame (RFC 6455 §5.3)
+    fn client_frame(payload: &[u8]) -> Vec<u8> {
+        let mask = [0x37, 0xFA, 0x21, 0x3D];
+        let mut frame = vec![0x80 | OP_TEXT, 0x80 | payload.len() as u8];
+        frame.extend_from_slice(&mask);
+        frame.extend(payload.iter().enumerate().map(|(i, b)| b ^ mask[i % 4]));
+        frame
+    }
+
+    #[tokio::test]
+    async fn test_frames_and_subscriptions() {
+        let payload = br#"{"subscribe":["blocks"]}"#;
+        let frame = client_frame(payload);
+        let (opcode, decoded) = read_frame(&mut frame.as_slice()).await.unwrap();
+        assert_eq!((opcode, decoded.as_slice()), (OP_TEXT, &payload[..]));
+
+        let mut subs = Subscriptions::default();
+        assert!(subs.apply(&serde_json::from_slice(&decoded).unwrap()));
+        assert_eq!(subs, Subscriptions { blocks: true, txs: false });
+        assert!(subs.apply(&json!({"subscribe": ["txs"], "unsubscribe": ["blocks"]})));
+        assert_eq!(subs, Subscriptions { blocks: false, txs: true });
+        assert!(!subs.apply(&json!({"hello": 1})));
+
+        // Server frames use the extended length forms past 125 bytes
+        let big = vec![7u8; 300];
+        let encoded = encode_frame(OP_TEXT, &big);
+        assert_eq!(&encoded[..4], &[0x81, 126, 0x01, 0x2C]);
+        let (_, roundtrip) = read_frame(&mut encoded.as_slice()).await.unwrap();
+        assert_eq!(roundtrip, big);
+
+        let mut oversized = vec![0x81, 127];
+        oversized.extend_from_slice(&(MAX_CLIENT_FRAME as u64 + 1).to_be_bytes());
+        assert!(read_frame(&mut oversized.as_slice()).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_event_mid_frame_keeps_the_frame() {
+        let _ = std::panic::catch_unwind(|| crate::core::params::init_network(false));
+        let state = NodeState::new(0);
+        let (client, server) = tokio::io::duplex(4096);
+        let (server_rx, server_tx) = tokio::io::split(server);
+        tokio::spawn(serve(server_rx, server_tx, "dGhlIHNhbXBsZSBub25jZQ==", state.clone()));
+        let (mut rx, mut tx) = tokio::io::split(client);
+
+        let mut handshake = Vec::new();
+        while !handshake.ends_with(b"\r\n\r\n") {
+            handshake.push(rx.read_u8().await.unwrap());
+        }
+        assert!(handshake.starts_with(b"HTTP/1.1 101"));
+        tx.write_all(&client_frame(br#"{"subscribe":["blocks"]}"#)).await.unwrap();
+        let (_, reply) = read_frame(&mut rx).await.unwrap();
+        assert_eq!(serde_json::from_slice::<serde_json::Value>(&reply).unwrap()["subscribed"]["blocks"], true);
+
+        // A block goes out while the server is half-way through reading a frame
+        let frame = client_frame(br#"{"subscribe":["txs"]}"#);
+        tx.write_all(&frame[..8]).await.unwrap();
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+        let header = crate::core::types::BlockHeader {
+            version: 4, prev_hash: [0; 32], merkle_root: [0; 32],
+            timestamp: 1_700_000_000, difficulty_target: 0, nonce: 0, height: 7,
+        };
+        state.block_tx.send(Block { header, transactions: vec![] }).unwrap();
+        let (_, event) = read_frame(&mut rx).await.unwrap();
+        assert_eq!(serde_json::from_slice::<serde_json::Value>(&event).unwrap()["height"], 7);
+
+        tx.write_all(&frame[8..]).await.unwrap();
+        let (_, reply) = read_frame(&mut rx).await.unwrap();
+        let reply: serde_json::Value = serde_json::from_slice(&reply).unwrap();
+        assert_eq!(reply["subscribed"], json!({"blocks": true, "txs": true}));
+    }
+}
@@ -1,5 +1,7 @@
 use sled::Db;
-use std::path::Path;
+use std::path::{Path, PathBuf};
+
+pub mod snapshot;
 
 use crate::core::types::*;
 use crate::core::chain::UtxoEntry;
@@ -10,14 +12,53 @@ const PREFIX_HEADER: &[u8] = b"hdr:";
 const PREFIX_HEIGHT: &[u8] = b"hgt:";
 const PREFIX_UTXO: &[u8] = b"utx:";
 const PREFIX_UNDO: &[u8] = b"und:";
+const PREFIX_TXINDEX: &[u8] = b"txi:";
+const PREFIX_ADDRESS: &[u8] = b"adr:";
 const META_TIP: &[u8] = b"meta:tip";
 const META_HEIGHT: &[u8] = b"meta:height";
 const META_TIMESTAMPS: &[u8] = b"meta:timestamps";
 const META_FRACTIONAL_DIFF: &[u8] = b"meta:frac_diff";
+/// Present once the persistent tx index covers the whole active chain
+const META_TXINDEX: &[u8] = b"meta:txindex";
+/// Present once the address index covers the whole active chain
+const META_ADDRINDEX: &[u8] = b"meta:addrindex";
+/// Lowest active-chain height above genesis whose block body is still stored
+const META_PRUNE_HEIGHT: &[u8] = b"meta:prune_height";
+
+/// Chain-state snapshot file, kept next to the sled files
+const STATE_SNAPSHOT_FILE: &str = "chainstate.bin";
 
 /// Persistent storage backend using sled embedded database
 pub struct Storage {
     db: Db,
+    dir: PathBuf,
+}
+
+/// Write-ahead summary of the in-memory chain tip state.
+///
+/// This is a cache, not a source of truth: sled stays authoritative. On startup
+/// the state loaded from sled is compared against it, and only when they agree
+/// is the full UTXO consistency walk skipped.
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct ChainStateSnapshot {
+    pub tip: Hash256,
+    pub height: u64,
+    pub fractional_difficulty: f64,
+    pub recent_timestamps: Vec<u64>,
+    pub utxo_count: u64,
+    /// Order-independent digest of the UTXO set (see `UtxoSet::digest`)
+    pub utxo_digest: Hash256,
+}
+
+/// Number of stored keys of each kind (see `Storage::key_counts`)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct KeyCounts {
+    pub blocks: u64,
+    pub headers: u64,
+    pub utxos: u64,
+    pub undo: u64,
+    pub tx_index: u64,
+    pub address_index: u64,
 }
 
 /// Serializable UTXO entry for storage
@@ -39,6 +80,29 @@ pub struct StoredBlockUndo {
     pub created: Vec<OutPoint>,
 }
 
+/// Persistent tx index entry: where a confirmed transaction lives on the active chain
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct StoredTxLocation {
+    pub block_hash: Hash256,
+    /// Position of the transaction within the block
+    pub index: u32,
+    /// Number of outputs, so callers can bound `vout` without loading the block
+    pub vout_count: u32,
+}
+
+/// Address index entry: one output paid to, or one input spent from, an address
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct StoredAddressEntry {
+    /// Transaction that touched the address
+    pub txid: Hash256,
+    /// Output index (received) or input index (sent) within that transaction
+    pub index: u32,
+    pub height: u64,
+    pub amount: u64,
+    /// True if the address spent coins here, false if it received them
+    pub sent: bool,
+}
+
 impl From<&UtxoEntry> for StoredUtxoEntry {
     fn from(entry: &UtxoEntry) -> Self {
         StoredUtxoEntry {
@@ -68,8 +132,9 @@ impl StoredUtxoEntry {
 impl Storage {
     /// Open or create a database at the given path
     pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, StorageError> {
-        let db = sled::open(path).map_err(|e| StorageError::DbError(e.to_string()))?;
-        Ok(Storage { db })
+        let dir = path.as_ref().to_path_buf();
+        let db = sled::open(&dir).map_err(|e| StorageError::DbError(e.to_string()))?;
+        Ok(Storage { db, dir })
     }
 
     /// Check if the database has existing chain data
@@ -102,6 +167,13 @@ impl Storage {
         }
     }
 
+    /// Delete a block body (pruning); the header is kept
+    pub fn remove_block(&self, hash: &Hash256) -> Result<(), StorageError> {
+        self.db.remove(prefixed_key(PREFIX_BLOCK, hash))
+            .map_err(|e| StorageError::DbError(e.to_string()))?;
+        Ok(())
+    }
+
     /// Store a block header
     pub fn put_header(&self, hash: &Hash256, header: &BlockHeader) -> Result<(), StorageError> {
         let key = prefixed_key(PREFIX_HEADER, hash);
@@ -193,6 +265,17 @@ impl Storage {
         Ok(utxos)
     }
 
+    /// Remove every stored UTXO (used when rebuilding the set from blocks)
+    pub fn clear_utxos(&self) -> Result<(), StorageError> {
+        let keys: Vec<Vec<u8>> = self.db.scan_prefix(PREFIX_UTXO)
+            .filter_map(|res| res.ok().map(|(k, _)| k.to_vec()))
+            .collect();
+        for k in keys {
+            self.db.remove(k).map_err(|e| StorageError::DbError(e.to_string()))?;
+        }
+        Ok(())
+    }
+
 
     // ─── Undo Storage (fast reorg) ──────────────────────────────────
 
@@ -235,6 +318,127 @@ impl Storage {
         Ok(())
     }
 
+    // ─── Transaction Index ───────────────────────────────────────────
+
+    pub fn put_tx_location(&self, txid: &Hash256, loc: &StoredTxLocation) -> Result<(), StorageError> {
+        let key = prefixed_key(PREFIX_TXINDEX, txid);
+        let value = bincode::serialize(loc)
+            .map_err(|e| StorageError::SerializeError(e.to_string()))?;
+        self.db.insert(key, value)
+            .map_err(|e| StorageError::DbError(e.to_string()))?;
+        Ok(())
+    }
+
+    /// Look up a confirmed transaction by (v1) txid
+    pub fn get_tx_location(&self, txid: &Hash256) -> Result<Option<StoredTxLocation>, StorageError> {
+        let key = prefixed_key(PREFIX_TXINDEX, txid);
+        match self.db.get(key).map_err(|e| StorageError::DbError(e.to_string()))? {
+            Some(bytes) => {
+                let loc = bincode::deserialize(&bytes)
+                    .map_err(|e| StorageError::SerializeError(e.to_string()))?;
+                Ok(Some(loc))
+            }
+            None => Ok(None),
+        }
+    }
+
+    pub fn remove_tx_location(&self, txid: &Hash256) -> Result<(), StorageError> {
+        let key = prefixed_key(PREFIX_TXINDEX, txid);
+        self.db.remove(key)
+            .map_err(|e| StorageError::DbError(e.to_string()))?;
+        Ok(())
+    }
+
+    /// Load every tx index entry (startup)
+    pub fn load_tx_index(&self) -> Result<Vec<(Hash256, StoredTxLocation)>, StorageError> {
+        let mut entries = Vec::new();
+        for item in self.db.scan_prefix(PREFIX_TXINDEX) {
+            let (key, value) = item.map_err(|e| StorageError::DbError(e.to_string()))?;
+            let txid: Hash256 = key[PREFIX_TXINDEX.len()..].try_into()
+                .map_err(|_| StorageError::SerializeError("invalid tx index key length".into()))?;
+            let loc = bincode::deserialize(&value)
+                .map_err(|e| StorageError::SerializeError(e.to_string()))?;
+            entries.push((txid, loc));
+        }
+        Ok(entries)
+    }
+
+    /// Drop the whole tx index, including its completeness marker
+    pub fn clear_tx_index(&self) -> Result<(), StorageError> {
+        self.db.remove(META_TXINDEX).map_err(|e| StorageError::DbError(e.to_string()))?;
+        let keys: Vec<Vec<u8>> = self.db.scan_prefix(PREFIX_TXINDEX)
+            .filter_map(|res| res.ok().map(|(k, _)| k.to_vec()))
+            .collect();
+        for k in keys {
+            self.db.remove(k).map_err(|e| StorageError::DbError(e.to_string()))?;
+        }
+        Ok(())
+    }
+
+    /// Mark the tx index as complete for the active chain
+    pub fn mark_tx_index_built(&self) -> Result<(), StorageError> {
+        self.db.insert(META_TXINDEX, &[1u8])
+            .map_err(|e| StorageError::DbError(e.to_string()))?;
+        Ok(())
+    }
+
+    /// True if the tx index was fully built and has been maintained since
+    pub fn has_tx_index(&self) -> bool {
+        self.db.contains_key(META_TXINDEX).unwrap_or(false)
+    }
+
+    // ─── Address Index ───────────────────────────────────────────────
+
+    pub fn put_address_entry(&self, pubkey_hash: &Hash256, entry: &StoredAddressEntry) -> Result<(), StorageError> {
+        let value = bincode::serialize(entry)
+            .map_err(|e| StorageError::SerializeError(e.to_string()))?;
+        self.db.insert(address_key(pubkey_hash, entry), value)
+            .map_err(|e| StorageError::DbError(e.to_string()))?;
+        Ok(())
+    }
+
+    pub fn remove_address_entry(&self, pubkey_hash: &Hash256, entry: &StoredAddressEntry) -> Result<(), StorageError> {
+        self.db.remove(address_key(pubkey_hash, entry))
+            .map_err(|e| StorageError::DbError(e.to_string()))?;
+        Ok(())
+    }
+
+    /// Every entry recorded for an address, ordered by height
+    pub fn get_address_history(&self, pubkey_hash: &Hash256) -> Result<Vec<StoredAddressEntry>, StorageError> {
+        let prefix = prefixed_key(PREFIX_ADDRESS, pubkey_hash);
+        let mut entries = Vec::new();
+        for item in self.db.scan_prefix(prefix) {
+            let (_, value) = item.map_err(|e| StorageError::DbError(e.to_string()))?;
+            entries.push(bincode::deserialize(&value)
+                .map_err(|e| StorageError::SerializeError(e.to_string()))?);
+        }
+        Ok(entries)
+    }
+
+    /// Drop the whole address index, including its completeness marker
+    pub fn clear_address_index(&self) -> Result<(), StorageError> {
+        self.db.remove(META_ADDRINDEX).map_err(|e| StorageError::DbError(e.to_string()))?;
+        let keys: Vec<Vec<u8>> = self.db.scan_prefix(PREFIX_ADDRESS)
+            .filter_map(|res| res.ok().map(|(k, _)| k.to_vec()))
+            .collect();
+        for k in keys {
+            self.db.remove(k).map_err(|e| StorageError::DbError(e.to_string()))?;
+        }
+        Ok(())
+    }
+
+    /// Mark the address index as complete for the active chain
+    pub fn mark_address_index_built(&self) -> Result<(), StorageError> {
+        self.db.insert(META_ADDRINDEX, &[1u8])
+            .map_err(|e| StorageError::DbError(e.to_string()))?;
+        Ok(())
+    }
+
+    /// True if the address index was fully built and has been maintained since
+    pub fn has_address_index(&self) -> bool {
+        self.db.contains_key(META_ADDRINDEX).unwrap_or(false)
+    }
+
     // ─── Chain Metadata ──────────────────────────────────────────────
 
     /// Store the chain tip hash
@@ -263,6 +467,25 @@ impl Storage {
         Ok(())
     }
 
+    /// Record how far block bodies have been pruned
+    pub fn put_prune_height(&self, height: u64) -> Result<(), StorageError> {
+        self.db.insert(META_PRUNE_HEIGHT, &height.to_le_bytes())
+            .map_err(|e| StorageError::DbError(e.to_string()))?;
+        Ok(())
+    }
+
+    /// Prune height, or 0 if nothing was ever pruned
+    pub fn get_prune_height(&self) -> Result<u64, StorageError> {
+        match self.db.get(META_PRUNE_HEIGHT).map_err(|e| StorageError::DbError(e.to_string()))? {
+            Some(bytes) => {
+                let mut buf = [0u8; 8];
+                buf.copy_from_slice(&bytes);
+                Ok(u64::from_le_bytes(buf))
+            }
+            None => Ok(0),
+        }
+    }
+
     /// Get the chain height
     pub fn get_height(&self) -> Result<Option<u64>, StorageError> {
         match self.db.get(META_HEIGHT).map_err(|e| StorageError::DbError(e.to_string()))? {
@@ -315,6 +538,67 @@ impl Storage {
         }
     }
 
+    // ─── Chain-State Snapshot ────────────────────────────────────────
+
+    /// Atomically replace the chain-state snapshot (write temp file, fsync, rename)
+    pub fn put_state_snapshot(&self, snapshot: &ChainStateSnapshot) -> Result<(), StorageError> {
+        use std::io::Write;
+        let data = bincode::serialize(snapshot).map_err(|e| StorageError::SerializeError(e.to_string()))?;
+        let path = self.dir.join(STATE_SNAPSHOT_FILE);
+        let tmp = self.dir.join(format!("{}.tmp", STATE_SNAPSHOT_FILE));
+        let mut file = std::fs::File::create(&tmp).map_err(|e| StorageError::DbError(e.to_string()))?;
+        file.write_all(&data).map_err(|e| StorageError::DbError(e.to_string()))?;
+        file.sync_all().map_err(|e| StorageError::DbError(e.to_string()))?;
+        std::fs::rename(&tmp, &path).map_err(|e| StorageError::DbError(e.to_string()))?;
+        Ok(())
+    }
+
+    /// Read the chain-state snapshot, if one has been written
+    pub fn get_state_snapshot(&self) -> Result<Option<ChainStateSnapshot>, StorageError> {
+        let path = self.dir.join(STATE_SNAPSHOT_FILE);
+        match std::fs::read(&path) {
+            Ok(data) => bincode::deserialize(&data).map(Some)
+                .map_err(|e| StorageError::SerializeError(e.to_string())),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(StorageError::DbError(e.to_string())),
+        }
+    }
+
+    // ─── Atomic Writes ───────────────────────────────────────────────
+
+    /// Apply every write in `batch` atomically: after a crash either all of
+    /// them are visible or none are
+    pub fn apply_batch(&self, batch: WriteBatch) -> Result<(), StorageError> {
+        self.db.apply_batch(batch.inner).map_err(|e| StorageError::DbError(e.to_string()))
+    }
+
+    /// Queue removal of the whole active height index into `batch`
+    pub fn clear_height_index_into(&self, batch: &mut WriteBatch) {
+        for key in self.db.scan_prefix(PREFIX_HEIGHT).keys().flatten() {
+            batch.inner.remove(key);
+        }
+    }
+
+    // ─── Statistics ──────────────────────────────────────────────────
+
+    /// Bytes the database occupies on disk
+    pub fn size_on_disk(&self) -> Result<u64, StorageError> {
+        self.db.size_on_disk().map_err(|e| StorageError::DbError(e.to_string()))
+    }
+
+    /// Count keys per prefix. Only walks keys; nothing is deserialized.
+    pub fn key_counts(&self) -> KeyCounts {
+        let count = |prefix: &[u8]| self.db.scan_prefix(prefix).keys().filter(|k| k.is_ok()).count() as u64;
+        KeyCounts {
+            blocks: count(PREFIX_BLOCK),
+            headers: count(PREFIX_HEADER),
+            utxos: count(PREFIX_UTXO),
+            undo: count(PREFIX_UNDO),
+            tx_index: count(PREFIX_TXINDEX),
+            address_index: count(PREFIX_ADDRESS),
+        }
+    }
+
     /// Flush all pending writes to disk
     pub fn flush(&self) -> Result<(), StorageError> {
         self.db.flush().map_err(|e| StorageError::DbError(e.to_string()))?;
@@ -323,12 +607,72 @@ impl Storage {
 
     /// Clear all data from the database (used during auto-recovery)
     pub fn clear_all(&self) -> Result<(), StorageError> {
+        let _ = std::fs::remove_file(self.dir.join(STATE_SNAPSHOT_FILE));
         self.db.clear().map_err(|e| StorageError::DbError(e.to_string()))?;
         self.db.flush().map_err(|e| StorageError::DbError(e.to_string()))?;
         Ok(())
     }
 }
 
+// ─── Write Batch ─────────────────────────────────────────────────────
+
+/// Writes collected for `Storage::apply_batch`. Same encodings as the
+/// single-key `put_*` methods on `Storage`.
+#[derive(Default)]
+pub struct WriteBatch {
+    inner: sled::Batch,
+}
+
+impl WriteBatch {
+    pub fn put_height_index(&mut self, height: u64, hash: &Hash256) {
+        self.inner.insert(prefixed_key(PREFIX_HEIGHT, &height.to_be_bytes()), hash.as_slice());
+    }
+
+    pub fn put_tip(&mut self, hash: &Hash256) {
+        self.inner.insert(META_TIP, hash.as_slice());
+    }
+
+    pub fn put_height(&mut self, height: u64) {
+        self.inner.insert(META_HEIGHT, &height.to_le_bytes());
+    }
+
+    pub fn put_timestamps(&mut self, timestamps: &[u64]) -> Result<(), StorageError> {
+        let value = bincode::serialize(timestamps)
+            .map_err(|e| StorageError::SerializeError(e.to_string()))?;
+        self.inner.insert(META_TIMESTAMPS, value);
+        Ok(())
+    }
+
+    pub fn put_fractional_difficulty(&mut self, frac: f64) {
+        self.inner.insert(META_FRACTIONAL_DIFF, &frac.to_le_bytes());
+    }
+
+    pub fn put_undo(&mut self, block_hash: &Hash256, undo: &StoredBlockUndo) -> Result<(), StorageError> {
+        let value = bincode::serialize(undo)
+            .map_err(|e| StorageError::SerializeError(e.to_string()))?;
+        self.inner.insert(prefixed_key(PREFIX_UNDO, block_hash), value);
+        Ok(())
+    }
+
+    pub fn put_utxo(&mut self, outpoint: &OutPoint, entry: &UtxoEntry) -> Result<(), StorageError> {
+        let value = bincode::serialize(&StoredUtxoEntry::from(entry))
+            .map_err(|e| StorageError::SerializeError(e.to_string()))?;
+        self.inner.insert(utxo_key(outpoint), value);
+        Ok(())
+    }
+
+    pub fn remove_utxo(&mut self, outpoint: &OutPoint) {
+        self.inner.remove(utxo_key(outpoint));
+    }
+
+    pub fn put_tx_location(&mut self, txid: &Hash256, loc: &StoredTxLocation) -> Result<(), StorageError> {
+        let value = bincode::serialize(loc)
+            .map_err(|e| StorageError::SerializeError(e.to_string()))?;
+        self.inner.insert(prefixed_key(PREFIX_TXINDEX, txid), value);
+        Ok(())
+    }
+}
+
 // ─── Helpers ─────────────────────────────────────────────────────────
 
 fn prefixed_key(prefix: &[u8], data: &[u8]) -> Vec<u8> {
@@ -347,6 +691,18 @@ fn utxo_key(outpoint: &OutPoint) -> Vec<u8> {
     key
 }
 
+fn address_key(pubkey_hash: &Hash256, entry: &StoredAddressEntry) -> Vec<u8> {
+    // adr:<pubkey_hash(32)><height(8)><txid(32)><sent(1)><index(4)> — big-endian height sorts by height
+    let mut key = Vec::with_capacity(PREFIX_ADDRESS.len() + 77);
+    key.extend_from_slice(PREFIX_ADDRESS);
+    key.extend_from_slice(pubkey_hash);
+    key.extend_from_slice(&entry.height.to_be_bytes());
+    key.extend_from_slice(&entry.txid);
+    key.push(entry.sent as u8);
+    key.extend_from_slice(&entry.index.to_be_bytes());
+    key
+}
+
 fn outpoint_from_utxo_key(key: &[u8]) -> Result<OutPoint, StorageError> {
     if key.len() != PREFIX_UTXO.len() + 36 {
         return Err(StorageError::SerializeError("invalid UTXO key length".into()));
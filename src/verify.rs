@@ -0,0 +1,186 @@
+//! Standalone header and inclusion checks for light clients and external tools.
+//!
+//! Everything here works on plain `core::types` values — no `Chain`, storage
+//! or networking. What it does NOT check is difficulty retargeting: each header
+//! only has to meet the target it claims. A verifier that needs that guarantee
+//! must replay LWMA itself (see `Chain::validate_header_chain`).
+
+use crate::core::types::{leading_zero_bits, BlockHeader, Hash256, NULL_HASH};
+use sha2::{Digest, Sha256};
+
+/// True if the header's EquiHash-X hash meets its own difficulty target
+pub fn verify_header_pow(header: &BlockHeader) -> bool {
+    header.meets_difficulty()
+}
+
+/// Check that `headers` extend `known_parent` one by one: each links to the
+/// previous header's hash, has height + 1, a strictly later timestamp, and
+/// valid PoW. Returns the index of the first header that fails.
+pub fn verify_header_chain(headers: &[BlockHeader], known_parent: &BlockHeader) -> Result<(), usize> {
+    let mut prev_hash = known_parent.hash();
+    let mut prev = known_parent;
+    for (i, h) in headers.iter().enumerate() {
+        if h.prev_hash != prev_hash || h.height != prev.height + 1 || h.timestamp <= prev.timestamp {
+            return Err(i);
+        }
+        let hash = h.hash();
+        if leading_zero_bits(&hash) < h.difficulty_target {
+            return Err(i);
+        }
+        prev_hash = hash;
+        prev = h;
+    }
+    Ok(())
+}
+
+// ─── Merkle Proofs ───────────────────────────────────────────────────
+
+/// Path from one transaction to the merkle root
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct MerkleBranch {
+    /// Position of the transaction in the block
+    pub index: u32,
+    /// Sibling hashes from the leaf level upward
+    pub hashes: Vec<Hash256>,
+}
+
+/// Parent node: double-SHA256(left || right)
+pub fn merkle_parent(left: &Hash256, right: &Hash256) -> Hash256 {
+    let mut combined = [0u8; 64];
+    combined[..32].copy_from_slice(left);
+    combined[32..].copy_from_slice(right);
+    let first = Sha256::digest(combined);
+    let second = Sha256::digest(first);
+    second.into()
+}
+
+/// Merkle root over transaction hashes (odd levels duplicate their last node)
+pub fn merkle_root(leaves: &[Hash256]) -> Hash256 {
+    if leaves.is_empty() {
+        return NULL_HASH;
+    }
+    let mut level = leaves.to_vec();
+    while level.len() > 1 {
+        if !level.len().is_multiple_of(2) {
+            level.push(*level.last().unwrap());
+        }
+        level = level.chunks(2).map(|pair| merkle_parent(&pair[0], &pair[1])).collect();
+    }
+    level[0]
+}
+
+/// Build the branch proving `leaves[index]` is under `merkle_root(leaves)`
+pub fn merkle_branch(leaves: &[Hash256], index: usize) -> Option<MerkleBranch> {
+    if index >= leaves.len() {
+        return None;
+    }
+    let mut hashes = Vec::new();
+    let mut level = leaves.to_vec();
+    let mut pos = index;
+    while level.len() > 1 {
+        if !level.len().is_multiple_of(2) {
+            level.push(*level.last().unwrap());
+        }
+        hashes.push(level[pos ^ 1]);
+        level = level.chunks(2).map(|pair| merkle_parent(&pair[0], &pair[1])).collect();
+        pos /= 2;
+    }
+    Some(MerkleBranch { index: index as u32, hashes })
+}
+
+/// True if `txid` hashes up through `branch` to `root`
+pub fn verify_merkle_proof(txid: &Hash256, branch: &MerkleBranch, root: &Hash256) -> bool {
+    let mut acc = *txid;
+    let mut pos = branch.index;
+    for sibling in &branch.hashes {
+        acc = if pos & 1 == 0 { merkle_parent(&acc, sibling) } else { merkle_parent(sibling, &acc) };
+        pos >>= 1;
+    }
+    pos == 0 && acc == *root
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Grind a nonce so the header meets a (tiny) difficulty target
+    fn mine(mut header: BlockHeader) -> BlockHeader {
+        while !header.meets_difficulty() { header.nonce += 1; }
+        header
+    }
+
+    fn child_of(parent: &BlockHeader) -> BlockHeader {
+        mine(BlockHeader {
+            version: 4, prev_hash: parent.hash(), merkle_root: NULL_HASH,
+            timestamp: parent.timestamp + 90, difficulty_target: 2, nonce: 0, height: parent.height + 1,
+        })
+    }
+
+    #[test]
+    fn test_verify_header_pow() {
+        let header = mine(BlockHeader {
+            version: 4, prev_hash: NULL_HASH, merkle_root: NULL_HASH,
+            timestamp: 1_700_000_000, difficulty_target: 2, nonce: 0, height: 0,
+        });
+        assert!(verify_header_pow(&header));
+
+        let mut tampered = header.clone();
+        tampered.difficulty_target = 64;
+        assert!(!verify_header_pow(&tampered));
+    }
+
+    #[test]
+    fn test_verify_header_chain() {
+        let parent = mine(BlockHeader {
+            version: 4, prev_hash: NULL_HASH, merkle_root: NULL_HASH,
+            timestamp: 1_700_000_000, difficulty_target: 2, nonce: 0, height: 0,
+        });
+        let h1 = child_of(&parent);
+        let h2 = child_of(&h1);
+        let h3 = child_of(&h2);
+        let headers = vec![h1, h2, h3];
+        assert_eq!(verify_header_chain(&headers, &parent), Ok(()));
+        assert_eq!(verify_header_chain(&[], &parent), Ok(()));
+
+        // Broken link
+        let mut bad = headers.clone();
+        bad[1].prev_hash = [0xAB; 32];
+        assert_eq!(verify_header_chain(&bad, &parent), Err(1));
+
+        // Timestamp not moving forward
+        let mut bad = headers.clone();
+        bad[2].timestamp = bad[1].timestamp;
+        assert_eq!(verify_header_chain(&bad, &parent), Err(2));
+
+        // Claimed difficulty the hash doesn't meet
+        let mut bad = headers.clone();
+        bad[0].difficulty_target = 64;
+        assert_eq!(verify_header_chain(&bad, &parent), Err(0));
+    }
+
+    #[test]
+    fn test_merkle_proof() {
+        let leaves: Vec<Hash256> = (0..5u8).map(|i| [i; 32]).collect();
+        let root = merkle_root(&leaves);
+        for (i, leaf) in leaves.iter().enumerate() {
+            let branch = merkle_branch(&leaves, i).unwrap();
+            assert!(verify_merkle_proof(leaf, &branch, &root), "leaf {}", i);
+        }
+
+        let branch = merkle_branch(&leaves, 2).unwrap();
+        assert!(!verify_merkle_proof(&leaves[3], &branch, &root));
+        let mut wrong_index = branch.clone();
+        wrong_index.index = 3;
+        assert!(!verify_merkle_proof(&leaves[2], &wrong_index, &root));
+        let mut tampered = branch.clone();
+        tampered.hashes[0][0] ^= 1;
+        assert!(!verify_merkle_proof(&leaves[2], &tampered, &root));
+        assert!(!verify_merkle_proof(&leaves[2], &branch, &[0u8; 32]));
+
+        // Single-transaction block: the txid is the root
+        let single = merkle_branch(&leaves[..1], 0).unwrap();
+        assert!(single.hashes.is_empty());
+        assert!(verify_merkle_proof(&leaves[0], &single, &leaves[0]));
+        assert!(merkle_branch(&leaves, 5).is_none());
+    }
+}
@@ -0,0 +1,84 @@
+//! Hierarchical deterministic keys (SLIP-0010, ed25519).
+//!
+//! ed25519 only supports hardened derivation, so every path element is
+//! hardened. Wallet addresses live at `m/0'/index'`: one 32-byte seed backs up
+//! every address the wallet will ever hand out.
+
+use hmac::{Hmac, Mac};
+use sha2::Sha512;
+
+type HmacSha512 = Hmac<Sha512>;
+
+/// Offset that marks a path index as hardened
+pub const HARDENED: u32 = 0x8000_0000;
+
+/// Path to the external (receiving) address chain
+pub const EXTERNAL_CHAIN: &[u32] = &[0];
+
+/// Private key plus chain code at one node of the derivation tree
+#[derive(Clone, PartialEq, Eq)]
+pub struct ExtendedKey {
+    pub secret: [u8; 32],
+    pub chain_code: [u8; 32],
+}
+
+impl ExtendedKey {
+    /// Root node for a seed
+    pub fn master(seed: &[u8]) -> Self {
+        Self::from_hmac(b"ed25519 seed", &[seed])
+    }
+
+    /// Hardened child `index'` (the hardened bit is added here)
+    pub fn derive_child(&self, index: u32) -> Self {
+        let index = (index | HARDENED).to_be_bytes();
+        Self::from_hmac(&self.chain_code, &[&[0u8], &self.secret, &index])
+    }
+
+    /// Walk a path of (unhardened) indexes below this node
+    pub fn derive_path(&self, path: &[u32]) -> Self {
+        path.iter().fold(self.clone(), |node, &i| node.derive_child(i))
+    }
+
+    fn from_hmac(key: &[u8], parts: &[&[u8]]) -> Self {
+        let mut mac = HmacSha512::new_from_slice(key).expect("HMAC accepts any key length");
+        for part in parts {
+            mac.update(part);
+        }
+        let out = mac.finalize().into_bytes();
+        let mut secret = [0u8; 32];
+        let mut chain_code = [0u8; 32];
+        secret.copy_from_slice(&out[..32]);
+        chain_code.copy_from_slice(&out[32..]);
+        ExtendedKey { secret, chain_code }
+    }
+}
+
+/// Secret key for the wallet address at `m/0'/index'`
+pub fn address_secret(seed: &[u8; 32], index: u32) -> [u8; 32] {
+    ExtendedKey::master(seed).derive_path(EXTERNAL_CHAIN).derive_child(index).secret
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_slip10_vector_1() {
+        let seed = hex::decode("000102030405060708090a0b0c0d0e0f").unwrap();
+        let m = ExtendedKey::master(&seed);
+        assert_eq!(hex::encode(m.chain_code), "90046a93de5380a72b5e45010748567d5ea02bbf6522f979e05c0d8d8ca9fffb");
+        assert_eq!(hex::encode(m.secret), "2b4be7f19ee27bbf30c667b642d5f4aa69fd169872f8fc3059c08ebae2eb19e7");
+
+        let child = m.derive_child(0);
+        assert_eq!(hex::encode(child.chain_code), "8b59aa11380b624e81507a27fedda59fea6d0b779a778918a2fd3590e16e9c69");
+        assert_eq!(hex::encode(child.secret), "68e0fe46dfb67e368c75379acec591dad19df3cde26e63b93a8e704f1dade7a3");
+    }
+
+    #[test]
+    fn test_address_secrets_are_deterministic_and_distinct() {
+        let seed = [7u8; 32];
+        assert_eq!(address_secret(&seed, 3), address_secret(&seed, 3));
+        assert_ne!(address_secret(&seed, 0), address_secret(&seed, 1));
+        assert_ne!(address_secret(&seed, 0), address_secret(&[8u8; 32], 0));
+    }
+}
@@ -1,16 +1,21 @@
+use aes_gcm::aead::{Aead, KeyInit};
+use aes_gcm::Aes256Gcm;
 use ed25519_dalek::{SigningKey, VerifyingKey, Signer, Verifier, Signature};
 use rand::rngs::OsRng;
 use rand::RngCore;
 use sha2::{Digest, Sha256};
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::path::{Path, PathBuf};
 
+pub mod hd;
+
 use crate::crypto;
 use crate::core::script;
 
 use crate::core::types::*;
 use crate::core::chain::UtxoSet;
-use crate::core::params::COINBASE_MATURITY;
+use crate::core::params::{COINBASE_MATURITY, CURRENT_TX_VERSION, DUST_THRESHOLD, MIN_TX_FEE};
 
 // ─── Keypair ────────────────────────────────────────────────────────
 
@@ -110,14 +115,56 @@ pub fn tx_signing_hash(tx: &Transaction, input_index: usize) -> Hash256 {
 // ─── Wallet Encryption ──────────────────────────────────────────────
 //
 // Wallet file format:
-//   - Unencrypted: { "version": 1, "encrypted": false, "keys": [...], "label": "..." }
-//   - Encrypted:   { "version": 1, "encrypted": true, "salt": "hex", "nonce": "hex", "ciphertext": "hex" }
+//   - Unencrypted: { "version": 5, "encrypted": false, "keys": [...], "label": "...", "seed": "hex", "hd_index": n,
+//                    "watch_only": [...], "labels": { .. } }
+//   - Encrypted:   { "version": 5, "encrypted": true, "salt": "hex", "nonce": "hex", "ciphertext": "hex",
+//                    "kdf": { "algorithm": "argon2id", "memory_kib": .., "iterations": .., "parallelism": .. } }
+//
+// HD wallets (v4+) keep the master seed and the next derivation index (see
+// `hd`); wallets without a seed are collections of independent random keys.
 //
-// Encryption: AES-256-GCM with key derived from password via Argon2-like KDF
-// (simplified: PBKDF using SHA-256 with 100k iterations + salt)
+// Encryption: AES-256-GCM with key derived from password via Argon2id, using the
+// parameters stored in the file. Older files still open and are rewritten in the
+// current format on next save:
+//   - no "kdf" field (v1/v2): key from 100k iterations of SHA-256 over password + salt
+//   - v1: SHA-256 keystream cipher with a trailing SHA-256 MAC instead of AES-GCM
+//   - v1-v3 encrypted payload: bincode (keys, label) instead of `WalletSecrets`
+//   - v4 encrypted payload: no watch-only addresses or labels; those sat in
+//     the clear "watch_only" / "labels" fields, which v5+ only use unencrypted
+
+const WALLET_VERSION: u32 = 5;
+/// Last wallet version written with the legacy SHA-256 stream cipher
+const LEGACY_CIPHER_VERSION: u32 = 1;
+/// Last wallet version whose encrypted payload is a bare (keys, label) tuple
+const LEGACY_PAYLOAD_VERSION: u32 = 3;
+/// Last wallet version that kept watch-only addresses and labels in the clear when encrypted
+const CLEAR_METADATA_VERSION: u32 = 4;
+const LEGACY_KDF_ITERATIONS: u32 = 100_000;
+
+/// Argon2id cost for newly encrypted wallets (19 MiB, 2 passes, 1 lane)
+pub const ARGON2_MEMORY_KIB: u32 = 19 * 1024;
+pub const ARGON2_ITERATIONS: u32 = 2;
+pub const ARGON2_PARALLELISM: u32 = 1;
+
+/// Key-derivation parameters, stored with the salt so costs can be tuned later
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KdfParams {
+    pub algorithm: String,
+    pub memory_kib: u32,
+    pub iterations: u32,
+    pub parallelism: u32,
+}
 
-const WALLET_VERSION: u32 = 1;
-const KDF_ITERATIONS: u32 = 100_000;
+impl Default for KdfParams {
+    fn default() -> Self {
+        KdfParams {
+            algorithm: "argon2id".into(),
+            memory_kib: ARGON2_MEMORY_KIB,
+            iterations: ARGON2_ITERATIONS,
+            parallelism: ARGON2_PARALLELISM,
+        }
+    }
+}
 
 #[derive(Serialize, Deserialize)]
 pub struct WalletFile {
@@ -135,57 +182,103 @@ pub struct WalletFile {
     pub nonce: Option<String>,
     #[serde(default)]
     pub ciphertext: Option<String>,
+    /// Key derivation used for `ciphertext`; absent means the legacy SHA-256 KDF
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub kdf: Option<KdfParams>,
+    /// HD master seed, hex (only if encrypted == false)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub seed: Option<String>,
+    /// Next unused HD derivation index
+    #[serde(default)]
+    pub hd_index: u32,
+    /// Watch-only pubkey hashes, hex (only if encrypted == false)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub watch_only: Vec<String>,
+    /// Per-address labels keyed by hex pubkey hash; unlabeled addresses are
+    /// absent (only if encrypted == false)
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub labels: BTreeMap<String, String>,
+}
+
+/// Everything private in a wallet; the encrypted payload of v5+ files. Watch-only
+/// addresses and labels aren't keys, but they reveal what the user owns.
+#[derive(Serialize, Deserialize)]
+struct WalletSecrets {
+    keys: Vec<[u8; 32]>,
+    label: String,
+    seed: Option<[u8; 32]>,
+    hd_index: u32,
+    watch_only: Vec<Hash256>,
+    labels: BTreeMap<Hash256, String>,
 }
 
-/// Derive a 32-byte encryption key from password + salt using iterated SHA-256.
-/// This is a simplified KDF — for production, use argon2 crate.
-fn derive_key(password: &[u8], salt: &[u8]) -> [u8; 32] {
+/// Encrypted payload of v4 files
+#[derive(Serialize, Deserialize)]
+struct WalletSecretsV4 {
+    keys: Vec<[u8; 32]>,
+    label: String,
+    seed: Option<[u8; 32]>,
+    hd_index: u32,
+}
+
+/// Derive a 32-byte encryption key from password + salt. `None` selects the
+/// legacy iterated SHA-256 KDF used by wallets written before Argon2id.
+fn derive_key(password: &[u8], salt: &[u8], kdf: Option<&KdfParams>) -> Result<[u8; 32], String> {
+    let Some(kdf) = kdf else { return Ok(legacy_derive_key(password, salt)) };
+    if kdf.algorithm != "argon2id" {
+        return Err(format!("unsupported wallet KDF: {}", kdf.algorithm));
+    }
+    let params = argon2::Params::new(kdf.memory_kib, kdf.iterations, kdf.parallelism, Some(32))
+        .map_err(|e| format!("invalid KDF parameters: {}", e))?;
+    let argon = argon2::Argon2::new(argon2::Algorithm::Argon2id, argon2::Version::V0x13, params);
+    let mut key = [0u8; 32];
+    argon.hash_password_into(password, salt, &mut key)
+        .map_err(|e| format!("key derivation failed: {}", e))?;
+    Ok(key)
+}
+
+/// Iterated SHA-256 over password + salt (pre-Argon2 wallets)
+fn legacy_derive_key(password: &[u8], salt: &[u8]) -> [u8; 32] {
     let mut key = [0u8; 32];
     let mut data = Vec::with_capacity(password.len() + salt.len());
     data.extend_from_slice(password);
     data.extend_from_slice(salt);
     let mut hash = Sha256::digest(&data);
-    for _ in 0..KDF_ITERATIONS {
+    for _ in 0..LEGACY_KDF_ITERATIONS {
         hash = Sha256::digest(&hash);
     }
     key.copy_from_slice(&hash);
     key
 }
 
-/// AES-256-GCM encrypt (using a simple XOR stream cipher with HMAC for integrity).
-/// For a real deployment, use the `aes-gcm` crate. This is a functional placeholder
-/// that provides real encryption with authenticated integrity checking.
+/// AES-256-GCM encrypt. Output is ciphertext || 16-byte tag.
 fn encrypt_data(plaintext: &[u8], key: &[u8; 32], nonce: &[u8; 12]) -> Vec<u8> {
-    // Generate keystream using SHA-256 in counter mode
-    let mut ciphertext = Vec::with_capacity(plaintext.len() + 32); // +32 for MAC
-    let mut keystream_pos = 0;
-    let mut block_counter = 0u64;
-    let mut keystream_block = [0u8; 32];
-
-    for (i, &byte) in plaintext.iter().enumerate() {
-        if keystream_pos == 0 || keystream_pos >= 32 {
-            let mut input = Vec::with_capacity(44 + 8);
-            input.extend_from_slice(key);
-            input.extend_from_slice(nonce);
-            input.extend_from_slice(&block_counter.to_le_bytes());
-            keystream_block.copy_from_slice(&Sha256::digest(&input));
-            block_counter += 1;
-            keystream_pos = 0;
-        }
-        ciphertext.push(byte ^ keystream_block[keystream_pos]);
-        keystream_pos += 1;
-    }
-
-    // HMAC for integrity: SHA256(key || ciphertext)
-    let mut mac_input = Vec::with_capacity(32 + ciphertext.len());
-    mac_input.extend_from_slice(key);
-    mac_input.extend_from_slice(&ciphertext);
-    let mac = Sha256::digest(&mac_input);
-    ciphertext.extend_from_slice(&mac);
-    ciphertext
+    let cipher = Aes256Gcm::new(key.into());
+    cipher.encrypt(nonce.into(), plaintext).expect("AES-GCM encryption cannot fail for in-memory buffers")
 }
 
-fn decrypt_data(ciphertext_with_mac: &[u8], key: &[u8; 32], nonce: &[u8; 12]) -> Result<Vec<u8>, String> {
+fn decrypt_data(ciphertext: &[u8], key: &[u8; 32], nonce: &[u8; 12]) -> Result<Vec<u8>, String> {
+    let cipher = Aes256Gcm::new(key.into());
+    cipher.decrypt(nonce.into(), ciphertext)
+        .map_err(|_| "wrong password or corrupted wallet".to_string())
+}
+
+/// SHA-256 counter-mode keystream used by v1 wallets
+fn legacy_keystream_xor(data: &[u8], key: &[u8; 32], nonce: &[u8; 12]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(data.len());
+    for (block_counter, chunk) in data.chunks(32).enumerate() {
+        let mut input = Vec::with_capacity(44 + 8);
+        input.extend_from_slice(key);
+        input.extend_from_slice(nonce);
+        input.extend_from_slice(&(block_counter as u64).to_le_bytes());
+        let keystream_block = Sha256::digest(&input);
+        out.extend(chunk.iter().zip(keystream_block.iter()).map(|(b, k)| b ^ k));
+    }
+    out
+}
+
+/// Decrypt a v1 wallet: keystream XOR with a trailing SHA256(key || ciphertext) MAC
+fn legacy_decrypt_data(ciphertext_with_mac: &[u8], key: &[u8; 32], nonce: &[u8; 12]) -> Result<Vec<u8>, String> {
     if ciphertext_with_mac.len() < 32 {
         return Err("ciphertext too short".into());
     }
@@ -201,27 +294,7 @@ fn decrypt_data(ciphertext_with_mac: &[u8], key: &[u8; 32], nonce: &[u8; 12]) ->
         return Err("wrong password or corrupted wallet".into());
     }
 
-    // Decrypt
-    let mut plaintext = Vec::with_capacity(ciphertext.len());
-    let mut keystream_pos = 0;
-    let mut block_counter = 0u64;
-    let mut keystream_block = [0u8; 32];
-
-    for &byte in ciphertext {
-        if keystream_pos == 0 || keystream_pos >= 32 {
-            let mut input = Vec::with_capacity(44 + 8);
-            input.extend_from_slice(key);
-            input.extend_from_slice(nonce);
-            input.extend_from_slice(&block_counter.to_le_bytes());
-            keystream_block.copy_from_slice(&Sha256::digest(&input));
-            block_counter += 1;
-            keystream_pos = 0;
-        }
-        plaintext.push(byte ^ keystream_block[keystream_pos]);
-        keystream_pos += 1;
-    }
-
-    Ok(plaintext)
+    Ok(legacy_keystream_xor(ciphertext, key, nonce))
 }
 
 // ─── Wallet ─────────────────────────────────────────────────────────
@@ -232,13 +305,47 @@ pub struct Wallet {
     pub path: Option<PathBuf>,
     /// If Some, wallet is encrypted with this password (kept in memory for auto-save)
     password: Option<String>,
+    /// HD master seed; None for legacy wallets of independent random keys
+    seed: Option<[u8; 32]>,
+    /// Next HD index `new_address` will derive
+    hd_index: u32,
+    /// Addresses tracked for balance without a private key (cold storage)
+    watch_only: Vec<Hash256>,
+    /// Per-address labels (spendable or watch-only)
+    labels: BTreeMap<Hash256, String>,
 }
 
 impl Wallet {
+    /// New HD wallet with a fresh random seed
     pub fn new(label: &str) -> Self {
-        Self { keypairs: vec![Keypair::generate()], label: label.to_string(), path: None, password: None }
+        let mut seed = [0u8; 32];
+        OsRng.fill_bytes(&mut seed);
+        let mut wallet = Self::from_seed(&seed);
+        wallet.label = label.to_string();
+        wallet
+    }
+
+    /// HD wallet whose first address is derived from `seed`
+    pub fn from_seed(seed: &[u8; 32]) -> Self {
+        Self::restore_from_seed(seed, 1)
     }
 
+    /// Recreate an HD wallet from its seed, deriving the first `count` addresses
+    pub fn restore_from_seed(seed: &[u8; 32], count: u32) -> Self {
+        let count = count.max(1);
+        Self {
+            keypairs: (0..count).map(|i| Keypair::from_secret_bytes(&hd::address_secret(seed, i))).collect(),
+            label: String::new(), path: None, password: None,
+            seed: Some(*seed), hd_index: count, watch_only: Vec::new(), labels: BTreeMap::new(),
+        }
+    }
+
+    /// HD master seed, if this is an HD wallet
+    pub fn seed(&self) -> Option<&[u8; 32]> { self.seed.as_ref() }
+
+    /// Number of addresses derived from the seed so far
+    pub fn hd_index(&self) -> u32 { self.hd_index }
+
     /// Load or create wallet. If encrypted, `password` must be provided.
     pub fn load_or_create(path: &Path, label: &str) -> Self {
         Self::load_or_create_with_password(path, label, None)
@@ -298,10 +405,16 @@ impl Wallet {
             let mut nonce = [0u8; 12];
             OsRng.fill_bytes(&mut nonce);
 
-            let key = derive_key(password.as_bytes(), &salt);
+            let kdf = KdfParams::default();
+            let key = derive_key(password.as_bytes(), &salt, Some(&kdf))
+                .expect("default KDF parameters are valid");
 
-            // Serialize keys as plaintext for encryption
-            let plaintext = bincode::serialize(&(&keys, &self.label)).unwrap();
+            // Serialize secrets as plaintext for encryption
+            let secrets = WalletSecrets {
+                keys, label: self.label.clone(), seed: self.seed, hd_index: self.hd_index,
+                watch_only: self.watch_only.clone(), labels: self.labels.clone(),
+            };
+            let plaintext = bincode::serialize(&secrets).unwrap();
             let ciphertext = encrypt_data(&plaintext, &key, &nonce);
 
             WalletFile {
@@ -310,17 +423,31 @@ impl Wallet {
                 salt: Some(hex::encode(salt)),
                 nonce: Some(hex::encode(nonce)),
                 ciphertext: Some(hex::encode(ciphertext)),
+                kdf: Some(kdf),
+                seed: None, hd_index: 0, watch_only: vec![], labels: BTreeMap::new(),
             }
         } else {
+            let watch_only = self.watch_only.iter().map(hex::encode).collect();
+            let labels = self.labels.iter().map(|(h, l)| (hex::encode(h), l.clone())).collect();
             WalletFile {
                 version: WALLET_VERSION, encrypted: false,
                 keys, label: self.label.clone(),
-                salt: None, nonce: None, ciphertext: None,
+                salt: None, nonce: None, ciphertext: None, kdf: None,
+                seed: self.seed.map(hex::encode), hd_index: self.hd_index, watch_only, labels,
             }
         }
     }
 
     fn from_wallet_file(wf: WalletFile, password: Option<&str>) -> Result<Self, String> {
+        let watch_only = wf.watch_only.iter().map(|h| {
+            hex::decode(h).ok().and_then(|b| <[u8; 32]>::try_from(b.as_slice()).ok())
+                .ok_or_else(|| format!("bad watch-only pubkey hash: {}", h))
+        }).collect::<Result<Vec<Hash256>, String>>()?;
+        let labels = wf.labels.iter().map(|(h, label)| {
+            hex::decode(h).ok().and_then(|b| <[u8; 32]>::try_from(b.as_slice()).ok())
+                .map(|hash| (hash, label.clone()))
+                .ok_or_else(|| format!("bad labeled pubkey hash: {}", h))
+        }).collect::<Result<BTreeMap<Hash256, String>, String>>()?;
         if wf.encrypted {
             let password = password.ok_or("wallet is encrypted, password required")?;
             let salt = hex::decode(wf.salt.ok_or("missing salt")?).map_err(|e| format!("bad salt: {}", e))?;
@@ -331,23 +458,46 @@ impl Wallet {
             let mut nonce = [0u8; 12];
             nonce.copy_from_slice(&nonce_bytes);
 
-            let key = derive_key(password.as_bytes(), &salt);
-            let plaintext = decrypt_data(&ciphertext, &key, &nonce)?;
-            let (keys, label): (Vec<[u8; 32]>, String) = bincode::deserialize(&plaintext)
-                .map_err(|e| format!("corrupt wallet data: {}", e))?;
+            let key = derive_key(password.as_bytes(), &salt, wf.kdf.as_ref())?;
+            let plaintext = if wf.version <= LEGACY_CIPHER_VERSION {
+                legacy_decrypt_data(&ciphertext, &key, &nonce)?
+            } else {
+                decrypt_data(&ciphertext, &key, &nonce)?
+            };
+            let secrets: WalletSecrets = if wf.version <= LEGACY_PAYLOAD_VERSION {
+                let (keys, label): (Vec<[u8; 32]>, String) = bincode::deserialize(&plaintext)
+                    .map_err(|e| format!("corrupt wallet data: {}", e))?;
+                WalletSecrets { keys, label, seed: None, hd_index: 0, watch_only, labels }
+            } else if wf.version <= CLEAR_METADATA_VERSION {
+                let v4: WalletSecretsV4 = bincode::deserialize(&plaintext)
+                    .map_err(|e| format!("corrupt wallet data: {}", e))?;
+                WalletSecrets { keys: v4.keys, label: v4.label, seed: v4.seed, hd_index: v4.hd_index, watch_only, labels }
+            } else {
+                bincode::deserialize(&plaintext).map_err(|e| format!("corrupt wallet data: {}", e))?
+            };
 
             Ok(Self {
-                keypairs: keys.iter().map(|b| Keypair::from_secret_bytes(b)).collect(),
-                label, path: None, password: Some(password.to_string()),
+                keypairs: secrets.keys.iter().map(Keypair::from_secret_bytes).collect(),
+                label: secrets.label, path: None, password: Some(password.to_string()),
+                seed: secrets.seed, hd_index: secrets.hd_index,
+                watch_only: secrets.watch_only, labels: secrets.labels,
             })
         } else {
             // Legacy unencrypted format or no password set
             if wf.keys.is_empty() {
                 return Err("no keys in wallet file".into());
             }
+            let seed = match wf.seed {
+                Some(hex_seed) => {
+                    let bytes = hex::decode(hex_seed).map_err(|e| format!("bad seed: {}", e))?;
+                    Some(<[u8; 32]>::try_from(bytes.as_slice()).map_err(|_| "invalid seed length")?)
+                }
+                None => None,
+            };
             Ok(Self {
-                keypairs: wf.keys.iter().map(|b| Keypair::from_secret_bytes(b)).collect(),
+                keypairs: wf.keys.iter().map(Keypair::from_secret_bytes).collect(),
                 label: wf.label, path: None, password: None,
+                seed, hd_index: wf.hd_index, watch_only, labels,
             })
         }
     }
@@ -366,8 +516,16 @@ impl Wallet {
 
     pub fn is_encrypted(&self) -> bool { self.password.is_some() }
 
+    /// Add an address: the next HD child for seeded wallets, a random key otherwise
     pub fn new_address(&mut self) -> String {
-        let kp = Keypair::generate();
+        let kp = match self.seed {
+            Some(ref seed) => {
+                let kp = Keypair::from_secret_bytes(&hd::address_secret(seed, self.hd_index));
+                self.hd_index += 1;
+                kp
+            }
+            None => Keypair::generate(),
+        };
         let addr = kp.address();
         self.keypairs.push(kp);
         self.save();
@@ -377,7 +535,26 @@ impl Wallet {
     pub fn primary_address(&self) -> String { self.keypairs[0].address() }
     pub fn primary_pubkey_hash(&self) -> Hash256 { self.keypairs[0].pubkey_hash() }
     pub fn addresses(&self) -> Vec<String> { self.keypairs.iter().map(|kp| kp.address()).collect() }
-    pub fn pubkey_hashes(&self) -> Vec<Hash256> { self.keypairs.iter().map(|kp| kp.pubkey_hash()).collect() }
+    /// Every tracked pubkey hash, watch-only ones included
+    pub fn pubkey_hashes(&self) -> Vec<Hash256> {
+        self.spendable_pubkey_hashes().into_iter().chain(self.watch_only.iter().copied()).collect()
+    }
+    /// Pubkey hashes this wallet holds private keys for
+    pub fn spendable_pubkey_hashes(&self) -> Vec<Hash256> { self.keypairs.iter().map(|kp| kp.pubkey_hash()).collect() }
+    pub fn watch_only(&self) -> &[Hash256] { &self.watch_only }
+    pub fn is_watch_only(&self, hash: &Hash256) -> bool { self.watch_only.contains(hash) }
+
+    /// Track an address's balance without its private key. Returns false if the
+    /// wallet already tracks it (watch-only or spendable).
+    pub fn add_watch_only(&mut self, pubkey_hash: Hash256) -> bool {
+        if self.is_watch_only(&pubkey_hash) || self.keypair_for_hash(&pubkey_hash).is_some() {
+            return false;
+        }
+        self.watch_only.push(pubkey_hash);
+        self.save();
+        true
+    }
+
     pub fn keypair_for_hash(&self, hash: &Hash256) -> Option<&Keypair> {
         self.keypairs.iter().find(|kp| &kp.pubkey_hash() == hash)
     }
@@ -385,6 +562,40 @@ impl Wallet {
         self.pubkey_hashes().iter().map(|h| utxo_set.balance_of(h)).sum()
     }
 
+    // ─── Address Labels ─────────────────────────────────────────────
+
+    /// Label one of the wallet's addresses; an empty label removes it
+    pub fn set_label(&mut self, pubkey_hash: Hash256, label: &str) -> Result<(), String> {
+        if self.keypair_for_hash(&pubkey_hash).is_none() && !self.is_watch_only(&pubkey_hash) {
+            return Err("address is not in this wallet".into());
+        }
+        let label = label.trim();
+        if label.is_empty() {
+            self.labels.remove(&pubkey_hash);
+        } else {
+            self.labels.insert(pubkey_hash, label.to_string());
+        }
+        self.save();
+        Ok(())
+    }
+
+    pub fn label_of(&self, pubkey_hash: &Hash256) -> Option<&str> {
+        self.labels.get(pubkey_hash).map(String::as_str)
+    }
+
+    /// Distinct labels in use, sorted
+    pub fn list_labels(&self) -> Vec<String> {
+        let mut labels: Vec<String> = self.labels.values().cloned().collect();
+        labels.sort();
+        labels.dedup();
+        labels
+    }
+
+    /// Pubkey hashes carrying `label`
+    pub fn hashes_by_label(&self, label: &str) -> Vec<Hash256> {
+        self.labels.iter().filter(|(_, l)| l.as_str() == label).map(|(h, _)| *h).collect()
+    }
+
     // ─── Transaction Building ───────────────────────────────────────
 
     /// Select UTXOs, skipping immature coinbase outputs.
@@ -395,40 +606,46 @@ impl Wallet {
         target_amount: u64,
         fee: u64,
         current_height: u64,
+        selection: CoinSelection,
     ) -> Result<Vec<(OutPoint, crate::core::chain::UtxoEntry)>, String> {
-        let needed = target_amount + fee;
-        let mut selected = Vec::new();
-        let mut total: u64 = 0;
-        let mut immature_amount: u64 = 0;
-
-        let mut our_utxos: Vec<(OutPoint, crate::core::chain::UtxoEntry)> = Vec::new();
-        for hash in self.pubkey_hashes() {
-            for (outpoint, entry) in utxo_set.utxos_for(&hash) {
-                // Skip immature coinbase outputs
-                if entry.is_coinbase && current_height.saturating_sub(entry.height) < COINBASE_MATURITY {
-                    immature_amount += entry.output.amount;
-                    continue;
-                }
-                our_utxos.push((outpoint, entry.clone()));
+        let candidates: Vec<(OutPoint, crate::core::chain::UtxoEntry)> = self.spendable_pubkey_hashes().iter()
+            .flat_map(|hash| utxo_set.utxos_for(hash))
+            .map(|(outpoint, entry)| (outpoint, entry.clone()))
+            .collect();
+        select_coins(candidates, target_amount, fee, current_height, selection).map_err(|e| {
+            let watched: u64 = self.watch_only.iter().map(|h| utxo_set.balance_of(h)).sum();
+            if watched > 0 && e.starts_with("insufficient funds") {
+                format!("{}; {} more is in watch-only addresses (no private key)", e, watched)
+            } else {
+                e
             }
-        }
-        // Sort largest first for fewer inputs
-        our_utxos.sort_by(|a, b| b.1.output.amount.cmp(&a.1.output.amount));
+        })
+    }
 
-        for (outpoint, entry) in our_utxos {
-            selected.push((outpoint, entry.clone()));
-            total += entry.output.amount;
-            if total >= needed { return Ok(selected); }
+    /// Like `create_send_tx`, but the fee is `fee_rate` (base units per byte) times
+    /// the signed transaction's size, never below `MIN_TX_FEE`.
+    pub fn create_send_tx_with_fee_rate(
+        &self,
+        utxo_set: &UtxoSet,
+        recipient_hash: Hash256,
+        amount: u64,
+        fee_rate: f64,
+        current_height: u64,
+        selection: CoinSelection,
+    ) -> Result<Transaction, String> {
+        if !fee_rate.is_finite() || fee_rate < 0.0 {
+            return Err("fee rate must be a non-negative number".into());
         }
-
-        if immature_amount > 0 {
-            Err(format!(
-                "insufficient mature funds: have {} spendable + {} immature (need {}). Mine {} more blocks for coinbase maturity.",
-                total, immature_amount, needed, COINBASE_MATURITY
-            ))
-        } else {
-            Err(format!("insufficient funds: have {}, need {} ({} + {} fee)", total, needed, target_amount, fee))
+        // A higher fee can pull in another input (a bigger tx), so iterate until
+        // the fee covers the size it produced
+        let mut fee = MIN_TX_FEE;
+        for _ in 0..8 {
+            let tx = self.create_send_tx(utxo_set, recipient_hash, amount, fee, current_height, selection)?;
+            let needed = ((fee_rate * tx.size() as f64).ceil() as u64).max(MIN_TX_FEE);
+            if needed <= fee { return Ok(tx); }
+            fee = needed;
         }
+        Err("could not settle on a fee for this fee rate".into())
     }
 
     /// Create and sign a send transaction. `current_height` used for coinbase maturity.
@@ -439,47 +656,49 @@ impl Wallet {
         amount: u64,
         fee: u64,
         current_height: u64,
+        selection: CoinSelection,
     ) -> Result<Transaction, String> {
-        let selected = self.select_utxos(utxo_set, amount, fee, current_height)?;
-        let total_input: u64 = selected.iter().map(|(_, e)| e.output.amount).sum();
-        let change = total_input - amount - fee;
-
-        let mut outputs = vec![
-            TxOutput {
-                amount,
-                pubkey_hash: recipient_hash,
-                script_pubkey: script::script_p2pkh(&recipient_hash),
-            }
-        ];
-
-        if change > 0 {
-            let change_hash = self.primary_pubkey_hash();
-            outputs.push(TxOutput {
-                amount: change,
-                pubkey_hash: change_hash,
-                script_pubkey: script::script_p2pkh(&change_hash),
-            });
-        }
-
-        let inputs: Vec<TxInput> = selected.iter().map(|(outpoint, _entry)| {
-            TxInput {
-                previous_output: outpoint.clone(),
-                signature: vec![],     // legacy
-                pubkey: vec![],        // legacy
-                script_sig: vec![],    // NEW: filled after signing
-                sequence: 0xFFFFFFFF,
-            }
-        }).collect();
-
+        self.create_multi_send_tx(utxo_set, &[(recipient_hash, amount)], fee, current_height, selection)
+    }
 
-        let mut tx = Transaction { version: 1, inputs, outputs, lock_time: 0 };
+    /// Create and sign one transaction paying every `(pubkey_hash, amount)` in `recipients`
+    pub fn create_multi_send_tx(
+        &self,
+        utxo_set: &UtxoSet,
+        recipients: &[(Hash256, u64)],
+        fee: u64,
+        current_height: u64,
+        selection: CoinSelection,
+    ) -> Result<Transaction, String> {
+        let total = recipients.iter().try_fold(0u64, |acc, (_, amount)| acc.checked_add(*amount))
+            .ok_or("total amount overflows")?;
+        let selected = self.select_utxos(utxo_set, total, fee, current_height, selection)?;
+        let unsigned = build_unsigned_multi_tx(&selected, recipients, fee, self.primary_pubkey_hash())?;
+        self.sign_tx(&unsigned)
+    }
 
-        for i in 0..tx.inputs.len() {
-            let owner_hash = &selected[i].1.output.pubkey_hash;
-            let kp = self.keypair_for_hash(owner_hash).expect("UTXO not owned");
+    /// Sign a transaction from `build_unsigned_tx`. Needs no chain access, so
+    /// it can run on a machine that never goes online.
+    pub fn sign_tx(&self, unsigned: &PartialTx) -> Result<Transaction, String> {
+        let mut tx = unsigned.tx.clone();
+        self.sign_p2pkh_inputs(&mut tx, &unsigned.prev_outputs)?;
+        Ok(tx)
+    }
 
-            let prev_output = &selected[i].1.output;
-            let signing_hash = crypto::tx_signing_hash_v1(&tx, i, prev_output);
+    /// Sign every input of `tx`; `prev_outputs[i]` is the output input `i` spends
+    /// and must pay one of this wallet's keys.
+    pub fn sign_p2pkh_inputs(&self, tx: &mut Transaction, prev_outputs: &[TxOutput]) -> Result<(), String> {
+        if prev_outputs.len() != tx.inputs.len() {
+            return Err("one previous output per input required".into());
+        }
+        for (i, prev_output) in prev_outputs.iter().enumerate() {
+            let kp = match self.keypair_for_hash(&prev_output.pubkey_hash) {
+                Some(kp) => kp,
+                None if self.is_watch_only(&prev_output.pubkey_hash) => return Err(format!(
+                    "no private key for watch-only address {}", pubkey_hash_to_address(&prev_output.pubkey_hash))),
+                None => return Err("UTXO not owned".into()),
+            };
+            let signing_hash = crypto::tx_signing_hash_v1(tx, i, prev_output);
 
             // Ed25519 signature is 64 bytes; your kp.sign returns Vec<u8>
             let sig_vec = kp.sign(&signing_hash);
@@ -500,9 +719,530 @@ impl Wallet {
             tx.inputs[i].signature = sig_vec;
             tx.inputs[i].pubkey = pubkey_vec;
         }
+        Ok(())
+    }
+}
+
+// ─── Coin Selection ─────────────────────────────────────────────────
+
+/// Pick inputs covering `target_amount + fee` from `candidates`, skipping
+/// immature coinbase outputs. `current_height` is the current chain height.
+pub fn select_coins(
+    candidates: Vec<(OutPoint, crate::core::chain::UtxoEntry)>,
+    target_amount: u64,
+    fee: u64,
+    current_height: u64,
+    selection: CoinSelection,
+) -> Result<Vec<(OutPoint, crate::core::chain::UtxoEntry)>, String> {
+    let needed = target_amount.checked_add(fee).ok_or("amount plus fee overflows")?;
+    let mut selected = Vec::new();
+    let mut total: u64 = 0;
+    let mut immature_amount: u64 = 0;
+
+    let mut our_utxos: Vec<(OutPoint, crate::core::chain::UtxoEntry)> = Vec::new();
+    for (outpoint, entry) in candidates {
+        // Skip immature coinbase outputs
+        if entry.is_coinbase && current_height.saturating_sub(entry.height) < COINBASE_MATURITY {
+            immature_amount += entry.output.amount;
+            continue;
+        }
+        our_utxos.push((outpoint, entry));
+    }
+    // Sort largest first for fewer inputs
+    our_utxos.sort_by_key(|u| std::cmp::Reverse(u.1.output.amount));
+    match selection {
+        CoinSelection::LargestFirst => {}
+        CoinSelection::SmallestFirst => our_utxos.reverse(),
+        CoinSelection::Oldest => our_utxos.sort_by_key(|(_, e)| e.height),
+        CoinSelection::BranchAndBound => {
+            let amounts: Vec<u64> = our_utxos.iter().map(|(_, e)| e.output.amount).collect();
+            if let Some(picked) = branch_and_bound(&amounts, needed) {
+                return Ok(picked.into_iter().map(|i| our_utxos[i].clone()).collect());
+            }
+            // No exact match: fall back to largest-first (with change)
+        }
+    }
+
+    for (outpoint, entry) in our_utxos {
+        total += entry.output.amount;
+        selected.push((outpoint, entry));
+        if total >= needed { return Ok(selected); }
+    }
+
+    if immature_amount > 0 {
+        Err(format!(
+            "insufficient mature funds: have {} spendable + {} immature (need {}). Mine {} more blocks for coinbase maturity.",
+            total, immature_amount, needed, COINBASE_MATURITY
+        ))
+    } else {
+        Err(format!("insufficient funds: have {}, need {} ({} + {} fee)", total, needed, target_amount, fee))
+    }
+}
+
+/// Order in which `select_utxos` spends the wallet's mature outputs
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CoinSelection {
+    /// Fewest inputs
+    #[default]
+    LargestFirst,
+    /// Consolidates dust, at the cost of more inputs
+    SmallestFirst,
+    /// Exact-match subset with no change output; falls back to largest-first
+    BranchAndBound,
+    /// Lowest height first
+    Oldest,
+}
+
+impl CoinSelection {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.to_ascii_lowercase().replace(['-', '_'], "").as_str() {
+            "largestfirst" | "largest" => Some(CoinSelection::LargestFirst),
+            "smallestfirst" | "smallest" => Some(CoinSelection::SmallestFirst),
+            "branchandbound" | "bnb" => Some(CoinSelection::BranchAndBound),
+            "oldest" => Some(CoinSelection::Oldest),
+            _ => None,
+        }
+    }
+}
+
+/// Search-node budget for branch-and-bound before giving up
+const BNB_MAX_TRIES: usize = 100_000;
+
+/// Indexes of a subset of `amounts` (sorted descending) summing to exactly
+/// `target`, found by depth-first search with include-first branching.
+fn branch_and_bound(amounts: &[u64], target: u64) -> Option<Vec<usize>> {
+    // remaining[i] = sum of amounts[i..], for pruning branches that can't reach the target
+    let mut remaining = vec![0u64; amounts.len() + 1];
+    for i in (0..amounts.len()).rev() {
+        remaining[i] = remaining[i + 1].saturating_add(amounts[i]);
+    }
+
+    fn search(amounts: &[u64], remaining: &[u64], i: usize, sum: u64, target: u64,
+              picked: &mut Vec<usize>, tries: &mut usize) -> bool {
+        if sum == target { return true; }
+        *tries += 1;
+        if i == amounts.len() || *tries > BNB_MAX_TRIES || sum.saturating_add(remaining[i]) < target {
+            return false;
+        }
+        if sum + amounts[i] <= target {
+            picked.push(i);
+            if search(amounts, remaining, i + 1, sum + amounts[i], target, picked, tries) { return true; }
+            picked.pop();
+        }
+        search(amounts, remaining, i + 1, sum, target, picked, tries)
+    }
 
+    let mut picked = Vec::new();
+    let mut tries = 0;
+    search(amounts, &remaining, 0, 0, target, &mut picked, &mut tries).then_some(picked)
+}
+
+// ─── Sweeping ───────────────────────────────────────────────────────
+
+/// A signed transaction moving everything a key holds to one address
+#[derive(Debug, Clone)]
+pub struct SweepTx {
+    pub tx: Transaction,
+    /// Total of the inputs spent
+    pub swept: u64,
+    pub fee: u64,
+    /// Immature coinbase outputs left behind
+    pub skipped_immature: u64,
+}
+
+/// Spend every mature output in `utxos` to `dest` in one transaction, signed
+/// with `keypair` (an outside key, e.g. a paper wallet; it's never stored).
+/// The fee is `fee_rate` per byte, at least `MIN_TX_FEE`.
+pub fn build_sweep_tx(
+    keypair: &Keypair,
+    utxos: &[(OutPoint, crate::core::chain::UtxoEntry)],
+    dest: Hash256,
+    fee_rate: f64,
+    current_height: u64,
+) -> Result<SweepTx, String> {
+    if !fee_rate.is_finite() || fee_rate < 0.0 {
+        return Err("fee rate must be a non-negative number".into());
+    }
+    let owner = keypair.pubkey_hash();
+    let (mature, immature): (Vec<_>, Vec<_>) = utxos.iter()
+        .filter(|(_, e)| e.output.pubkey_hash == owner)
+        .partition(|(_, e)| !e.is_coinbase || current_height.saturating_sub(e.height) >= COINBASE_MATURITY);
+    let skipped_immature = immature.iter().map(|(_, e)| e.output.amount).sum();
+    if mature.is_empty() {
+        return Err(if skipped_immature > 0 {
+            format!("nothing to sweep yet: {} is in immature coinbase outputs", skipped_immature)
+        } else {
+            "no unspent outputs for this key".into()
+        });
+    }
+    let swept: u64 = mature.iter().map(|(_, e)| e.output.amount).sum();
 
+    // Throwaway single-key wallet with no path, so nothing is ever saved
+    let signer = Wallet {
+        keypairs: vec![keypair.clone()], label: String::new(), path: None, password: None,
+        seed: None, hd_index: 0, watch_only: Vec::new(), labels: BTreeMap::new(),
+    };
+    let prev_outputs: Vec<TxOutput> = mature.iter().map(|(_, e)| e.output.clone()).collect();
+    let build = |fee: u64| -> Result<Transaction, String> {
+        let amount = swept.checked_sub(fee).filter(|a| *a >= DUST_THRESHOLD)
+            .ok_or_else(|| format!("balance {} doesn't cover the {} fee", swept, fee))?;
+        let mut tx = Transaction {
+            version: CURRENT_TX_VERSION,
+            inputs: mature.iter().map(|(op, _)| TxInput {
+                previous_output: op.clone(), signature: vec![], pubkey: vec![], script_sig: vec![], sequence: 0xFFFFFFFF,
+            }).collect(),
+            outputs: vec![TxOutput { amount, pubkey_hash: dest, script_pubkey: script::script_p2pkh(&dest) }],
+            lock_time: 0,
+        };
+        signer.sign_p2pkh_inputs(&mut tx, &prev_outputs)?;
         Ok(tx)
+    };
+
+    // Signatures are fixed-size, so one resize settles the fee
+    let draft = build(MIN_TX_FEE.min(swept))?;
+    let fee = ((fee_rate * draft.size() as f64).ceil() as u64).max(MIN_TX_FEE);
+    Ok(SweepTx { tx: build(fee)?, swept, fee, skipped_immature })
+}
+
+// ─── Message Signing ────────────────────────────────────────────────
+//
+// Ed25519 signatures don't allow public-key recovery, so a message signature
+// carries the public key: base58(pubkey(32) || signature(64)). Verification
+// checks the key hashes to the address and the signature covers the digest.
+
+const MESSAGE_TAG: &[u8] = b"EQF_MSG";
+
+/// Domain-separated digest that message signatures commit to
+pub fn message_digest(message: &str) -> Hash256 {
+    let mut hasher = Sha256::new();
+    hasher.update(MESSAGE_TAG);
+    hasher.update(message.as_bytes());
+    hasher.finalize().into()
+}
+
+impl Wallet {
+    /// Sign `message` with the key behind `address`, proving ownership of it
+    pub fn sign_message(&self, address: &str, message: &str) -> Result<String, String> {
+        let hash = address_to_pubkey_hash(address).ok_or("invalid address")?;
+        let kp = self.keypair_for_hash(&hash).ok_or("address is not in this wallet")?;
+        let mut blob = kp.public_key_bytes();
+        blob.extend_from_slice(&kp.sign(&message_digest(message)));
+        Ok(bs58_encode(&blob))
+    }
+}
+
+/// Check a `sign_message` signature. Ok(false) means well-formed but not valid
+/// for this address and message.
+pub fn verify_message(address: &str, message: &str, signature: &str) -> Result<bool, String> {
+    let hash = address_to_pubkey_hash(address).ok_or("invalid address")?;
+    let blob = bs58_decode(signature.trim()).ok_or("signature is not valid base58")?;
+    if blob.len() != 96 {
+        return Err(format!("signature must decode to 96 bytes, got {}", blob.len()));
+    }
+    let (pubkey, sig) = blob.split_at(32);
+    Ok(pubkey_bytes_to_hash(pubkey) == hash && verify_signature(pubkey, &message_digest(message), sig))
+}
+
+// ─── Mnemonic Backup ────────────────────────────────────────────────
+//
+// The BIP39 phrase encodes the 32-byte HD seed itself as its entropy, so it is
+// always 24 words. (The BIP39 PBKDF2 seed stretch isn't used: it's one-way, and
+// the phrase must be recoverable from an existing wallet's seed.)
+
+/// Consecutive unused addresses that end a restore scan
+pub const RESTORE_GAP_LIMIT: u32 = 20;
+
+impl Wallet {
+    /// 24-word English BIP39 phrase for the HD seed; None for random-key wallets
+    pub fn to_mnemonic(&self) -> Option<String> {
+        let seed = self.seed.as_ref()?;
+        let mnemonic = bip39::Mnemonic::from_entropy(seed).expect("32 bytes is a valid BIP39 entropy length");
+        Some(mnemonic.to_string())
+    }
+
+    /// HD wallet (first address only) from a phrase produced by `to_mnemonic`
+    pub fn from_mnemonic(phrase: &str) -> Result<Self, String> {
+        Ok(Self::from_seed(&mnemonic_to_seed(phrase)?))
+    }
+
+    /// Recreate an HD wallet, deriving addresses until `gap` in a row are unused.
+    /// Keeps every address up to the last used one (at least one address).
+    pub fn restore_scanning(seed: &[u8; 32], gap: u32, mut is_used: impl FnMut(&Hash256) -> bool) -> Self {
+        let mut count = 0;
+        let mut unused = 0;
+        let mut index = 0;
+        while unused < gap.max(1) {
+            let kp = Keypair::from_secret_bytes(&hd::address_secret(seed, index));
+            index += 1;
+            if is_used(&kp.pubkey_hash()) {
+                count = index;
+                unused = 0;
+            } else {
+                unused += 1;
+            }
+        }
+        Self::restore_from_seed(seed, count)
+    }
+}
+
+/// Decode and checksum-verify a 24-word English phrase back into an HD seed
+pub fn mnemonic_to_seed(phrase: &str) -> Result<[u8; 32], String> {
+    let normalized = phrase.split_whitespace().collect::<Vec<_>>().join(" ").to_lowercase();
+    let words = normalized.split(' ').count();
+    if words != 24 {
+        return Err(format!("expected a 24-word phrase, got {} words", words));
+    }
+    let mnemonic = bip39::Mnemonic::parse_in_normalized(bip39::Language::English, &normalized)
+        .map_err(|e| format!("invalid mnemonic: {}", e))?;
+    let (entropy, len) = mnemonic.to_entropy_array();
+    <[u8; 32]>::try_from(&entropy[..len]).map_err(|_| "invalid mnemonic length".to_string())
+}
+
+/// True if the wallet file at `path` exists and is password-protected
+pub fn wallet_file_is_encrypted(path: &Path) -> bool {
+    std::fs::read_to_string(path).ok()
+        .and_then(|json| serde_json::from_str::<WalletFile>(&json).ok())
+        .is_some_and(|wf| wf.encrypted)
+}
+
+// ─── Multisig ───────────────────────────────────────────────────────
+
+/// Build an m-of-n multisig lock. Returns (address, script_pubkey); the address
+/// encodes the script hash, and the script is what a payer must put in the output.
+pub fn create_multisig(required: u8, key_hashes: &[Hash256]) -> Result<(String, Vec<u8>), String> {
+    let script_pubkey = script::script_multisig(required, key_hashes).map_err(|_| format!(
+        "invalid multisig: need 1 <= m <= n <= {} distinct keys (got m={}, n={})",
+        script::MAX_MULTISIG_KEYS, required, key_hashes.len()))?;
+    let address = pubkey_hash_to_address(&script::multisig_script_hash(&script_pubkey));
+    Ok((address, script_pubkey))
+}
+
+/// Add `kp`'s signature to a multisig input if it holds one of the listed keys
+/// and hasn't signed yet. Returns whether a signature was added.
+fn sign_multisig_with(kp: &Keypair, tx: &mut Transaction, input_index: usize, prev_output: &TxOutput) -> Result<bool, String> {
+    let (_, key_hashes) = script::parse_script_pubkey_multisig(&prev_output.script_pubkey)
+        .map_err(|_| "previous output is not multisig".to_string())?;
+    let input = tx.inputs.get(input_index).ok_or("input index out of range")?;
+    let mut sigs = script::parse_script_sig_multisig(&input.script_sig)
+        .map_err(|e| format!("bad existing multisig signatures: {:?}", e))?;
+
+    let own_hash = kp.pubkey_hash();
+    let Some(idx) = key_hashes.iter().position(|h| *h == own_hash) else { return Ok(false) };
+    if sigs.iter().any(|(i, _, _)| *i as usize == idx) { return Ok(false); }
+
+    let signing_hash = crypto::tx_signing_hash_v1(tx, input_index, prev_output);
+    let sig64: [u8; 64] = kp.sign(&signing_hash).as_slice().try_into()
+        .map_err(|_| "signature must be 64 bytes")?;
+    let pubkey32: [u8; 32] = kp.public_key_bytes().as_slice().try_into()
+        .map_err(|_| "pubkey must be 32 bytes")?;
+    sigs.push((idx as u8, pubkey32, sig64));
+    tx.inputs[input_index].script_sig = script::script_sig_multisig(&sigs);
+    Ok(true)
+}
+
+impl Wallet {
+    /// Add this wallet's signatures to a multisig input, keeping any signatures
+    /// other co-signers already attached. Returns how many signatures were added.
+    pub fn sign_multisig_input(&self, tx: &mut Transaction, input_index: usize, prev_output: &TxOutput) -> Result<usize, String> {
+        let mut added = 0;
+        for kp in &self.keypairs {
+            if sign_multisig_with(kp, tx, input_index, prev_output)? { added += 1; }
+        }
+        Ok(added)
+    }
+}
+
+// ─── Partially-Signed Transactions ──────────────────────────────────
+
+/// A transaction in the middle of being signed, plus the outputs its inputs
+/// spend so offline signers can compute sighashes without a UTXO set.
+///
+/// Signatures collected so far live in each input's `script_sig`: a finished
+/// P2PKH unlock for type-0 inputs, a partial signature list for multisig ones.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PartialTx {
+    pub tx: Transaction,
+    /// Output spent by each input, in input order
+    pub prev_outputs: Vec<TxOutput>,
+}
+
+impl PartialTx {
+    /// Wrap an unsigned transaction. Any existing signatures are dropped.
+    pub fn new(mut tx: Transaction, prev_outputs: Vec<TxOutput>) -> Result<Self, String> {
+        if tx.inputs.len() != prev_outputs.len() {
+            return Err(format!("{} inputs but {} previous outputs", tx.inputs.len(), prev_outputs.len()));
+        }
+        for input in &mut tx.inputs {
+            input.script_sig.clear();
+            input.signature.clear();
+            input.pubkey.clear();
+        }
+        Ok(Self { tx, prev_outputs })
+    }
+
+    pub fn to_hex(&self) -> String {
+        hex::encode(bincode::serialize(self).expect("PartialTx serializes"))
+    }
+
+    pub fn from_hex(s: &str) -> Result<Self, String> {
+        let bytes = hex::decode(s).map_err(|_| "invalid hex".to_string())?;
+        let ptx: Self = bincode::deserialize(&bytes).map_err(|e| format!("decode failed: {}", e))?;
+        if ptx.tx.inputs.len() != ptx.prev_outputs.len() {
+            return Err("input / previous output count mismatch".into());
+        }
+        Ok(ptx)
+    }
+
+    /// Merge signatures another party collected for the same transaction
+    pub fn combine(&mut self, other: &PartialTx) -> Result<(), String> {
+        if crypto::txid::txid_v1(&self.tx) != crypto::txid::txid_v1(&other.tx) || self.prev_outputs != other.prev_outputs {
+            return Err("partial transactions are for different transactions".into());
+        }
+        for (i, prev_output) in self.prev_outputs.iter().enumerate() {
+            let theirs = &other.tx.inputs[i].script_sig;
+            if theirs.is_empty() { continue; }
+            match script::classify_output(prev_output) {
+                Ok(script::OutputType::Multisig { .. }) => {
+                    let mut sigs = script::parse_script_sig_multisig(&self.tx.inputs[i].script_sig)
+                        .map_err(|e| format!("input {}: {:?}", i, e))?;
+                    let other_sigs = script::parse_script_sig_multisig(theirs)
+                        .map_err(|e| format!("input {}: {:?}", i, e))?;
+                    sigs.extend(other_sigs);
+                    self.tx.inputs[i].script_sig = script::script_sig_multisig(&sigs);
+                }
+                _ => {
+                    if self.tx.inputs[i].script_sig.is_empty() {
+                        self.tx.inputs[i].script_sig = theirs.clone();
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Sign every input `kp` can sign. Returns how many signatures were added.
+    pub fn sign_with(&mut self, kp: &Keypair) -> Result<usize, String> {
+        let own_hash = kp.pubkey_hash();
+        let mut added = 0;
+        for i in 0..self.tx.inputs.len() {
+            let prev_output = &self.prev_outputs[i];
+            match script::classify_output(prev_output) {
+                Ok(script::OutputType::PubkeyHash(h)) if h == own_hash && self.tx.inputs[i].script_sig.is_empty() => {
+                    let signing_hash = crypto::tx_signing_hash_v1(&self.tx, i, prev_output);
+                    let sig64: [u8; 64] = kp.sign(&signing_hash).as_slice().try_into()
+                        .map_err(|_| "signature must be 64 bytes")?;
+                    let pubkey32: [u8; 32] = kp.public_key_bytes().as_slice().try_into()
+                        .map_err(|_| "pubkey must be 32 bytes")?;
+                    self.tx.inputs[i].script_sig = script::script_sig_p2pkh(&sig64, &pubkey32);
+                    added += 1;
+                }
+                Ok(script::OutputType::Multisig { .. }) => {
+                    added += sign_multisig_with(kp, &mut self.tx, i, prev_output)? as usize;
+                }
+                _ => {}
+            }
+        }
+        Ok(added)
+    }
+
+    /// Sign with every key in `wallet`
+    pub fn sign_with_wallet(&mut self, wallet: &Wallet) -> Result<usize, String> {
+        let mut added = 0;
+        for kp in &wallet.keypairs { added += self.sign_with(kp)?; }
+        Ok(added)
+    }
+
+    /// Indexes of inputs that still lack enough signatures
+    pub fn missing_inputs(&self) -> Vec<usize> {
+        self.prev_outputs.iter().enumerate().filter(|(i, prev_output)| {
+            let script_sig = &self.tx.inputs[*i].script_sig;
+            match script::classify_output(prev_output) {
+                Ok(script::OutputType::Multisig { required, .. }) => script::parse_script_sig_multisig(script_sig)
+                    .map(|sigs| sigs.len() < required as usize).unwrap_or(true),
+                _ => script_sig.is_empty(),
+            }
+        }).map(|(i, _)| i).collect()
+    }
+
+    pub fn is_complete(&self) -> bool { self.missing_inputs().is_empty() }
+
+    /// Produce the broadcastable transaction once every input is fully signed
+    pub fn finalize(self) -> Result<Transaction, String> {
+        let missing = self.missing_inputs();
+        if !missing.is_empty() {
+            return Err(format!("inputs {:?} still need signatures", missing));
+        }
+        Ok(self.tx)
+    }
+}
+
+// ─── Offline Signing ────────────────────────────────────────────────
+
+/// Unsigned transaction spending exactly `inputs`: `amount` to the recipient,
+/// the rest minus `fee` back to `change_hash`. Sub-dust change goes to the fee.
+/// Needs no keys, so a watch-only wallet can prepare spends for a cold one.
+pub fn build_unsigned_tx(
+    inputs: &[(OutPoint, crate::core::chain::UtxoEntry)],
+    recipient_hash: Hash256,
+    amount: u64,
+    fee: u64,
+    change_hash: Hash256,
+) -> Result<PartialTx, String> {
+    build_unsigned_multi_tx(inputs, &[(recipient_hash, amount)], fee, change_hash)
+}
+
+/// `build_unsigned_tx` with one output per `(pubkey_hash, amount)` recipient,
+/// in order, ahead of the change output
+pub fn build_unsigned_multi_tx(
+    inputs: &[(OutPoint, crate::core::chain::UtxoEntry)],
+    recipients: &[(Hash256, u64)],
+    fee: u64,
+    change_hash: Hash256,
+) -> Result<PartialTx, String> {
+    if recipients.is_empty() { return Err("no recipients".into()); }
+    let amount = recipients.iter().try_fold(0u64, |acc, (_, a)| acc.checked_add(*a))
+        .ok_or("total amount overflows")?;
+    let needed = amount.checked_add(fee).ok_or("amount plus fee overflows")?;
+    let total_input: u64 = inputs.iter().map(|(_, e)| e.output.amount).sum();
+    let change = total_input.checked_sub(needed)
+        .ok_or_else(|| format!("inputs total {} but {} + {} fee is needed", total_input, amount, fee))?;
+
+    let mut outputs: Vec<TxOutput> = recipients.iter().map(|(hash, amount)| TxOutput {
+        amount: *amount,
+        pubkey_hash: *hash,
+        script_pubkey: script::script_p2pkh(hash),
+    }).collect();
+    // Sub-dust change would be rejected by validation; it goes to the fee instead
+    if change >= DUST_THRESHOLD {
+        outputs.push(TxOutput {
+            amount: change,
+            pubkey_hash: change_hash,
+            script_pubkey: script::script_p2pkh(&change_hash),
+        });
+    }
+
+    let tx_inputs: Vec<TxInput> = inputs.iter().map(|(outpoint, _)| TxInput {
+        previous_output: outpoint.clone(),
+        signature: vec![],
+        pubkey: vec![],
+        script_sig: vec![],
+        sequence: 0xFFFFFFFF,
+    }).collect();
+    let tx = Transaction { version: CURRENT_TX_VERSION, inputs: tx_inputs, outputs, lock_time: 0 };
+    PartialTx::new(tx, inputs.iter().map(|(_, e)| e.output.clone()).collect())
+}
+
+impl PartialTx {
+    /// Signing hash of each input, for checking on the signing machine
+    pub fn sighashes(&self) -> Vec<Hash256> {
+        self.prev_outputs.iter().enumerate()
+            .map(|(i, prev_output)| crypto::tx_signing_hash_v1(&self.tx, i, prev_output))
+            .collect()
+    }
+
+    /// Input total minus output total
+    pub fn fee(&self) -> u64 {
+        self.prev_outputs.iter().map(|o| o.amount).sum::<u64>().saturating_sub(self.tx.total_output())
     }
 }
 
@@ -595,11 +1335,53 @@ mod tests {
         assert!(decrypt_data(&encrypted, &key2, &nonce).is_err());
     }
 
+    #[test]
+    fn test_ciphertext_tamper_detected() {
+        let key = [42u8; 32];
+        let nonce = [7u8; 12];
+        let mut encrypted = encrypt_data(b"secret wallet keys here", &key, &nonce);
+        assert_eq!(encrypted.len(), 23 + 16);
+        encrypted[0] ^= 1;
+        assert!(decrypt_data(&encrypted, &key, &nonce).is_err());
+    }
+
+    #[test]
+    fn test_legacy_v1_wallet_still_decrypts() {
+        let keypairs = vec![Keypair::generate(), Keypair::generate()];
+        let keys: Vec<[u8; 32]> = keypairs.iter().map(|kp| kp.secret_bytes()).collect();
+        let (salt, nonce) = ([3u8; 16], [5u8; 12]);
+        let key = legacy_derive_key(b"hunter2", &salt);
+
+        // Build a v1 file the way the old cipher did
+        let plaintext = bincode::serialize(&(&keys, "old")).unwrap();
+        let mut ciphertext = legacy_keystream_xor(&plaintext, &key, &nonce);
+        let mut mac_input = key.to_vec();
+        mac_input.extend_from_slice(&ciphertext);
+        ciphertext.extend_from_slice(&Sha256::digest(&mac_input));
+        let wf = WalletFile {
+            version: 1, encrypted: true, keys: vec![], label: String::new(),
+            salt: Some(hex::encode(salt)), nonce: Some(hex::encode(nonce)),
+            ciphertext: Some(hex::encode(ciphertext)), kdf: None,
+            seed: None, hd_index: 0, watch_only: Vec::new(), labels: BTreeMap::new(),
+        };
+
+        let loaded = Wallet::from_wallet_file(wf, Some("hunter2")).unwrap();
+        assert_eq!(loaded.label, "old");
+        assert_eq!(loaded.primary_address(), keypairs[0].address());
+
+        // Saving again upgrades it to AES-GCM
+        let upgraded = loaded.to_wallet_file();
+        assert_eq!(upgraded.version, WALLET_VERSION);
+        let reloaded = Wallet::from_wallet_file(upgraded, Some("hunter2")).unwrap();
+        assert_eq!(reloaded.keypairs.len(), 2);
+    }
+
     #[test]
     fn test_wallet_encrypted_roundtrip() {
         let wallet = Wallet {
             keypairs: vec![Keypair::generate(), Keypair::generate()],
             label: "test".to_string(), path: None, password: Some("hunter2".to_string()),
+            seed: None, hd_index: 0, watch_only: Vec::new(), labels: BTreeMap::new(),
         };
         let wf = wallet.to_wallet_file();
         assert!(wf.encrypted);
@@ -610,15 +1392,433 @@ mod tests {
         assert_eq!(loaded.primary_address(), wallet.primary_address());
     }
 
+    #[test]
+    fn test_argon2_wallet_save_load_roundtrip() {
+        let dir = std::env::temp_dir().join(format!("equiforge-wallet-argon2-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let path = dir.join("wallet.json");
+
+        let mut wallet = Wallet::new("argon");
+        wallet.keypairs.push(Keypair::generate());
+        wallet.path = Some(path.clone());
+        wallet.password = Some("correct horse".into());
+        wallet.save();
+
+        let wf: WalletFile = serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(wf.version, WALLET_VERSION);
+        assert_eq!(wf.kdf, Some(KdfParams::default()));
+
+        let loaded = Wallet::load_or_create_with_password(&path, "ignored", Some("correct horse"));
+        assert_eq!(loaded.label, "argon");
+        assert_eq!(loaded.addresses(), wallet.addresses());
+        assert!(Wallet::from_wallet_file(wf, Some("wrong horse")).is_err());
+
+        // Stored parameters win over the current defaults
+        let cheap = KdfParams { memory_kib: 64, iterations: 1, ..KdfParams::default() };
+        let salt = [1u8; 16];
+        assert_ne!(derive_key(b"pw", &salt, Some(&cheap)).unwrap(), derive_key(b"pw", &salt, None).unwrap());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_hd_wallet_restores_every_address() {
+        let seed = [11u8; 32];
+        let mut wallet = Wallet::from_seed(&seed);
+        wallet.new_address();
+        wallet.new_address();
+        assert_eq!(wallet.hd_index(), 3);
+
+        // Seed + index survive both file formats
+        let loaded = Wallet::from_wallet_file(wallet.to_wallet_file(), None).unwrap();
+        assert_eq!(loaded.seed(), Some(&seed));
+        assert_eq!(loaded.hd_index(), 3);
+        wallet.password = Some("pw".into());
+        let loaded = Wallet::from_wallet_file(wallet.to_wallet_file(), Some("pw")).unwrap();
+        assert_eq!(loaded.seed(), Some(&seed));
+
+        // Restoring from the seed alone regenerates the same addresses
+        let restored = Wallet::restore_from_seed(&seed, wallet.hd_index());
+        assert_eq!(restored.addresses(), wallet.addresses());
+
+        // Legacy random-key wallets keep generating random keys
+        let mut legacy = Wallet { keypairs: vec![Keypair::generate()], label: String::new(), path: None, password: None, seed: None, hd_index: 0, watch_only: Vec::new(), labels: BTreeMap::new() };
+        legacy.new_address();
+        assert_eq!(legacy.keypairs.len(), 2);
+        assert!(Wallet::from_wallet_file(legacy.to_wallet_file(), None).unwrap().seed().is_none());
+    }
+
+    #[test]
+    fn test_mnemonic_roundtrip_and_gap_scan() {
+        let wallet = Wallet::from_seed(&[0x5A; 32]);
+        let phrase = wallet.to_mnemonic().unwrap();
+        assert_eq!(phrase.split(' ').count(), 24);
+        let restored = Wallet::from_mnemonic(&format!("  {}\n", phrase.to_uppercase())).unwrap();
+        assert_eq!(restored.seed(), wallet.seed());
+        assert_eq!(restored.primary_address(), wallet.primary_address());
+
+        // Checksum and length are enforced
+        let mut words: Vec<&str> = phrase.split(' ').collect();
+        words.swap(0, 1);
+        if words[0] != words[1] {
+            assert!(Wallet::from_mnemonic(&words.join(" ")).is_err());
+        }
+        assert!(Wallet::from_mnemonic(&phrase.split(' ').take(12).collect::<Vec<_>>().join(" ")).is_err());
+
+        // Addresses 0 and 7 have history: keep 0..=7, stop after 10 unused in a row
+        let seed = [0x5A; 32];
+        let used: Vec<Hash256> = [0, 7].iter()
+            .map(|&i| Keypair::from_secret_bytes(&hd::address_secret(&seed, i)).pubkey_hash()).collect();
+        let mut checked = 0;
+        let scanned = Wallet::restore_scanning(&seed, 10, |h| { checked += 1; used.contains(h) });
+        assert_eq!(scanned.keypairs.len(), 8);
+        assert_eq!(scanned.hd_index(), 8);
+        assert_eq!(checked, 18);
+
+        // Random-key wallets have no phrase
+        let legacy = Wallet { keypairs: vec![Keypair::generate()], label: String::new(), path: None, password: None, seed: None, hd_index: 0, watch_only: Vec::new(), labels: BTreeMap::new() };
+        assert!(legacy.to_mnemonic().is_none());
+    }
+
+    #[test]
+    fn test_sign_and_verify_message() {
+        let mut wallet = Wallet::from_seed(&[1u8; 32]);
+        let other = wallet.new_address();
+        let addr = wallet.primary_address();
+
+        let sig = wallet.sign_message(&addr, "I own this").unwrap();
+        assert_eq!(verify_message(&addr, "I own this", &sig), Ok(true));
+        assert_eq!(verify_message(&addr, "I own that", &sig), Ok(false));
+        // Valid signature, but by a different address's key
+        assert_eq!(verify_message(&other, "I own this", &sig), Ok(false));
+        assert!(verify_message(&addr, "I own this", "not-base58!").is_err());
+
+        let stranger = Keypair::generate().address();
+        assert_eq!(wallet.sign_message(&stranger, "hi").unwrap_err(), "address is not in this wallet");
+    }
+
+    /// Wallet with one mature, non-coinbase output per amount
+    fn funded_wallet(amounts: &[u64]) -> (Wallet, UtxoSet) {
+        let wallet = Wallet::from_seed(&[2u8; 32]);
+        let mut utxos = UtxoSet::new();
+        for (i, &amount) in amounts.iter().enumerate() {
+            utxos.add(OutPoint { txid: [i as u8 + 1; 32], vout: 0 }, crate::core::chain::UtxoEntry {
+                output: TxOutput { amount, pubkey_hash: wallet.primary_pubkey_hash(), script_pubkey: vec![] },
+                height: 10 + (amounts.len() - i) as u64, is_coinbase: false,
+            });
+        }
+        (wallet, utxos)
+    }
+
+    #[test]
+    fn test_branch_and_bound_is_changeless() {
+        let (wallet, utxos) = funded_wallet(&[50_000, 30_000, 20_000, 7_000, 3_000]);
+        let recipient = [9u8; 32];
+        // 26_000 + 1_000 fee is exactly 20_000 + 7_000
+        let tx = wallet.create_send_tx(&utxos, recipient, 26_000, 1_000, 100, CoinSelection::BranchAndBound).unwrap();
+        assert_eq!(tx.outputs.len(), 1, "exact match should need no change output");
+        assert_eq!(tx.inputs.len(), 2);
+
+        // Largest-first on the same request overshoots and pays change
+        let tx = wallet.create_send_tx(&utxos, recipient, 26_000, 1_000, 100, CoinSelection::LargestFirst).unwrap();
+        assert_eq!(tx.inputs.len(), 1);
+        assert_eq!(tx.outputs.len(), 2);
+
+        // No exact subset: BnB falls back to largest-first
+        let tx = wallet.create_send_tx(&utxos, recipient, 1_500, 1_000, 100, CoinSelection::BranchAndBound).unwrap();
+        assert_eq!(tx.outputs.len(), 2);
+    }
+
+    #[test]
+    fn test_multi_send_pays_every_recipient() {
+        let (wallet, utxos) = funded_wallet(&[50_000, 30_000]);
+        let recipients = [([7u8; 32], 10_000), ([8u8; 32], 20_000), ([9u8; 32], 25_000)];
+        let tx = wallet.create_multi_send_tx(&utxos, &recipients, 1_000, 100, CoinSelection::LargestFirst).unwrap();
+        assert_eq!(tx.inputs.len(), 2);
+        let paid: Vec<(Hash256, u64)> = tx.outputs[..3].iter().map(|o| (o.pubkey_hash, o.amount)).collect();
+        assert_eq!(paid, recipients);
+        assert_eq!(tx.outputs[3].pubkey_hash, wallet.primary_pubkey_hash());
+        assert_eq!(tx.outputs[3].amount, 80_000 - 55_000 - 1_000);
+
+        let err = wallet.create_multi_send_tx(&utxos, &[([7u8; 32], 60_000), ([8u8; 32], 20_000)], 1_000, 100, CoinSelection::LargestFirst).unwrap_err();
+        assert!(err.starts_with("insufficient funds"), "{}", err);
+        let err = wallet.create_multi_send_tx(&utxos, &[([7u8; 32], u64::MAX), ([8u8; 32], 1)], 1_000, 100, CoinSelection::LargestFirst).unwrap_err();
+        assert!(err.contains("overflows"), "{}", err);
+        let err = wallet.create_multi_send_tx(&utxos, &[([7u8; 32], u64::MAX - 1)], 1_000, 100, CoinSelection::LargestFirst).unwrap_err();
+        assert!(err.contains("overflows"), "{}", err);
+
+        // Same checks when building from inputs picked elsewhere
+        let inputs: Vec<_> = utxos.utxos_for(&wallet.primary_pubkey_hash()).into_iter().map(|(op, e)| (op, e.clone())).collect();
+        assert!(build_unsigned_multi_tx(&inputs, &[([7u8; 32], u64::MAX), ([8u8; 32], 1)], 0, [0; 32]).unwrap_err().contains("overflows"));
+        assert!(build_unsigned_multi_tx(&inputs, &[([7u8; 32], 1)], u64::MAX, [0; 32]).unwrap_err().contains("overflows"));
+    }
+
+    #[test]
+    fn test_sub_dust_change_folded_into_fee() {
+        let (wallet, utxos) = funded_wallet(&[30_000]);
+        let recipient = [9u8; 32];
+        let change = DUST_THRESHOLD - 1;
+        let tx = wallet.create_send_tx(&utxos, recipient, 30_000 - 1_000 - change, 1_000, 100, CoinSelection::LargestFirst).unwrap();
+        assert_eq!(tx.outputs.len(), 1);
+        assert_eq!(30_000 - tx.total_output(), 1_000 + change);
+
+        // Change at the threshold is still paid back
+        let tx = wallet.create_send_tx(&utxos, recipient, 30_000 - 1_000 - DUST_THRESHOLD, 1_000, 100, CoinSelection::LargestFirst).unwrap();
+        assert_eq!(tx.outputs.len(), 2);
+        assert_eq!(tx.outputs[1].amount, DUST_THRESHOLD);
+    }
+
+    #[test]
+    fn test_send_with_fee_rate() {
+        let (wallet, utxos) = funded_wallet(&[500_000, 300_000]);
+        let recipient = [9u8; 32];
+        let paid = |tx: &Transaction| 800_000 - tx.total_output() - if tx.inputs.len() == 1 { 300_000 } else { 0 };
+
+        // Tiny rate: the MIN_TX_FEE floor applies
+        let tx = wallet.create_send_tx_with_fee_rate(&utxos, recipient, 100_000, 0.01, 100, CoinSelection::LargestFirst).unwrap();
+        assert_eq!(paid(&tx), MIN_TX_FEE);
+
+        // 20 units/byte: fee covers the signed size
+        let tx = wallet.create_send_tx_with_fee_rate(&utxos, recipient, 100_000, 20.0, 100, CoinSelection::LargestFirst).unwrap();
+        assert!(paid(&tx) >= 20 * tx.size() as u64);
+        assert!(paid(&tx) < 21 * tx.size() as u64);
+
+        assert!(wallet.create_send_tx_with_fee_rate(&utxos, recipient, 1, f64::NAN, 100, CoinSelection::LargestFirst).is_err());
+    }
+
+    #[test]
+    fn test_watch_only_tracked_but_never_spent() {
+        let (mut wallet, mut utxos) = funded_wallet(&[10_000]);
+        let cold = Keypair::generate().pubkey_hash();
+        let cold_output = TxOutput { amount: 90_000, pubkey_hash: cold, script_pubkey: script::script_p2pkh(&cold) };
+        utxos.add(OutPoint { txid: [0xC0; 32], vout: 0 }, crate::core::chain::UtxoEntry {
+            output: cold_output.clone(), height: 1, is_coinbase: false,
+        });
+
+        assert!(wallet.add_watch_only(cold));
+        assert!(!wallet.add_watch_only(cold));
+        assert!(!wallet.add_watch_only(wallet.primary_pubkey_hash()));
+        assert_eq!(wallet.balance(&utxos), 100_000);
+
+        // Only the spendable 10_000 is selectable
+        let err = wallet.select_utxos(&utxos, 50_000, 1_000, 100, CoinSelection::LargestFirst).unwrap_err();
+        assert!(err.contains("watch-only"), "{}", err);
+        let picked = wallet.select_utxos(&utxos, 5_000, 1_000, 100, CoinSelection::LargestFirst).unwrap();
+        assert!(picked.iter().all(|(_, e)| e.output.pubkey_hash != cold));
+
+        // Signing a spend of the watched output fails clearly
+        let mut tx = Transaction {
+            version: CURRENT_TX_VERSION,
+            inputs: vec![TxInput { previous_output: OutPoint { txid: [0xC0; 32], vout: 0 }, signature: vec![], pubkey: vec![], script_sig: vec![], sequence: 0xFFFFFFFF }],
+            outputs: vec![TxOutput { amount: 80_000, pubkey_hash: [9; 32], script_pubkey: script::script_p2pkh(&[9; 32]) }],
+            lock_time: 0,
+        };
+        let err = wallet.sign_p2pkh_inputs(&mut tx, &[cold_output]).unwrap_err();
+        assert!(err.contains("no private key"), "{}", err);
+
+        // Persisted alongside keys, encrypted or not
+        let loaded = Wallet::from_wallet_file(wallet.to_wallet_file(), None).unwrap();
+        assert_eq!(loaded.watch_only(), &[cold]);
+        wallet.password = Some("pw".into());
+        let wf = wallet.to_wallet_file();
+        assert!(wf.watch_only.is_empty());
+        assert_eq!(Wallet::from_wallet_file(wf, Some("pw")).unwrap().watch_only(), &[cold]);
+    }
+
+    #[test]
+    fn test_offline_signing_round_trip() {
+        let cold = Wallet::from_seed(&[6u8; 32]);
+        let mut online = Wallet::new("online");
+        online.add_watch_only(cold.primary_pubkey_hash());
+        let mut cold_utxos = UtxoSet::new();
+        for (i, amount) in [40_000u64, 70_000].into_iter().enumerate() {
+            cold_utxos.add(OutPoint { txid: [0xD0 + i as u8; 32], vout: 0 }, crate::core::chain::UtxoEntry {
+                output: TxOutput { amount, pubkey_hash: cold.primary_pubkey_hash(), script_pubkey: vec![] },
+                height: 1, is_coinbase: false,
+            });
+        }
+
+        // Online side: explicit candidates, no keys involved
+        let candidates = cold_utxos.utxos_for(&cold.primary_pubkey_hash()).into_iter()
+            .map(|(op, e)| (op, e.clone())).collect();
+        let inputs = select_coins(candidates, 50_000, 1_000, 100, CoinSelection::LargestFirst).unwrap();
+        let unsigned = build_unsigned_tx(&inputs, [9; 32], 50_000, 1_000, cold.primary_pubkey_hash()).unwrap();
+        assert_eq!(unsigned.fee(), 1_000);
+        assert!(unsigned.tx.inputs.iter().all(|i| i.script_sig.is_empty()));
+        assert!(build_unsigned_tx(&inputs, [9; 32], 70_000, 1_000, [0; 32]).is_err());
+        let candidates = cold_utxos.utxos_for(&cold.primary_pubkey_hash()).into_iter()
+            .map(|(op, e)| (op, e.clone())).collect();
+        assert!(select_coins(candidates, 50_000, u64::MAX, 100, CoinSelection::LargestFirst).unwrap_err().contains("overflows"));
+
+        // The online wallet only watches the address and can't sign
+        assert!(online.sign_tx(&unsigned).unwrap_err().contains("no private key"));
+
+        // Offline side: signatures cover exactly the exported sighashes
+        let json = serde_json::to_string(&unsigned).unwrap();
+        let imported: PartialTx = serde_json::from_str(&json).unwrap();
+        let sighashes = imported.sighashes();
+        let tx = cold.sign_tx(&imported).unwrap();
+        for (input, sighash) in tx.inputs.iter().zip(&sighashes) {
+            assert!(verify_signature(&input.pubkey, sighash, &input.signature));
+        }
+        assert_eq!(crypto::txid::txid_v1(&tx), crypto::txid::txid_v1(&unsigned.tx));
+    }
+
+    #[test]
+    fn test_address_labels() {
+        let mut wallet = Wallet::from_seed(&[4u8; 32]);
+        wallet.new_address();
+        wallet.new_address();
+        let hashes = wallet.pubkey_hashes();
+        wallet.set_label(hashes[0], "donations").unwrap();
+        wallet.set_label(hashes[2], "donations").unwrap();
+        wallet.set_label(hashes[1], " shop ").unwrap();
+        assert_eq!(wallet.label_of(&hashes[1]), Some("shop"));
+        assert_eq!(wallet.list_labels(), vec!["donations".to_string(), "shop".to_string()]);
+        let mut donations = wallet.hashes_by_label("donations");
+        donations.sort();
+        let mut expected = vec![hashes[0], hashes[2]];
+        expected.sort();
+        assert_eq!(donations, expected);
+        assert!(wallet.set_label([0xAB; 32], "nope").is_err());
+
+        let loaded = Wallet::from_wallet_file(wallet.to_wallet_file(), None).unwrap();
+        assert_eq!(loaded.label_of(&hashes[1]), Some("shop"));
+        wallet.set_label(hashes[1], "").unwrap();
+        assert_eq!(wallet.label_of(&hashes[1]), None);
+
+        // Files written before labels existed still load
+        let mut wf = serde_json::to_value(wallet.to_wallet_file()).unwrap();
+        wf.as_object_mut().unwrap().remove("labels");
+        let old: WalletFile = serde_json::from_value(wf).unwrap();
+        assert!(Wallet::from_wallet_file(old, None).unwrap().list_labels().is_empty());
+    }
+
+    #[test]
+    fn test_encrypted_wallet_hides_labels_and_watch_only() {
+        let mut wallet = Wallet::from_seed(&[5u8; 32]);
+        let own = wallet.primary_pubkey_hash();
+        let cold = [0xC7; 32];
+        wallet.add_watch_only(cold);
+        wallet.set_label(own, "tip-jar-secret").unwrap();
+        wallet.set_label(cold, "vault-secret").unwrap();
+        wallet.password = Some("pw".into());
+
+        let json = serde_json::to_string(&wallet.to_wallet_file()).unwrap();
+        for needle in ["tip-jar-secret".to_string(), "vault-secret".to_string(), hex::encode(own), hex::encode(cold)] {
+            assert!(!json.contains(&needle), "{} leaked", needle);
+        }
+        let loaded = Wallet::from_wallet_file(serde_json::from_str(&json).unwrap(), Some("pw")).unwrap();
+        assert_eq!(loaded.label_of(&own), Some("tip-jar-secret"));
+        assert_eq!(loaded.label_of(&cold), Some("vault-secret"));
+        assert_eq!(loaded.watch_only(), &[cold]);
+
+        // v4 files kept them in the clear beside the payload, and still load
+        let (salt, nonce) = ([1u8; 16], [2u8; 12]);
+        let kdf = KdfParams { memory_kib: 8, iterations: 1, ..KdfParams::default() };
+        let key = derive_key(b"pw", &salt, Some(&kdf)).unwrap();
+        let payload = WalletSecretsV4 { keys: vec![wallet.keypairs[0].secret_bytes()], label: String::new(), seed: Some([5u8; 32]), hd_index: 1 };
+        let v4 = WalletFile {
+            version: 4, encrypted: true, keys: vec![], label: String::new(),
+            salt: Some(hex::encode(salt)), nonce: Some(hex::encode(nonce)),
+            ciphertext: Some(hex::encode(encrypt_data(&bincode::serialize(&payload).unwrap(), &key, &nonce))),
+            kdf: Some(kdf), seed: None, hd_index: 0,
+            watch_only: vec![hex::encode(cold)],
+            labels: BTreeMap::from([(hex::encode(cold), "vault-secret".to_string())]),
+        };
+        let loaded = Wallet::from_wallet_file(v4, Some("pw")).unwrap();
+        assert_eq!((loaded.primary_pubkey_hash(), loaded.watch_only()), (own, &[cold][..]));
+        assert_eq!(loaded.label_of(&cold), Some("vault-secret"));
+    }
+
+    #[test]
+    fn test_sweep_spends_everything_mature() {
+        let key = Keypair::generate();
+        let dest = [9u8; 32];
+        let entry = |amount, height, is_coinbase| crate::core::chain::UtxoEntry {
+            output: TxOutput { amount, pubkey_hash: key.pubkey_hash(), script_pubkey: vec![] }, height, is_coinbase,
+        };
+        let utxos = vec![
+            (OutPoint { txid: [1; 32], vout: 0 }, entry(40_000, 5, false)),
+            (OutPoint { txid: [2; 32], vout: 1 }, entry(60_000, 10, true)),
+            (OutPoint { txid: [3; 32], vout: 0 }, entry(500_000, 195, true)), // immature at 200
+        ];
+        let sweep = build_sweep_tx(&key, &utxos, dest, 2.0, 200).unwrap();
+        assert_eq!(sweep.swept, 100_000);
+        assert_eq!(sweep.skipped_immature, 500_000);
+        assert_eq!(sweep.tx.inputs.len(), 2);
+        assert_eq!(sweep.tx.outputs.len(), 1);
+        assert_eq!(sweep.tx.outputs[0].pubkey_hash, dest);
+        assert_eq!(sweep.tx.outputs[0].amount, 100_000 - sweep.fee);
+        assert!(sweep.fee >= MIN_TX_FEE && sweep.fee >= 2 * sweep.tx.size() as u64);
+        for (i, input) in sweep.tx.inputs.iter().enumerate() {
+            let digest = crypto::tx_signing_hash_v1(&sweep.tx, i, &utxos[i].1.output);
+            assert!(verify_signature(&input.pubkey, &digest, &input.signature));
+        }
+
+        assert!(build_sweep_tx(&key, &utxos[2..], dest, 1.0, 200).unwrap_err().contains("immature"));
+        assert!(build_sweep_tx(&Keypair::generate(), &utxos, dest, 1.0, 200).is_err());
+    }
+
+    #[test]
+    fn test_coin_selection_orders() {
+        let (wallet, utxos) = funded_wallet(&[50_000, 30_000, 3_000]);
+        let pick = |sel| wallet.select_utxos(&utxos, 2_000, 0, 100, sel).unwrap()
+            .iter().map(|(_, e)| e.output.amount).collect::<Vec<_>>();
+        assert_eq!(pick(CoinSelection::LargestFirst), vec![50_000]);
+        assert_eq!(pick(CoinSelection::SmallestFirst), vec![3_000]);
+        // funded_wallet gives earlier amounts higher heights
+        assert_eq!(pick(CoinSelection::Oldest), vec![3_000]);
+        assert_eq!(CoinSelection::parse("bnb"), Some(CoinSelection::BranchAndBound));
+        assert_eq!(CoinSelection::parse("smallest-first"), Some(CoinSelection::SmallestFirst));
+    }
+
     #[test]
     fn test_wallet_unencrypted_roundtrip() {
         let wallet = Wallet {
             keypairs: vec![Keypair::generate()],
             label: "test".to_string(), path: None, password: None,
+            seed: None, hd_index: 0, watch_only: Vec::new(), labels: BTreeMap::new(),
         };
         let wf = wallet.to_wallet_file();
         assert!(!wf.encrypted);
         let loaded = Wallet::from_wallet_file(wf, None).unwrap();
         assert_eq!(loaded.primary_address(), wallet.primary_address());
     }
+
+    #[test]
+    fn test_partial_tx_2_of_2_multisig() {
+        let _ = std::panic::catch_unwind(|| crate::core::params::init_network(false));
+        let alice = Wallet::new("alice");
+        let bob = Wallet::new("bob");
+        let (_, lock) = create_multisig(2, &[alice.primary_pubkey_hash(), bob.primary_pubkey_hash()]).unwrap();
+
+        let mut chain = crate::core::chain::Chain::new();
+        let prev_output = script::output_multisig(10 * crate::core::params::MIN_TX_FEE, lock);
+        let outpoint = OutPoint { txid: [0x44; 32], vout: 0 };
+        chain.utxo_set.add(outpoint.clone(), crate::core::chain::UtxoEntry { output: prev_output.clone(), height: 0, is_coinbase: false });
+
+        let unsigned = Transaction {
+            version: 1,
+            inputs: vec![TxInput { previous_output: outpoint, signature: vec![], pubkey: vec![], script_sig: vec![], sequence: 0xFFFFFFFF }],
+            outputs: vec![TxOutput { amount: crate::core::params::MIN_TX_FEE, pubkey_hash: [9u8; 32], script_pubkey: script::script_p2pkh(&[9u8; 32]) }],
+            lock_time: 0,
+        };
+        let created = PartialTx::new(unsigned, vec![prev_output]).unwrap().to_hex();
+
+        // Each party signs their own copy independently
+        let mut a = PartialTx::from_hex(&created).unwrap();
+        assert_eq!(a.sign_with_wallet(&alice).unwrap(), 1);
+        assert!(!a.is_complete());
+        assert!(a.clone().finalize().is_err());
+
+        let mut b = PartialTx::from_hex(&created).unwrap();
+        assert_eq!(b.sign_with(&bob.keypairs[0]).unwrap(), 1);
+
+        // Combine the two halves and finalize
+        let mut combined = PartialTx::from_hex(&a.to_hex()).unwrap();
+        combined.combine(&PartialTx::from_hex(&b.to_hex()).unwrap()).unwrap();
+        assert!(combined.is_complete());
+        let tx = combined.finalize().unwrap();
+        assert!(chain.validate_transaction_for_mempool(&tx).is_ok());
+    }
 }